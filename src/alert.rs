@@ -1,6 +1,7 @@
 use crate::TlsError;
 use crate::buffer::CryptoBuffer;
 use crate::parse_buffer::ParseBuffer;
+use portable_atomic::{AtomicU8, Ordering};
 
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -101,6 +102,16 @@ impl Alert {
         Self { level, description }
     }
 
+    /// The alert to send to the peer when aborting the connection because of `error`.
+    pub(crate) fn for_error(error: TlsError) -> Option<Self> {
+        match error {
+            TlsError::AbortHandshake(level, description) => Some(Self::new(level, description)),
+            error => error
+                .alert_description()
+                .map(|description| Self::new(AlertLevel::Fatal, description)),
+        }
+    }
+
     pub fn parse(buf: &mut ParseBuffer<'_>) -> Result<Alert, TlsError> {
         let level = buf.read_u8()?;
         let desc = buf.read_u8()?;
@@ -119,3 +130,35 @@ impl Alert {
         Ok(())
     }
 }
+
+/// A fatal alert the read half of a split connection failed with, for the write half to send on
+/// its next call.
+pub(crate) struct PendingAlert(AtomicU8);
+
+impl PendingAlert {
+    /// No alert is pending. Not a valid alert description.
+    const NONE: u8 = u8::MAX;
+
+    pub(crate) const fn new() -> Self {
+        Self(AtomicU8::new(Self::NONE))
+    }
+
+    /// Record the alert to send for `error`, if it calls for one.
+    pub(crate) fn set_for_error(&self, error: TlsError) {
+        if let Some(Alert {
+            level: AlertLevel::Fatal,
+            description,
+        }) = Alert::for_error(error)
+        {
+            self.0.store(description as u8, Ordering::Release);
+        }
+    }
+
+    pub(crate) fn take(&self) -> Option<AlertDescription> {
+        AlertDescription::of(self.0.swap(Self::NONE, Ordering::AcqRel))
+    }
+
+    pub(crate) fn clear(&mut self) {
+        *self.0.get_mut() = Self::NONE;
+    }
+}
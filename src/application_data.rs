@@ -1,10 +1,17 @@
+use crate::TlsError;
 use crate::buffer::CryptoBuffer;
 use crate::record::RecordHeader;
+#[cfg(feature = "tls12")]
+use crate::record_reader::reassemble_handshake;
+use crate::record_reader::{Fragment, HandshakeMessages, reassemble_streamed_handshake};
 use core::fmt::{Debug, Formatter};
 
 pub struct ApplicationData<'a> {
     pub(crate) header: RecordHeader,
     pub(crate) data: CryptoBuffer<'a>,
+    /// The handshake message split across records that is held in the read buffer, for a record
+    /// received by the record reader. The held bytes precede `data`.
+    pub(crate) fragment: Option<&'a mut Fragment>,
 }
 
 impl Debug for ApplicationData<'_> {
@@ -25,6 +32,25 @@ impl<'a> ApplicationData<'a> {
         Self {
             header,
             data: rx_buf,
+            fragment: None,
         }
     }
+
+    /// The complete handshake messages of the record, following the held part of a split
+    /// message. A trailing incomplete message is held until the next record.
+    #[cfg(feature = "tls12")]
+    pub(crate) fn into_handshake_messages(self) -> Result<&'a [u8], TlsError> {
+        let fragment = self.fragment.ok_or(TlsError::InternalError)?;
+        let (data, offset, len) = self.data.release();
+        reassemble_handshake(data, offset + len, fragment)
+    }
+
+    /// Like [`Self::into_handshake_messages`], but streaming the `Certificate` messages if enabled.
+    pub(crate) fn into_streamed_handshake_messages(
+        self,
+    ) -> Result<HandshakeMessages<'a>, TlsError> {
+        let fragment = self.fragment.ok_or(TlsError::InternalError)?;
+        let (data, offset, len) = self.data.release();
+        reassemble_streamed_handshake(data, offset + len, fragment)
+    }
 }
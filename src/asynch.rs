@@ -1,47 +1,46 @@
-use core::sync::atomic::{AtomicBool, Ordering};
-
 use crate::TlsError;
+use crate::alert::{Alert, AlertDescription, AlertLevel, PendingAlert};
+use crate::client::TlsClient;
 use crate::common::decrypted_buffer_info::DecryptedBufferInfo;
 use crate::common::decrypted_read_handler::DecryptedReadHandler;
-use crate::connection::{Handshake, State, decrypt_record};
+use crate::connection::decrypt_record;
 use crate::flush_policy::FlushPolicy;
 use crate::key_schedule::KeySchedule;
 use crate::key_schedule::{ReadKeySchedule, WriteKeySchedule};
 use crate::read_buffer::ReadBuffer;
-use crate::record::{ClientRecord, ClientRecordHeader};
-use crate::record_reader::{RecordReader, RecordReaderBorrowMut};
-use crate::write_buffer::{WriteBuffer, WriteBufferBorrowMut};
+use crate::record::ClientRecordHeader;
+use crate::record_buffer::{RecordBuffer, RecordBuffers};
+use crate::record_reader::RecordReaderBorrowMut;
+use crate::server_connection;
+use crate::write_buffer::WriteBufferBorrowMut;
 use embedded_io::Error as _;
 use embedded_io::ErrorType;
 use embedded_io_async::{BufRead, Read as AsyncRead, Write as AsyncWrite};
+use portable_atomic::{AtomicBool, Ordering};
 
 pub use crate::config::*;
 
 /// Type representing an async TLS connection. An instance of this type can
 /// be used to establish a TLS connection, write and read encrypted data over this connection,
 /// and closing to free up the underlying resources.
-pub struct TlsConnection<'a, Socket, CipherSuite>
+///
+/// The record buffers are borrowed for `'a`. See [`owned::TlsConnection`](crate::owned::TlsConnection)
+/// for a connection owning its buffers.
+pub struct TlsConnection<'a, Socket, CipherSuite, Rx = &'a mut [u8], Tx = &'a mut [u8]>
 where
-    Socket: AsyncRead + AsyncWrite + 'a,
+    Socket: AsyncRead + AsyncWrite,
     CipherSuite: TlsCipherSuite + 'static,
 {
     delegate: Socket,
-    opened: AtomicBool,
-    key_schedule: KeySchedule<CipherSuite>,
-    record_reader: RecordReader<'a>,
-    record_write_buf: WriteBuffer<'a>,
-    decrypted: DecryptedBufferInfo,
+    client: TlsClient<'a, CipherSuite, Rx, Tx>,
     flush_policy: FlushPolicy,
 }
 
 impl<'a, Socket, CipherSuite> TlsConnection<'a, Socket, CipherSuite>
 where
-    Socket: AsyncRead + AsyncWrite + 'a,
+    Socket: AsyncRead + AsyncWrite,
     CipherSuite: TlsCipherSuite + 'static,
 {
-    pub fn is_opened(&mut self) -> bool {
-        *self.opened.get_mut()
-    }
     /// Create a new TLS connection with the provided context and a async I/O implementation
     ///
     /// NOTE: The record read buffer should be sized to fit an encrypted TLS record. The size of this record
@@ -53,22 +52,104 @@ where
     /// records if depending on the size of the write buffer.
     /// The largest of the two buffers will be used to encode the TLS handshake record, hence either of the
     /// buffers must at least be large enough to encode a handshake.
+    ///
+    /// [`min_read_buffer_size`] and [`min_write_buffer_size`] compute the sizes a configuration
+    /// needs. Opening the connection fails with [`TlsError::ReadBufferTooSmall`] or
+    /// [`TlsError::WriteBufferTooSmall`] before the handshake starts if they are too small.
     pub fn new(
         delegate: Socket,
         record_read_buf: &'a mut [u8],
         record_write_buf: &'a mut [u8],
+    ) -> Self {
+        Self::from_client(delegate, TlsClient::new(record_read_buf, record_write_buf))
+    }
+
+    /// Create a new TLS connection using a single buffer for reading and writing, for
+    /// request/response protocols where reads and writes never overlap.
+    ///
+    /// The buffer should be sized like the read buffer of [`Self::new`]. Written data must be
+    /// flushed before reading, and the received data must be read before writing, or the call
+    /// fails with [`TlsError::BufferInUse`]. The connection cannot be split.
+    pub fn new_half_duplex(delegate: Socket, record_buf: &'a mut [u8]) -> Self {
+        Self::from_client(delegate, TlsClient::new_half_duplex(record_buf))
+    }
+
+    /// Create a new TLS connection from the parts returned by [`Self::into_parts`], with
+    /// [`Self::new`] or [`Self::new_half_duplex`].
+    pub fn from_parts(delegate: Socket, buffers: RecordBuffers<'a>) -> Self {
+        match buffers {
+            RecordBuffers::FullDuplex(record_read_buf, record_write_buf) => {
+                Self::new(delegate, record_read_buf, record_write_buf)
+            }
+            RecordBuffers::HalfDuplex(record_buf) => Self::new_half_duplex(delegate, record_buf),
+        }
+    }
+
+    /// Reset the connection like [`Self::reset`], and return the transport and the buffers,
+    /// to be passed back to [`Self::from_parts`].
+    pub fn into_parts(self) -> (Socket, RecordBuffers<'a>) {
+        (self.delegate, self.client.into_buffers())
+    }
+}
+
+impl<Socket, CipherSuite, const RX: usize, const TX: usize>
+    TlsConnection<'_, Socket, CipherSuite, [u8; RX], [u8; TX]>
+where
+    Socket: AsyncRead + AsyncWrite,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    /// Reset the connection like [`Self::reset`], and return the transport and the read and
+    /// write buffers, to be passed back to [`Self::from_buffers`].
+    pub fn into_parts(self) -> (Socket, [u8; RX], [u8; TX]) {
+        let (record_read_buf, record_write_buf) = self.client.into_buffers();
+        (self.delegate, record_read_buf, record_write_buf)
+    }
+}
+
+impl<'a, Socket, CipherSuite, Rx, Tx> TlsConnection<'a, Socket, CipherSuite, Rx, Tx>
+where
+    Socket: AsyncRead + AsyncWrite,
+    CipherSuite: TlsCipherSuite + 'static,
+    Rx: RecordBuffer<'a>,
+    Tx: RecordBuffer<'a>,
+{
+    pub(crate) fn from_client(
+        delegate: Socket,
+        client: TlsClient<'a, CipherSuite, Rx, Tx>,
     ) -> Self {
         Self {
             delegate,
-            opened: AtomicBool::new(false),
-            key_schedule: KeySchedule::new(),
-            record_reader: RecordReader::new(record_read_buf),
-            record_write_buf: WriteBuffer::new(record_write_buf),
-            decrypted: DecryptedBufferInfo::default(),
+            client,
             flush_policy: FlushPolicy::default(),
         }
     }
 
+    /// Create a new TLS connection from buffers of any [`RecordBuffer`] storage, such as arrays
+    /// owned by the connection.
+    ///
+    /// See [`Self::new`] for how to size the buffers.
+    pub fn from_buffers(delegate: Socket, record_read_buf: Rx, record_write_buf: Tx) -> Self {
+        Self::from_client(
+            delegate,
+            TlsClient::from_buffers(record_read_buf, record_write_buf),
+        )
+    }
+
+    /// Reset the connection to its initial state over the new transport `delegate`, returning
+    /// the previous one, so that [`Self::open`] can perform a new handshake, for instance after
+    /// an error.
+    ///
+    /// The secrets and the buffers are zeroized. Nothing is sent: use [`Self::shutdown_write`]
+    /// first to close the connection gracefully.
+    pub fn reset(&mut self, delegate: Socket) -> Socket {
+        self.client.reset();
+        core::mem::replace(&mut self.delegate, delegate)
+    }
+
+    pub fn is_opened(&mut self) -> bool {
+        self.client.is_opened()
+    }
+
     /// Returns a reference to the current flush policy.
     ///
     /// The flush policy controls whether the underlying transport is flushed
@@ -87,11 +168,27 @@ where
         self.flush_policy = policy;
     }
 
+    /// Fill `out` with keying material exported from the connection with `label` and
+    /// `context` (RFC 8446, Section 7.5), for instance to derive keys for another protocol or
+    /// for `tls-exporter` channel binding (RFC 9266).
+    ///
+    /// Both sides of the connection export the same keying material. Returns
+    /// [`TlsError::MissingHandshake`] before the handshake completes, and
+    /// [`TlsError::Unimplemented`] on a TLS 1.2 connection.
+    pub fn export_keying_material(
+        &self,
+        label: &[u8],
+        context: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), TlsError> {
+        self.client.export_keying_material(label, context, out)
+    }
+
     /// Open a TLS connection, performing the handshake with the configuration provided when
     /// creating the connection instance.
     ///
     /// Returns an error if the handshake does not proceed. If an error occurs, the connection
-    /// instance must be recreated.
+    /// instance must be recreated, or reset with [`Self::reset`].
     pub async fn open<Provider>(
         &mut self,
         mut context: TlsContext<'_, Provider>,
@@ -99,35 +196,75 @@ where
     where
         Provider: CryptoProvider<CipherSuite = CipherSuite>,
     {
-        let mut handshake: Handshake<CipherSuite> = Handshake::new();
-        if let (Ok(verifier), Some(server_name)) = (
-            context.crypto_provider.verifier(),
-            context.config.server_name,
-        ) {
-            verifier.set_hostname_verification(server_name)?;
+        if self.client.wants_key_share()
+            && let Some(key_share) = context.take_key_share()
+        {
+            self.client.set_key_share(key_share);
         }
-        let mut state = State::ClientHello;
-
-        while state != State::ApplicationData {
-            let next_state = state
-                .process(
-                    &mut self.delegate,
-                    &mut handshake,
-                    &mut self.record_reader,
-                    &mut self.record_write_buf,
-                    &mut self.key_schedule,
-                    context.config,
-                    &mut context.crypto_provider,
-                )
-                .await?;
-            trace!("State {:?} -> {:?}", state, next_state);
-            state = next_state;
+        loop {
+            let result = self
+                .client
+                .handle_handshake(&context.config, &mut context.crypto_provider);
+            context.flush_key_log(&mut self.client.key_schedule);
+            self.transmit(true).await?;
+            result?;
+
+            if !self.client.is_handshaking() {
+                return Ok(());
+            }
+            if self.client.wants_read() {
+                self.receive().await?;
+            }
         }
-        *self.opened.get_mut() = true;
+    }
 
+    /// Send the records encoded by the client, flushing the transport afterwards if
+    /// `flush_transport` is set.
+    async fn transmit(&mut self, flush_transport: bool) -> Result<(), TlsError> {
+        let tx = self.client.pending_transmit();
+        if tx.is_empty() {
+            return Ok(());
+        }
+
+        self.delegate
+            .write_all(tx)
+            .await
+            .map_err(|e| TlsError::Io(e.kind()))?;
+        let len = tx.len();
+        self.client.transmitted(len);
+
+        if flush_transport {
+            self.flush_transport().await?;
+        }
         Ok(())
     }
 
+    /// Receive the next bytes of the current record.
+    async fn receive(&mut self) -> Result<(), TlsError> {
+        let read = self
+            .delegate
+            .read(self.client.rx_buffer())
+            .await
+            .map_err(|e| TlsError::Io(e.kind()))?;
+        if read == 0 {
+            return Err(TlsError::IoError);
+        }
+
+        match self.client.receive(read) {
+            Err(e) => Err(self.abort(e).await),
+            ok => ok,
+        }
+    }
+
+    /// Send the alert encoded by the client after a fatal error, if any.
+    async fn abort(&mut self, error: TlsError) -> TlsError {
+        if let Err(e) = self.transmit(true).await {
+            warn!("Failed to send alert: {:?}", e);
+        }
+
+        error
+    }
+
     /// Encrypt and send the provided slice over the connection. The connection
     /// must be opened before writing.
     ///
@@ -137,48 +274,19 @@ where
     ///
     /// Returns the number of bytes buffered/written.
     pub async fn write(&mut self, buf: &[u8]) -> Result<usize, TlsError> {
-        if self.is_opened() {
-            if !self
-                .record_write_buf
-                .contains(ClientRecordHeader::ApplicationData)
-            {
-                self.flush().await?;
-                self.record_write_buf
-                    .start_record(ClientRecordHeader::ApplicationData)?;
-            }
-
-            let buffered = self.record_write_buf.append(buf);
-
-            if self.record_write_buf.is_full() {
-                self.flush().await?;
-            }
-
-            Ok(buffered)
-        } else {
-            Err(TlsError::MissingHandshake)
-        }
+        let result = self.client.write(buf);
+        self.transmit(result.is_err() || self.flush_policy.flush_transport())
+            .await?;
+        result
     }
 
     /// Force all previously written, buffered bytes to be encoded into a tls record and written
     /// to the connection.
     pub async fn flush(&mut self) -> Result<(), TlsError> {
-        if !self.record_write_buf.is_empty() {
-            let key_schedule = self.key_schedule.write_state();
-            let slice = self.record_write_buf.close_record(key_schedule)?;
-
-            self.delegate
-                .write_all(slice)
-                .await
-                .map_err(|e| TlsError::Io(e.kind()))?;
-
-            key_schedule.increment_counter();
-
-            if self.flush_policy.flush_transport() {
-                self.flush_transport().await?;
-            }
-        }
-
-        Ok(())
+        let result = self.client.flush();
+        self.transmit(result.is_err() || self.flush_policy.flush_transport())
+            .await?;
+        result
     }
 
     #[inline]
@@ -190,10 +298,12 @@ where
     }
 
     fn create_read_buffer(&mut self) -> ReadBuffer<'_> {
-        self.decrypted.create_read_buffer(self.record_reader.buf)
+        self.client.read_buffer()
     }
 
     /// Read and decrypt data filling the provided slice.
+    ///
+    /// Returns `Ok(0)` once the server has closed its side of the connection with `close_notify`.
     pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, TlsError> {
         if buf.is_empty() {
             return Ok(0);
@@ -207,59 +317,82 @@ where
     }
 
     /// Reads buffered data. If nothing is in memory, it'll wait for a TLS record and process it.
+    ///
+    /// Returns an empty buffer once the server has closed its side of the connection with
+    /// `close_notify`.
     pub async fn read_buffered(&mut self) -> Result<ReadBuffer<'_>, TlsError> {
-        if self.is_opened() {
-            while self.decrypted.is_empty() {
-                self.read_application_data().await?;
-            }
+        self.poll_read().await?;
+        Ok(self.create_read_buffer())
+    }
 
-            Ok(self.create_read_buffer())
-        } else {
-            Err(TlsError::MissingHandshake)
+    /// Receive records until application data is available, or the server has closed its side
+    /// of the connection.
+    async fn poll_read(&mut self) -> Result<(), TlsError> {
+        loop {
+            match self.client.poll_read() {
+                Ok(true) => return Ok(()),
+                Ok(false) => self.receive().await?,
+                Err(e) => return Err(self.abort(e).await),
+            }
         }
     }
 
-    async fn read_application_data(&mut self) -> Result<(), TlsError> {
-        let buf_ptr_range = self.record_reader.buf.as_ptr_range();
-        let record = self
-            .record_reader
-            .read(&mut self.delegate, self.key_schedule.read_state())
-            .await?;
+    /// Send an alert to the server.
+    ///
+    /// Any buffered data is flushed first. Sending a fatal alert closes the connection, after
+    /// which reads and writes fail with [`TlsError::MissingHandshake`].
+    pub async fn send_alert(
+        &mut self,
+        level: AlertLevel,
+        description: AlertDescription,
+    ) -> Result<(), TlsError> {
+        self.flush().await?;
+        let result = self.client.send_alert(level, description);
+        self.transmit(true).await?;
+        result
+    }
 
-        let mut handler = DecryptedReadHandler {
-            source_buffer: buf_ptr_range,
-            buffer_info: &mut self.decrypted,
-            is_open: self.opened.get_mut(),
-        };
-        decrypt_record(
-            self.key_schedule.read_state(),
-            record,
-            |_key_schedule, record| handler.handle(record),
-        )?;
+    /// Send `close_notify` to the server, closing the write side of the connection.
+    ///
+    /// Any buffered data is flushed first. Reading keeps working until the server closes its
+    /// side as well, at which point reads return EOF.
+    pub async fn shutdown_write(&mut self) -> Result<(), TlsError> {
+        self.flush().await?;
+        let result = self.client.shutdown_write();
+        self.transmit(true).await?;
+        result
+    }
+
+    async fn shutdown_internal(&mut self) -> Result<(), TlsError> {
+        // The data received but not read is discarded anyway
+        self.create_read_buffer().pop_all();
+        self.shutdown_write().await?;
+
+        while !self.client.read_closed {
+            self.create_read_buffer().pop_all();
+            self.poll_read().await?;
+        }
 
         Ok(())
     }
 
+    /// Gracefully close a connection instance, returning the ownership of the I/O provider.
+    ///
+    /// Unlike [`Self::close`], this waits for the server's `close_notify`. Data that arrives in
+    /// the meantime is discarded.
+    pub async fn shutdown(mut self) -> Result<Socket, (Socket, TlsError)> {
+        match self.shutdown_internal().await {
+            Ok(()) => Ok(self.delegate),
+            Err(e) => Err((self.delegate, e)),
+        }
+    }
+
     /// Close a connection instance, returning the ownership of the config, random generator and the async I/O provider.
     async fn close_internal(&mut self) -> Result<(), TlsError> {
         self.flush().await?;
-
-        let is_opened = self.is_opened();
-        let (write_key_schedule, read_key_schedule) = self.key_schedule.as_split();
-        let slice = self.record_write_buf.write_record(
-            &ClientRecord::close_notify(is_opened),
-            write_key_schedule,
-            Some(read_key_schedule),
-        )?;
-
-        self.delegate
-            .write_all(slice)
-            .await
-            .map_err(|e| TlsError::Io(e.kind()))?;
-
-        self.key_schedule.write_state().increment_counter();
-
-        self.flush_transport().await
+        let result = self.client.close();
+        self.transmit(true).await?;
+        result
     }
 
     /// Close a connection instance, returning the ownership of the async I/O provider.
@@ -270,6 +403,11 @@ where
         }
     }
 
+    /// Split the connection into a reader and a writer that can be used concurrently.
+    ///
+    /// # Panics
+    ///
+    /// If the connection was created with [`Self::new_half_duplex`], see [`Self::try_split`].
     pub fn split(
         &mut self,
     ) -> (
@@ -279,49 +417,87 @@ where
     where
         Socket: Clone,
     {
-        let (wks, rks) = self.key_schedule.as_split();
+        assert!(
+            !self.client.is_half_duplex(),
+            "A half-duplex connection cannot be split"
+        );
+        let client = &mut self.client;
+        let (wks, rks) = client.key_schedule.as_split();
 
         let reader = TlsReader {
-            opened: &self.opened,
+            opened: &client.opened,
+            pending_alert: &client.pending_alert,
+            read_closed: &mut client.read_closed,
             delegate: self.delegate.clone(),
             key_schedule: rks,
-            record_reader: self.record_reader.reborrow_mut(),
-            decrypted: &mut self.decrypted,
+            record_reader: client.record_reader.reborrow_mut(),
+            decrypted: &mut client.decrypted,
         };
         let writer = TlsWriter {
-            opened: &self.opened,
+            opened: &client.opened,
+            pending_alert: &client.pending_alert,
+            write_closed: &mut client.write_closed,
             delegate: self.delegate.clone(),
             key_schedule: wks,
-            record_write_buf: self.record_write_buf.reborrow_mut(),
+            record_write_buf: client.record_write_buf.reborrow_mut(),
             flush_policy: self.flush_policy,
         };
 
         (reader, writer)
     }
+
+    /// Split the connection into a reader and a writer that can be used concurrently.
+    ///
+    /// Fails with [`TlsError::BufferInUse`] if the connection was created with
+    /// [`Self::new_half_duplex`], as its single buffer cannot be used for both directions.
+    #[allow(clippy::type_complexity)]
+    pub fn try_split(
+        &mut self,
+    ) -> Result<
+        (
+            TlsReader<'_, Socket, CipherSuite>,
+            TlsWriter<'_, Socket, CipherSuite>,
+        ),
+        TlsError,
+    >
+    where
+        Socket: Clone,
+    {
+        if self.client.is_half_duplex() {
+            return Err(TlsError::BufferInUse);
+        }
+        Ok(self.split())
+    }
 }
 
-impl<'a, Socket, CipherSuite> ErrorType for TlsConnection<'a, Socket, CipherSuite>
+impl<'a, Socket, CipherSuite, Rx, Tx> ErrorType for TlsConnection<'a, Socket, CipherSuite, Rx, Tx>
 where
-    Socket: AsyncRead + AsyncWrite + 'a,
+    Socket: AsyncRead + AsyncWrite,
     CipherSuite: TlsCipherSuite + 'static,
+    Rx: RecordBuffer<'a>,
+    Tx: RecordBuffer<'a>,
 {
     type Error = TlsError;
 }
 
-impl<'a, Socket, CipherSuite> AsyncRead for TlsConnection<'a, Socket, CipherSuite>
+impl<'a, Socket, CipherSuite, Rx, Tx> AsyncRead for TlsConnection<'a, Socket, CipherSuite, Rx, Tx>
 where
-    Socket: AsyncRead + AsyncWrite + 'a,
+    Socket: AsyncRead + AsyncWrite,
     CipherSuite: TlsCipherSuite + 'static,
+    Rx: RecordBuffer<'a>,
+    Tx: RecordBuffer<'a>,
 {
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
         TlsConnection::read(self, buf).await
     }
 }
 
-impl<'a, Socket, CipherSuite> BufRead for TlsConnection<'a, Socket, CipherSuite>
+impl<'a, Socket, CipherSuite, Rx, Tx> BufRead for TlsConnection<'a, Socket, CipherSuite, Rx, Tx>
 where
-    Socket: AsyncRead + AsyncWrite + 'a,
+    Socket: AsyncRead + AsyncWrite,
     CipherSuite: TlsCipherSuite + 'static,
+    Rx: RecordBuffer<'a>,
+    Tx: RecordBuffer<'a>,
 {
     async fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
         self.read_buffered().await.map(|mut buf| buf.peek_all())
@@ -332,10 +508,12 @@ where
     }
 }
 
-impl<'a, Socket, CipherSuite> AsyncWrite for TlsConnection<'a, Socket, CipherSuite>
+impl<'a, Socket, CipherSuite, Rx, Tx> AsyncWrite for TlsConnection<'a, Socket, CipherSuite, Rx, Tx>
 where
-    Socket: AsyncRead + AsyncWrite + 'a,
+    Socket: AsyncRead + AsyncWrite,
     CipherSuite: TlsCipherSuite + 'static,
+    Rx: RecordBuffer<'a>,
+    Tx: RecordBuffer<'a>,
 {
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         TlsConnection::write(self, buf).await
@@ -351,6 +529,8 @@ where
     CipherSuite: TlsCipherSuite + 'static,
 {
     opened: &'a AtomicBool,
+    pending_alert: &'a PendingAlert,
+    read_closed: &'a mut bool,
     delegate: Socket,
     key_schedule: &'a mut ReadKeySchedule<CipherSuite>,
     record_reader: RecordReaderBorrowMut<'a>,
@@ -376,10 +556,20 @@ where
     }
 
     /// Reads buffered data. If nothing is in memory, it'll wait for a TLS record and process it.
+    ///
+    /// Returns an empty buffer once the server has closed its side of the connection with
+    /// `close_notify`.
+    ///
+    /// On a fatal error, the alert telling the server why is sent by the [`TlsWriter`] on its
+    /// next call.
     pub async fn read_buffered(&mut self) -> Result<ReadBuffer<'_>, TlsError> {
         if self.opened.load(Ordering::Acquire) {
-            while self.decrypted.is_empty() {
-                self.read_application_data().await?;
+            while self.decrypted.is_empty() && !*self.read_closed {
+                if let Err(e) = self.read_application_data().await {
+                    self.pending_alert.set_for_error(e);
+                    self.opened.store(false, Ordering::Release);
+                    return Err(e);
+                }
             }
 
             Ok(self.create_read_buffer())
@@ -400,6 +590,7 @@ where
             source_buffer: buf_ptr_range,
             buffer_info: self.decrypted,
             is_open: &mut opened,
+            read_closed: self.read_closed,
         };
         let result = decrypt_record(self.key_schedule, record, |_key_schedule, record| {
             handler.handle(record)
@@ -417,6 +608,8 @@ where
     CipherSuite: TlsCipherSuite + 'static,
 {
     opened: &'a AtomicBool,
+    pending_alert: &'a PendingAlert,
+    write_closed: &'a mut bool,
     delegate: Socket,
     key_schedule: &'a mut WriteKeySchedule<CipherSuite>,
     record_write_buf: WriteBufferBorrowMut<'a>,
@@ -435,6 +628,79 @@ where
             .await
             .map_err(|e| TlsError::Io(e.kind()))
     }
+
+    /// Send an alert to the server.
+    ///
+    /// Any buffered data is flushed first. Sending a fatal alert closes the connection for both
+    /// halves.
+    pub async fn send_alert(
+        &mut self,
+        level: AlertLevel,
+        description: AlertDescription,
+    ) -> Result<(), TlsError> {
+        self.send_pending_alert().await?;
+        if !self.opened.load(Ordering::Acquire) {
+            return Err(TlsError::MissingHandshake);
+        }
+        if *self.write_closed {
+            return Err(TlsError::ConnectionClosed);
+        }
+
+        self.flush().await?;
+        self.write_alert(&Alert::new(level, description)).await
+    }
+
+    /// Send `close_notify` to the server, closing the write side of the connection.
+    ///
+    /// Any buffered data is flushed first. The [`TlsReader`] keeps working until the server
+    /// closes its side as well, at which point reads return EOF.
+    pub async fn shutdown_write(&mut self) -> Result<(), TlsError> {
+        self.send_pending_alert().await?;
+        if !self.opened.load(Ordering::Acquire) {
+            return Err(TlsError::MissingHandshake);
+        }
+
+        if !*self.write_closed {
+            self.flush().await?;
+            self.write_alert(&Alert::new(
+                AlertLevel::Warning,
+                AlertDescription::CloseNotify,
+            ))
+            .await?;
+            *self.write_closed = true;
+        }
+
+        Ok(())
+    }
+
+    async fn send_pending_alert(&mut self) -> Result<(), TlsError> {
+        if let Some(description) = self.pending_alert.take()
+            && !*self.write_closed
+        {
+            self.write_alert(&Alert::new(AlertLevel::Fatal, description))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn write_alert(&mut self, alert: &Alert) -> Result<(), TlsError> {
+        if let AlertLevel::Fatal = alert.level {
+            self.opened.store(false, Ordering::Release);
+        }
+
+        let slice = self
+            .record_write_buf
+            .write_alert(alert, self.key_schedule)?;
+
+        self.delegate
+            .write_all(slice)
+            .await
+            .map_err(|e| TlsError::Io(e.kind()))?;
+
+        self.key_schedule.increment_counter();
+
+        self.flush_transport().await
+    }
 }
 
 impl<Socket, CipherSuite> AsRef<Socket> for TlsWriter<'_, Socket, CipherSuite>
@@ -498,7 +764,12 @@ where
     CipherSuite: TlsCipherSuite + 'static,
 {
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.send_pending_alert().await?;
         if self.opened.load(Ordering::Acquire) {
+            if *self.write_closed {
+                return Err(TlsError::ConnectionClosed);
+            }
+
             if !self
                 .record_write_buf
                 .contains(ClientRecordHeader::ApplicationData)
@@ -521,6 +792,7 @@ where
     }
 
     async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.send_pending_alert().await?;
         if !self.record_write_buf.is_empty() {
             let slice = self.record_write_buf.close_record(self.key_schedule)?;
 
@@ -539,3 +811,210 @@ where
         Ok(())
     }
 }
+
+/// Type representing the server side of an async TLS connection.
+///
+/// Once the handshake has been performed with [`Self::open`], reading and writing work just like
+/// for [`TlsConnection`].
+pub struct TlsServerConnection<'a, Socket, CipherSuite>
+where
+    Socket: AsyncRead + AsyncWrite + 'a,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    inner: TlsConnection<'a, Socket, CipherSuite>,
+}
+
+impl<'a, Socket, CipherSuite> TlsServerConnection<'a, Socket, CipherSuite>
+where
+    Socket: AsyncRead + AsyncWrite + 'a,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    pub fn is_opened(&mut self) -> bool {
+        self.inner.is_opened()
+    }
+
+    /// Create a new server TLS connection with the provided buffers and async I/O implementation.
+    ///
+    /// See [`TlsConnection::new`] for how to size the buffers.
+    pub fn new(
+        delegate: Socket,
+        record_read_buf: &'a mut [u8],
+        record_write_buf: &'a mut [u8],
+    ) -> Self {
+        let mut inner = TlsConnection::new(delegate, record_read_buf, record_write_buf);
+        inner.client.key_schedule = KeySchedule::new_server();
+        Self { inner }
+    }
+
+    /// Returns a reference to the current flush policy.
+    #[inline]
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.inner.flush_policy()
+    }
+
+    /// Replace the current flush policy with the provided one.
+    #[inline]
+    pub fn set_flush_policy(&mut self, policy: FlushPolicy) {
+        self.inner.set_flush_policy(policy);
+    }
+
+    /// Fill `out` with keying material exported from the connection with `label` and
+    /// `context`.
+    ///
+    /// See [`TlsConnection::export_keying_material`].
+    pub fn export_keying_material(
+        &self,
+        label: &[u8],
+        context: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), TlsError> {
+        self.inner.export_keying_material(label, context, out)
+    }
+
+    /// Accept a TLS connection, performing the server side of the handshake with the provided
+    /// configuration.
+    ///
+    /// The certificate and private key are taken from [`CryptoProvider::server_cert`] and
+    /// [`CryptoProvider::signer`]. Client certificates are checked with
+    /// [`CryptoProvider::verifier`] if [`TlsConfig::enable_client_auth`] is set.
+    ///
+    /// Returns an error if the handshake does not proceed. If an error occurs, the connection
+    /// instance must be recreated.
+    pub async fn open<Provider>(
+        &mut self,
+        mut context: TlsContext<'_, Provider>,
+    ) -> Result<(), TlsError>
+    where
+        Provider: CryptoProvider<CipherSuite = CipherSuite>,
+    {
+        let inner = &mut self.inner;
+        let mut handshake = server_connection::ServerHandshake::new();
+        loop {
+            let result = handshake.handle(
+                &mut inner.client,
+                &context.config,
+                &mut context.crypto_provider,
+            );
+            context.flush_key_log(&mut inner.client.key_schedule);
+            inner.transmit(true).await?;
+            result?;
+
+            if !handshake.is_handshaking() {
+                return Ok(());
+            }
+            if handshake.wants_read(&inner.client) {
+                inner.receive().await?;
+            }
+        }
+    }
+
+    /// Encrypt and send the provided slice over the connection.
+    ///
+    /// See [`TlsConnection::write`].
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, TlsError> {
+        self.inner.write(buf).await
+    }
+
+    /// Force all previously written, buffered bytes to be encoded into a tls record and written
+    /// to the connection.
+    pub async fn flush(&mut self) -> Result<(), TlsError> {
+        self.inner.flush().await
+    }
+
+    /// Read and decrypt data filling the provided slice.
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, TlsError> {
+        self.inner.read(buf).await
+    }
+
+    /// Reads buffered data. If nothing is in memory, it'll wait for a TLS record and process it.
+    pub async fn read_buffered(&mut self) -> Result<ReadBuffer<'_>, TlsError> {
+        self.inner.read_buffered().await
+    }
+
+    /// Send an alert to the client.
+    ///
+    /// See [`TlsConnection::send_alert`].
+    pub async fn send_alert(
+        &mut self,
+        level: AlertLevel,
+        description: AlertDescription,
+    ) -> Result<(), TlsError> {
+        self.inner.send_alert(level, description).await
+    }
+
+    /// Send `close_notify` to the client, closing the write side of the connection.
+    pub async fn shutdown_write(&mut self) -> Result<(), TlsError> {
+        self.inner.shutdown_write().await
+    }
+
+    /// Gracefully close a connection instance, returning the ownership of the I/O provider.
+    ///
+    /// See [`TlsConnection::shutdown`].
+    pub async fn shutdown(self) -> Result<Socket, (Socket, TlsError)> {
+        self.inner.shutdown().await
+    }
+
+    /// Close a connection instance, returning the ownership of the async I/O provider.
+    pub async fn close(self) -> Result<Socket, (Socket, TlsError)> {
+        self.inner.close().await
+    }
+
+    /// Split the connection into a reader and a writer that can be used concurrently.
+    pub fn split(
+        &mut self,
+    ) -> (
+        TlsReader<'_, Socket, CipherSuite>,
+        TlsWriter<'_, Socket, CipherSuite>,
+    )
+    where
+        Socket: Clone,
+    {
+        self.inner.split()
+    }
+}
+
+impl<'a, Socket, CipherSuite> ErrorType for TlsServerConnection<'a, Socket, CipherSuite>
+where
+    Socket: AsyncRead + AsyncWrite + 'a,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    type Error = TlsError;
+}
+
+impl<'a, Socket, CipherSuite> AsyncRead for TlsServerConnection<'a, Socket, CipherSuite>
+where
+    Socket: AsyncRead + AsyncWrite + 'a,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        TlsServerConnection::read(self, buf).await
+    }
+}
+
+impl<'a, Socket, CipherSuite> BufRead for TlsServerConnection<'a, Socket, CipherSuite>
+where
+    Socket: AsyncRead + AsyncWrite + 'a,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    async fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+        self.inner.fill_buf().await
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+    }
+}
+
+impl<'a, Socket, CipherSuite> AsyncWrite for TlsServerConnection<'a, Socket, CipherSuite>
+where
+    Socket: AsyncRead + AsyncWrite + 'a,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        TlsServerConnection::write(self, buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        TlsServerConnection::flush(self).await
+    }
+}
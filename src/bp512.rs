@@ -0,0 +1,190 @@
+//! The brainpoolP512r1 curve of RFC 5639, for which no crate is published. Defined over the
+//! same `primefield` and `primeorder` arithmetic as the `bp256` and `bp384` crates, with the
+//! parameters of RFC 5639, Section 3.7.
+
+use bp256::elliptic_curve::{
+    self, CurveArithmetic, PrimeCurveArithmetic,
+    bigint::{Odd, U512},
+    consts::U64,
+    hazmat::FieldArithmetic,
+};
+use primeorder::{PrimeCurveParams, mul_backend, point_arithmetic};
+
+use self::field::FieldElement;
+use self::scalar::Scalar;
+
+const MODULUS_HEX: &str = "aadd9db8dbe9c48b3fd4e6ae33c9fc07cb308db3b3c9d20ed6639cca703308717d4d9b009bc66842aecda12ae6a380e62881ff2f2d82c68528aa6056583a48f3";
+const ORDER_HEX: &str = "aadd9db8dbe9c48b3fd4e6ae33c9fc07cb308db3b3c9d20ed6639cca70330870553e5c414ca92619418661197fac10471db1d381085ddaddb58796829ca90069";
+const ORDER: Odd<U512> = Odd::<U512>::from_be_hex(ORDER_HEX);
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, PartialOrd, Ord)]
+pub struct BrainpoolP512r1;
+
+impl elliptic_curve::Curve for BrainpoolP512r1 {
+    type FieldBytesSize = U64;
+
+    type Uint = U512;
+
+    const ORDER: Odd<U512> = ORDER;
+}
+
+impl elliptic_curve::PrimeCurve for BrainpoolP512r1 {}
+
+impl elliptic_curve::point::PointCompression for BrainpoolP512r1 {
+    const COMPRESS_POINTS: bool = false;
+}
+
+pub(crate) type SecretKey = elliptic_curve::SecretKey<BrainpoolP512r1>;
+
+impl CurveArithmetic for BrainpoolP512r1 {
+    type AffinePoint = primeorder::AffinePoint<Self>;
+    type ProjectivePoint = primeorder::ProjectivePoint<Self>;
+    type Scalar = Scalar;
+}
+
+impl FieldArithmetic for BrainpoolP512r1 {
+    type FieldElement = FieldElement;
+}
+
+impl PrimeCurveArithmetic for BrainpoolP512r1 {
+    type CurveGroup = primeorder::ProjectivePoint<Self>;
+}
+
+impl PrimeCurveParams for BrainpoolP512r1 {
+    type PointArithmetic = point_arithmetic::EquationAIsGeneric;
+    type Backend = mul_backend::VariableOnly;
+
+    const EQUATION_A: FieldElement = FieldElement::from_hex_vartime(
+        "7830a3318b603b89e2327145ac234cc594cbdd8d3df91610a83441caea9863bc2ded5d5aa8253aa10a2ef1c98b9ac8b57f1117a72bf2c7b9e7c1ac4d77fc94ca",
+    );
+    const EQUATION_B: FieldElement = FieldElement::from_hex_vartime(
+        "3df91610a83441caea9863bc2ded5d5aa8253aa10a2ef1c98b9ac8b57f1117a72bf2c7b9e7c1ac4d77fc94cadc083e67984050b75ebae5dd2809bd638016f723",
+    );
+    const GENERATOR: (FieldElement, FieldElement) = (
+        FieldElement::from_hex_vartime(
+            "81aee4bdd82ed9645a21322e9c4c6a9385ed9f70b5d916c1b43b62eef4d0098eff3b1f78e2d0d48d50d1687b93b97d5f7c6d5047406a5e688b352209bcb9f822",
+        ),
+        FieldElement::from_hex_vartime(
+            "7dde385d566332ecc0eabfa9cf7822fdf209f70024a57b1aa000c55b881f8111b2dcde494a5f485e5bca4bd88a2763aed1ca2b2fa8f0540678cd1e0f3ad80892",
+        ),
+    );
+}
+
+impl brainpool_ecdsa::EcdsaCurve for BrainpoolP512r1 {
+    const NORMALIZE_S: bool = false;
+}
+
+mod field {
+    use bp256::elliptic_curve::{
+        bigint::U512,
+        ff::PrimeField,
+        ops::BatchInvert,
+        subtle::{Choice, ConstantTimeEq, CtOption},
+    };
+
+    use super::MODULUS_HEX;
+
+    primefield::monty_field_params! {
+        name: FieldParams,
+        modulus: MODULUS_HEX,
+        uint: U512,
+        byte_order: primefield::ByteOrder::BigEndian,
+        multiplicative_generator: 2,
+        doc: "Montgomery parameters for brainpoolP512's field modulus"
+    }
+
+    primefield::monty_field_element! {
+        name: FieldElement,
+        params: FieldParams,
+        uint: U512,
+        doc: "Element in the brainpoolP512 finite field modulo p"
+    }
+
+    primefield::monty_field_arithmetic! {
+        name: FieldElement,
+        params: FieldParams,
+        uint: U512
+    }
+
+    impl BatchInvert for FieldElement {}
+
+    #[cfg(test)]
+    mod tests {
+        use super::{FieldElement, U512};
+
+        primefield::test_primefield!(FieldElement, U512);
+    }
+}
+
+mod scalar {
+    use bp256::elliptic_curve::{
+        self,
+        bigint::U512,
+        ff::PrimeField,
+        scalar::{FromUintUnchecked, IsHigh},
+        subtle::{Choice, ConstantTimeEq, ConstantTimeGreater, CtOption},
+    };
+    use primeorder::wnaf;
+
+    use super::{BrainpoolP512r1, ORDER, ORDER_HEX};
+
+    primefield::monty_field_params! {
+        name: ScalarParams,
+        modulus: ORDER_HEX,
+        uint: U512,
+        byte_order: primefield::ByteOrder::BigEndian,
+        multiplicative_generator: 7,
+        doc: "Montgomery parameters for brainpoolP512's scalar modulus"
+    }
+
+    primefield::monty_field_element! {
+        name: Scalar,
+        params: ScalarParams,
+        uint: U512,
+        doc: "Element in the brainpoolP512 scalar field modulo n"
+    }
+
+    primefield::monty_field_arithmetic! {
+        name: Scalar,
+        params: ScalarParams,
+        uint: U512
+    }
+
+    primefield::monty_field_reduce! {
+        name: Scalar,
+        params: ScalarParams,
+        uint: U512,
+    }
+
+    elliptic_curve::scalar_impls!(BrainpoolP512r1, Scalar);
+
+    wnaf::impl_wnaf_size_for_scalar!(Scalar);
+
+    impl AsRef<Scalar> for Scalar {
+        fn as_ref(&self) -> &Scalar {
+            self
+        }
+    }
+
+    impl FromUintUnchecked for Scalar {
+        type Uint = U512;
+
+        fn from_uint_unchecked(uint: Self::Uint) -> Self {
+            Self::from_uint_unchecked(uint)
+        }
+    }
+
+    impl IsHigh for Scalar {
+        fn is_high(&self) -> Choice {
+            const MODULUS_SHR1: U512 = ORDER.as_ref().shr_vartime(1);
+            self.to_canonical().ct_gt(&MODULUS_SHR1)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{Scalar, U512};
+
+        primefield::test_primefield!(Scalar, U512);
+    }
+}
@@ -6,6 +6,8 @@ pub struct CryptoBuffer<'b> {
     buf: &'b mut [u8],
     offset: usize,
     len: usize,
+    /// Whether only the length of the encoded data is tracked, see [`Self::counting`].
+    counting: bool,
 }
 
 impl<'b> CryptoBuffer<'b> {
@@ -15,6 +17,15 @@ impl<'b> CryptoBuffer<'b> {
             buf: &mut [],
             offset: 0,
             len: 0,
+            counting: false,
+        }
+    }
+
+    /// A buffer that discards the data, to measure the length of an encoding.
+    pub(crate) fn counting() -> Self {
+        Self {
+            counting: true,
+            ..Self::empty()
         }
     }
 
@@ -23,6 +34,7 @@ impl<'b> CryptoBuffer<'b> {
             buf,
             offset: 0,
             len: 0,
+            counting: false,
         }
     }
 
@@ -31,11 +43,15 @@ impl<'b> CryptoBuffer<'b> {
             buf,
             offset: 0,
             len: pos,
+            counting: false,
         }
     }
 
     pub fn push(&mut self, b: u8) -> Result<(), TlsError> {
-        if self.space() > 0 {
+        if self.counting {
+            self.len += 1;
+            Ok(())
+        } else if self.space() > 0 {
             self.buf[self.offset + self.len] = b;
             self.len += 1;
             Ok(())
@@ -62,7 +78,9 @@ impl<'b> CryptoBuffer<'b> {
 
     fn set(&mut self, idx: usize, val: u8) -> Result<(), TlsError> {
         if idx < self.len {
-            self.buf[self.offset + idx] = val;
+            if !self.counting {
+                self.buf[self.offset + idx] = val;
+            }
             Ok(())
         } else {
             error!(
@@ -89,15 +107,24 @@ impl<'b> CryptoBuffer<'b> {
     }
 
     pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        if self.counting {
+            return &mut [];
+        }
         &mut self.buf[self.offset..self.offset + self.len]
     }
 
     pub fn as_slice(&self) -> &[u8] {
+        if self.counting {
+            return &[];
+        }
         &self.buf[self.offset..self.offset + self.len]
     }
 
     fn extend_internal(&mut self, other: &[u8]) -> Result<(), TlsError> {
-        if self.space() < other.len() {
+        if self.counting {
+            self.len += other.len();
+            Ok(())
+        } else if self.space() < other.len() {
             error!(
                 "Failed to extend buffer. Space: {} required: {}",
                 self.space(),
@@ -121,7 +148,7 @@ impl<'b> CryptoBuffer<'b> {
     }
 
     fn truncate_internal(&mut self, len: usize) {
-        if len <= self.capacity() - self.offset {
+        if self.counting || len <= self.capacity() - self.offset {
             self.len = len;
         }
     }
@@ -165,6 +192,7 @@ impl<'b> CryptoBuffer<'b> {
             buf: self.buf,
             len: new_len,
             offset,
+            counting: self.counting,
         }
     }
 
@@ -178,7 +206,7 @@ impl<'b> CryptoBuffer<'b> {
 
         let r = op(self)?;
 
-        let len = (self.len() - start) as u8;
+        let len = u8::try_from(self.len() - start).map_err(|_| TlsError::EncodeError)?;
         self.set(len_pos, len)?;
 
         Ok(r)
@@ -194,7 +222,7 @@ impl<'b> CryptoBuffer<'b> {
 
         let r = op(self)?;
 
-        let len = (self.len() - start) as u16;
+        let len = u16::try_from(self.len() - start).map_err(|_| TlsError::EncodeError)?;
         self.set_u16(len_pos, len)?;
 
         Ok(r)
@@ -210,7 +238,10 @@ impl<'b> CryptoBuffer<'b> {
 
         let r = op(self)?;
 
-        let len = (self.len() - start) as u32;
+        let len = u32::try_from(self.len() - start)
+            .ok()
+            .filter(|len| *len < 1 << 24)
+            .ok_or(TlsError::EncodeError)?;
         self.set_u24(len_pos, len)?;
 
         Ok(r)
@@ -23,7 +23,7 @@ impl ChangeCipherSpec {
         Ok(Self {})
     }
 
-    #[allow(dead_code, clippy::unused_self)]
+    #[allow(clippy::unused_self)]
     pub(crate) fn encode(self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
         buf.push(1).map_err(|_| TlsError::EncodeError)?;
         Ok(())
@@ -9,6 +9,13 @@ pub enum CipherSuite {
     TlsAes128CcmSha256 = 0x1304,
     TlsAes128Ccm8Sha256 = 0x1305,
     TlsPskAes128GcmSha256 = 0x00A8,
+    TlsEcdheEcdsaWithAes128GcmSha256 = 0xC02B,
+    TlsEcdheEcdsaWithAes256GcmSha384 = 0xC02C,
+    TlsEcdheRsaWithAes128GcmSha256 = 0xC02F,
+    TlsEcdheRsaWithAes256GcmSha384 = 0xC030,
+    TlsSha256Sha256 = 0xC0B4,
+    TlsSha384Sha384 = 0xC0B5,
+    TlsSm4GcmSm3 = 0x00C6,
 }
 
 impl CipherSuite {
@@ -20,6 +27,21 @@ impl CipherSuite {
             v if v == Self::TlsAes128CcmSha256 as u16 => Ok(Self::TlsAes128CcmSha256),
             v if v == Self::TlsAes128Ccm8Sha256 as u16 => Ok(Self::TlsAes128Ccm8Sha256),
             v if v == Self::TlsPskAes128GcmSha256 as u16 => Ok(Self::TlsPskAes128GcmSha256),
+            v if v == Self::TlsEcdheEcdsaWithAes128GcmSha256 as u16 => {
+                Ok(Self::TlsEcdheEcdsaWithAes128GcmSha256)
+            }
+            v if v == Self::TlsEcdheEcdsaWithAes256GcmSha384 as u16 => {
+                Ok(Self::TlsEcdheEcdsaWithAes256GcmSha384)
+            }
+            v if v == Self::TlsEcdheRsaWithAes128GcmSha256 as u16 => {
+                Ok(Self::TlsEcdheRsaWithAes128GcmSha256)
+            }
+            v if v == Self::TlsEcdheRsaWithAes256GcmSha384 as u16 => {
+                Ok(Self::TlsEcdheRsaWithAes256GcmSha384)
+            }
+            v if v == Self::TlsSha256Sha256 as u16 => Ok(Self::TlsSha256Sha256),
+            v if v == Self::TlsSha384Sha384 as u16 => Ok(Self::TlsSha384Sha384),
+            v if v == Self::TlsSm4GcmSm3 as u16 => Ok(Self::TlsSm4GcmSm3),
             _ => Err(ParseError::InvalidData),
         }
     }
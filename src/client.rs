@@ -0,0 +1,708 @@
+use core::marker::PhantomData;
+use core::ops::Range;
+
+use portable_atomic::{AtomicBool, Ordering};
+
+use crate::TlsError;
+use crate::alert::{Alert, AlertDescription, AlertLevel, PendingAlert};
+use crate::common::decrypted_buffer_info::DecryptedBufferInfo;
+use crate::common::decrypted_read_handler::DecryptedReadHandler;
+use crate::config::{
+    CryptoProvider, TlsCipherSuite, TlsConfig, TlsVerifier, min_read_buffer_size,
+    min_write_buffer_size,
+};
+use crate::connection::{Handshake, State, decrypt_record};
+use crate::key_schedule::KeySchedule;
+use crate::key_share_pool::KeyShare;
+use crate::read_buffer::ReadBuffer;
+use crate::record::{ClientRecord, ClientRecordHeader};
+use crate::record_buffer::{RecordBuffer, RecordBuffers};
+use crate::record_reader::RecordReader;
+use crate::write_buffer::WriteBuffer;
+
+/// The TLS client state machine, independent of the transport.
+///
+/// The bytes received from the server are copied into [`TlsClient::rx_buffer`], and the
+/// records to send are taken from [`TlsClient::pending_transmit`], so the client can be driven
+/// from any event loop. [`TlsConnection`](crate::TlsConnection) and
+/// [`blocking::TlsConnection`](crate::blocking::TlsConnection) drive it over `embedded-io`
+/// transports.
+///
+/// Records are encoded one at a time in the write buffer: the pending records must be
+/// transmitted before the next one can be encoded.
+///
+/// A client created with [`TlsClient::new_half_duplex`] uses a single buffer in turn for
+/// reading and writing. The buffers are borrowed for `'a`, unless the client is created with
+/// [`TlsClient::from_buffers`] from arrays it owns.
+pub struct TlsClient<'a, CipherSuite, Rx = &'a mut [u8], Tx = &'a mut [u8]>
+where
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    state: State,
+    handshake: Handshake<CipherSuite>,
+    pub(crate) opened: AtomicBool,
+    pub(crate) pending_alert: PendingAlert,
+    pub(crate) read_closed: bool,
+    pub(crate) write_closed: bool,
+    pub(crate) key_schedule: KeySchedule<CipherSuite>,
+    pub(crate) record_reader: RecordReader<Rx>,
+    pub(crate) record_write_buf: WriteBuffer<Tx>,
+    pub(crate) decrypted: DecryptedBufferInfo,
+    /// The encoded records in the write buffer that were not transmitted yet.
+    tx: Range<usize>,
+    /// The side holding the buffer, for a half-duplex client.
+    shared: Option<SharedBuffer>,
+    _buffers: PhantomData<&'a mut [u8]>,
+}
+
+/// The side of a half-duplex client holding the shared buffer.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SharedBuffer {
+    Read,
+    Write,
+}
+
+/// Warn if a read buffer of `len` bytes cannot fit the largest records, which servers send
+/// unless a max fragment length is negotiated.
+fn warn_if_read_buffer_small<CipherSuite: TlsCipherSuite>(len: usize) {
+    let required = min_read_buffer_size::<CipherSuite>(None, 0);
+    if len < required {
+        warn!(
+            "Read buffer is smaller than {} bytes, which may cause problems!",
+            required
+        );
+    }
+}
+
+impl<'a, CipherSuite> TlsClient<'a, CipherSuite>
+where
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    /// Create a new TLS client with the provided buffers.
+    ///
+    /// See [`TlsConnection::new`](crate::TlsConnection::new) for how to size the buffers.
+    pub fn new(record_read_buf: &'a mut [u8], record_write_buf: &'a mut [u8]) -> Self {
+        Self::from_buffers(record_read_buf, record_write_buf)
+    }
+
+    /// Create a new TLS client using a single buffer for reading and writing.
+    ///
+    /// The buffer is used half-duplex: written data must be flushed and transmitted before
+    /// reading, and the received data must be read before writing, or the call fails with
+    /// [`TlsError::BufferInUse`]. It must fit the largest record in either direction.
+    pub fn new_half_duplex(record_buf: &'a mut [u8]) -> Self {
+        warn_if_read_buffer_small::<CipherSuite>(record_buf.len());
+        let mut record_reader = RecordReader::new(record_buf);
+        let record_write_buf = WriteBuffer::new(record_reader.take_buf());
+        Self {
+            state: State::ClientHello,
+            handshake: Handshake::new(),
+            opened: AtomicBool::new(false),
+            pending_alert: PendingAlert::new(),
+            read_closed: false,
+            write_closed: false,
+            key_schedule: KeySchedule::new(),
+            record_reader,
+            record_write_buf,
+            decrypted: DecryptedBufferInfo::default(),
+            tx: 0..0,
+            shared: Some(SharedBuffer::Write),
+            _buffers: PhantomData,
+        }
+    }
+
+    /// Reset the client, and return its buffers.
+    pub fn into_buffers(self) -> RecordBuffers<'a> {
+        if self.is_half_duplex() {
+            let (_, record_buf) = self.release();
+            RecordBuffers::HalfDuplex(record_buf)
+        } else {
+            let (record_read_buf, record_write_buf) = self.release();
+            RecordBuffers::FullDuplex(record_read_buf, record_write_buf)
+        }
+    }
+}
+
+impl<CipherSuite, const RX: usize, const TX: usize> TlsClient<'_, CipherSuite, [u8; RX], [u8; TX]>
+where
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    /// Reset the client, and return the read and write buffers.
+    pub fn into_buffers(self) -> ([u8; RX], [u8; TX]) {
+        self.release()
+    }
+}
+
+impl<'a, CipherSuite, Rx, Tx> TlsClient<'a, CipherSuite, Rx, Tx>
+where
+    CipherSuite: TlsCipherSuite + 'static,
+    Rx: RecordBuffer<'a>,
+    Tx: RecordBuffer<'a>,
+{
+    /// Create a new TLS client with the provided buffer storage, such as arrays owned by the
+    /// client.
+    ///
+    /// See [`TlsConnection::new`](crate::TlsConnection::new) for how to size the buffers.
+    pub fn from_buffers(record_read_buf: Rx, record_write_buf: Tx) -> Self {
+        warn_if_read_buffer_small::<CipherSuite>(record_read_buf.as_ref().len());
+        Self {
+            state: State::ClientHello,
+            handshake: Handshake::new(),
+            opened: AtomicBool::new(false),
+            pending_alert: PendingAlert::new(),
+            read_closed: false,
+            write_closed: false,
+            key_schedule: KeySchedule::new(),
+            record_reader: RecordReader::new(record_read_buf),
+            record_write_buf: WriteBuffer::new(record_write_buf),
+            decrypted: DecryptedBufferInfo::default(),
+            tx: 0..0,
+            shared: None,
+            _buffers: PhantomData,
+        }
+    }
+
+    /// Return to the initial state, so that a new handshake can be performed, for instance
+    /// over a new transport after the connection was closed or failed.
+    ///
+    /// The secrets of the key schedule and the buffers are zeroized.
+    pub fn reset(&mut self) {
+        self.hand_over_to_writer();
+
+        self.state = State::ClientHello;
+        self.handshake = Handshake::new();
+        *self.opened.get_mut() = false;
+        self.pending_alert.clear();
+        self.read_closed = false;
+        self.write_closed = false;
+        self.key_schedule.reset();
+        self.record_reader.reset();
+        self.record_write_buf.reset();
+        self.decrypted = DecryptedBufferInfo::default();
+        self.tx = 0..0;
+    }
+
+    /// Reset the client, and return the read and write buffers. The buffer of a half-duplex
+    /// client is held by the write side.
+    fn release(mut self) -> (Rx, Tx) {
+        self.reset();
+        (self.record_reader.buf, self.record_write_buf.into_buf())
+    }
+
+    /// Whether the client uses a single buffer for reading and writing.
+    pub fn is_half_duplex(&self) -> bool {
+        self.shared.is_some()
+    }
+
+    /// Whether the handshake completed and the connection was not closed by a fatal alert.
+    pub fn is_opened(&self) -> bool {
+        self.opened.load(Ordering::Acquire)
+    }
+
+    /// Whether the handshake has not completed yet.
+    pub fn is_handshaking(&self) -> bool {
+        self.state != State::ApplicationData
+    }
+
+    /// Fill `out` with keying material exported from the connection with `label` and
+    /// `context`, see [`TlsConnection::export_keying_material`].
+    ///
+    /// [`TlsConnection::export_keying_material`]: crate::TlsConnection::export_keying_material
+    pub fn export_keying_material(
+        &self,
+        label: &[u8],
+        context: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), TlsError> {
+        if self.is_handshaking() {
+            return Err(TlsError::MissingHandshake);
+        }
+        self.key_schedule
+            .export_keying_material(label, context, out)
+    }
+
+    /// Use `key_share`, generated ahead of time, for the next handshake instead of generating
+    /// one when the `ClientHello` is encoded.
+    ///
+    /// The key share is dropped if the handshake has already started.
+    pub fn set_key_share(&mut self, key_share: KeyShare) {
+        if self.wants_key_share() {
+            self.handshake.secret = Some(key_share.0);
+        }
+    }
+
+    /// Whether the next handshake has no key share yet, see [`Self::set_key_share`].
+    pub(crate) fn wants_key_share(&self) -> bool {
+        self.state == State::ClientHello && self.handshake.secret.is_none()
+    }
+
+    /// Whether the handshake waits for more bytes from the server.
+    pub fn wants_read(&self) -> bool {
+        self.state.is_reading() && self.tx.is_empty() && !self.record_reader.is_complete()
+    }
+
+    /// The encoded records to send to the server.
+    pub fn pending_transmit(&self) -> &[u8] {
+        self.record_write_buf.encoded(self.tx.clone())
+    }
+
+    /// The first `len` bytes of the write buffer hold a record encoded by another state
+    /// machine, to be transmitted.
+    pub(crate) fn set_pending_transmit(&mut self, len: usize) {
+        self.tx = 0..len;
+    }
+
+    /// The first `len` bytes of [`Self::pending_transmit`] were sent.
+    pub fn transmitted(&mut self, len: usize) {
+        self.tx.start = usize::min(self.tx.start + len, self.tx.end);
+        if self.tx.is_empty() {
+            self.tx = 0..0;
+        }
+    }
+
+    /// The buffer to receive the next bytes from the server into.
+    ///
+    /// It only spans the rest of the record being received, and is empty while decrypted
+    /// application data is waiting to be read, or while the buffer of a half-duplex client is
+    /// in use for writing.
+    pub fn rx_buffer(&mut self) -> &mut [u8] {
+        if self.decrypted.is_empty() && self.claim_read_buffer().is_ok() {
+            self.record_reader.rx_buffer()
+        } else {
+            &mut []
+        }
+    }
+
+    /// `len` bytes were received into [`Self::rx_buffer`].
+    pub fn receive(&mut self, len: usize) -> Result<(), TlsError> {
+        self.record_reader.receive(len).map_err(|e| self.abort(e))
+    }
+
+    /// Pass the secrets derived by [`Self::handle_handshake`] to `key_log`. To be called after
+    /// each call when the handshake is driven directly rather than with a
+    /// [`TlsContext`](crate::TlsContext).
+    #[cfg(feature = "key-log")]
+    pub fn flush_key_log(&mut self, key_log: &mut dyn crate::KeyLog) {
+        self.key_schedule.flush_key_log(Some(key_log));
+    }
+
+    /// Make progress on the handshake: process the received records, and encode the records
+    /// of the client flight.
+    ///
+    /// Returns once a record is pending transmission, more bytes must be received (see
+    /// [`Self::wants_read`]) or the handshake is complete.
+    pub fn handle_handshake<Provider>(
+        &mut self,
+        config: &TlsConfig,
+        crypto_provider: &mut Provider,
+    ) -> Result<(), TlsError>
+    where
+        Provider: CryptoProvider<CipherSuite = CipherSuite>,
+    {
+        if self.state == State::ClientHello
+            && let (Ok(verifier), Some(server_name)) =
+                (crypto_provider.verifier(), config.server_name)
+        {
+            verifier.set_hostname_verification(server_name)?;
+        }
+        if self.state == State::ClientHello {
+            self.validate_buffers(config)?;
+            let streaming = crypto_provider
+                .verifier()
+                .map_or(true, |verifier| verifier.streams_certificates());
+            self.record_reader.set_certificate_streaming(streaming);
+        }
+
+        while self.tx.is_empty() && self.state != State::ApplicationData {
+            let state = self.state;
+            if state.is_reading() {
+                self.claim_read_buffer()?;
+                let result = match self
+                    .record_reader
+                    .next_record(self.key_schedule.read_state())
+                {
+                    Ok(Some(record)) => state.process_record(
+                        &mut self.handshake,
+                        &mut self.key_schedule,
+                        config,
+                        crypto_provider,
+                        record,
+                    ),
+                    Ok(None) => return Ok(()),
+                    Err(e) => Err(e),
+                };
+                self.state = result.map_err(|e| self.abort(e))?;
+            } else {
+                self.claim_write_buffer(false)?;
+                let (next, tx) = state.write_record(
+                    &mut self.handshake,
+                    &mut self.key_schedule,
+                    config,
+                    crypto_provider,
+                    &mut self.record_write_buf,
+                )?;
+                self.tx = 0..tx.len();
+                // TLS 1.3 only counts protected records
+                if state != State::ChangeCipherSpec {
+                    self.key_schedule.write_state().increment_counter();
+                }
+                self.state =
+                    state.record_written(next?, &mut self.handshake, &mut self.key_schedule)?;
+            }
+            trace!("State {:?} -> {:?}", state, self.state);
+
+            if self.state == State::ApplicationData {
+                self.set_opened();
+            }
+        }
+        Ok(())
+    }
+
+    /// Mark the handshake as complete, once it has been performed by another state machine.
+    pub(crate) fn set_opened(&mut self) {
+        self.state = State::ApplicationData;
+        self.read_closed = false;
+        self.write_closed = false;
+        *self.opened.get_mut() = true;
+    }
+
+    /// Encrypt the provided slice into the current application data record. The client must be
+    /// opened before writing.
+    ///
+    /// The record is only encoded once full, or by [`Self::flush`]. Returns the number of bytes
+    /// buffered, which is 0 while records are pending transmission.
+    pub fn write(&mut self, buf: &[u8]) -> Result<usize, TlsError> {
+        if !self.is_opened() {
+            return Err(TlsError::MissingHandshake);
+        }
+        if self.write_closed {
+            return Err(TlsError::ConnectionClosed);
+        }
+        if !self.tx.is_empty() {
+            return Ok(0);
+        }
+        self.claim_write_buffer(false)?;
+
+        if !self
+            .record_write_buf
+            .contains(ClientRecordHeader::ApplicationData)
+        {
+            self.flush()?;
+            if !self.tx.is_empty() {
+                return Ok(0);
+            }
+            self.record_write_buf
+                .start_record(ClientRecordHeader::ApplicationData)?;
+        }
+
+        let buffered = self.record_write_buf.append(buf);
+
+        if self.record_write_buf.is_full() {
+            self.flush()?;
+        }
+
+        Ok(buffered)
+    }
+
+    /// Encode all previously written, buffered bytes into a record to transmit.
+    ///
+    /// Fails with [`TlsError::InsufficientSpace`] if records are still pending transmission.
+    pub fn flush(&mut self) -> Result<(), TlsError> {
+        if self.record_write_buf.is_empty() {
+            return Ok(());
+        }
+        if !self.tx.is_empty() {
+            return Err(TlsError::InsufficientSpace);
+        }
+
+        let key_schedule = self.key_schedule.write_state();
+        match self.record_write_buf.close_record(key_schedule) {
+            Ok(slice) => {
+                self.tx = 0..slice.len();
+                key_schedule.increment_counter();
+                Ok(())
+            }
+            Err(e) => Err(self.abort(e)),
+        }
+    }
+
+    /// Decrypt the received records until application data is available, or the server has
+    /// closed its side of the connection.
+    ///
+    /// Returns `false` if more bytes must be received first.
+    pub fn poll_read(&mut self) -> Result<bool, TlsError> {
+        if !self.is_opened() {
+            return Err(TlsError::MissingHandshake);
+        }
+        if self.decrypted.is_empty() && !self.read_closed {
+            self.claim_read_buffer()?;
+        }
+
+        while self.decrypted.is_empty() && !self.read_closed {
+            if !self.record_reader.is_complete() {
+                return Ok(false);
+            }
+            self.decrypt_application_data().map_err(|e| self.abort(e))?;
+        }
+        Ok(true)
+    }
+
+    fn decrypt_application_data(&mut self) -> Result<(), TlsError> {
+        let buf_ptr_range = self.record_reader.buf.as_ref().as_ptr_range();
+        let Some(record) = self
+            .record_reader
+            .next_record(self.key_schedule.read_state())?
+        else {
+            return Ok(());
+        };
+
+        let mut handler = DecryptedReadHandler {
+            source_buffer: buf_ptr_range,
+            buffer_info: &mut self.decrypted,
+            is_open: self.opened.get_mut(),
+            read_closed: &mut self.read_closed,
+        };
+        decrypt_record(
+            self.key_schedule.read_state(),
+            record,
+            |_key_schedule, record| handler.handle(record),
+        )
+    }
+
+    /// The application data decrypted by [`Self::poll_read`].
+    ///
+    /// This is empty once the server has closed its side of the connection with
+    /// `close_notify`.
+    pub fn read_buffer(&mut self) -> ReadBuffer<'_> {
+        self.decrypted
+            .create_read_buffer(self.record_reader.buf.as_mut())
+    }
+
+    /// Read decrypted application data, filling the provided slice.
+    ///
+    /// Returns `None` if more bytes must be received first, and `Some(0)` once the server has
+    /// closed its side of the connection with `close_notify`.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<Option<usize>, TlsError> {
+        if !self.poll_read()? {
+            return Ok(None);
+        }
+        Ok(Some(self.read_buffer().pop_into(buf)))
+    }
+
+    /// Encode an alert to send to the server.
+    ///
+    /// Buffered data is discarded, so it must be flushed and transmitted first. Sending a fatal
+    /// alert closes the connection, after which reads and writes fail with
+    /// [`TlsError::MissingHandshake`].
+    pub fn send_alert(
+        &mut self,
+        level: AlertLevel,
+        description: AlertDescription,
+    ) -> Result<(), TlsError> {
+        if !self.is_opened() {
+            return Err(TlsError::MissingHandshake);
+        }
+        if self.write_closed {
+            return Err(TlsError::ConnectionClosed);
+        }
+
+        self.write_alert(&Alert::new(level, description))
+    }
+
+    /// Encode `close_notify`, closing the write side of the connection.
+    ///
+    /// Like for [`Self::send_alert`], buffered data must be flushed and transmitted first.
+    /// Reading keeps working until the server closes its side as well.
+    pub fn shutdown_write(&mut self) -> Result<(), TlsError> {
+        if !self.is_opened() {
+            return Err(TlsError::MissingHandshake);
+        }
+
+        if !self.write_closed {
+            self.write_alert(&Alert::new(
+                AlertLevel::Warning,
+                AlertDescription::CloseNotify,
+            ))?;
+            self.write_closed = true;
+        }
+
+        Ok(())
+    }
+
+    /// Encode `close_notify` to close the connection, even if the handshake did not complete.
+    ///
+    /// Buffered data must be flushed and transmitted first.
+    pub fn close(&mut self) -> Result<(), TlsError> {
+        if self.write_closed {
+            return Ok(());
+        }
+        if !self.tx.is_empty() {
+            return Err(TlsError::InsufficientSpace);
+        }
+        self.claim_write_buffer(true)?;
+
+        let is_opened = self.is_opened();
+        let (write_key_schedule, read_key_schedule) = self.key_schedule.as_split();
+        let slice = self.record_write_buf.write_record(
+            &ClientRecord::close_notify(is_opened),
+            write_key_schedule,
+            Some(read_key_schedule),
+        )?;
+        self.tx = 0..slice.len();
+        write_key_schedule.increment_counter();
+        self.write_closed = true;
+
+        Ok(())
+    }
+
+    fn write_alert(&mut self, alert: &Alert) -> Result<(), TlsError> {
+        if !self.tx.is_empty() {
+            return Err(TlsError::InsufficientSpace);
+        }
+        self.claim_write_buffer(matches!(alert.level, AlertLevel::Fatal))?;
+        if let AlertLevel::Fatal = alert.level {
+            *self.opened.get_mut() = false;
+        }
+
+        let key_schedule = self.key_schedule.write_state();
+        let slice = self.record_write_buf.write_alert(alert, key_schedule)?;
+        self.tx = 0..slice.len();
+        key_schedule.increment_counter();
+
+        Ok(())
+    }
+
+    /// Encode an alert for the peer to tell it why the handshake failed.
+    fn write_handshake_alert(&mut self, alert: Alert, encrypted: bool) -> Result<(), TlsError> {
+        if !self.tx.is_empty() {
+            return Err(TlsError::InsufficientSpace);
+        }
+        self.claim_write_buffer(true)?;
+
+        let (write_key_schedule, read_key_schedule) = self.key_schedule.as_split();
+        let slice = self.record_write_buf.write_record(
+            &ClientRecord::Alert(alert, encrypted),
+            write_key_schedule,
+            Some(read_key_schedule),
+        )?;
+        self.tx = 0..slice.len();
+        write_key_schedule.increment_counter();
+
+        Ok(())
+    }
+
+    /// Check that the buffers fit the records of the handshake configured by `config`, before
+    /// it starts.
+    fn validate_buffers(&self, config: &TlsConfig) -> Result<(), TlsError> {
+        let (read_len, write_len) = match self.shared {
+            Some(_) => {
+                let len = self.record_reader.buf.as_ref().len() + self.record_write_buf.capacity();
+                (len, len)
+            }
+            None => (
+                self.record_reader.buf.as_ref().len(),
+                self.record_write_buf.capacity(),
+            ),
+        };
+
+        let required =
+            min_write_buffer_size::<CipherSuite>(config.client_hello_len::<CipherSuite>()?);
+        if write_len < required {
+            error!(
+                "Write buffer too small. Size: {} Required: {}",
+                write_len, required
+            );
+            return Err(TlsError::WriteBufferTooSmall(required));
+        }
+
+        // Without a max fragment length, smaller buffers still work with servers sending
+        // smaller records
+        if let Some(max_fragment_length) = config.max_fragment_length {
+            let required = min_read_buffer_size::<CipherSuite>(Some(max_fragment_length), 0);
+            if read_len < required {
+                error!(
+                    "Read buffer too small. Size: {} Required: {}",
+                    read_len, required
+                );
+                return Err(TlsError::ReadBufferTooSmall(required));
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand the buffer of a half-duplex client over to the read side, once the written data
+    /// has been flushed and transmitted.
+    pub(crate) fn claim_read_buffer(&mut self) -> Result<(), TlsError> {
+        if self.shared != Some(SharedBuffer::Write) {
+            return Ok(());
+        }
+        if !self.tx.is_empty() || !self.record_write_buf.is_empty() {
+            error!("Written data must be flushed before reading from a half-duplex connection");
+            return Err(TlsError::BufferInUse);
+        }
+
+        self.record_reader.set_buf(self.record_write_buf.take_buf());
+        self.shared = Some(SharedBuffer::Read);
+        Ok(())
+    }
+
+    /// Hand the buffer of a half-duplex client over to the write side, once the received data
+    /// has been read, unless it is to be discarded.
+    pub(crate) fn claim_write_buffer(&mut self, discard: bool) -> Result<(), TlsError> {
+        if self.shared != Some(SharedBuffer::Read) {
+            return Ok(());
+        }
+        if !discard && (!self.decrypted.is_empty() || !self.record_reader.is_idle()) {
+            error!("Received data must be read before writing to a half-duplex connection");
+            return Err(TlsError::BufferInUse);
+        }
+
+        self.hand_over_to_writer();
+        Ok(())
+    }
+
+    /// Hand the buffer of a half-duplex client over to the write side, discarding the received
+    /// data.
+    fn hand_over_to_writer(&mut self) {
+        if self.shared == Some(SharedBuffer::Read) {
+            self.decrypted = DecryptedBufferInfo::default();
+            self.record_write_buf.set_buf(self.record_reader.take_buf());
+            self.shared = Some(SharedBuffer::Write);
+        }
+    }
+
+    /// Tear down a handshake driven by another state machine after a fatal error, encoding an
+    /// alert, encrypted if `encrypted` is set, to tell the peer why if possible.
+    pub(crate) fn abort_handshake(&mut self, error: TlsError, encrypted: bool) -> TlsError {
+        if let Some(alert) = Alert::for_error(error) {
+            let description = alert.description;
+            if let Err(e) = self.write_handshake_alert(alert, encrypted) {
+                warn!("Failed to send {:?} alert: {:?}", description, e);
+            }
+        }
+
+        error
+    }
+
+    /// Tear down the connection after a fatal error, encoding an alert to tell the server why
+    /// if possible.
+    fn abort(&mut self, error: TlsError) -> TlsError {
+        let Some(alert) = Alert::for_error(error) else {
+            return error;
+        };
+
+        let description = alert.description;
+        let result = if self.state.is_reading() {
+            self.write_handshake_alert(alert, self.state.encrypts_alerts())
+        } else if self.is_opened() && !self.write_closed {
+            self.write_alert(&alert)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            warn!("Failed to send {:?} alert: {:?}", description, e);
+        }
+
+        error
+    }
+}
@@ -2,13 +2,14 @@ use core::ops::Range;
 
 use crate::{
     TlsError, alert::AlertDescription, common::decrypted_buffer_info::DecryptedBufferInfo,
-    config::TlsCipherSuite, handshake::ServerHandshake, record::ServerRecord,
+    config::TlsCipherSuite, handshake::IncomingHandshake, record::ServerRecord,
 };
 
 pub struct DecryptedReadHandler<'a> {
     pub source_buffer: Range<*const u8>,
     pub buffer_info: &'a mut DecryptedBufferInfo,
     pub is_open: &'a mut bool,
+    pub read_closed: &'a mut bool,
 }
 
 impl DecryptedReadHandler<'_> {
@@ -41,16 +42,28 @@ impl DecryptedReadHandler<'_> {
                 self.buffer_info.consumed = 0;
                 Ok(())
             }
-            ServerRecord::Alert(alert) => {
-                if let AlertDescription::CloseNotify = alert.description {
+            ServerRecord::Alert(alert) => match alert.description {
+                AlertDescription::CloseNotify => {
+                    // The server won't send any more data, but we may still write.
+                    *self.read_closed = true;
+                    Ok(())
+                }
+                AlertDescription::UserCanceled => {
+                    // RFC 8446, Section 6.1: user_canceled is followed by a close_notify, which
+                    // is what actually closes the connection.
+                    debug!("Server canceled the connection");
+                    Ok(())
+                }
+                description => {
+                    // RFC 8446, Section 6: all other alerts are treated as error alerts,
+                    // regardless of the level in the message.
+                    warn!("Received alert: {:?} {:?}", alert.level, description);
                     *self.is_open = false;
-                    Err(TlsError::ConnectionClosed)
-                } else {
-                    Err(TlsError::InternalError)
+                    Err(TlsError::HandshakeAborted(alert.level, description))
                 }
-            }
-            ServerRecord::ChangeCipherSpec(_) => Err(TlsError::InternalError),
-            ServerRecord::Handshake(ServerHandshake::NewSessionTicket(_)) => {
+            },
+            ServerRecord::ChangeCipherSpec(_) => Err(TlsError::InvalidRecord),
+            ServerRecord::Handshake(IncomingHandshake::NewSessionTicket(_)) => {
                 // TODO: we should validate extensions and abort. We can do this automatically
                 // as long as the connection is unsplit, however, split connections must be aborted
                 // by the user.
@@ -1,26 +1,111 @@
 use core::marker::PhantomData;
+use core::ops::Deref;
+use core::time::Duration;
 
 use crate::TlsError;
 use crate::cipher_suites::CipherSuite;
+use crate::extensions::ExtensionType;
+use crate::extensions::extension_data::alpn::AlpnProtocols;
 use crate::extensions::extension_data::signature_algorithms::SignatureScheme;
-use crate::extensions::extension_data::supported_groups::NamedGroup;
+pub use crate::extensions::extension_data::supported_groups::NamedGroup;
+use crate::grease;
+use crate::handshake::HANDSHAKE_HEADER_LEN;
 pub use crate::handshake::certificate::{CertificateEntryRef, CertificateRef};
 pub use crate::handshake::certificate_verify::CertificateVerifyRef;
-use aes_gcm::{AeadInPlace, Aes128Gcm, Aes256Gcm, KeyInit};
+use crate::handshake::client_hello::ClientHello;
+use crate::key_exchange::EphemeralSecret;
+#[cfg(feature = "key-log")]
+use crate::key_log::{KeyLog, KeyLogRef};
+use crate::key_schedule::KeySchedule;
+use crate::key_share_pool::{KeyShare, KeySharePool};
+use crate::owned;
+use crate::record::RecordHeader;
+use aes_gcm::{AeadCore, AeadInPlace, Aes128Gcm, Aes256Gcm, KeyInit};
 use digest::core_api::BlockSizeUser;
 use digest::{Digest, FixedOutput, OutputSizeUser, Reset};
 use ecdsa::elliptic_curve::SecretKey;
 use generic_array::ArrayLength;
 use heapless::Vec;
+use heapless::vec::VecView;
 use p256::ecdsa::SigningKey;
 use rand_core::CryptoRngCore;
 pub use sha2::{Sha256, Sha384};
-use typenum::{Sum, U10, U12, U16, U32};
+use typenum::{Sum, U10, U12, U16, U32, Unsigned};
 
 pub use crate::extensions::extension_data::max_fragment_length::MaxFragmentLength;
 
 pub const TLS_RECORD_OVERHEAD: usize = 128;
 
+/// The largest plaintext fragment of a record, unless a smaller one is negotiated.
+const MAX_FRAGMENT_LEN: usize = 16384;
+
+/// The size of the handshake header, request context and list length of a `Certificate` message.
+const CERTIFICATE_MESSAGE_OVERHEAD: usize = HANDSHAKE_HEADER_LEN + 1 + 3;
+
+/// The longest connection ID sent to the peer, see [`TlsConfig::with_connection_id`].
+pub(crate) const MAX_CONNECTION_ID_LEN: usize = 20;
+
+/// The number of bytes a record protected with `CipherSuite` adds to its plaintext, header
+/// included.
+#[must_use]
+pub const fn record_overhead<CipherSuite: TlsCipherSuite>() -> usize {
+    // TLS 1.3 appends the content type, TLS 1.2 prepends an explicit nonce
+    let expansion = if cfg!(feature = "tls12") { 8 } else { 1 };
+    RecordHeader::LEN + expansion + <<CipherSuite::Cipher as AeadCore>::TagSize as Unsigned>::USIZE
+}
+
+/// The minimum size of the read buffer for a connection with `CipherSuite`.
+///
+/// The buffer must fit a record with the largest plaintext fragment, which is bounded by
+/// `max_fragment_length` if one is configured. The padding of TLS 1.3 records counts towards
+/// the fragment length (RFC 8446, Section 5.4), so it needs no extra room.
+///
+/// A handshake message split across records is held in the buffer until the record completing
+/// it is received. `max_certificate_chain_len` is the size of the server certificate chain: the
+/// DER certificates plus 5 bytes each, or 0 with a PSK. If the [`TlsVerifier`] streams
+/// certificates, only the largest certificate of the chain counts.
+#[must_use]
+pub const fn min_read_buffer_size<CipherSuite: TlsCipherSuite>(
+    max_fragment_length: Option<MaxFragmentLength>,
+    max_certificate_chain_len: usize,
+) -> usize {
+    let fragment_len = match max_fragment_length {
+        Some(max_fragment_length) => max_fragment_length.fragment_len(),
+        None => MAX_FRAGMENT_LEN,
+    };
+    // The record header is received apart from the record
+    let record_len = fragment_len + record_overhead::<CipherSuite>() - RecordHeader::LEN;
+
+    if max_certificate_chain_len == 0 {
+        record_len
+    } else {
+        record_len + CERTIFICATE_MESSAGE_OVERHEAD + max_certificate_chain_len
+    }
+}
+
+/// The minimum size of the write buffer for a connection with `CipherSuite`, sending a
+/// `ClientHello` of `client_hello_len` bytes (see [`TlsConfig::client_hello_len`]).
+///
+/// Application data is split into records of the buffer size minus [`TLS_RECORD_OVERHEAD`], so
+/// a larger buffer writes fewer records. A client certificate must fit in the buffer as well.
+#[must_use]
+pub const fn min_write_buffer_size<CipherSuite: TlsCipherSuite>(client_hello_len: usize) -> usize {
+    let client_hello = RecordHeader::LEN + client_hello_len;
+    let finished = HANDSHAKE_HEADER_LEN
+        + <<CipherSuite::Hash as OutputSizeUser>::OutputSize as Unsigned>::USIZE
+        + record_overhead::<CipherSuite>();
+    let application_data = TLS_RECORD_OVERHEAD + 1;
+
+    let mut size = client_hello;
+    if finished > size {
+        size = finished;
+    }
+    if application_data > size {
+        size = application_data;
+    }
+    size
+}
+
 // longest label is 12b -> buf <= 2 + 1 + 6 + longest + 1 + hash_out = hash_out + 22
 type LongestLabel = U12;
 type LabelOverhead = U10;
@@ -38,28 +123,122 @@ pub trait TlsCipherSuite {
 
     type Hash: Digest + Reset + Clone + OutputSizeUser + BlockSizeUser + FixedOutput;
     type LabelBufferSize: ArrayLength<u8>;
+
+    /// Compute the DTLS 1.3 record number encryption mask from the `sn_key` and the first 16
+    /// bytes of the record ciphertext (RFC 9147, Section 4.2.3).
+    ///
+    /// Cipher suites that are not used with DTLS can keep the default, which fails.
+    fn record_number_mask(key: &[u8], sample: &[u8; 16]) -> Result<[u8; 16], TlsError> {
+        let _ = (key, sample);
+        Err(TlsError::Unimplemented)
+    }
+
+    /// The TLS 1.2 cipher suites using the same AEAD and PRF hash, offered along with
+    /// [`Self::CODE_POINT`] when the `tls12` feature is enabled.
+    #[cfg(feature = "tls12")]
+    const TLS12_CODE_POINTS: &'static [u16] = &[];
+}
+
+fn aes_record_number_mask<Cipher>(key: &[u8], sample: &[u8; 16]) -> Result<[u8; 16], TlsError>
+where
+    Cipher: aes_gcm::aes::cipher::BlockEncrypt + aes_gcm::aes::cipher::KeyInit,
+    Cipher: aes_gcm::aes::cipher::BlockSizeUser<BlockSize = U16>,
+{
+    let cipher = Cipher::new_from_slice(key).map_err(|_| TlsError::CryptoError)?;
+    let mut block = (*sample).into();
+    cipher.encrypt_block(&mut block);
+    Ok(block.into())
 }
 
 pub struct Aes128GcmSha256;
 impl TlsCipherSuite for Aes128GcmSha256 {
     const CODE_POINT: u16 = CipherSuite::TlsAes128GcmSha256 as u16;
+    #[cfg(feature = "tls12")]
+    const TLS12_CODE_POINTS: &'static [u16] = &[
+        CipherSuite::TlsEcdheEcdsaWithAes128GcmSha256 as u16,
+        CipherSuite::TlsEcdheRsaWithAes128GcmSha256 as u16,
+    ];
     type Cipher = Aes128Gcm;
     type KeyLen = U16;
     type IvLen = U12;
 
     type Hash = Sha256;
     type LabelBufferSize = LabelBuffer<Self>;
+
+    fn record_number_mask(key: &[u8], sample: &[u8; 16]) -> Result<[u8; 16], TlsError> {
+        aes_record_number_mask::<aes_gcm::aes::Aes128>(key, sample)
+    }
 }
 
 pub struct Aes256GcmSha384;
 impl TlsCipherSuite for Aes256GcmSha384 {
     const CODE_POINT: u16 = CipherSuite::TlsAes256GcmSha384 as u16;
+    #[cfg(feature = "tls12")]
+    const TLS12_CODE_POINTS: &'static [u16] = &[
+        CipherSuite::TlsEcdheEcdsaWithAes256GcmSha384 as u16,
+        CipherSuite::TlsEcdheRsaWithAes256GcmSha384 as u16,
+    ];
     type Cipher = Aes256Gcm;
     type KeyLen = U32;
     type IvLen = U12;
 
     type Hash = Sha384;
     type LabelBufferSize = LabelBuffer<Self>;
+
+    fn record_number_mask(key: &[u8], sample: &[u8; 16]) -> Result<[u8; 16], TlsError> {
+        aes_record_number_mask::<aes_gcm::aes::Aes256>(key, sample)
+    }
+}
+
+/// The integrity-only `TLS_SHA256_SHA256` cipher suite (RFC 9150), which authenticates records
+/// with HMAC-SHA256 but does not encrypt them.
+///
+/// Only for deployments where the traffic must be inspectable, and only supported by TLS 1.3.
+#[cfg(feature = "integrity-only")]
+pub struct Sha256Sha256;
+#[cfg(feature = "integrity-only")]
+impl TlsCipherSuite for Sha256Sha256 {
+    const CODE_POINT: u16 = CipherSuite::TlsSha256Sha256 as u16;
+    type Cipher = crate::HmacIntegrity<Sha256>;
+    type KeyLen = U32;
+    type IvLen = U32;
+
+    type Hash = Sha256;
+    type LabelBufferSize = LabelBuffer<Self>;
+}
+
+/// The integrity-only `TLS_SHA384_SHA384` cipher suite (RFC 9150), which authenticates records
+/// with HMAC-SHA384 but does not encrypt them.
+///
+/// Only for deployments where the traffic must be inspectable, and only supported by TLS 1.3.
+#[cfg(feature = "integrity-only")]
+pub struct Sha384Sha384;
+#[cfg(feature = "integrity-only")]
+impl TlsCipherSuite for Sha384Sha384 {
+    const CODE_POINT: u16 = CipherSuite::TlsSha384Sha384 as u16;
+    type Cipher = crate::HmacIntegrity<Sha384>;
+    type KeyLen = typenum::U48;
+    type IvLen = typenum::U48;
+
+    type Hash = Sha384;
+    type LabelBufferSize = LabelBuffer<Self>;
+}
+
+/// The `TLS_SM4_GCM_SM3` cipher suite (RFC 8998), SM4 in GCM mode with SM3.
+///
+/// Usually combined with the [`NamedGroup::CurveSm2`] key exchange and the
+/// [`SignatureScheme::Sm2sigSm3`] signatures. Only supported by TLS 1.3.
+#[cfg(feature = "shangmi")]
+pub struct Sm4GcmSm3;
+#[cfg(feature = "shangmi")]
+impl TlsCipherSuite for Sm4GcmSm3 {
+    const CODE_POINT: u16 = CipherSuite::TlsSm4GcmSm3 as u16;
+    type Cipher = aes_gcm::AesGcm<sm4::Sm4, U12>;
+    type KeyLen = U16;
+    type IvLen = U12;
+
+    type Hash = sm3::Sm3;
+    type LabelBufferSize = LabelBuffer<Self>;
 }
 
 /// A TLS 1.3 verifier.
@@ -85,10 +264,47 @@ where
         cert: CertificateRef,
     ) -> Result<(), TlsError>;
 
+    /// Whether the server certificate is passed entry by entry to
+    /// [`Self::verify_certificate_entry`] and [`Self::verify_certificate_chain`] rather than as a
+    /// whole to [`Self::verify_certificate`].
+    ///
+    /// Streaming lets the client receive a certificate chain larger than its read buffer.
+    fn streams_certificates(&self) -> bool {
+        false
+    }
+
+    /// Verify the next entry of a streamed certificate chain, starting with the end-entity
+    /// certificate.
+    fn verify_certificate_entry(&mut self, entry: CertificateEntryRef) -> Result<(), TlsError> {
+        let _ = entry;
+        Err(TlsError::Unimplemented)
+    }
+
+    /// Complete the verification of a streamed certificate chain once all its entries have been
+    /// passed to [`Self::verify_certificate_entry`].
+    fn verify_certificate_chain(&mut self, transcript: &CipherSuite::Hash) -> Result<(), TlsError> {
+        let _ = transcript;
+        Err(TlsError::Unimplemented)
+    }
+
     /// Verify the certificate signature.
     ///
     /// The signature verification uses the transcript and certificate provided earlier to decode the provided signature.
     fn verify_signature(&mut self, verify: CertificateVerifyRef) -> Result<(), crate::TlsError>;
+
+    /// Verify the signature of a TLS 1.2 `ServerKeyExchange` over `message`, which holds the
+    /// client and server randoms followed by the server key exchange parameters.
+    ///
+    /// The signature is made with the key of the certificate provided earlier.
+    #[cfg(feature = "tls12")]
+    fn verify_tls12_signature(
+        &mut self,
+        message: &[u8],
+        verify: CertificateVerifyRef,
+    ) -> Result<(), crate::TlsError> {
+        let _ = (message, verify);
+        Err(TlsError::Unimplemented)
+    }
 }
 
 pub struct NoVerify;
@@ -109,9 +325,33 @@ where
         Ok(())
     }
 
+    fn streams_certificates(&self) -> bool {
+        true
+    }
+
+    fn verify_certificate_entry(&mut self, _entry: CertificateEntryRef) -> Result<(), TlsError> {
+        Ok(())
+    }
+
+    fn verify_certificate_chain(
+        &mut self,
+        _transcript: &CipherSuite::Hash,
+    ) -> Result<(), TlsError> {
+        Ok(())
+    }
+
     fn verify_signature(&mut self, _verify: CertificateVerifyRef) -> Result<(), crate::TlsError> {
         Ok(())
     }
+
+    #[cfg(feature = "tls12")]
+    fn verify_tls12_signature(
+        &mut self,
+        _message: &[u8],
+        _verify: CertificateVerifyRef,
+    ) -> Result<(), crate::TlsError> {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -119,11 +359,19 @@ where
 #[must_use = "TlsConfig does nothing unless consumed"]
 pub struct TlsConfig<'a> {
     pub(crate) server_name: Option<&'a str>,
-    pub(crate) alpn_protocols: Option<&'a [&'a [u8]]>,
+    pub(crate) alpn_protocols: Option<AlpnProtocols<'a>>,
     pub(crate) psk: Option<(&'a [u8], Vec<&'a [u8], 4>)>,
     pub(crate) signature_schemes: Vec<SignatureScheme, 25>,
     pub(crate) named_groups: Vec<NamedGroup, 13>,
     pub(crate) max_fragment_length: Option<MaxFragmentLength>,
+    pub(crate) padding: Option<usize>,
+    pub(crate) grease: bool,
+    pub(crate) middlebox_compat: bool,
+    pub(crate) client_auth: bool,
+    pub(crate) connection_id: Option<&'a [u8]>,
+    pub(crate) custom_extensions: &'a [CustomExtension<'a>],
+    pub(crate) mtu: usize,
+    pub(crate) retransmission_timeout: Duration,
 }
 
 pub trait TlsClock {
@@ -148,7 +396,8 @@ pub trait CryptoProvider {
         Err::<&mut NoVerify, _>(crate::TlsError::Unimplemented)
     }
 
-    /// Provide a signing key for client certificate authentication.
+    /// Provide a signing key for client certificate authentication, or for the server
+    /// certificate when used with a [`TlsServerConnection`](crate::TlsServerConnection).
     ///
     /// The provider resolves the private key internally (e.g. from memory, flash, or a hardware
     /// crypto module such as an HSM/TPM/secure element).
@@ -168,6 +417,22 @@ pub trait CryptoProvider {
     fn client_cert(&mut self) -> Option<Certificate<impl AsRef<[u8]>>> {
         None::<Certificate<&[u8]>>
     }
+
+    /// Resolve the server certificate sent by a [`TlsServerConnection`](crate::TlsServerConnection).
+    ///
+    /// Only needed for handshakes that are not authenticated with a pre-shared key.
+    fn server_cert(&mut self) -> Option<Certificate<impl AsRef<[u8]>>> {
+        None::<Certificate<&[u8]>>
+    }
+
+    /// Receive the `data` of an extension the server sent in `EncryptedExtensions` in response
+    /// to one of the [`TlsConfig::with_custom_extensions`].
+    ///
+    /// Returning an error aborts the handshake.
+    fn custom_extension(&mut self, extension_type: u16, data: &[u8]) -> Result<(), TlsError> {
+        let _ = (extension_type, data);
+        Ok(())
+    }
 }
 
 impl<T: CryptoProvider> CryptoProvider for &mut T {
@@ -193,6 +458,14 @@ impl<T: CryptoProvider> CryptoProvider for &mut T {
     fn client_cert(&mut self) -> Option<Certificate<impl AsRef<[u8]>>> {
         T::client_cert(self)
     }
+
+    fn server_cert(&mut self) -> Option<Certificate<impl AsRef<[u8]>>> {
+        T::server_cert(self)
+    }
+
+    fn custom_extension(&mut self, extension_type: u16, data: &[u8]) -> Result<(), TlsError> {
+        T::custom_extension(self, extension_type, data)
+    }
 }
 
 pub struct NoSign;
@@ -206,7 +479,7 @@ impl<S> signature::Signer<S> for NoSign {
 pub struct UnsecureProvider<'a, CipherSuite, RNG> {
     rng: RNG,
     priv_key: Option<&'a [u8]>,
-    client_cert: Option<Certificate<&'a [u8]>>,
+    cert: Option<Certificate<&'a [u8]>>,
     _marker: PhantomData<CipherSuite>,
 }
 
@@ -217,7 +490,7 @@ impl<RNG: CryptoRngCore> UnsecureProvider<'_, (), RNG> {
         UnsecureProvider {
             rng,
             priv_key: None,
-            client_cert: None,
+            cert: None,
             _marker: PhantomData,
         }
     }
@@ -229,8 +502,9 @@ impl<'a, CipherSuite: TlsCipherSuite, RNG: CryptoRngCore> UnsecureProvider<'a, C
         self
     }
 
+    /// The certificate to authenticate with, as a client or a server.
     pub fn with_cert(mut self, cert: Certificate<&'a [u8]>) -> Self {
-        self.client_cert = Some(cert);
+        self.cert = Some(cert);
         self
     }
 }
@@ -260,7 +534,11 @@ impl<CipherSuite: TlsCipherSuite, RNG: CryptoRngCore> CryptoProvider
     }
 
     fn client_cert(&mut self) -> Option<Certificate<impl AsRef<[u8]>>> {
-        self.client_cert.clone()
+        self.cert.clone()
+    }
+
+    fn server_cert(&mut self) -> Option<Certificate<impl AsRef<[u8]>>> {
+        self.cert.clone()
     }
 }
 
@@ -270,21 +548,117 @@ pub struct TlsContext<'a, Provider>
 where
     Provider: CryptoProvider,
 {
-    pub(crate) config: &'a TlsConfig<'a>,
+    pub(crate) config: ContextConfig<'a>,
     pub(crate) crypto_provider: Provider,
+    pub(crate) key_shares: Option<&'a mut VecView<KeyShare>>,
+    #[cfg(feature = "key-log")]
+    pub(crate) key_log: Option<KeyLogRef<'a>>,
+}
+
+/// The configuration of a [`TlsContext`]: a borrowed [`TlsConfig`], or the view of an
+/// [`owned::TlsConfig`]. Created from either with [`From`].
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ContextConfig<'a>(ContextConfigInner<'a>);
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[allow(clippy::large_enum_variant)]
+enum ContextConfigInner<'a> {
+    Borrowed(&'a TlsConfig<'a>),
+    Owned(TlsConfig<'a>),
+}
+
+impl<'a> From<&'a TlsConfig<'a>> for ContextConfig<'a> {
+    fn from(config: &'a TlsConfig<'a>) -> Self {
+        Self(ContextConfigInner::Borrowed(config))
+    }
+}
+
+impl<'a, const SERVER_NAME: usize, const ALPN: usize, const PSK: usize, const PSK_IDENTITY: usize>
+    From<&'a owned::TlsConfig<SERVER_NAME, ALPN, PSK, PSK_IDENTITY>> for ContextConfig<'a>
+{
+    fn from(config: &'a owned::TlsConfig<SERVER_NAME, ALPN, PSK, PSK_IDENTITY>) -> Self {
+        Self(ContextConfigInner::Owned(config.as_config()))
+    }
+}
+
+impl<'a> Deref for ContextConfig<'a> {
+    type Target = TlsConfig<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        match &self.0 {
+            ContextConfigInner::Borrowed(config) => config,
+            ContextConfigInner::Owned(config) => config,
+        }
+    }
 }
 
 impl<'a, Provider> TlsContext<'a, Provider>
 where
     Provider: CryptoProvider,
 {
-    /// Create a new context with a given config and a crypto provider.
-    pub fn new(config: &'a TlsConfig<'a>, crypto_provider: Provider) -> Self {
+    /// Create a new context with a given config, a [`TlsConfig`] or an [`owned::TlsConfig`],
+    /// and a crypto provider.
+    pub fn new(config: impl Into<ContextConfig<'a>>, crypto_provider: Provider) -> Self {
         Self {
-            config,
+            config: config.into(),
             crypto_provider,
+            key_shares: None,
+            #[cfg(feature = "key-log")]
+            key_log: None,
         }
     }
+
+    /// Take the key share of the handshake from `pool` instead of generating it when the
+    /// connection is opened.
+    ///
+    /// A key share is only taken if the handshake needs one, and is never used again.
+    #[must_use]
+    pub fn with_key_shares<const N: usize>(mut self, pool: &'a mut KeySharePool<N>) -> Self {
+        self.key_shares = Some(pool.as_mut_view());
+        self
+    }
+
+    /// Take a key share for the group of the `ClientHello` out of the pool, if any. Key shares
+    /// of other groups stay in the pool.
+    pub(crate) fn take_key_share(&mut self) -> Option<KeyShare> {
+        let group = self.config.key_share_group().ok()?;
+        let shares = self.key_shares.as_mut()?;
+        let Some(index) = shares.iter().rposition(|share| share.group() == group) else {
+            if !shares.is_empty() {
+                warn!("No pooled key share is for {:?}", group);
+            }
+            return None;
+        };
+        Some(shares.swap_remove(index))
+    }
+
+    /// Log the secrets of the connection to `key_log` as they are derived, so that captures of
+    /// its traffic can be decrypted.
+    ///
+    /// Anyone holding the logged secrets can decrypt the connection, so this is only meant for
+    /// debugging.
+    #[cfg(feature = "key-log")]
+    #[must_use]
+    pub fn with_key_log(mut self, key_log: &'a mut (dyn KeyLog + Send)) -> Self {
+        self.key_log = Some(KeyLogRef(key_log));
+        self
+    }
+
+    /// Pass the secrets derived by `key_schedule` to the key log, if any.
+    #[cfg_attr(not(feature = "key-log"), allow(clippy::unused_self, unused_variables))]
+    #[inline]
+    pub(crate) fn flush_key_log<CipherSuite: TlsCipherSuite>(
+        &mut self,
+        key_schedule: &mut KeySchedule<CipherSuite>,
+    ) {
+        #[cfg(feature = "key-log")]
+        key_schedule.flush_key_log(match &mut self.key_log {
+            Some(KeyLogRef(key_log)) => Some(&mut **key_log),
+            None => None,
+        });
+    }
 }
 
 impl<'a> TlsConfig<'a> {
@@ -296,6 +670,14 @@ impl<'a> TlsConfig<'a> {
             psk: None,
             server_name: None,
             alpn_protocols: None,
+            padding: None,
+            grease: false,
+            middlebox_compat: false,
+            client_auth: false,
+            connection_id: None,
+            custom_extensions: &[],
+            mtu: 1200,
+            retransmission_timeout: Duration::from_secs(1),
         };
 
         if cfg!(feature = "alloc") {
@@ -315,12 +697,26 @@ impl<'a> TlsConfig<'a> {
                 .ok()
         );
         unwrap!(config.signature_schemes.push(SignatureScheme::Ed25519).ok());
+        #[cfg(feature = "ed448")]
+        unwrap!(config.signature_schemes.push(SignatureScheme::Ed448).ok());
 
         unwrap!(config.named_groups.push(NamedGroup::Secp256r1));
 
         config
     }
 
+    /// Create a [`TlsConfigBuilder`], which validates the configuration.
+    pub fn builder() -> TlsConfigBuilder<'a> {
+        TlsConfigBuilder::new()
+    }
+
+    /// The length of the `ClientHello` sent with this configuration, to size the write buffer
+    /// with [`min_write_buffer_size`]. Fails if no key share can be generated for the named
+    /// groups, or the custom extensions are invalid.
+    pub fn client_hello_len<CipherSuite: TlsCipherSuite>(&self) -> Result<usize, TlsError> {
+        ClientHello::<CipherSuite>::encoded_len(self)
+    }
+
     /// Enable RSA ciphers even if they might not be supported.
     pub fn enable_rsa_signatures(mut self) -> Self {
         unwrap!(
@@ -366,8 +762,11 @@ impl<'a> TlsConfig<'a> {
     /// The server will select one of the offered protocols and echo it back
     /// in EncryptedExtensions. This is required for endpoints that multiplex
     /// protocols on a single port (e.g. AWS IoT Core MQTT over port 443).
+    ///
+    /// On a [`crate::TlsServerConnection`], the protocols are listed in order of preference and
+    /// the first one the client offers is selected.
     pub fn with_alpn(mut self, protocols: &'a [&'a [u8]]) -> Self {
-        self.alpn_protocols = Some(protocols);
+        self.alpn_protocols = Some(AlpnProtocols::List(protocols));
         self
     }
 
@@ -400,11 +799,167 @@ impl<'a> TlsConfig<'a> {
         self
     }
 
+    /// Pad the `ClientHello` to at least `len` bytes using the padding extension.
+    ///
+    /// Some middleboxes drop `ClientHello` messages between 256 and 511 bytes long; padding to
+    /// 512 bytes works around them. The length covers the `ClientHello` handshake message,
+    /// excluding the record header.
+    ///
+    /// From [RFC 7685, Section 4.  Example Usage](https://www.rfc-editor.org/rfc/rfc7685#section-4):
+    ///
+    /// > The motivation for this extension is to allow clients to pad the
+    /// > ClientHello to a desired size in order to avoid implementation bugs
+    /// > caused by certain ClientHello sizes.
+    pub fn with_padding(mut self, len: usize) -> Self {
+        self.padding = Some(len);
+        self
+    }
+
+    /// Send GREASE values in the `ClientHello`.
+    ///
+    /// Reserved values are randomly chosen for the cipher suites, supported versions,
+    /// supported groups and signature algorithms, and two extra extensions are sent.
+    /// The values are drawn from the [`CryptoProvider::rng`] for every handshake.
+    ///
+    /// From [RFC 8701, Section 1.  Introduction](https://www.rfc-editor.org/rfc/rfc8701#section-1):
+    ///
+    /// > This document reserves a number of TLS protocol values, referred to
+    /// > as GREASE values.  These values may be advertised to ensure peers
+    /// > correctly handle unknown values.
+    pub fn enable_grease(mut self) -> Self {
+        self.grease = true;
+        self
+    }
+
+    /// Make the TLS 1.3 handshake look like a TLS 1.2 session resumption to middleboxes.
+    ///
+    /// A random 32-byte session ID is sent in the `ClientHello`, which the server must echo,
+    /// and a `ChangeCipherSpec` record is sent before the second client flight.
+    ///
+    /// From [RFC 8446, Appendix D.4.  Middlebox Compatibility Mode](https://www.rfc-editor.org/rfc/rfc8446#appendix-D.4):
+    ///
+    /// > Field measurements \[Ben17a\] \[Ben17b\] \[Res17a\] \[Res17b\] have found
+    /// > that a significant number of middleboxes misbehave when a TLS
+    /// > client/server pair negotiates TLS 1.3.  Implementations can increase
+    /// > the chance of making connections through those middleboxes by making
+    /// > the TLS 1.3 handshake look more like a TLS 1.2 handshake.
+    pub fn enable_middlebox_compatibility(mut self) -> Self {
+        self.middlebox_compat = true;
+        self
+    }
+
+    /// Request a certificate from the client.
+    ///
+    /// Only used by a [`TlsServerConnection`](crate::TlsServerConnection). The client
+    /// certificate is verified with the [`CryptoProvider::verifier`], and clients that do not
+    /// send a certificate are rejected with a `certificate_required` alert. Client certificates
+    /// are not requested in handshakes authenticated with a pre-shared key.
+    pub fn enable_client_auth(mut self) -> Self {
+        self.client_auth = true;
+        self
+    }
+
+    /// Configure a pre-shared key and the identities to offer for it.
+    ///
+    /// A [`TlsServerConnection`](crate::TlsServerConnection) accepts any of the identities,
+    /// and falls back to a certificate-based handshake if the client offers none of them.
     pub fn with_psk(mut self, psk: &'a [u8], identities: &[&'a [u8]]) -> Self {
         // TODO: Remove potential panic
         self.psk = Some((psk, unwrap!(Vec::from_slice(identities).ok())));
         self
     }
+
+    /// Set the maximum size of the datagrams sent by a DTLS connection.
+    ///
+    /// Handshake messages are fragmented to fit, and application data written in one go must
+    /// fit in a single datagram. The default of 1200 bytes fits in the IPv6 minimum MTU.
+    ///
+    /// Only used by DTLS connections.
+    pub fn with_mtu(mut self, mtu: usize) -> Self {
+        self.mtu = mtu;
+        self
+    }
+
+    /// Offer a connection ID (RFC 9146), which the server puts in the records it sends.
+    ///
+    /// A connection ID lets the server associate records with the connection when the client
+    /// address changes, e.g. after a NAT rebinding. An empty connection ID asks the server to
+    /// use a connection ID for its own side only.
+    ///
+    /// Only used by DTLS connections.
+    pub fn with_connection_id(mut self, cid: &'a [u8]) -> Self {
+        self.connection_id = Some(cid);
+        self
+    }
+
+    /// Send application-defined `extensions` in the `ClientHello`, e.g. a draft extension
+    /// under test or a device-class hint.
+    ///
+    /// The extensions are sent in order, before `padding` and `pre_shared_key`, which must be
+    /// the last extension. The extensions the server sends back in `EncryptedExtensions` with
+    /// the same types are given to [`CryptoProvider::custom_extension`].
+    ///
+    /// The types must be distinct, not known to embedded-tls and not GREASE values: the
+    /// `ClientHello` cannot be encoded otherwise. [`TlsConfigBuilder::build`] checks this.
+    pub fn with_custom_extensions(mut self, extensions: &'a [CustomExtension<'a>]) -> Self {
+        self.custom_extensions = extensions;
+        self
+    }
+
+    /// Whether the custom extensions have distinct types, none known to embedded-tls.
+    pub(crate) fn custom_extensions_valid(&self) -> bool {
+        self.custom_extensions.iter().enumerate().all(|(i, e)| {
+            ExtensionType::from_u16(e.extension_type).is_none()
+                && !grease::is_grease(e.extension_type)
+                && self.custom_extensions[..i]
+                    .iter()
+                    .all(|other| other.extension_type != e.extension_type)
+        })
+    }
+
+    /// The group of the key share sent in the `ClientHello`: the most preferred named group
+    /// that key shares can be generated for. A configuration without such a group is rejected.
+    pub(crate) fn key_share_group(&self) -> Result<NamedGroup, TlsError> {
+        self.named_groups
+            .iter()
+            .copied()
+            .find(|g| EphemeralSecret::supports(*g))
+            .ok_or_else(|| {
+                error!("None of the named groups supports key shares");
+                TlsError::InvalidKeyShare
+            })
+    }
+
+    /// Generate the secret of the key share sent in the `ClientHello`, unless the `secret`
+    /// generated ahead of time is for [`Self::key_share_group`].
+    pub(crate) fn key_share(
+        &self,
+        secret: Option<EphemeralSecret>,
+        rng: &mut impl CryptoRngCore,
+    ) -> Result<EphemeralSecret, TlsError> {
+        let group = self.key_share_group()?;
+        match secret {
+            Some(secret) if secret.group() == group => return Ok(secret),
+            Some(secret) => warn!(
+                "Discarding the key share for {:?}, the ClientHello sends one for {:?}",
+                secret.group(),
+                group
+            ),
+            None => {}
+        }
+        EphemeralSecret::generate(group, rng).ok_or(TlsError::InvalidKeyShare)
+    }
+
+    /// Set the initial retransmission timeout of DTLS handshake flights.
+    ///
+    /// The timeout doubles with every retransmission. The default is 1 second, as recommended
+    /// by [RFC 9147, Section 5.8.2](https://www.rfc-editor.org/rfc/rfc9147#section-5.8.2).
+    ///
+    /// Only used by DTLS connections.
+    pub fn with_retransmission_timeout(mut self, timeout: Duration) -> Self {
+        self.retransmission_timeout = timeout;
+        self
+    }
 }
 
 impl Default for TlsConfig<'_> {
@@ -413,9 +968,409 @@ impl Default for TlsConfig<'_> {
     }
 }
 
+/// An invalid or contradictory setting found by [`TlsConfigBuilder::build`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// No signature scheme is configured.
+    NoSignatureSchemes,
+    /// More than 25 signature schemes are configured.
+    TooManySignatureSchemes,
+    /// More than 13 named groups are configured.
+    TooManyNamedGroups,
+    /// None of the named groups is one a key share can be generated for, such as
+    /// [`NamedGroup::Secp256r1`].
+    MissingKeyShareGroup,
+    /// A pre-shared key is configured without any identity.
+    MissingPskIdentity,
+    /// More than 4 identities are configured for the pre-shared key.
+    TooManyPskIdentities,
+    /// The ALPN list is empty, a protocol name is empty or longer than 255 bytes, or the list
+    /// is too long to encode.
+    InvalidAlpnProtocols,
+    /// The `ClientHello` does not fit in the write buffer, which must be at least this size.
+    WriteBufferTooSmall(usize),
+    /// The `ClientHello` cannot be encoded to check its size against the write buffer, such as
+    /// with a server name longer than 65535 bytes.
+    InvalidClientHello,
+    /// A setting does not fit in the capacity of an [`owned::TlsConfig`](crate::owned::TlsConfig).
+    InsufficientCapacity,
+    /// Custom extensions are not copied into an [`owned::TlsConfig`](crate::owned::TlsConfig),
+    /// see [`owned::TlsConfig::with_custom_extensions`](crate::owned::TlsConfig::with_custom_extensions).
+    CustomExtensionsNotCopied,
+    /// Two custom extensions have the same type, or a custom extension has a type known to
+    /// embedded-tls, such as `pre_shared_key`, or a GREASE type.
+    InvalidCustomExtension,
+}
+
+impl core::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl core::error::Error for ConfigError {}
+
+/// A builder validating the configuration before creating a [`TlsConfig`].
+///
+/// Unlike the [`TlsConfig`] setters, which panic on settings that do not fit, invalid or
+/// contradictory settings are reported as a [`ConfigError`] by [`Self::build`].
+#[derive(Debug, Clone)]
+#[must_use = "TlsConfigBuilder does nothing unless built"]
+pub struct TlsConfigBuilder<'a> {
+    config: TlsConfig<'a>,
+    signature_schemes: Option<&'a [SignatureScheme]>,
+    named_groups: Option<&'a [NamedGroup]>,
+    psk: Option<(&'a [u8], &'a [&'a [u8]])>,
+    write_buffer_size: Option<(usize, MinWriteBufferSize)>,
+}
+
+/// Computes the [`min_write_buffer_size`] of a configuration for a given cipher suite.
+type MinWriteBufferSize = fn(&TlsConfig<'_>) -> Result<usize, TlsError>;
+
+impl<'a> TlsConfigBuilder<'a> {
+    /// Create a builder with the defaults of [`TlsConfig::new`].
+    pub fn new() -> Self {
+        Self {
+            config: TlsConfig::new(),
+            signature_schemes: None,
+            named_groups: None,
+            psk: None,
+            write_buffer_size: None,
+        }
+    }
+
+    /// Validate the settings and create the configuration.
+    pub fn build(self) -> Result<TlsConfig<'a>, ConfigError> {
+        let mut config = self.config;
+
+        if let Some(schemes) = self.signature_schemes {
+            config.signature_schemes =
+                Vec::from_slice(schemes).map_err(|_| ConfigError::TooManySignatureSchemes)?;
+        }
+        if config.signature_schemes.is_empty() {
+            return Err(ConfigError::NoSignatureSchemes);
+        }
+
+        if let Some(groups) = self.named_groups {
+            config.named_groups =
+                Vec::from_slice(groups).map_err(|_| ConfigError::TooManyNamedGroups)?;
+        }
+        if !config
+            .named_groups
+            .iter()
+            .any(|g| EphemeralSecret::supports(*g))
+        {
+            return Err(ConfigError::MissingKeyShareGroup);
+        }
+
+        if let Some((psk, identities)) = self.psk {
+            if identities.is_empty() {
+                return Err(ConfigError::MissingPskIdentity);
+            }
+            let identities =
+                Vec::from_slice(identities).map_err(|_| ConfigError::TooManyPskIdentities)?;
+            config.psk = Some((psk, identities));
+        }
+
+        if let Some(protocols) = config.alpn_protocols {
+            let len: usize = protocols.iter().map(|p| 1 + p.len()).sum();
+            if protocols.iter().next().is_none()
+                || protocols.iter().any(|p| p.is_empty() || p.len() > 255)
+                || len > usize::from(u16::MAX)
+            {
+                return Err(ConfigError::InvalidAlpnProtocols);
+            }
+        }
+
+        if !config.custom_extensions_valid() {
+            return Err(ConfigError::InvalidCustomExtension);
+        }
+
+        if let Some((size, min_size)) = self.write_buffer_size {
+            let min_size = min_size(&config).map_err(|_| ConfigError::InvalidClientHello)?;
+            if size < min_size {
+                return Err(ConfigError::WriteBufferTooSmall(min_size));
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Offer the `schemes` in the `signature_algorithms` extension, in order of preference,
+    /// instead of the defaults.
+    pub fn with_signature_schemes(mut self, schemes: &'a [SignatureScheme]) -> Self {
+        self.signature_schemes = Some(schemes);
+        self
+    }
+
+    /// Offer the `groups` in the `supported_groups` extension, in order of preference, instead
+    /// of the defaults. The key share is generated for the first group that is supported, such
+    /// as [`NamedGroup::Secp256r1`], [`NamedGroup::CurveSm2`] with the `shangmi` feature, the
+    /// brainpool groups [`NamedGroup::BrainpoolP256r1Tls13`], [`NamedGroup::BrainpoolP384r1Tls13`]
+    /// and [`NamedGroup::BrainpoolP512r1Tls13`] with the `brainpool` feature, or
+    /// [`NamedGroup::X448`] with the `x448` feature.
+    pub fn with_named_groups(mut self, groups: &'a [NamedGroup]) -> Self {
+        self.named_groups = Some(groups);
+        self
+    }
+
+    /// Check that the `ClientHello` fits in a write buffer of `size` bytes for a connection
+    /// with `CipherSuite`, see [`min_write_buffer_size`].
+    pub fn with_write_buffer_size<CipherSuite: TlsCipherSuite>(mut self, size: usize) -> Self {
+        self.write_buffer_size = Some((size, |config| {
+            config
+                .client_hello_len::<CipherSuite>()
+                .map(min_write_buffer_size::<CipherSuite>)
+        }));
+        self
+    }
+
+    /// See [`TlsConfig::with_server_name`].
+    pub fn with_server_name(mut self, server_name: &'a str) -> Self {
+        self.config = self.config.with_server_name(server_name);
+        self
+    }
+
+    /// See [`TlsConfig::with_alpn`].
+    pub fn with_alpn(mut self, protocols: &'a [&'a [u8]]) -> Self {
+        self.config = self.config.with_alpn(protocols);
+        self
+    }
+
+    /// See [`TlsConfig::with_max_fragment_length`].
+    pub fn with_max_fragment_length(mut self, max_fragment_length: MaxFragmentLength) -> Self {
+        self.config = self.config.with_max_fragment_length(max_fragment_length);
+        self
+    }
+
+    /// See [`TlsConfig::with_padding`].
+    pub fn with_padding(mut self, len: usize) -> Self {
+        self.config = self.config.with_padding(len);
+        self
+    }
+
+    /// See [`TlsConfig::enable_grease`].
+    pub fn enable_grease(mut self) -> Self {
+        self.config = self.config.enable_grease();
+        self
+    }
+
+    /// See [`TlsConfig::enable_middlebox_compatibility`].
+    pub fn enable_middlebox_compatibility(mut self) -> Self {
+        self.config = self.config.enable_middlebox_compatibility();
+        self
+    }
+
+    /// See [`TlsConfig::enable_client_auth`].
+    pub fn enable_client_auth(mut self) -> Self {
+        self.config = self.config.enable_client_auth();
+        self
+    }
+
+    /// See [`TlsConfig::with_psk`]. At most 4 identities can be offered.
+    pub fn with_psk(mut self, psk: &'a [u8], identities: &'a [&'a [u8]]) -> Self {
+        self.psk = Some((psk, identities));
+        self
+    }
+
+    /// See [`TlsConfig::with_mtu`].
+    pub fn with_mtu(mut self, mtu: usize) -> Self {
+        self.config = self.config.with_mtu(mtu);
+        self
+    }
+
+    /// See [`TlsConfig::with_connection_id`].
+    pub fn with_connection_id(mut self, cid: &'a [u8]) -> Self {
+        self.config = self.config.with_connection_id(cid);
+        self
+    }
+
+    /// See [`TlsConfig::with_retransmission_timeout`].
+    pub fn with_retransmission_timeout(mut self, timeout: Duration) -> Self {
+        self.config = self.config.with_retransmission_timeout(timeout);
+        self
+    }
+
+    /// See [`TlsConfig::with_custom_extensions`].
+    pub fn with_custom_extensions(mut self, extensions: &'a [CustomExtension<'a>]) -> Self {
+        self.config = self.config.with_custom_extensions(extensions);
+        self
+    }
+}
+
+impl Default for TlsConfigBuilder<'_> {
+    fn default() -> Self {
+        TlsConfigBuilder::new()
+    }
+}
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Certificate<D> {
     X509(D),
     RawPublicKey(D),
 }
+
+/// An application-defined extension sent in the `ClientHello`, see
+/// [`TlsConfig::with_custom_extensions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CustomExtension<'a> {
+    /// The extension type, which must not be known to embedded-tls.
+    pub extension_type: u16,
+    /// The `extension_data`, without the length prefix.
+    pub data: &'a [u8],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Aes128GcmSha256;
+
+    #[test]
+    fn builder_defaults() {
+        let config = TlsConfig::builder()
+            .with_server_name("example.com")
+            .build()
+            .unwrap();
+        assert_eq!(config.server_name, Some("example.com"));
+        assert_eq!(config.signature_schemes, TlsConfig::new().signature_schemes);
+        assert_eq!(config.named_groups, TlsConfig::new().named_groups);
+    }
+
+    #[test]
+    fn builder_sets_schemes_and_groups_in_order() {
+        let config = TlsConfig::builder()
+            .with_signature_schemes(&[
+                SignatureScheme::Ed25519,
+                SignatureScheme::EcdsaSecp256r1Sha256,
+            ])
+            .with_named_groups(&[NamedGroup::X25519, NamedGroup::Secp256r1])
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.signature_schemes,
+            [
+                SignatureScheme::Ed25519,
+                SignatureScheme::EcdsaSecp256r1Sha256
+            ]
+        );
+        assert_eq!(
+            config.named_groups,
+            [NamedGroup::X25519, NamedGroup::Secp256r1]
+        );
+    }
+
+    #[test]
+    fn builder_rejects_invalid_settings() {
+        let result = TlsConfig::builder().with_signature_schemes(&[]).build();
+        assert_eq!(result.unwrap_err(), ConfigError::NoSignatureSchemes);
+
+        let result = TlsConfig::builder()
+            .with_named_groups(&[NamedGroup::X25519])
+            .build();
+        assert_eq!(result.unwrap_err(), ConfigError::MissingKeyShareGroup);
+
+        let result = TlsConfig::builder().with_psk(b"key", &[]).build();
+        assert_eq!(result.unwrap_err(), ConfigError::MissingPskIdentity);
+
+        let result = TlsConfig::builder()
+            .with_psk(b"key", &[b"a", b"b", b"c", b"d", b"e"])
+            .build();
+        assert_eq!(result.unwrap_err(), ConfigError::TooManyPskIdentities);
+
+        let result = TlsConfig::builder().with_alpn(&[b"h2", b""]).build();
+        assert_eq!(result.unwrap_err(), ConfigError::InvalidAlpnProtocols);
+
+        let extensions = [CustomExtension {
+            extension_type: ExtensionType::PreSharedKey as u16,
+            data: &[],
+        }];
+        let result = TlsConfig::builder()
+            .with_custom_extensions(&extensions)
+            .build();
+        assert_eq!(result.unwrap_err(), ConfigError::InvalidCustomExtension);
+    }
+
+    #[test]
+    fn builder_checks_write_buffer_size() {
+        let min_size = min_write_buffer_size::<Aes128GcmSha256>(
+            TlsConfig::new()
+                .with_padding(512)
+                .client_hello_len::<Aes128GcmSha256>()
+                .unwrap(),
+        );
+
+        let result = TlsConfig::builder()
+            .with_padding(512)
+            .with_write_buffer_size::<Aes128GcmSha256>(min_size - 1)
+            .build();
+        assert_eq!(
+            result.unwrap_err(),
+            ConfigError::WriteBufferTooSmall(min_size)
+        );
+
+        let result = TlsConfig::builder()
+            .with_padding(512)
+            .with_write_buffer_size::<Aes128GcmSha256>(min_size)
+            .build();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn builder_rejects_unencodable_client_hello() {
+        let server_name = "a".repeat(usize::from(u16::MAX) + 1);
+        let result = TlsConfig::builder()
+            .with_server_name(&server_name)
+            .with_write_buffer_size::<Aes128GcmSha256>(16640)
+            .build();
+        assert_eq!(result.unwrap_err(), ConfigError::InvalidClientHello);
+    }
+
+    #[test]
+    fn config_without_key_share_group_is_rejected() {
+        let mut config = TlsConfig::new();
+        config.named_groups = Vec::from_slice(&[NamedGroup::X25519]).unwrap();
+        assert!(matches!(
+            config.client_hello_len::<Aes128GcmSha256>(),
+            Err(TlsError::InvalidKeyShare)
+        ));
+        assert!(config.key_share(None, &mut rand::rngs::OsRng).is_err());
+    }
+
+    #[cfg(feature = "x448")]
+    #[test]
+    fn pooled_key_shares_of_other_groups_stay_in_pool() {
+        use crate::key_share_pool::{KeyShare, KeySharePool};
+        use rand::rngs::OsRng;
+
+        let config = TlsConfig::builder()
+            .with_named_groups(&[NamedGroup::X448])
+            .build()
+            .unwrap();
+        let mut pool = KeySharePool::<2>::new();
+        pool.push(KeyShare::generate(&mut OsRng)).unwrap();
+
+        {
+            let provider = UnsecureProvider::new::<Aes128GcmSha256>(OsRng);
+            let mut context = TlsContext::new(&config, provider).with_key_shares(&mut pool);
+            assert!(context.take_key_share().is_none());
+        }
+        assert_eq!(pool.len(), 1);
+
+        pool.push(unwrap!(KeyShare::generate_for(
+            NamedGroup::X448,
+            &mut OsRng
+        )))
+        .unwrap();
+        {
+            let provider = UnsecureProvider::new::<Aes128GcmSha256>(OsRng);
+            let mut context = TlsContext::new(&config, provider).with_key_shares(&mut pool);
+            let key_share = context.take_key_share().unwrap();
+            assert_eq!(key_share.group(), NamedGroup::X448);
+        }
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.take().unwrap().group(), NamedGroup::Secp256r1);
+    }
+}
@@ -1,24 +1,26 @@
+use crate::change_cipher_spec::ChangeCipherSpec;
 use crate::config::{TlsCipherSuite, TlsConfig};
-use crate::handshake::{ClientHandshake, ServerHandshake};
+use crate::handshake::server_hello::ServerHello;
+use crate::handshake::{IncomingHandshake, OutgoingHandshake, Random};
+#[cfg(feature = "tls12")]
+use crate::key_schedule::Tls12Records;
 use crate::key_schedule::{KeySchedule, ReadKeySchedule, WriteKeySchedule};
 use crate::record::{ClientRecord, ServerRecord};
-use crate::record_reader::RecordReader;
+#[cfg(feature = "tls12")]
+use crate::tls12;
 use crate::write_buffer::WriteBuffer;
-use crate::{CertificateVerify, CryptoProvider, TlsError, TlsVerifier};
+use crate::{CertificateVerify, CryptoProvider, SignatureScheme, TlsError, TlsVerifier};
 use crate::{
     alert::{Alert, AlertDescription, AlertLevel},
     handshake::{certificate::CertificateRef, certificate_request::CertificateRequest},
 };
 use core::fmt::Debug;
 use digest::Digest;
-use embedded_io::Error as _;
-use embedded_io::{Read as BlockingRead, Write as BlockingWrite};
-use embedded_io_async::{Read as AsyncRead, Write as AsyncWrite};
 
 use crate::application_data::ApplicationData;
 use crate::buffer::CryptoBuffer;
+use crate::key_exchange::EphemeralSecret;
 use digest::generic_array::typenum::Unsigned;
-use p256::ecdh::EphemeralSecret;
 use signature::SignerMut;
 
 use crate::content_types::ContentType;
@@ -36,9 +38,15 @@ pub(crate) fn decrypt_record<CipherSuite>(
 where
     CipherSuite: TlsCipherSuite,
 {
+    #[cfg(feature = "tls12")]
+    if key_schedule.tls12_records() == Some(Tls12Records::Protected) {
+        return tls12::decrypt_record(key_schedule, record, cb);
+    }
+
     if let ServerRecord::ApplicationData(ApplicationData {
         header,
         data: mut app_data,
+        fragment,
     }) = record
     {
         let server_key = key_schedule.get_key()?;
@@ -47,7 +55,7 @@ where
         let crypto = <CipherSuite::Cipher as KeyInit>::new(server_key);
         crypto
             .decrypt_in_place(&nonce, header.data(), &mut app_data)
-            .map_err(|_| TlsError::CryptoError)?;
+            .map_err(|_| TlsError::BadRecordMac)?;
 
         let padding = app_data
             .as_slice()
@@ -66,24 +74,58 @@ where
         // Remove the content type
         app_data.truncate(app_data.len() - 1);
 
-        let mut buf = ParseBuffer::new(app_data.as_slice());
+        // Handshake messages must not be interleaved with other records (RFC 8446, Section 5.1)
+        if content_type != ContentType::Handshake
+            && fragment
+                .as_deref()
+                .is_some_and(|fragment| fragment.held > 0)
+        {
+            return Err(TlsError::InvalidRecord);
+        }
+
         match content_type {
             ContentType::Handshake => {
+                let record = ApplicationData {
+                    header,
+                    data: app_data,
+                    fragment,
+                };
+                let messages = record.into_streamed_handshake_messages()?;
+
+                let mut buf = ParseBuffer::new(messages.continued);
+                read_certificate_entries(key_schedule, &mut buf, &mut cb)?;
+                if messages.continued_last {
+                    cb(
+                        key_schedule,
+                        ServerRecord::Handshake(IncomingHandshake::CertificateEnd),
+                    )?;
+                }
+
                 // Decode potentially coalesced handshake messages
+                let mut buf = ParseBuffer::new(messages.complete);
                 while buf.remaining() > 0 {
-                    let inner = ServerHandshake::read(&mut buf, key_schedule.transcript_hash())?;
+                    let inner = IncomingHandshake::read(&mut buf, key_schedule.transcript_hash())?;
                     cb(key_schedule, ServerRecord::Handshake(inner))?;
                 }
+
+                if !messages.started.is_empty() {
+                    let mut buf = ParseBuffer::new(messages.started);
+                    IncomingHandshake::<CipherSuite>::read_certificate_start(
+                        &mut buf,
+                        key_schedule.transcript_hash(),
+                    )?;
+                    read_certificate_entries(key_schedule, &mut buf, &mut cb)?;
+                }
             }
             ContentType::ApplicationData => {
                 let inner = ApplicationData::new(app_data, header);
                 cb(key_schedule, ServerRecord::ApplicationData(inner))?;
             }
             ContentType::Alert => {
-                let alert = Alert::parse(&mut buf)?;
+                let alert = Alert::parse(&mut ParseBuffer::new(app_data.as_slice()))?;
                 cb(key_schedule, ServerRecord::Alert(alert))?;
             }
-            _ => return Err(TlsError::Unimplemented),
+            _ => return Err(TlsError::UnknownContentType),
         }
         key_schedule.increment_counter();
     } else {
@@ -93,6 +135,25 @@ where
     Ok(())
 }
 
+/// Decode the entries of a streamed `Certificate` message, passing each one to `cb`.
+fn read_certificate_entries<'a, CipherSuite>(
+    key_schedule: &mut ReadKeySchedule<CipherSuite>,
+    buf: &mut ParseBuffer<'a>,
+    cb: &mut impl FnMut(
+        &mut ReadKeySchedule<CipherSuite>,
+        ServerRecord<'a, CipherSuite>,
+    ) -> Result<(), TlsError>,
+) -> Result<(), TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    while !buf.is_empty() {
+        let entry = IncomingHandshake::read_certificate_entry(buf, key_schedule.transcript_hash())?;
+        cb(key_schedule, ServerRecord::Handshake(entry))?;
+    }
+    Ok(())
+}
+
 pub(crate) fn encrypt<CipherSuite>(
     key_schedule: &WriteKeySchedule<CipherSuite>,
     buf: &mut CryptoBuffer<'_>,
@@ -132,9 +193,13 @@ pub struct Handshake<CipherSuite>
 where
     CipherSuite: TlsCipherSuite,
 {
-    traffic_hash: Option<CipherSuite::Hash>,
-    secret: Option<EphemeralSecret>,
-    certificate_request: Option<CertificateRequest>,
+    pub(crate) traffic_hash: Option<CipherSuite::Hash>,
+    pub(crate) secret: Option<EphemeralSecret>,
+    pub(crate) certificate_request: Option<CertificateRequest>,
+    /// The session ID sent in the middlebox compatibility mode.
+    pub(crate) legacy_session_id: Option<Random>,
+    #[cfg(feature = "tls12")]
+    pub(crate) tls12: tls12::Tls12Handshake,
 }
 
 impl<CipherSuite> Handshake<CipherSuite>
@@ -146,6 +211,9 @@ where
             traffic_hash: None,
             secret: None,
             certificate_request: None,
+            legacy_session_id: None,
+            #[cfg(feature = "tls12")]
+            tls12: tls12::Tls12Handshake::new(),
         }
     }
 }
@@ -156,239 +224,163 @@ pub enum State {
     ClientHello,
     ServerHello,
     ServerVerify,
+    /// The `ChangeCipherSpec` sent before the second flight in the middlebox compatibility mode.
+    ChangeCipherSpec,
     ClientCert,
     ClientCertVerify,
     ClientFinished,
+    #[cfg(feature = "tls12")]
+    Tls12ServerFlight,
+    #[cfg(feature = "tls12")]
+    Tls12ClientCert,
+    #[cfg(feature = "tls12")]
+    Tls12ClientKeyExchange,
+    #[cfg(feature = "tls12")]
+    Tls12ChangeCipherSpec,
+    #[cfg(feature = "tls12")]
+    Tls12ClientFinished,
+    #[cfg(feature = "tls12")]
+    Tls12ServerFinished,
     ApplicationData,
 }
 
-impl<'a> State {
-    #[allow(clippy::too_many_arguments)]
-    pub async fn process<'v, Transport, Provider>(
+impl State {
+    /// Whether this state waits for a record from the server.
+    pub(crate) fn is_reading(self) -> bool {
+        match self {
+            State::ServerHello | State::ServerVerify => true,
+            #[cfg(feature = "tls12")]
+            State::Tls12ServerFlight | State::Tls12ServerFinished => true,
+            _ => false,
+        }
+    }
+
+    /// Whether alerts sent in this state are encrypted.
+    pub(crate) fn encrypts_alerts(self) -> bool {
+        match self {
+            State::ServerHello => false,
+            #[cfg(feature = "tls12")]
+            State::Tls12ServerFlight => false,
+            _ => true,
+        }
+    }
+
+    /// Process a record received from the server, see [`Self::is_reading`].
+    pub(crate) fn process_record<Provider>(
         self,
-        transport: &mut Transport,
         handshake: &mut Handshake<Provider::CipherSuite>,
-        record_reader: &mut RecordReader<'_>,
-        tx_buf: &mut WriteBuffer<'_>,
         key_schedule: &mut KeySchedule<Provider::CipherSuite>,
-        config: &TlsConfig<'a>,
+        config: &TlsConfig<'_>,
         crypto_provider: &mut Provider,
+        record: ServerRecord<'_, Provider::CipherSuite>,
     ) -> Result<State, TlsError>
     where
-        Transport: AsyncRead + AsyncWrite + 'a,
         Provider: CryptoProvider,
     {
         match self {
-            State::ClientHello => {
-                let (state, tx) =
-                    client_hello(key_schedule, config, crypto_provider, tx_buf, handshake)?;
-
-                respond(tx, transport, key_schedule).await?;
-
-                Ok(state)
-            }
             State::ServerHello => {
-                let record = record_reader
-                    .read(transport, key_schedule.read_state())
-                    .await?;
-
-                let result = process_server_hello(handshake, key_schedule, record);
-
-                handle_processing_error(result, transport, key_schedule, tx_buf).await
+                process_server_hello(handshake, key_schedule, crypto_provider, record)
             }
             State::ServerVerify => {
-                let record = record_reader
-                    .read(transport, key_schedule.read_state())
-                    .await?;
-
-                let result =
-                    process_server_verify(handshake, key_schedule, crypto_provider, record);
-
-                handle_processing_error(result, transport, key_schedule, tx_buf).await
+                process_server_verify(handshake, key_schedule, config, crypto_provider, record)
             }
-            State::ClientCert => {
-                let (state, tx) = client_cert(handshake, key_schedule, crypto_provider, tx_buf)?;
-
-                respond(tx, transport, key_schedule).await?;
-
-                Ok(state)
+            #[cfg(feature = "tls12")]
+            State::Tls12ServerFlight => {
+                tls12::process_server_record(handshake, key_schedule, crypto_provider, record)
             }
-            State::ClientCertVerify => {
-                let (result, tx) = client_cert_verify(key_schedule, crypto_provider, tx_buf)?;
-
-                respond(tx, transport, key_schedule).await?;
-
-                result
+            #[cfg(feature = "tls12")]
+            State::Tls12ServerFinished => {
+                tls12::process_server_finished(handshake, key_schedule, record)
             }
-            State::ClientFinished => {
-                let tx = client_finished(key_schedule, tx_buf)?;
-
-                respond(tx, transport, key_schedule).await?;
-
-                client_finished_finalize(key_schedule, handshake)
-            }
-            State::ApplicationData => Ok(State::ApplicationData),
+            _ => Err(TlsError::InternalError),
         }
     }
 
-    #[allow(clippy::too_many_arguments)]
-    pub fn process_blocking<'v, Transport, Provider>(
+    /// Encode the next record of the client flight into `tx_buf`.
+    ///
+    /// Returns the record along with the state to continue with, or the error to fail with
+    /// once the record has been sent. [`Self::record_written`] must be called once the write
+    /// sequence number has been incremented.
+    pub(crate) fn write_record<'r, Provider>(
         self,
-        transport: &mut Transport,
         handshake: &mut Handshake<Provider::CipherSuite>,
-        record_reader: &mut RecordReader<'_>,
-        tx_buf: &mut WriteBuffer,
         key_schedule: &mut KeySchedule<Provider::CipherSuite>,
-        config: &TlsConfig<'a>,
+        config: &TlsConfig,
         crypto_provider: &mut Provider,
-    ) -> Result<State, TlsError>
+        tx_buf: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+    ) -> Result<(Result<State, TlsError>, &'r [u8]), TlsError>
     where
-        Transport: BlockingRead + BlockingWrite + 'a,
         Provider: CryptoProvider,
     {
         match self {
             State::ClientHello => {
-                let (state, tx) =
-                    client_hello(key_schedule, config, crypto_provider, tx_buf, handshake)?;
-
-                respond_blocking(tx, transport, key_schedule)?;
-
-                Ok(state)
-            }
-            State::ServerHello => {
-                let record = record_reader.read_blocking(transport, key_schedule.read_state())?;
-
-                let result = process_server_hello(handshake, key_schedule, record);
-
-                handle_processing_error_blocking(result, transport, key_schedule, tx_buf)
-            }
-            State::ServerVerify => {
-                let record = record_reader.read_blocking(transport, key_schedule.read_state())?;
-
-                let result =
-                    process_server_verify(handshake, key_schedule, crypto_provider, record);
-
-                handle_processing_error_blocking(result, transport, key_schedule, tx_buf)
-            }
-            State::ClientCert => {
-                let (state, tx) = client_cert(handshake, key_schedule, crypto_provider, tx_buf)?;
-
-                respond_blocking(tx, transport, key_schedule)?;
-
-                Ok(state)
-            }
-            State::ClientCertVerify => {
-                let (result, tx) = client_cert_verify(key_schedule, crypto_provider, tx_buf)?;
-
-                respond_blocking(tx, transport, key_schedule)?;
-
-                result
+                client_hello(key_schedule, config, crypto_provider, tx_buf, handshake)
+                    .map(|(state, tx)| (Ok(state), tx))
             }
+            State::ChangeCipherSpec => change_cipher_spec(handshake, key_schedule, tx_buf)
+                .map(|(state, tx)| (Ok(state), tx)),
+            State::ClientCert => client_cert(handshake, key_schedule, crypto_provider, tx_buf)
+                .map(|(state, tx)| (Ok(state), tx)),
+            State::ClientCertVerify => client_cert_verify(key_schedule, crypto_provider, tx_buf),
             State::ClientFinished => {
-                let tx = client_finished(key_schedule, tx_buf)?;
-
-                respond_blocking(tx, transport, key_schedule)?;
-
-                client_finished_finalize(key_schedule, handshake)
+                client_finished(key_schedule, tx_buf).map(|tx| (Ok(State::ApplicationData), tx))
             }
-            State::ApplicationData => Ok(State::ApplicationData),
+            #[cfg(feature = "tls12")]
+            State::Tls12ClientCert
+            | State::Tls12ClientKeyExchange
+            | State::Tls12ChangeCipherSpec
+            | State::Tls12ClientFinished => self
+                .tls12_client_flight(handshake, key_schedule, tx_buf)
+                .map(|(state, tx)| (Ok(state), tx)),
+            _ => Err(TlsError::InternalError),
         }
     }
-}
-
-fn handle_processing_error_blocking<CipherSuite>(
-    result: Result<State, TlsError>,
-    transport: &mut impl BlockingWrite,
-    key_schedule: &mut KeySchedule<CipherSuite>,
-    tx_buf: &mut WriteBuffer,
-) -> Result<State, TlsError>
-where
-    CipherSuite: TlsCipherSuite,
-{
-    if let Err(TlsError::AbortHandshake(level, description)) = result {
-        let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
-        let tx = tx_buf.write_record(
-            &ClientRecord::Alert(Alert { level, description }, false),
-            write_key_schedule,
-            Some(read_key_schedule),
-        )?;
 
-        respond_blocking(tx, transport, key_schedule)?;
+    /// Complete the transition to `next`, the state returned by [`Self::write_record`].
+    pub(crate) fn record_written<CipherSuite>(
+        self,
+        next: State,
+        handshake: &mut Handshake<CipherSuite>,
+        key_schedule: &mut KeySchedule<CipherSuite>,
+    ) -> Result<State, TlsError>
+    where
+        CipherSuite: TlsCipherSuite,
+    {
+        if self == State::ClientFinished {
+            client_finished_finalize(key_schedule, handshake)
+        } else {
+            Ok(next)
+        }
     }
 
-    result
-}
-
-fn respond_blocking<CipherSuite>(
-    tx: &[u8],
-    transport: &mut impl BlockingWrite,
-    key_schedule: &mut KeySchedule<CipherSuite>,
-) -> Result<(), TlsError>
-where
-    CipherSuite: TlsCipherSuite,
-{
-    transport
-        .write_all(tx)
-        .map_err(|e| TlsError::Io(e.kind()))?;
-
-    key_schedule.write_state().increment_counter();
-
-    transport.flush().map_err(|e| TlsError::Io(e.kind()))?;
-
-    Ok(())
-}
-
-async fn handle_processing_error<CipherSuite>(
-    result: Result<State, TlsError>,
-    transport: &mut impl AsyncWrite,
-    key_schedule: &mut KeySchedule<CipherSuite>,
-    tx_buf: &mut WriteBuffer<'_>,
-) -> Result<State, TlsError>
-where
-    CipherSuite: TlsCipherSuite,
-{
-    if let Err(TlsError::AbortHandshake(level, description)) = result {
-        let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
-        let tx = tx_buf.write_record(
-            &ClientRecord::Alert(Alert { level, description }, false),
-            write_key_schedule,
-            Some(read_key_schedule),
-        )?;
-
-        respond(tx, transport, key_schedule).await?;
+    /// Write the next record of the TLS 1.2 client flight.
+    #[cfg(feature = "tls12")]
+    fn tls12_client_flight<'r, CipherSuite>(
+        self,
+        handshake: &mut Handshake<CipherSuite>,
+        key_schedule: &mut KeySchedule<CipherSuite>,
+        tx_buf: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+    ) -> Result<(State, &'r [u8]), TlsError>
+    where
+        CipherSuite: TlsCipherSuite,
+    {
+        match self {
+            State::Tls12ClientCert => tls12::client_cert(key_schedule, tx_buf),
+            State::Tls12ClientKeyExchange => {
+                tls12::client_key_exchange(handshake, key_schedule, tx_buf)
+            }
+            State::Tls12ChangeCipherSpec => tls12::change_cipher_spec(key_schedule, tx_buf),
+            State::Tls12ClientFinished => tls12::client_finished(handshake, key_schedule, tx_buf),
+            _ => Err(TlsError::InternalError),
+        }
     }
-
-    result
-}
-
-async fn respond<CipherSuite>(
-    tx: &[u8],
-    transport: &mut impl AsyncWrite,
-    key_schedule: &mut KeySchedule<CipherSuite>,
-) -> Result<(), TlsError>
-where
-    CipherSuite: TlsCipherSuite,
-{
-    transport
-        .write_all(tx)
-        .await
-        .map_err(|e| TlsError::Io(e.kind()))?;
-
-    key_schedule.write_state().increment_counter();
-
-    transport
-        .flush()
-        .await
-        .map_err(|e| TlsError::Io(e.kind()))?;
-
-    Ok(())
 }
 
 fn client_hello<'r, Provider>(
     key_schedule: &mut KeySchedule<Provider::CipherSuite>,
     config: &TlsConfig,
     crypto_provider: &mut Provider,
-    tx_buf: &'r mut WriteBuffer,
+    tx_buf: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
     handshake: &mut Handshake<Provider::CipherSuite>,
 ) -> Result<(State, &'r [u8]), TlsError>
 where
@@ -396,38 +388,50 @@ where
 {
     key_schedule.initialize_early_secret(config.psk.as_ref().map(|p| p.0))?;
     let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
-    let client_hello = ClientRecord::client_hello(config, crypto_provider);
+    let client_hello =
+        ClientRecord::client_hello(config, crypto_provider, handshake.secret.take())?;
     let slice = tx_buf.write_record(&client_hello, write_key_schedule, Some(read_key_schedule))?;
 
-    if let ClientRecord::Handshake(ClientHandshake::ClientHello(client_hello), _) = client_hello {
-        handshake.secret.replace(client_hello.secret);
+    if let ClientRecord::Handshake(OutgoingHandshake::ClientHello(client_hello), _) = client_hello {
+        key_schedule.set_client_random(client_hello.random);
+        #[cfg(feature = "tls12")]
+        if client_hello.offers_tls12() {
+            handshake.tls12.client_random = client_hello.random;
+            key_schedule
+                .read_state()
+                .set_tls12_records(Some(Tls12Records::Plaintext));
+        }
+        handshake.secret = client_hello.secret;
+        handshake.legacy_session_id = client_hello.legacy_session_id;
         Ok((State::ServerHello, slice))
     } else {
         Err(TlsError::EncodeError)
     }
 }
 
-fn process_server_hello<CipherSuite>(
-    handshake: &mut Handshake<CipherSuite>,
-    key_schedule: &mut KeySchedule<CipherSuite>,
-    record: ServerRecord<'_, CipherSuite>,
+fn process_server_hello<Provider>(
+    handshake: &mut Handshake<Provider::CipherSuite>,
+    key_schedule: &mut KeySchedule<Provider::CipherSuite>,
+    #[cfg_attr(not(feature = "tls12"), allow(unused_variables))] crypto_provider: &mut Provider,
+    record: ServerRecord<'_, Provider::CipherSuite>,
 ) -> Result<State, TlsError>
 where
-    CipherSuite: TlsCipherSuite,
+    Provider: CryptoProvider,
 {
     match record {
         ServerRecord::Handshake(server_handshake) => match server_handshake {
-            ServerHandshake::ServerHello(server_hello) => {
-                trace!("********* ServerHello");
-                let secret = handshake.secret.take().ok_or(TlsError::InvalidHandshake)?;
-                let shared = server_hello
-                    .calculate_shared_secret(&secret)
-                    .ok_or(TlsError::InvalidKeyShare)?;
-                key_schedule.initialize_handshake_secret(shared.raw_secret_bytes())?;
-                Ok(State::ServerVerify)
+            IncomingHandshake::ServerHello(server_hello) => {
+                process_tls13_server_hello(handshake, key_schedule, &server_hello)
             }
             _ => Err(TlsError::InvalidHandshake),
         },
+        // Handshake records are passed undecoded when TLS 1.2 was offered
+        #[cfg(feature = "tls12")]
+        ServerRecord::ApplicationData(record)
+            if key_schedule.read_state().tls12_records() == Some(Tls12Records::Plaintext) =>
+        {
+            tls12::process_server_hello(handshake, key_schedule, crypto_provider, record)
+        }
         ServerRecord::Alert(alert) => {
             Err(TlsError::HandshakeAborted(alert.level, alert.description))
         }
@@ -435,9 +439,36 @@ where
     }
 }
 
+pub(crate) fn process_tls13_server_hello<CipherSuite>(
+    handshake: &mut Handshake<CipherSuite>,
+    key_schedule: &mut KeySchedule<CipherSuite>,
+    server_hello: &ServerHello,
+) -> Result<State, TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    trace!("********* ServerHello");
+    let session_id = handshake
+        .legacy_session_id
+        .as_ref()
+        .map_or(&[][..], |id| &id[..]);
+    if server_hello.legacy_session_id() != session_id {
+        warn!("ServerHello does not echo the ClientHello session ID");
+        return Err(TlsError::AbortHandshake(
+            AlertLevel::Fatal,
+            AlertDescription::IllegalParameter,
+        ));
+    }
+    let secret = handshake.secret.take().ok_or(TlsError::InvalidHandshake)?;
+    let shared = server_hello.calculate_shared_secret(&secret)?;
+    key_schedule.initialize_handshake_secret(shared.raw_secret_bytes())?;
+    Ok(State::ServerVerify)
+}
+
 fn process_server_verify<Provider>(
     handshake: &mut Handshake<Provider::CipherSuite>,
     key_schedule: &mut KeySchedule<Provider::CipherSuite>,
+    config: &TlsConfig<'_>,
     crypto_provider: &mut Provider,
     record: ServerRecord<'_, Provider::CipherSuite>,
 ) -> Result<State, TlsError>
@@ -448,49 +479,21 @@ where
     decrypt_record(key_schedule.read_state(), record, |key_schedule, record| {
         match record {
             ServerRecord::Handshake(server_handshake) => {
-                match server_handshake {
-                    ServerHandshake::EncryptedExtensions(_) => {}
-                    ServerHandshake::Certificate(certificate) => {
-                        let transcript = key_schedule.transcript_hash();
-                        if let Ok(verifier) = crypto_provider.verifier() {
-                            verifier.verify_certificate(transcript, certificate)?;
-                            debug!("Certificate verified!");
-                        } else {
-                            debug!("Certificate verification skipped due to no verifier!");
-                        }
-                    }
-                    ServerHandshake::CertificateVerify(verify) => {
-                        if let Ok(verifier) = crypto_provider.verifier() {
-                            verifier.verify_signature(verify)?;
-                            debug!("Signature verified!");
-                        } else {
-                            debug!("Signature verification skipped due to no verifier!");
-                        }
-                    }
-                    ServerHandshake::CertificateRequest(request) => {
-                        handshake.certificate_request.replace(request.try_into()?);
-                    }
-                    ServerHandshake::Finished(finished) => {
-                        if !key_schedule.verify_server_finished(&finished)? {
-                            warn!("Server signature verification failed");
-                            return Err(TlsError::InvalidSignature);
-                        }
-
-                        // trace!("server verified {}", verified);
-                        state = if handshake.certificate_request.is_some() {
-                            State::ClientCert
-                        } else {
-                            handshake
-                                .traffic_hash
-                                .replace(key_schedule.transcript_hash().clone());
-                            State::ClientFinished
-                        };
-                    }
-                    _ => return Err(TlsError::InvalidHandshake),
+                if let Some(next_state) = process_server_handshake(
+                    handshake,
+                    key_schedule,
+                    config,
+                    crypto_provider,
+                    server_handshake,
+                )? {
+                    state = next_state;
                 }
             }
             ServerRecord::ChangeCipherSpec(_) => {}
-            _ => return Err(TlsError::InvalidRecord),
+            ServerRecord::Alert(alert) => {
+                return Err(TlsError::HandshakeAborted(alert.level, alert.description));
+            }
+            ServerRecord::ApplicationData(_) => return Err(TlsError::InvalidRecord),
         }
 
         Ok(())
@@ -498,11 +501,128 @@ where
     Ok(state)
 }
 
+/// Process a message of the encrypted part of the server handshake flight.
+///
+/// Returns the next state once the server `Finished` has been verified.
+pub(crate) fn process_server_handshake<Provider>(
+    handshake: &mut Handshake<Provider::CipherSuite>,
+    key_schedule: &mut ReadKeySchedule<Provider::CipherSuite>,
+    config: &TlsConfig<'_>,
+    crypto_provider: &mut Provider,
+    server_handshake: IncomingHandshake<'_, Provider::CipherSuite>,
+) -> Result<Option<State>, TlsError>
+where
+    Provider: CryptoProvider,
+{
+    match server_handshake {
+        IncomingHandshake::EncryptedExtensions(extensions) => {
+            for extension in extensions.unknown_extensions() {
+                // RFC 8446, Section 4.2: only the custom extensions were offered, and an
+                // unsolicited extension MUST abort the handshake with "unsupported_extension"
+                if !config
+                    .custom_extensions
+                    .iter()
+                    .any(|e| e.extension_type == extension.extension_type)
+                {
+                    warn!("Unsolicited extension type {}", extension.extension_type);
+                    return Err(TlsError::UnknownExtensionType);
+                }
+                crypto_provider.custom_extension(extension.extension_type, extension.data)?;
+            }
+        }
+        IncomingHandshake::Certificate(certificate) => {
+            let transcript = key_schedule.transcript_hash();
+            if let Ok(verifier) = crypto_provider.verifier() {
+                verifier.verify_certificate(transcript, certificate)?;
+                debug!("Certificate verified!");
+            } else {
+                debug!("Certificate verification skipped due to no verifier!");
+            }
+        }
+        IncomingHandshake::CertificateEntry(entry) => {
+            if let Ok(verifier) = crypto_provider.verifier() {
+                verifier.verify_certificate_entry(entry)?;
+            }
+        }
+        IncomingHandshake::CertificateEnd => {
+            let transcript = key_schedule.transcript_hash();
+            if let Ok(verifier) = crypto_provider.verifier() {
+                verifier.verify_certificate_chain(transcript)?;
+                debug!("Certificate verified!");
+            } else {
+                debug!("Certificate verification skipped due to no verifier!");
+            }
+        }
+        IncomingHandshake::CertificateVerify(verify) => {
+            if let Ok(verifier) = crypto_provider.verifier() {
+                verifier.verify_signature(verify)?;
+                debug!("Signature verified!");
+            } else {
+                debug!("Signature verification skipped due to no verifier!");
+            }
+        }
+        IncomingHandshake::CertificateRequest(request) => {
+            handshake.certificate_request.replace(request.try_into()?);
+        }
+        IncomingHandshake::Finished(finished) => {
+            if !key_schedule.verify_finished(&finished)? {
+                warn!("Server signature verification failed");
+                return Err(TlsError::InvalidSignature);
+            }
+
+            // trace!("server verified {}", verified);
+            return Ok(Some(if handshake.legacy_session_id.is_some() {
+                if handshake.certificate_request.is_none() {
+                    handshake
+                        .traffic_hash
+                        .replace(key_schedule.transcript_hash().clone());
+                }
+                State::ChangeCipherSpec
+            } else if handshake.certificate_request.is_some() {
+                State::ClientCert
+            } else {
+                handshake
+                    .traffic_hash
+                    .replace(key_schedule.transcript_hash().clone());
+                State::ClientFinished
+            }));
+        }
+        _ => return Err(TlsError::InvalidHandshake),
+    }
+
+    Ok(None)
+}
+
+/// Send the `ChangeCipherSpec` record of the middlebox compatibility mode, which is neither
+/// protected nor counted in the write sequence number.
+fn change_cipher_spec<'r, CipherSuite>(
+    handshake: &Handshake<CipherSuite>,
+    key_schedule: &mut KeySchedule<CipherSuite>,
+    buffer: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+) -> Result<(State, &'r [u8]), TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
+    let slice = buffer.write_record(
+        &ClientRecord::ChangeCipherSpec(ChangeCipherSpec::new()),
+        write_key_schedule,
+        Some(read_key_schedule),
+    )?;
+
+    let next = if handshake.certificate_request.is_some() {
+        State::ClientCert
+    } else {
+        State::ClientFinished
+    };
+    Ok((next, slice))
+}
+
 fn client_cert<'r, Provider>(
     handshake: &mut Handshake<Provider::CipherSuite>,
     key_schedule: &mut KeySchedule<Provider::CipherSuite>,
     crypto_provider: &mut Provider,
-    buffer: &'r mut WriteBuffer,
+    buffer: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
 ) -> Result<(State, &'r [u8]), TlsError>
 where
     Provider: CryptoProvider,
@@ -530,7 +650,7 @@ where
 
     buffer
         .write_record(
-            &ClientRecord::Handshake(ClientHandshake::ClientCert(certificate), true),
+            &ClientRecord::Handshake(OutgoingHandshake::Certificate(certificate), true),
             write_key_schedule,
             Some(read_key_schedule),
         )
@@ -540,44 +660,23 @@ where
 fn client_cert_verify<'r, Provider>(
     key_schedule: &mut KeySchedule<Provider::CipherSuite>,
     crypto_provider: &mut Provider,
-    buffer: &'r mut WriteBuffer,
+    buffer: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
 ) -> Result<(Result<State, TlsError>, &'r [u8]), TlsError>
 where
     Provider: CryptoProvider,
 {
-    let (result, record) = match crypto_provider.signer() {
-        Ok((mut signing_key, signature_scheme)) => {
-            let ctx_str = b"TLS 1.3, client CertificateVerify\x00";
-
-            // 64 (pad) + 34 (ctx) + 48 (SHA-384) = 146 bytes required
-            let mut msg: heapless::Vec<u8, 146> = heapless::Vec::new();
-            msg.resize(64, 0x20).map_err(|_| TlsError::EncodeError)?;
-            msg.extend_from_slice(ctx_str)
-                .map_err(|_| TlsError::EncodeError)?;
-            msg.extend_from_slice(&key_schedule.transcript_hash().clone().finalize())
-                .map_err(|_| TlsError::EncodeError)?;
-
-            let signature = signing_key.sign(&msg);
-
-            trace!(
-                "Signature: {:?} ({})",
-                signature.as_ref(),
-                signature.as_ref().len()
-            );
-
-            let certificate_verify = CertificateVerify {
-                signature_scheme,
-                signature: heapless::Vec::from_slice(signature.as_ref()).unwrap(),
-            };
-
-            (
-                Ok(State::ClientFinished),
-                ClientRecord::Handshake(
-                    ClientHandshake::ClientCertVerify(certificate_verify),
-                    true,
-                ),
-            )
-        }
+    let ctx_str = b"TLS 1.3, client CertificateVerify\x00";
+    let (result, record) = match crypto_provider
+        .signer()
+        .and_then(|signer| sign_certificate_verify(ctx_str, key_schedule.transcript_hash(), signer))
+    {
+        Ok(certificate_verify) => (
+            Ok(State::ClientFinished),
+            ClientRecord::Handshake(
+                OutgoingHandshake::CertificateVerify(certificate_verify),
+                true,
+            ),
+        ),
         Err(e) => {
             error!("Failed to obtain signing key: {:?}", e);
             (
@@ -597,21 +696,59 @@ where
         .map(|slice| (result, slice))
 }
 
+/// Sign the handshake transcript for a `CertificateVerify` message.
+///
+/// `ctx_str` is the context string for the client or server signature, including the
+/// terminating zero byte. `signer` is the signing key and scheme returned by
+/// [`CryptoProvider::signer`].
+pub(crate) fn sign_certificate_verify<Signature>(
+    ctx_str: &[u8],
+    transcript: &(impl Digest + Clone),
+    signer: (impl SignerMut<Signature>, SignatureScheme),
+) -> Result<CertificateVerify, TlsError>
+where
+    Signature: AsRef<[u8]>,
+{
+    let (mut signing_key, signature_scheme) = signer;
+
+    // 64 (pad) + 34 (ctx) + 48 (SHA-384) = 146 bytes required
+    let mut msg: heapless::Vec<u8, 146> = heapless::Vec::new();
+    msg.resize(64, 0x20).map_err(|_| TlsError::EncodeError)?;
+    msg.extend_from_slice(ctx_str)
+        .map_err(|_| TlsError::EncodeError)?;
+    msg.extend_from_slice(&transcript.clone().finalize())
+        .map_err(|_| TlsError::EncodeError)?;
+
+    let signature = signing_key.sign(&msg);
+
+    trace!(
+        "Signature: {:?} ({})",
+        signature.as_ref(),
+        signature.as_ref().len()
+    );
+
+    Ok(CertificateVerify {
+        signature_scheme,
+        signature: heapless::Vec::from_slice(signature.as_ref())
+            .map_err(|_| TlsError::EncodeError)?,
+    })
+}
+
 fn client_finished<'r, CipherSuite>(
     key_schedule: &mut KeySchedule<CipherSuite>,
-    buffer: &'r mut WriteBuffer,
+    buffer: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
 ) -> Result<&'r [u8], TlsError>
 where
     CipherSuite: TlsCipherSuite,
 {
     let client_finished = key_schedule
-        .create_client_finished()
+        .create_finished()
         .map_err(|_| TlsError::InvalidHandshake)?;
 
     let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
 
     buffer.write_record(
-        &ClientRecord::Handshake(ClientHandshake::Finished(client_finished), true),
+        &ClientRecord::Handshake(OutgoingHandshake::Finished(client_finished), true),
         write_key_schedule,
         Some(read_key_schedule),
     )
@@ -1,6 +1,6 @@
 use crate::application_data::ApplicationData;
 use crate::extensions::extension_data::supported_groups::NamedGroup;
-use p256::ecdh::SharedSecret;
+use crate::key_exchange::SharedSecret;
 
 pub struct CryptoEngine {}
 
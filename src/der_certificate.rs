@@ -32,16 +32,39 @@ pub const ECDSA_SHA256: AlgorithmIdentifier = AlgorithmIdentifier {
     oid: ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2"),
     parameters: None,
 };
-#[cfg(feature = "p384")]
+#[cfg(any(feature = "p384", feature = "brainpool"))]
 pub const ECDSA_SHA384: AlgorithmIdentifier = AlgorithmIdentifier {
     oid: ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.3"),
     parameters: None,
 };
+#[cfg(feature = "brainpool")]
+pub const ECDSA_SHA512: AlgorithmIdentifier = AlgorithmIdentifier {
+    oid: ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.4"),
+    parameters: None,
+};
 #[cfg(feature = "ed25519")]
 pub const ED25519: AlgorithmIdentifier = AlgorithmIdentifier {
     oid: ObjectIdentifier::new_unwrap("1.3.101.112"),
     parameters: None,
 };
+#[cfg(feature = "ed448")]
+pub const ED448: AlgorithmIdentifier = AlgorithmIdentifier {
+    oid: ObjectIdentifier::new_unwrap("1.3.101.113"),
+    parameters: None,
+};
+#[cfg(feature = "shangmi")]
+pub const SM2_SM3: AlgorithmIdentifier = AlgorithmIdentifier {
+    oid: ObjectIdentifier::new_unwrap("1.2.156.10197.1.501"),
+    parameters: None,
+};
+#[cfg(feature = "brainpool")]
+pub const BRAINPOOL_P256R1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.36.3.3.2.8.1.1.7");
+#[cfg(feature = "brainpool")]
+pub const BRAINPOOL_P384R1: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.36.3.3.2.8.1.1.11");
+#[cfg(feature = "brainpool")]
+pub const BRAINPOOL_P512R1: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.36.3.3.2.8.1.1.13");
 #[cfg(feature = "rsa")]
 pub const RSA_PKCS1_SHA256: AlgorithmIdentifier = AlgorithmIdentifier {
     oid: ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.11"),
@@ -0,0 +1,252 @@
+use core::time::Duration;
+
+use embedded_io::Error as _;
+
+use crate::TlsError;
+use crate::alert::{Alert, AlertDescription, AlertLevel};
+use crate::config::{CryptoProvider, TlsCipherSuite, TlsContext, TlsVerifier};
+use crate::dtls::client::DtlsClient;
+
+/// A connected datagram socket, such as a UDP socket connected to the server.
+#[allow(async_fn_in_trait)]
+pub trait DatagramSocket {
+    type Error: embedded_io::Error;
+
+    /// Send `buf` as a single datagram.
+    async fn send(&mut self, buf: &[u8]) -> Result<(), Self::Error>;
+
+    /// Receive a single datagram into `buf`, returning its length.
+    ///
+    /// Returns `None` if no datagram was received within `timeout`, if any. Datagrams larger
+    /// than `buf` may be truncated.
+    async fn recv(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<Option<usize>, Self::Error>;
+}
+
+impl<T: DatagramSocket> DatagramSocket for &mut T {
+    type Error = T::Error;
+
+    async fn send(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        T::send(self, buf).await
+    }
+
+    async fn recv(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<Option<usize>, Self::Error> {
+        T::recv(self, buf, timeout).await
+    }
+}
+
+/// Type representing an async DTLS 1.3 client connection over a [`DatagramSocket`].
+pub struct DtlsConnection<'a, Socket, CipherSuite>
+where
+    Socket: DatagramSocket + 'a,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    delegate: Socket,
+    client: DtlsClient<'a, CipherSuite>,
+}
+
+impl<'a, Socket, CipherSuite> DtlsConnection<'a, Socket, CipherSuite>
+where
+    Socket: DatagramSocket + 'a,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    /// Create a new DTLS connection with the provided datagram socket.
+    ///
+    /// Half of the read buffer receives datagrams, the other half reassembles handshake
+    /// messages, so each half must fit the largest datagram and handshake message sent by the
+    /// server. The write buffer holds the datagram being sent, of up to
+    /// [`TlsConfig::with_mtu`](crate::TlsConfig::with_mtu) bytes, and the last flight of the
+    /// handshake until it is acknowledged.
+    pub fn new(
+        delegate: Socket,
+        record_read_buf: &'a mut [u8],
+        record_write_buf: &'a mut [u8],
+    ) -> Self {
+        Self {
+            delegate,
+            client: DtlsClient::new(record_read_buf, record_write_buf),
+        }
+    }
+
+    pub fn is_opened(&self) -> bool {
+        self.client.is_connected()
+    }
+
+    /// Open a DTLS connection, performing the handshake with the configuration provided when
+    /// creating the connection instance.
+    ///
+    /// Lost datagrams are retransmitted, and the handshake fails with
+    /// [`TlsError::Io`] `TimedOut` if the server stops responding. If an error occurs, the
+    /// connection instance must be recreated.
+    pub async fn open<Provider>(
+        &mut self,
+        mut context: TlsContext<'_, Provider>,
+    ) -> Result<(), TlsError>
+    where
+        Provider: CryptoProvider<CipherSuite = CipherSuite>,
+    {
+        if let (Ok(verifier), Some(server_name)) = (
+            context.crypto_provider.verifier(),
+            context.config.server_name,
+        ) {
+            verifier.set_hostname_verification(server_name)?;
+        }
+
+        match self.handshake(&mut context).await {
+            Err(e) => Err(self.abort(e).await),
+            ok => ok,
+        }
+    }
+
+    async fn handshake<Provider>(
+        &mut self,
+        context: &mut TlsContext<'_, Provider>,
+    ) -> Result<(), TlsError>
+    where
+        Provider: CryptoProvider<CipherSuite = CipherSuite>,
+    {
+        if self.client.wants_key_share()
+            && let Some(key_share) = context.take_key_share()
+        {
+            self.client.set_key_share(key_share);
+        }
+        self.client
+            .start(&context.config, &mut context.crypto_provider)?;
+        context.flush_key_log(&mut self.client.key_schedule);
+
+        while self.client.is_handshaking() {
+            self.transmit().await?;
+
+            if self.receive().await? {
+                self.client
+                    .handle_handshake(&context.config, &mut context.crypto_provider)?;
+                context.flush_key_log(&mut self.client.key_schedule);
+            }
+        }
+        self.transmit().await
+    }
+
+    /// Send all datagrams queued by the client.
+    async fn transmit(&mut self) -> Result<(), TlsError> {
+        while let Some(datagram) = self.client.poll_transmit()? {
+            self.delegate
+                .send(datagram)
+                .await
+                .map_err(|e| TlsError::Io(e.kind()))?;
+        }
+        Ok(())
+    }
+
+    /// Wait for a datagram, or handle the retransmission timer expiring.
+    ///
+    /// Returns whether a datagram was received.
+    async fn receive(&mut self) -> Result<bool, TlsError> {
+        let timeout = self.client.timeout();
+        let received = self
+            .delegate
+            .recv(self.client.rx_buffer(), timeout)
+            .await
+            .map_err(|e| TlsError::Io(e.kind()))?;
+        if let Some(len) = received {
+            self.client.receive(len);
+            Ok(true)
+        } else {
+            self.client.handle_timeout()?;
+            Ok(false)
+        }
+    }
+
+    /// Encrypt and send the provided slice over the connection, in a single datagram. The
+    /// connection must be opened before writing.
+    ///
+    /// Returns the number of bytes sent, which is less than `buf.len()` if it does not fit in a
+    /// datagram.
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, TlsError> {
+        let (len, datagram) = self.client.write(buf)?;
+        self.delegate
+            .send(datagram)
+            .await
+            .map_err(|e| TlsError::Io(e.kind()))?;
+        Ok(len)
+    }
+
+    /// Read and decrypt data filling the provided slice.
+    ///
+    /// Returns `Ok(0)` once the server has closed its side of the connection with `close_notify`.
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, TlsError> {
+        if !self.client.is_connected() {
+            return Err(TlsError::MissingHandshake);
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            match self.client.read(buf) {
+                Ok(Some(len)) => return Ok(len),
+                Ok(None) => {}
+                Err(e) => return Err(self.abort(e).await),
+            }
+
+            // Acknowledge messages and retransmit the final flight of the handshake if needed
+            self.transmit().await?;
+            self.receive().await?;
+        }
+    }
+
+    /// Send an alert to the server.
+    ///
+    /// Sending a fatal alert closes the connection, after which reads and writes fail with
+    /// [`TlsError::MissingHandshake`].
+    pub async fn send_alert(
+        &mut self,
+        level: AlertLevel,
+        description: AlertDescription,
+    ) -> Result<(), TlsError> {
+        if !self.client.is_connected() {
+            return Err(TlsError::MissingHandshake);
+        }
+        self.write_alert(&Alert::new(level, description)).await
+    }
+
+    async fn write_alert(&mut self, alert: &Alert) -> Result<(), TlsError> {
+        let datagram = self.client.alert(alert)?;
+        self.delegate
+            .send(datagram)
+            .await
+            .map_err(|e| TlsError::Io(e.kind()))
+    }
+
+    /// Tear down the connection after a fatal error, telling the server why if possible.
+    async fn abort(&mut self, error: TlsError) -> TlsError {
+        if let Some(alert) = Alert::for_error(error)
+            && let Err(e) = self.write_alert(&alert).await
+        {
+            warn!("Failed to send {:?} alert: {:?}", alert.description, e);
+        }
+
+        error
+    }
+
+    /// Send `close_notify` to the server and return the ownership of the socket.
+    pub async fn close(mut self) -> Result<Socket, (Socket, TlsError)> {
+        if self.client.is_connected()
+            && let Err(e) = self
+                .write_alert(&Alert::new(
+                    AlertLevel::Warning,
+                    AlertDescription::CloseNotify,
+                ))
+                .await
+        {
+            return Err((self.delegate, e));
+        }
+        Ok(self.delegate)
+    }
+}
@@ -0,0 +1,752 @@
+use core::cmp::Ordering;
+use core::ops::Range;
+use core::time::Duration;
+
+use digest::Digest;
+use heapless::Vec;
+use p256::elliptic_curve::rand_core::RngCore;
+
+use crate::TlsError;
+use crate::alert::{Alert, AlertDescription, AlertLevel};
+use crate::config::{CryptoProvider, TlsCipherSuite, TlsConfig};
+use crate::connection::{self, Handshake, process_server_handshake, sign_certificate_verify};
+use crate::content_types::ContentType;
+use crate::dtls::flight::{
+    DTLS_HANDSHAKE_HEADER_LEN, Flight, FragmentHeader, Reassembler, Reassembly, encode_ack,
+    parse_ack,
+};
+use crate::dtls::record::{APPLICATION_EPOCH, HANDSHAKE_EPOCH, Record, RecordLayer, RecordNumber};
+use crate::extensions::extension_data::supported_versions::DTLS13;
+use crate::grease::Grease;
+use crate::handshake::certificate::CertificateRef;
+use crate::handshake::client_hello::ClientHello;
+use crate::handshake::server_hello::{HelloRetryRequest, ServerHello};
+use crate::handshake::{
+    HANDSHAKE_HEADER_LEN, HandshakeType, IncomingHandshake, OutgoingHandshake, Random,
+};
+use crate::key_schedule::KeySchedule;
+use crate::key_share_pool::KeyShare;
+use crate::parse_buffer::ParseBuffer;
+
+/// How many times a flight is retransmitted before giving up.
+const MAX_RETRANSMISSIONS: u32 = 6;
+
+/// The retransmission timeout is doubled on every retransmission, up to this value.
+const MAX_RETRANSMISSION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The number of handshake records that can be acknowledged at once.
+const MAX_ACKS: usize = 32;
+
+/// Size of a record number in an ACK.
+const ACK_RECORD_NUMBER_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum State {
+    Start,
+    ServerHello,
+    ServerFlight,
+    Connected,
+    Closed,
+}
+
+/// The DTLS 1.3 client state machine, independent of the datagram transport.
+///
+/// The connection feeds it the received datagrams and sends the datagrams it produces, and
+/// calls [`DtlsClient::handle_timeout`] when nothing was received within
+/// [`DtlsClient::timeout`].
+pub(crate) struct DtlsClient<'a, CipherSuite>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    state: State,
+    pub(crate) key_schedule: KeySchedule<CipherSuite>,
+    handshake: Handshake<CipherSuite>,
+    random: Random,
+    grease: Option<Grease>,
+    hello_retry: bool,
+    records: RecordLayer<CipherSuite>,
+    flight: Flight<'a>,
+    /// Datagrams are encoded here, this is the whole write buffer until the MTU is known.
+    datagram: &'a mut [u8],
+    reassembler: Reassembler<'a>,
+    rx_buf: &'a mut [u8],
+    /// The records of the last datagram that were not processed yet.
+    rx: Range<usize>,
+    /// Application data that was not read yet.
+    pending: Range<usize>,
+    /// The handshake records received that have not been acknowledged yet.
+    acks: Vec<RecordNumber, MAX_ACKS>,
+    send_ack: bool,
+    mtu: usize,
+    initial_timeout: Duration,
+    timeout: Duration,
+    retransmissions: u32,
+    read_closed: bool,
+}
+
+impl<'a, CipherSuite> DtlsClient<'a, CipherSuite>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    /// Half of the read buffer receives datagrams, the other half reassembles handshake
+    /// messages.
+    pub fn new(read_buf: &'a mut [u8], write_buf: &'a mut [u8]) -> Self {
+        let (rx_buf, reassembly_buf) = read_buf.split_at_mut(read_buf.len() / 2);
+        Self {
+            state: State::Start,
+            key_schedule: KeySchedule::new().with_dtls_labels(),
+            handshake: Handshake::new(),
+            random: [0; 32],
+            grease: None,
+            hello_retry: false,
+            records: RecordLayer::new(),
+            flight: Flight::new(&mut []),
+            datagram: write_buf,
+            reassembler: Reassembler::new(reassembly_buf),
+            rx_buf,
+            rx: 0..0,
+            pending: 0..0,
+            acks: Vec::new(),
+            send_ack: false,
+            mtu: 0,
+            initial_timeout: Duration::ZERO,
+            timeout: Duration::ZERO,
+            retransmissions: 0,
+            read_closed: false,
+        }
+    }
+
+    /// Whether the connection is still waiting for the handshake to complete.
+    ///
+    /// The final flight of the client must be acknowledged by the server, unless the server
+    /// already sent application data.
+    pub fn is_handshaking(&self) -> bool {
+        match self.state {
+            State::Connected => !self.flight.is_empty() && self.pending.is_empty(),
+            State::Closed => false,
+            _ => true,
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state == State::Connected
+    }
+
+    /// Use `key_share`, generated ahead of time, for the handshake instead of generating one in
+    /// [`Self::start`].
+    ///
+    /// The key share is dropped if the handshake has already started.
+    pub fn set_key_share(&mut self, key_share: KeyShare) {
+        if self.wants_key_share() {
+            self.handshake.secret = Some(key_share.0);
+        }
+    }
+
+    /// Whether the handshake has no key share yet, see [`Self::set_key_share`].
+    pub(crate) fn wants_key_share(&self) -> bool {
+        self.state == State::Start && self.handshake.secret.is_none()
+    }
+
+    /// Start the handshake by queueing the `ClientHello`.
+    pub fn start<Provider>(
+        &mut self,
+        config: &TlsConfig,
+        crypto_provider: &mut Provider,
+    ) -> Result<(), TlsError>
+    where
+        Provider: CryptoProvider<CipherSuite = CipherSuite>,
+    {
+        if self.state != State::Start {
+            return Err(TlsError::InternalError);
+        }
+
+        // The write buffer holds the flight, followed by the datagram being sent
+        let write_buf = core::mem::take(&mut self.datagram);
+        let flight_len = write_buf
+            .len()
+            .checked_sub(config.mtu)
+            .ok_or(TlsError::InsufficientSpace)?;
+        let (flight, datagram) = write_buf.split_at_mut(flight_len);
+        self.flight = Flight::new(flight);
+        self.datagram = datagram;
+        self.mtu = config.mtu;
+        self.initial_timeout = config.retransmission_timeout;
+        self.timeout = config.retransmission_timeout;
+
+        self.key_schedule
+            .initialize_early_secret(config.psk.as_ref().map(|p| p.0))?;
+
+        crypto_provider.rng().fill_bytes(&mut self.random);
+        self.key_schedule.set_client_random(self.random);
+        self.grease = config
+            .grease
+            .then(|| Grease::random(&mut crypto_provider.rng()));
+        let secret = config.key_share(self.handshake.secret.take(), &mut crypto_provider.rng())?;
+
+        let max_fragment_len = self.max_fragment_len(0);
+        let client_hello = OutgoingHandshake::ClientHello(ClientHello::dtls(
+            config,
+            self.random,
+            secret,
+            self.grease,
+            None,
+        ));
+        self.flight
+            .push(&client_hello, 0, max_fragment_len, &mut self.key_schedule)?;
+        if let OutgoingHandshake::ClientHello(client_hello) = client_hello {
+            self.handshake.secret = client_hello.secret;
+        }
+
+        self.state = State::ServerHello;
+        Ok(())
+    }
+
+    fn max_fragment_len(&self, epoch: u64) -> usize {
+        self.mtu
+            .saturating_sub(self.records.overhead(epoch) + DTLS_HANDSHAKE_HEADER_LEN)
+    }
+
+    /// The next datagram to send, if any.
+    pub fn poll_transmit(&mut self) -> Result<Option<&[u8]>, TlsError> {
+        let epoch = self.records.write_epoch();
+        if self.send_ack && !self.acks.is_empty() && epoch != 0 {
+            self.send_ack = false;
+            self.acks.sort_unstable();
+
+            let max_acks =
+                (self.mtu.saturating_sub(self.records.overhead(epoch) + 2)) / ACK_RECORD_NUMBER_LEN;
+            let acks = &self.acks[..self.acks.len().min(max_acks)];
+            let (len, _) = self
+                .records
+                .encode(self.datagram, epoch, ContentType::Ack, |buf| {
+                    encode_ack(buf, acks)
+                })?;
+            if self.state == State::Connected {
+                self.acks.clear();
+            }
+            return Ok(Some(&self.datagram[..len]));
+        }
+
+        let len = self
+            .flight
+            .next_datagram(&mut self.records, self.datagram)?;
+        Ok((len > 0).then(|| &self.datagram[..len]))
+    }
+
+    /// How long to wait for a datagram before calling [`Self::handle_timeout`].
+    pub fn timeout(&self) -> Option<Duration> {
+        match self.state {
+            State::ServerHello | State::ServerFlight => Some(self.timeout),
+            State::Connected if !self.flight.is_empty() => Some(self.timeout),
+            _ => None,
+        }
+    }
+
+    /// Nothing was received within [`Self::timeout`]: retransmit the last flight, or ask the
+    /// server for the missing parts of its flight.
+    pub fn handle_timeout(&mut self) -> Result<(), TlsError> {
+        self.retransmissions += 1;
+        if self.retransmissions > MAX_RETRANSMISSIONS {
+            warn!("DTLS handshake timed out");
+            return Err(TlsError::Io(embedded_io::ErrorKind::TimedOut));
+        }
+        self.timeout = (self.timeout * 2).min(MAX_RETRANSMISSION_TIMEOUT);
+
+        if self.flight.is_empty() {
+            self.send_ack = true;
+        } else {
+            trace!("Retransmitting flight");
+            self.flight.retransmit();
+        }
+        Ok(())
+    }
+
+    fn progress(&mut self) {
+        self.timeout = self.initial_timeout;
+        self.retransmissions = 0;
+    }
+
+    /// The buffer to receive the next datagram into.
+    pub fn rx_buffer(&mut self) -> &mut [u8] {
+        self.rx_buf
+    }
+
+    /// A datagram of `len` bytes was received into [`Self::rx_buffer`].
+    pub fn receive(&mut self, len: usize) {
+        self.rx = 0..len;
+    }
+
+    fn next_record(&mut self) -> Option<Record> {
+        while !self.rx.is_empty() {
+            let start = self.rx.start;
+            let (consumed, record) = self.records.decode(&mut self.rx_buf[self.rx.clone()]);
+            if consumed == 0 {
+                self.rx = 0..0;
+                return None;
+            }
+            self.rx.start += consumed;
+
+            if let Some(mut record) = record {
+                record.payload = record.payload.start + start..record.payload.end + start;
+                return Some(record);
+            }
+        }
+        None
+    }
+
+    /// Process the records received during the handshake.
+    pub fn handle_handshake<Provider>(
+        &mut self,
+        config: &TlsConfig,
+        crypto_provider: &mut Provider,
+    ) -> Result<(), TlsError>
+    where
+        Provider: CryptoProvider<CipherSuite = CipherSuite>,
+    {
+        while self.pending.is_empty()
+            && let Some(record) = self.next_record()
+        {
+            if self.state == State::Connected {
+                self.process_record(&record)?;
+                continue;
+            }
+
+            match record.content_type {
+                ContentType::Handshake => {
+                    self.process_handshake_record(&record, config, crypto_provider)?;
+                }
+                ContentType::Alert => {
+                    let alert = Alert::parse(&mut ParseBuffer::new(&self.rx_buf[record.payload]))?;
+                    return Err(TlsError::HandshakeAborted(alert.level, alert.description));
+                }
+                ContentType::Ack => self.process_ack(&record)?,
+                _ => trace!(
+                    "Discarding {:?} record during handshake",
+                    record.content_type
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// The epoch the next handshake messages from the server are protected with.
+    fn expected_epoch(&self) -> u64 {
+        match self.state {
+            State::Start | State::ServerHello => 0,
+            State::ServerFlight => HANDSHAKE_EPOCH,
+            State::Connected | State::Closed => APPLICATION_EPOCH,
+        }
+    }
+
+    /// Pass the fragments of a handshake record to the reassembler, and call `process` for
+    /// every complete message.
+    fn reassemble(
+        &mut self,
+        record: &Record,
+        mut process: impl FnMut(&mut Self) -> Result<(), TlsError>,
+    ) -> Result<(), TlsError> {
+        let mut data = record.payload.clone();
+        while !data.is_empty() {
+            let (header, body, rest) = FragmentHeader::parse(&self.rx_buf[data.clone()])?;
+            let result = match record.number.epoch.cmp(&self.expected_epoch()) {
+                Ordering::Equal => self.reassembler.insert(&header, body)?,
+                Ordering::Less => Reassembly::Old,
+                Ordering::Greater => Reassembly::Ignored,
+            };
+            data.start = data.end - rest.len();
+
+            match result {
+                Reassembly::Old => {
+                    // The server retransmits its flight when ours got lost
+                    if !self.flight.is_empty() {
+                        self.flight.retransmit();
+                    }
+                }
+                Reassembly::Ignored => {}
+                Reassembly::Incomplete | Reassembly::Complete => {
+                    self.progress();
+                    if !self.acks.contains(&record.number) {
+                        self.acks.push(record.number).ok();
+                    }
+                    if result == Reassembly::Complete {
+                        process(self)?;
+                        self.reassembler.next();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn process_handshake_record<Provider>(
+        &mut self,
+        record: &Record,
+        config: &TlsConfig,
+        crypto_provider: &mut Provider,
+    ) -> Result<(), TlsError>
+    where
+        Provider: CryptoProvider<CipherSuite = CipherSuite>,
+    {
+        self.reassemble(record, |this| match this.state {
+            State::ServerHello => {
+                let message = this.reassembler.message();
+                if message[0] != HandshakeType::ServerHello as u8 {
+                    return Err(TlsError::InvalidHandshake);
+                }
+                if ServerHello::is_hello_retry_request(&message[HANDSHAKE_HEADER_LEN..]) {
+                    this.process_hello_retry_request(config)
+                } else {
+                    this.process_server_hello(config)
+                }
+            }
+            State::ServerFlight => this.process_server_flight(config, crypto_provider),
+            // Messages following the server Finished in the same datagram
+            _ => this.process_post_handshake_message(),
+        })
+    }
+
+    fn process_hello_retry_request(&mut self, config: &TlsConfig) -> Result<(), TlsError> {
+        trace!("********* HelloRetryRequest");
+        if self.hello_retry {
+            return Err(TlsError::AbortHandshake(
+                AlertLevel::Fatal,
+                AlertDescription::UnexpectedMessage,
+            ));
+        }
+        self.hello_retry = true;
+
+        let message = self.reassembler.message();
+        let hello_retry_request =
+            HelloRetryRequest::parse(&mut ParseBuffer::new(&message[HANDSHAKE_HEADER_LEN..]))?;
+        if hello_retry_request.cipher_suite() != CipherSuite::CODE_POINT {
+            return Err(TlsError::InvalidCipherSuite);
+        }
+        if hello_retry_request.selected_version() != Some(DTLS13) {
+            return Err(TlsError::InvalidSupportedVersions);
+        }
+        // Only a single group is supported, for which a key share was sent already
+        if hello_retry_request.selected_group().is_some() {
+            return Err(TlsError::InvalidKeyShare);
+        }
+        let cookie = hello_retry_request
+            .cookie()
+            .ok_or(TlsError::AbortHandshake(
+                AlertLevel::Fatal,
+                AlertDescription::IllegalParameter,
+            ))?;
+
+        self.key_schedule.hash_client_hello();
+        self.key_schedule.transcript_hash().update(message);
+
+        // The HelloRetryRequest acknowledges the first ClientHello
+        self.flight.clear();
+
+        let secret = self
+            .handshake
+            .secret
+            .take()
+            .ok_or(TlsError::InvalidHandshake)?;
+        let max_fragment_len = self
+            .mtu
+            .saturating_sub(self.records.overhead(0) + DTLS_HANDSHAKE_HEADER_LEN);
+        let client_hello = OutgoingHandshake::ClientHello(ClientHello::dtls(
+            config,
+            self.random,
+            secret,
+            self.grease,
+            Some(cookie),
+        ));
+        self.flight
+            .push(&client_hello, 0, max_fragment_len, &mut self.key_schedule)?;
+        if let OutgoingHandshake::ClientHello(client_hello) = client_hello {
+            self.handshake.secret = client_hello.secret;
+        }
+        Ok(())
+    }
+
+    fn process_server_hello(&mut self, config: &TlsConfig) -> Result<(), TlsError> {
+        trace!("********* ServerHello");
+        let message = self.reassembler.message();
+        let IncomingHandshake::ServerHello(server_hello) = IncomingHandshake::<CipherSuite>::read(
+            &mut ParseBuffer::new(message),
+            self.key_schedule.transcript_hash(),
+        )?
+        else {
+            return Err(TlsError::InvalidHandshake);
+        };
+
+        if server_hello.selected_version() != Some(DTLS13) {
+            return Err(TlsError::InvalidSupportedVersions);
+        }
+        if server_hello.cipher_suite() != CipherSuite::CODE_POINT {
+            return Err(TlsError::InvalidCipherSuite);
+        }
+
+        match (config.connection_id, server_hello.connection_id()) {
+            (Some(local), Some(peer)) => self.records.set_connection_ids(local, peer)?,
+            (None, Some(_)) => {
+                return Err(TlsError::AbortHandshake(
+                    AlertLevel::Fatal,
+                    AlertDescription::UnsupportedExtension,
+                ));
+            }
+            // The server does not support connection IDs
+            _ => {}
+        }
+
+        let secret = self
+            .handshake
+            .secret
+            .take()
+            .ok_or(TlsError::InvalidHandshake)?;
+        let shared = server_hello.calculate_shared_secret(&secret)?;
+        self.key_schedule
+            .initialize_handshake_secret(shared.raw_secret_bytes())?;
+
+        let (write_key_schedule, read_key_schedule) = self.key_schedule.as_split();
+        self.records
+            .install_write_epoch(HANDSHAKE_EPOCH, write_key_schedule)?;
+        self.records
+            .install_read_epoch(HANDSHAKE_EPOCH, read_key_schedule)?;
+
+        // The ServerHello acknowledges the ClientHello
+        self.flight.clear();
+        self.state = State::ServerFlight;
+        Ok(())
+    }
+
+    fn process_server_flight<Provider>(
+        &mut self,
+        config: &TlsConfig,
+        crypto_provider: &mut Provider,
+    ) -> Result<(), TlsError>
+    where
+        Provider: CryptoProvider<CipherSuite = CipherSuite>,
+    {
+        let message = self.reassembler.message();
+        let server_handshake = IncomingHandshake::read(
+            &mut ParseBuffer::new(message),
+            self.key_schedule.transcript_hash(),
+        )?;
+        trace!("********* {:?}", server_handshake.handshake_type());
+
+        if let Some(next_state) = process_server_handshake(
+            &mut self.handshake,
+            self.key_schedule.read_state(),
+            config,
+            crypto_provider,
+            server_handshake,
+        )? {
+            self.send_client_flight(next_state, crypto_provider)?;
+        }
+        Ok(())
+    }
+
+    /// Queue the final flight of the handshake and switch to the application traffic keys.
+    fn send_client_flight<Provider>(
+        &mut self,
+        next_state: connection::State,
+        crypto_provider: &mut Provider,
+    ) -> Result<(), TlsError>
+    where
+        Provider: CryptoProvider<CipherSuite = CipherSuite>,
+    {
+        let max_fragment_len = self.max_fragment_len(HANDSHAKE_EPOCH);
+
+        if next_state == connection::State::ClientCert {
+            self.handshake
+                .traffic_hash
+                .replace(self.key_schedule.transcript_hash().clone());
+
+            let request_context = &self
+                .handshake
+                .certificate_request
+                .as_ref()
+                .ok_or(TlsError::InvalidHandshake)?
+                .request_context;
+
+            // Declare cert before certificate so owned data outlives the CertificateRef that borrows it
+            let cert = crypto_provider.client_cert();
+            let has_cert = cert.is_some();
+            let mut certificate = CertificateRef::with_context(request_context);
+            if let Some(ref cert) = cert {
+                certificate.add(cert.into())?;
+            }
+            self.flight.push(
+                &OutgoingHandshake::Certificate(certificate),
+                HANDSHAKE_EPOCH,
+                max_fragment_len,
+                &mut self.key_schedule,
+            )?;
+            drop(cert);
+
+            if has_cert {
+                let certificate_verify = sign_certificate_verify(
+                    b"TLS 1.3, client CertificateVerify\x00",
+                    self.key_schedule.transcript_hash(),
+                    crypto_provider.signer()?,
+                )?;
+                self.flight.push(
+                    &OutgoingHandshake::CertificateVerify(certificate_verify),
+                    HANDSHAKE_EPOCH,
+                    max_fragment_len,
+                    &mut self.key_schedule,
+                )?;
+            }
+        }
+
+        let client_finished = self
+            .key_schedule
+            .create_finished()
+            .map_err(|_| TlsError::InvalidHandshake)?;
+        self.flight.push(
+            &OutgoingHandshake::Finished(client_finished),
+            HANDSHAKE_EPOCH,
+            max_fragment_len,
+            &mut self.key_schedule,
+        )?;
+
+        self.key_schedule.replace_transcript_hash(
+            self.handshake
+                .traffic_hash
+                .take()
+                .ok_or(TlsError::InvalidHandshake)?,
+        );
+        self.key_schedule.initialize_master_secret()?;
+        let (write_key_schedule, read_key_schedule) = self.key_schedule.as_split();
+        self.records
+            .install_write_epoch(APPLICATION_EPOCH, write_key_schedule)?;
+        self.records
+            .install_read_epoch(APPLICATION_EPOCH, read_key_schedule)?;
+
+        // The client Finished acknowledges the server flight
+        self.acks.clear();
+        self.send_ack = false;
+        self.state = State::Connected;
+        Ok(())
+    }
+
+    fn process_ack(&mut self, record: &Record) -> Result<(), TlsError> {
+        let mut progress = false;
+        for number in parse_ack(&self.rx_buf[record.payload.clone()])? {
+            progress |= self.flight.ack(number);
+        }
+
+        if progress {
+            self.progress();
+            if self.flight.is_acked() {
+                self.flight.clear();
+            }
+        }
+        Ok(())
+    }
+
+    /// Process a handshake message received after the handshake.
+    fn process_post_handshake_message(&mut self) -> Result<(), TlsError> {
+        match self.reassembler.message()[0] {
+            // Section 5.8.4: NewSessionTicket messages are acknowledged, tickets are not
+            // supported.
+            t if t == HandshakeType::NewSessionTicket as u8 => {
+                self.send_ack = true;
+                Ok(())
+            }
+            t if t == HandshakeType::KeyUpdate as u8 => Err(TlsError::Unimplemented),
+            _ => Err(TlsError::AbortHandshake(
+                AlertLevel::Fatal,
+                AlertDescription::UnexpectedMessage,
+            )),
+        }
+    }
+
+    /// Process a record received after the handshake.
+    fn process_record(&mut self, record: &Record) -> Result<(), TlsError> {
+        match record.content_type {
+            ContentType::ApplicationData if record.number.epoch == APPLICATION_EPOCH => {
+                if !self.read_closed {
+                    self.pending = record.payload.clone();
+                }
+            }
+            ContentType::Handshake if record.number.epoch != 0 => {
+                self.reassemble(record, Self::process_post_handshake_message)?;
+            }
+            ContentType::Ack => self.process_ack(record)?,
+            ContentType::Alert if record.number.epoch != 0 => {
+                let alert =
+                    Alert::parse(&mut ParseBuffer::new(&self.rx_buf[record.payload.clone()]))?;
+                match alert.description {
+                    AlertDescription::CloseNotify => {
+                        trace!("Received close_notify, no more data will be sent by the server");
+                        self.read_closed = true;
+                    }
+                    AlertDescription::UserCanceled => {}
+                    _ => {
+                        self.state = State::Closed;
+                        return Err(TlsError::HandshakeAborted(alert.level, alert.description));
+                    }
+                }
+            }
+            content_type => trace!("Discarding {:?} record", content_type),
+        }
+        Ok(())
+    }
+
+    /// Read application data received from the server.
+    ///
+    /// Returns `None` when the received datagrams have been processed, `Some(0)` once the
+    /// server closed its side of the connection.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<Option<usize>, TlsError> {
+        loop {
+            if !self.pending.is_empty() {
+                let len = buf.len().min(self.pending.len());
+                let start = self.pending.start;
+                buf[..len].copy_from_slice(&self.rx_buf[start..start + len]);
+                self.pending.start += len;
+                return Ok(Some(len));
+            }
+            if self.read_closed {
+                return Ok(Some(0));
+            }
+
+            let Some(record) = self.next_record() else {
+                return Ok(None);
+            };
+            self.process_record(&record)?;
+        }
+    }
+
+    /// Encode as much of `data` as fits in a datagram.
+    ///
+    /// Returns the number of bytes encoded and the datagram to send.
+    pub fn write(&mut self, data: &[u8]) -> Result<(usize, &[u8]), TlsError> {
+        if self.state != State::Connected {
+            return Err(TlsError::MissingHandshake);
+        }
+
+        let len = data
+            .len()
+            .min(self.mtu - self.records.overhead(APPLICATION_EPOCH));
+        let (datagram_len, _) = self.records.encode(
+            self.datagram,
+            APPLICATION_EPOCH,
+            ContentType::ApplicationData,
+            |buf| buf.extend_from_slice(&data[..len]),
+        )?;
+        Ok((len, &self.datagram[..datagram_len]))
+    }
+
+    /// Encode an alert. Sending a fatal alert closes the connection.
+    pub fn alert(&mut self, alert: &Alert) -> Result<&[u8], TlsError> {
+        if let AlertLevel::Fatal = alert.level {
+            self.state = State::Closed;
+        }
+
+        let epoch = self.records.write_epoch();
+        let (len, _) = self
+            .records
+            .encode(self.datagram, epoch, ContentType::Alert, |buf| {
+                alert.encode(buf)
+            })?;
+        Ok(&self.datagram[..len])
+    }
+}
@@ -0,0 +1,476 @@
+//! Fragmentation, reassembly and acknowledgement of DTLS 1.3 handshake messages,
+//! see [RFC 9147, Section 5](https://www.rfc-editor.org/rfc/rfc9147#section-5).
+
+use heapless::Vec;
+
+use crate::TlsError;
+use crate::buffer::CryptoBuffer;
+use crate::config::TlsCipherSuite;
+use crate::content_types::ContentType;
+use crate::dtls::record::{RecordLayer, RecordNumber};
+use crate::handshake::{HANDSHAKE_HEADER_LEN, OutgoingHandshake};
+use crate::key_schedule::KeySchedule;
+
+/// Size of the DTLS handshake header: the TLS handshake header followed by the message
+/// sequence number, fragment offset and fragment length.
+pub(crate) const DTLS_HANDSHAKE_HEADER_LEN: usize = 12;
+
+const MAX_FRAGMENTS: usize = 64;
+const MAX_RANGES: usize = 8;
+
+/// The header of a handshake message fragment.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct FragmentHeader {
+    pub msg_type: u8,
+    pub length: usize,
+    pub message_seq: u16,
+    pub offset: usize,
+    pub fragment_length: usize,
+}
+
+impl FragmentHeader {
+    /// Split the first fragment off `data`, returning its header, its body and the rest of the
+    /// record.
+    pub fn parse(data: &[u8]) -> Result<(Self, &[u8], &[u8]), TlsError> {
+        let header = data
+            .first_chunk::<DTLS_HANDSHAKE_HEADER_LEN>()
+            .ok_or(TlsError::InvalidHandshake)?;
+        let u24 =
+            |b: &[u8]| (usize::from(b[0]) << 16) | (usize::from(b[1]) << 8) | usize::from(b[2]);
+
+        let header = Self {
+            msg_type: header[0],
+            length: u24(&header[1..4]),
+            message_seq: u16::from_be_bytes([header[4], header[5]]),
+            offset: u24(&header[6..9]),
+            fragment_length: u24(&header[9..12]),
+        };
+        if header.offset + header.fragment_length > header.length {
+            return Err(TlsError::InvalidHandshake);
+        }
+
+        let (body, rest) = data[DTLS_HANDSHAKE_HEADER_LEN..]
+            .split_at_checked(header.fragment_length)
+            .ok_or(TlsError::InvalidHandshake)?;
+        Ok((header, body, rest))
+    }
+
+    pub fn encode(&self, buf: &mut CryptoBuffer) -> Result<(), TlsError> {
+        buf.push(self.msg_type)?;
+        buf.push_u24(self.length as u32)?;
+        buf.push_u16(self.message_seq)?;
+        buf.push_u24(self.offset as u32)?;
+        buf.push_u24(self.fragment_length as u32)
+    }
+}
+
+#[derive(Debug)]
+struct Message {
+    /// Position of the message body in the flight storage.
+    start: usize,
+    msg_type: u8,
+    length: usize,
+    seq: u16,
+    epoch: u64,
+}
+
+#[derive(Debug)]
+struct Fragment {
+    message: usize,
+    offset: usize,
+    length: usize,
+    /// The record this fragment was last sent in.
+    record: Option<RecordNumber>,
+    acked: bool,
+    pending: bool,
+}
+
+/// The handshake messages sent in one flight, kept until they are acknowledged so they can be
+/// retransmitted.
+pub(crate) struct Flight<'a> {
+    storage: &'a mut [u8],
+    used: usize,
+    next_message_seq: u16,
+    messages: Vec<Message, 8>,
+    fragments: Vec<Fragment, MAX_FRAGMENTS>,
+}
+
+impl<'a> Flight<'a> {
+    pub fn new(storage: &'a mut [u8]) -> Self {
+        Self {
+            storage,
+            used: 0,
+            next_message_seq: 0,
+            messages: Vec::new(),
+            fragments: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Whether every fragment of the flight has been acknowledged.
+    pub fn is_acked(&self) -> bool {
+        self.fragments.iter().all(|f| f.acked)
+    }
+
+    /// Forget the flight, which has been acknowledged by the peer.
+    ///
+    /// The messages of the next flight continue the message sequence.
+    pub fn clear(&mut self) {
+        self.used = 0;
+        self.messages.clear();
+        self.fragments.clear();
+    }
+
+    /// Add a message to the flight, updating the transcript.
+    ///
+    /// The message is split into fragments of at most `max_fragment_len` bytes, to be sent in
+    /// records of `epoch`.
+    pub fn push<CipherSuite>(
+        &mut self,
+        handshake: &OutgoingHandshake<'_, '_, CipherSuite>,
+        epoch: u64,
+        max_fragment_len: usize,
+        key_schedule: &mut KeySchedule<CipherSuite>,
+    ) -> Result<(), TlsError>
+    where
+        CipherSuite: TlsCipherSuite,
+    {
+        if max_fragment_len == 0 {
+            return Err(TlsError::InsufficientSpace);
+        }
+
+        // The message is encoded in its TLS form, which is what the transcript covers
+        // (RFC 9147, Section 5.2), right before its body.
+        let header_start = self.used + DTLS_HANDSHAKE_HEADER_LEN - HANDSHAKE_HEADER_LEN;
+        let storage = self
+            .storage
+            .get_mut(header_start..)
+            .ok_or(TlsError::InsufficientSpace)?;
+        let mut buf = CryptoBuffer::wrap(storage);
+        handshake.encode(&mut buf)?;
+        let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
+        handshake.finalize(
+            &mut buf,
+            read_key_schedule.transcript_hash(),
+            write_key_schedule,
+        )?;
+        let length = buf.len() - HANDSHAKE_HEADER_LEN;
+
+        let message = self.messages.len();
+        self.messages
+            .push(Message {
+                start: self.used + DTLS_HANDSHAKE_HEADER_LEN,
+                msg_type: self.storage[header_start],
+                length,
+                seq: self.next_message_seq,
+                epoch,
+            })
+            .map_err(|_| TlsError::InsufficientSpace)?;
+
+        let mut offset = 0;
+        loop {
+            let fragment_len = (length - offset).min(max_fragment_len);
+            self.fragments
+                .push(Fragment {
+                    message,
+                    offset,
+                    length: fragment_len,
+                    record: None,
+                    acked: false,
+                    pending: true,
+                })
+                .map_err(|_| TlsError::InsufficientSpace)?;
+            offset += fragment_len;
+            if offset >= length {
+                break;
+            }
+        }
+
+        self.used += DTLS_HANDSHAKE_HEADER_LEN + length;
+        self.next_message_seq += 1;
+        Ok(())
+    }
+
+    /// Send every fragment that has not been acknowledged again.
+    pub fn retransmit(&mut self) {
+        for fragment in &mut self.fragments {
+            fragment.pending = !fragment.acked;
+        }
+    }
+
+    /// Mark the fragment sent in the record `number` as received by the peer.
+    ///
+    /// Returns whether it was not acknowledged before.
+    pub fn ack(&mut self, number: RecordNumber) -> bool {
+        let mut progress = false;
+        for fragment in &mut self.fragments {
+            if fragment.record == Some(number) && !fragment.acked {
+                fragment.acked = true;
+                fragment.pending = false;
+                progress = true;
+            }
+        }
+        progress
+    }
+
+    /// Pack the pending fragments into a datagram of at most `out.len()` bytes, one record per
+    /// fragment.
+    ///
+    /// Returns the length of the datagram, which is 0 if there is nothing to send.
+    pub fn next_datagram<CipherSuite>(
+        &mut self,
+        records: &mut RecordLayer<CipherSuite>,
+        out: &mut [u8],
+    ) -> Result<usize, TlsError>
+    where
+        CipherSuite: TlsCipherSuite,
+    {
+        let mut len = 0;
+        for fragment in self.fragments.iter_mut().filter(|f| f.pending) {
+            let message = &self.messages[fragment.message];
+            let record_len =
+                records.overhead(message.epoch) + DTLS_HANDSHAKE_HEADER_LEN + fragment.length;
+            if len + record_len > out.len() {
+                if len == 0 {
+                    return Err(TlsError::InsufficientSpace);
+                }
+                break;
+            }
+
+            let header = FragmentHeader {
+                msg_type: message.msg_type,
+                length: message.length,
+                message_seq: message.seq,
+                offset: fragment.offset,
+                fragment_length: fragment.length,
+            };
+            let body = &self.storage[message.start + fragment.offset..][..fragment.length];
+            let (record_len, number) = records.encode(
+                &mut out[len..],
+                message.epoch,
+                ContentType::Handshake,
+                |buf| {
+                    header.encode(buf)?;
+                    buf.extend_from_slice(body)
+                },
+            )?;
+
+            fragment.record = Some(number);
+            fragment.pending = false;
+            len += record_len;
+        }
+        Ok(len)
+    }
+}
+
+/// The outcome of passing a fragment to the [`Reassembler`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Reassembly {
+    /// The fragment belongs to a message that was already processed.
+    Old,
+    /// The fragment belongs to a future message, or could not be tracked, and was dropped.
+    Ignored,
+    /// The fragment was stored, but the message is still incomplete.
+    Incomplete,
+    /// The message is complete and can be processed.
+    Complete,
+}
+
+/// Reassembles the handshake messages received from the peer, one at a time and in order.
+pub(crate) struct Reassembler<'a> {
+    buf: &'a mut [u8],
+    next_seq: u16,
+    message: Option<(u8, usize)>,
+    /// The ranges of the message body received so far, sorted and merged.
+    received: Vec<(usize, usize), MAX_RANGES>,
+}
+
+impl<'a> Reassembler<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self {
+            buf,
+            next_seq: 0,
+            message: None,
+            received: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, header: &FragmentHeader, body: &[u8]) -> Result<Reassembly, TlsError> {
+        if header.message_seq < self.next_seq {
+            return Ok(Reassembly::Old);
+        }
+        if header.message_seq > self.next_seq {
+            return Ok(Reassembly::Ignored);
+        }
+
+        match self.message {
+            None => {
+                if HANDSHAKE_HEADER_LEN + header.length > self.buf.len() {
+                    return Err(TlsError::InsufficientSpace);
+                }
+                self.buf[0] = header.msg_type;
+                self.buf[1..4].copy_from_slice(&(header.length as u32).to_be_bytes()[1..]);
+                self.message = Some((header.msg_type, header.length));
+            }
+            Some(message) if message != (header.msg_type, header.length) => {
+                return Err(TlsError::InvalidHandshake);
+            }
+            Some(_) => {}
+        }
+
+        let (start, end) = (header.offset, header.offset + header.fragment_length);
+        if start < end {
+            let mut ranges: Vec<(usize, usize), { MAX_RANGES + 1 }> = Vec::new();
+            let mut new = (start, end);
+            for &(s, e) in &self.received {
+                if e < new.0 || s > new.1 {
+                    unwrap!(ranges.push((s, e)).ok());
+                } else {
+                    new = (new.0.min(s), new.1.max(e));
+                }
+            }
+            unwrap!(ranges.push(new).ok());
+            if ranges.len() > MAX_RANGES {
+                return Ok(Reassembly::Ignored);
+            }
+            ranges.sort_unstable();
+
+            self.buf[HANDSHAKE_HEADER_LEN + start..HANDSHAKE_HEADER_LEN + end]
+                .copy_from_slice(body);
+            self.received.clear();
+            unwrap!(self.received.extend_from_slice(&ranges).ok());
+        }
+
+        if self.received.as_slice() == [(0, header.length)] || header.length == 0 {
+            Ok(Reassembly::Complete)
+        } else {
+            Ok(Reassembly::Incomplete)
+        }
+    }
+
+    /// The complete message, in its TLS form.
+    pub fn message(&self) -> &[u8] {
+        let length = self.message.map_or(0, |(_, length)| length);
+        &self.buf[..HANDSHAKE_HEADER_LEN + length]
+    }
+
+    /// Start reassembling the next message.
+    pub fn next(&mut self) {
+        self.next_seq = self.next_seq.wrapping_add(1);
+        self.message = None;
+        self.received.clear();
+    }
+}
+
+/// Encode an ACK record body acknowledging `records` (RFC 9147, Section 7).
+pub(crate) fn encode_ack(buf: &mut CryptoBuffer, records: &[RecordNumber]) -> Result<(), TlsError> {
+    buf.with_u16_length(|buf| {
+        for record in records {
+            buf.extend_from_slice(&record.epoch.to_be_bytes())?;
+            buf.extend_from_slice(&record.seq.to_be_bytes())?;
+        }
+        Ok(())
+    })
+}
+
+/// Parse the record numbers of an ACK record body.
+pub(crate) fn parse_ack(data: &[u8]) -> Result<impl Iterator<Item = RecordNumber> + '_, TlsError> {
+    let (len, records) = data
+        .split_first_chunk::<2>()
+        .ok_or(TlsError::InvalidRecord)?;
+    let records = records
+        .get(..u16::from_be_bytes(*len) as usize)
+        .ok_or(TlsError::InvalidRecord)?;
+    let (records, rest) = records.as_chunks::<16>();
+    if !rest.is_empty() {
+        return Err(TlsError::InvalidRecord);
+    }
+
+    Ok(records.iter().map(|record| {
+        let (epoch, seq) = record.split_at(8);
+        RecordNumber {
+            epoch: u64::from_be_bytes(unwrap!(epoch.try_into().ok())),
+            seq: u64::from_be_bytes(unwrap!(seq.try_into().ok())),
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(offset: usize, fragment_length: usize) -> FragmentHeader {
+        FragmentHeader {
+            msg_type: 11,
+            length: 10,
+            message_seq: 1,
+            offset,
+            fragment_length,
+        }
+    }
+
+    #[test]
+    fn test_reassembly() {
+        let mut buf = [0; 32];
+        let mut reassembler = Reassembler::new(&mut buf);
+        let body = *b"0123456789";
+
+        assert_eq!(
+            reassembler.insert(&header(0, 10), &body).unwrap(),
+            Reassembly::Ignored
+        );
+        reassembler.next();
+
+        assert_eq!(
+            reassembler.insert(&header(6, 4), &body[6..]).unwrap(),
+            Reassembly::Incomplete
+        );
+        assert_eq!(
+            reassembler.insert(&header(0, 3), &body[..3]).unwrap(),
+            Reassembly::Incomplete
+        );
+        assert_eq!(
+            reassembler.insert(&header(2, 5), &body[2..7]).unwrap(),
+            Reassembly::Complete
+        );
+        assert_eq!(reassembler.message(), b"\x0b\x00\x00\x0a0123456789");
+
+        reassembler.next();
+        assert_eq!(
+            reassembler.insert(&header(0, 10), &body).unwrap(),
+            Reassembly::Old
+        );
+    }
+
+    #[test]
+    fn test_reassembly_rejects_inconsistent_fragments() {
+        let mut buf = [0; 32];
+        let mut reassembler = Reassembler::new(&mut buf);
+        reassembler.next();
+
+        reassembler.insert(&header(0, 3), b"012").unwrap();
+        let mut other = header(3, 3);
+        other.length = 12;
+        assert!(reassembler.insert(&other, b"345").is_err());
+    }
+
+    #[test]
+    fn test_ack_roundtrip() {
+        let records = [
+            RecordNumber { epoch: 0, seq: 1 },
+            RecordNumber { epoch: 2, seq: 7 },
+        ];
+        let mut buf = [0; 64];
+        let mut buffer = CryptoBuffer::wrap(&mut buf);
+        encode_ack(&mut buffer, &records).unwrap();
+        let len = buffer.len();
+        assert_eq!(len, 2 + 2 * 16);
+
+        let parsed: Vec<RecordNumber, 2> = parse_ack(&buf[..len]).unwrap().collect();
+        assert_eq!(parsed.as_slice(), &records);
+    }
+}
@@ -0,0 +1,22 @@
+//! DTLS 1.3 client connections over datagram transports ([RFC 9147]).
+//!
+//! The handshake is the TLS 1.3 one, with the messages fragmented to fit in datagrams and
+//! retransmitted until they are acknowledged. Records use the unified header with encrypted
+//! record numbers, and can carry a connection ID so the connection survives changes of the
+//! client address, see [`TlsConfig::with_connection_id`](crate::TlsConfig::with_connection_id).
+//!
+//! Instead of `embedded_io` streams, connections use a [`DatagramSocket`], which is implemented
+//! for [`std::net::UdpSocket`] by the [`blocking`] connection.
+//!
+//! [RFC 9147]: https://www.rfc-editor.org/rfc/rfc9147
+
+mod asynch;
+pub mod blocking;
+mod client;
+mod flight;
+pub(crate) mod record;
+
+pub use asynch::*;
+
+#[cfg(all(test, feature = "std"))]
+mod tests;
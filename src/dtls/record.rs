@@ -0,0 +1,525 @@
+//! The DTLS 1.3 record layer, see [RFC 9147, Section 4](https://www.rfc-editor.org/rfc/rfc9147#section-4).
+
+use core::ops::Range;
+
+use aes_gcm::aead::{AeadCore, AeadInPlace, KeyInit};
+use generic_array::GenericArray;
+use heapless::Vec;
+use typenum::Unsigned;
+
+use crate::TlsError;
+use crate::buffer::CryptoBuffer;
+use crate::config::{MAX_CONNECTION_ID_LEN, TlsCipherSuite};
+use crate::content_types::ContentType;
+use crate::handshake::DTLS_LEGACY_VERSION;
+use crate::key_schedule::{IvArray, KeyArray, KeySchedule, ReadKeySchedule, WriteKeySchedule};
+
+/// Epoch of the records protected with the handshake traffic keys.
+pub(crate) const HANDSHAKE_EPOCH: u64 = 2;
+
+/// Epoch of the records protected with the application traffic keys.
+pub(crate) const APPLICATION_EPOCH: u64 = 3;
+
+const PLAINTEXT_HEADER_LEN: usize = 13;
+
+// Section 4.  The DTLS Record Layer
+// The first byte of a DTLSCiphertext is 001CSLEE
+const UNIFIED_HEADER_MASK: u8 = 0b1110_0000;
+const UNIFIED_HEADER: u8 = 0b0010_0000;
+const CID_FLAG: u8 = 0b0001_0000;
+const SEQ16_FLAG: u8 = 0b0000_1000;
+const LENGTH_FLAG: u8 = 0b0000_0100;
+const EPOCH_BITS: u8 = 0b0000_0011;
+
+/// Records may not be protected with a sequence number beyond 2^48 - 1.
+const MAX_SEQUENCE_NUMBER: u64 = (1 << 48) - 1;
+
+/// The number of a record, unique within the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct RecordNumber {
+    pub epoch: u64,
+    pub seq: u64,
+}
+
+/// A record received from the peer, with the payload decrypted in place.
+#[derive(Debug)]
+pub(crate) struct Record {
+    pub number: RecordNumber,
+    pub content_type: ContentType,
+    /// Position of the payload in the datagram.
+    pub payload: Range<usize>,
+}
+
+/// Tracks the highest sequence number received in an epoch, and which of the 64 records
+/// before it have been received, to discard replayed records.
+#[derive(Debug, Default)]
+pub(crate) struct ReplayWindow {
+    next: u64,
+    received: u64,
+}
+
+impl ReplayWindow {
+    /// The sequence number following the highest one received.
+    pub fn next(&self) -> u64 {
+        self.next
+    }
+
+    pub fn contains(&self, seq: u64) -> bool {
+        if seq >= self.next {
+            return false;
+        }
+
+        let age = self.next - 1 - seq;
+        age >= 64 || self.received & (1 << age) != 0
+    }
+
+    pub fn insert(&mut self, seq: u64) {
+        if seq >= self.next {
+            let shift = seq + 1 - self.next;
+            self.received = if shift >= 64 {
+                0
+            } else {
+                self.received << shift
+            };
+            self.received |= 1;
+            self.next = seq + 1;
+        } else {
+            self.received |= 1 << (self.next - 1 - seq);
+        }
+    }
+}
+
+/// Reconstruct a full sequence number from its `bits` low bits, choosing the one closest to
+/// the `expected` sequence number (RFC 9147, Section 4.2.2).
+pub(crate) fn reconstruct_sequence_number(expected: u64, low: u64, bits: u32) -> u64 {
+    let window = 1 << bits;
+    let candidate = (expected & !(window - 1)) | low;
+
+    if candidate + window / 2 <= expected {
+        candidate + window
+    } else if candidate > expected + window / 2 && candidate >= window {
+        candidate - window
+    } else {
+        candidate
+    }
+}
+
+struct EpochKeys<CipherSuite>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    key: KeyArray<CipherSuite>,
+    iv: IvArray<CipherSuite>,
+    sn_key: KeyArray<CipherSuite>,
+}
+
+impl<CipherSuite> EpochKeys<CipherSuite>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    fn record_number_mask(&self, ciphertext: &[u8]) -> Result<[u8; 16], TlsError> {
+        let sample = ciphertext
+            .first_chunk::<16>()
+            .ok_or(TlsError::InvalidRecord)?;
+        CipherSuite::record_number_mask(&self.sn_key, sample)
+    }
+}
+
+struct WriteEpoch<CipherSuite>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    keys: EpochKeys<CipherSuite>,
+    seq: u64,
+}
+
+struct ReadEpoch<CipherSuite>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    keys: EpochKeys<CipherSuite>,
+    window: ReplayWindow,
+}
+
+/// Protects and unprotects the records of one side of a connection.
+///
+/// The keys of an epoch are copied out of the key schedule when the epoch is installed, so
+/// handshake records can still be (re)transmitted and received once the key schedule has moved
+/// on to the application traffic secrets.
+pub(crate) struct RecordLayer<CipherSuite>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    plaintext_seq: u64,
+    plaintext_window: ReplayWindow,
+    write: [Option<WriteEpoch<CipherSuite>>; 2],
+    read: [Option<ReadEpoch<CipherSuite>>; 2],
+    write_epoch: u64,
+    /// The connection ID to include in the records sent to the peer.
+    peer_cid: Vec<u8, MAX_CONNECTION_ID_LEN>,
+    /// The length of the connection ID included in the records received from the peer.
+    local_cid_len: usize,
+}
+
+impl<CipherSuite> RecordLayer<CipherSuite>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    pub fn new() -> Self {
+        Self {
+            plaintext_seq: 0,
+            plaintext_window: ReplayWindow::default(),
+            write: [None, None],
+            read: [None, None],
+            write_epoch: 0,
+            peer_cid: Vec::new(),
+            local_cid_len: 0,
+        }
+    }
+
+    /// Use the negotiated connection IDs for the records of the encrypted epochs.
+    pub fn set_connection_ids(&mut self, local: &[u8], peer: &[u8]) -> Result<(), TlsError> {
+        self.local_cid_len = local.len();
+        self.peer_cid = Vec::from_slice(peer).map_err(|_| TlsError::InsufficientSpace)?;
+        Ok(())
+    }
+
+    /// The epoch used for records that are not part of a handshake flight.
+    pub fn write_epoch(&self) -> u64 {
+        self.write_epoch
+    }
+
+    /// Install the current write keys of `key_schedule` for `epoch`, and use it for all
+    /// records that are not part of a handshake flight.
+    pub fn install_write_epoch(
+        &mut self,
+        epoch: u64,
+        key_schedule: &WriteKeySchedule<CipherSuite>,
+    ) -> Result<(), TlsError> {
+        let keys = EpochKeys {
+            key: key_schedule.get_key()?.clone(),
+            iv: key_schedule.get_iv()?.clone(),
+            sn_key: key_schedule.get_sn_key()?,
+        };
+        self.write[Self::index(epoch)?] = Some(WriteEpoch { keys, seq: 0 });
+        self.write_epoch = epoch;
+        Ok(())
+    }
+
+    /// Install the current read keys of `key_schedule` for `epoch`.
+    pub fn install_read_epoch(
+        &mut self,
+        epoch: u64,
+        key_schedule: &ReadKeySchedule<CipherSuite>,
+    ) -> Result<(), TlsError> {
+        let keys = EpochKeys {
+            key: key_schedule.get_key()?.clone(),
+            iv: key_schedule.get_iv()?.clone(),
+            sn_key: key_schedule.get_sn_key()?,
+        };
+        self.read[Self::index(epoch)?] = Some(ReadEpoch {
+            keys,
+            window: ReplayWindow::default(),
+        });
+        Ok(())
+    }
+
+    fn index(epoch: u64) -> Result<usize, TlsError> {
+        match epoch {
+            HANDSHAKE_EPOCH => Ok(0),
+            APPLICATION_EPOCH => Ok(1),
+            _ => Err(TlsError::InternalError),
+        }
+    }
+
+    /// The number of bytes a record of `epoch` adds to its payload.
+    pub fn overhead(&self, epoch: u64) -> usize {
+        if epoch == 0 {
+            PLAINTEXT_HEADER_LEN
+        } else {
+            // header byte, connection ID, 16 bit sequence number and length, inner content
+            // type and authentication tag
+            1 + self.peer_cid.len()
+                + 2
+                + 2
+                + 1
+                + <CipherSuite::Cipher as AeadCore>::TagSize::to_usize()
+        }
+    }
+
+    /// Encode a record of `epoch` into `buf`, with the payload written by `payload`.
+    ///
+    /// Returns the length of the record and its record number.
+    pub fn encode(
+        &mut self,
+        buf: &mut [u8],
+        epoch: u64,
+        content_type: ContentType,
+        payload: impl FnOnce(&mut CryptoBuffer) -> Result<(), TlsError>,
+    ) -> Result<(usize, RecordNumber), TlsError> {
+        if epoch == 0 {
+            return self.encode_plaintext(buf, content_type, payload);
+        }
+
+        let header_len = 1 + self.peer_cid.len() + 2 + 2;
+        if buf.len() < header_len {
+            return Err(TlsError::InsufficientSpace);
+        }
+        let write = self.write[Self::index(epoch)?]
+            .as_mut()
+            .ok_or(TlsError::InternalError)?;
+        if write.seq > MAX_SEQUENCE_NUMBER {
+            return Err(TlsError::InternalError);
+        }
+
+        let (header, body) = buf.split_at_mut(header_len);
+        let tag_len = <CipherSuite::Cipher as AeadCore>::TagSize::to_usize();
+        let body_capacity = body
+            .len()
+            .checked_sub(tag_len)
+            .ok_or(TlsError::InsufficientSpace)?;
+
+        let mut inner = CryptoBuffer::wrap(&mut body[..body_capacity]);
+        payload(&mut inner)?;
+        inner.push(content_type as u8)?;
+        let len = inner.len();
+
+        let mut flags = UNIFIED_HEADER | SEQ16_FLAG | LENGTH_FLAG | (epoch as u8 & EPOCH_BITS);
+        if !self.peer_cid.is_empty() {
+            flags |= CID_FLAG;
+        }
+        header[0] = flags;
+        let seq_pos = 1 + self.peer_cid.len();
+        header[1..seq_pos].copy_from_slice(&self.peer_cid);
+        header[seq_pos..seq_pos + 2].copy_from_slice(&(write.seq as u16).to_be_bytes());
+        header[seq_pos + 2..].copy_from_slice(&((len + tag_len) as u16).to_be_bytes());
+
+        // Section 4.2.3: the record number is encrypted after the record is protected, so the
+        // additional data contains the plaintext sequence number
+        let nonce = KeySchedule::<CipherSuite>::get_nonce(write.seq, &write.keys.iv);
+        let cipher = <CipherSuite::Cipher as KeyInit>::new(&write.keys.key);
+        let tag = cipher
+            .encrypt_in_place_detached(&nonce, header, &mut body[..len])
+            .map_err(|_| TlsError::CryptoError)?;
+        body[len..len + tag_len].copy_from_slice(&tag);
+
+        let mask = write.keys.record_number_mask(body)?;
+        header[seq_pos] ^= mask[0];
+        header[seq_pos + 1] ^= mask[1];
+
+        let number = RecordNumber {
+            epoch,
+            seq: write.seq,
+        };
+        write.seq += 1;
+        Ok((header_len + len + tag_len, number))
+    }
+
+    fn encode_plaintext(
+        &mut self,
+        buf: &mut [u8],
+        content_type: ContentType,
+        payload: impl FnOnce(&mut CryptoBuffer) -> Result<(), TlsError>,
+    ) -> Result<(usize, RecordNumber), TlsError> {
+        if buf.len() < PLAINTEXT_HEADER_LEN {
+            return Err(TlsError::InsufficientSpace);
+        }
+        if self.plaintext_seq > MAX_SEQUENCE_NUMBER {
+            return Err(TlsError::InternalError);
+        }
+
+        let (header, body) = buf.split_at_mut(PLAINTEXT_HEADER_LEN);
+        let mut inner = CryptoBuffer::wrap(body);
+        payload(&mut inner)?;
+        let len = inner.len();
+
+        header[0] = content_type as u8;
+        header[1..3].copy_from_slice(&DTLS_LEGACY_VERSION.to_be_bytes());
+        // The epoch is 0, followed by the 48 bit sequence number
+        header[3..11].copy_from_slice(&self.plaintext_seq.to_be_bytes());
+        header[11..13].copy_from_slice(&(len as u16).to_be_bytes());
+
+        let number = RecordNumber {
+            epoch: 0,
+            seq: self.plaintext_seq,
+        };
+        self.plaintext_seq += 1;
+        Ok((PLAINTEXT_HEADER_LEN + len, number))
+    }
+
+    /// Decode the first record of `datagram`, decrypting it in place.
+    ///
+    /// Returns the number of bytes consumed, and the record unless it had to be discarded.
+    /// Section 4.5.2: invalid records are silently discarded.
+    pub fn decode(&mut self, datagram: &mut [u8]) -> (usize, Option<Record>) {
+        match datagram.first() {
+            None => (0, None),
+            Some(first) if first & UNIFIED_HEADER_MASK == UNIFIED_HEADER => {
+                self.decode_ciphertext(datagram)
+            }
+            Some(first)
+                if *first == ContentType::Handshake as u8 || *first == ContentType::Alert as u8 =>
+            {
+                self.decode_plaintext(datagram)
+            }
+            Some(_) => {
+                trace!("Discarding datagram with unknown record type");
+                (datagram.len(), None)
+            }
+        }
+    }
+
+    fn decode_plaintext(&mut self, datagram: &[u8]) -> (usize, Option<Record>) {
+        let Some(header) = datagram.first_chunk::<PLAINTEXT_HEADER_LEN>() else {
+            return (datagram.len(), None);
+        };
+
+        let epoch = u16::from_be_bytes([header[3], header[4]]);
+        let mut seq = [0; 8];
+        seq[2..].copy_from_slice(&header[5..11]);
+        let seq = u64::from_be_bytes(seq);
+        let len = u16::from_be_bytes([header[11], header[12]]) as usize;
+
+        let end = PLAINTEXT_HEADER_LEN + len;
+        if end > datagram.len() {
+            return (datagram.len(), None);
+        }
+        if epoch != 0 || self.plaintext_window.contains(seq) {
+            return (end, None);
+        }
+        self.plaintext_window.insert(seq);
+
+        let Some(content_type) = ContentType::of(header[0]) else {
+            return (end, None);
+        };
+
+        (
+            end,
+            Some(Record {
+                number: RecordNumber { epoch: 0, seq },
+                content_type,
+                payload: PLAINTEXT_HEADER_LEN..end,
+            }),
+        )
+    }
+
+    fn decode_ciphertext(&mut self, datagram: &mut [u8]) -> (usize, Option<Record>) {
+        let flags = datagram[0];
+
+        // Records without the connection ID that was negotiated are discarded
+        let has_cid = flags & CID_FLAG != 0;
+        if has_cid != (self.local_cid_len > 0) {
+            return (datagram.len(), None);
+        }
+
+        let seq_pos = 1 + self.local_cid_len;
+        let seq_len = if flags & SEQ16_FLAG == 0 { 1 } else { 2 };
+        let mut header_len = seq_pos + seq_len;
+        let end = if flags & LENGTH_FLAG == 0 {
+            datagram.len()
+        } else {
+            let Some(len) = datagram.get(header_len..header_len + 2) else {
+                return (datagram.len(), None);
+            };
+            let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+            header_len += 2;
+            header_len + len
+        };
+        if end > datagram.len() {
+            return (datagram.len(), None);
+        }
+
+        let record = self.unprotect(&mut datagram[..end], header_len, seq_pos, seq_len);
+        if record.is_none() {
+            trace!("Discarding record");
+        }
+        (end, record)
+    }
+
+    fn unprotect(
+        &mut self,
+        record: &mut [u8],
+        header_len: usize,
+        seq_pos: usize,
+        seq_len: usize,
+    ) -> Option<Record> {
+        let epoch = match record[0] & EPOCH_BITS {
+            2 => HANDSHAKE_EPOCH,
+            3 => APPLICATION_EPOCH,
+            _ => return None,
+        };
+        let read = self.read[Self::index(epoch).ok()?].as_mut()?;
+
+        let tag_len = <CipherSuite::Cipher as AeadCore>::TagSize::to_usize();
+        let (header, body) = record.split_at_mut(header_len);
+        let len = body.len().checked_sub(tag_len)?;
+
+        let mask = read.keys.record_number_mask(body).ok()?;
+        let mut low = 0;
+        for (byte, mask) in header[seq_pos..seq_pos + seq_len].iter_mut().zip(mask) {
+            *byte ^= mask;
+            low = (low << 8) | u64::from(*byte);
+        }
+        let seq = reconstruct_sequence_number(read.window.next(), low, seq_len as u32 * 8);
+        if read.window.contains(seq) {
+            return None;
+        }
+
+        let (ciphertext, tag) = body.split_at_mut(len);
+        let nonce = KeySchedule::<CipherSuite>::get_nonce(seq, &read.keys.iv);
+        let cipher = <CipherSuite::Cipher as KeyInit>::new(&read.keys.key);
+        cipher
+            .decrypt_in_place_detached(&nonce, header, ciphertext, GenericArray::from_slice(tag))
+            .ok()?;
+        read.window.insert(seq);
+
+        // Remove the padding and the inner content type
+        let content_len = ciphertext.iter().rposition(|b| *b != 0)?;
+        let content_type = ContentType::of(ciphertext[content_len])?;
+
+        Some(Record {
+            number: RecordNumber { epoch, seq },
+            content_type,
+            payload: header_len..header_len + content_len,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconstruct_sequence_number() {
+        assert_eq!(reconstruct_sequence_number(0, 0, 16), 0);
+        assert_eq!(reconstruct_sequence_number(5, 7, 8), 7);
+        assert_eq!(reconstruct_sequence_number(0x1fe, 0x01, 8), 0x201);
+        assert_eq!(reconstruct_sequence_number(0x201, 0xff, 8), 0x1ff);
+        assert_eq!(reconstruct_sequence_number(0x1_0001, 0xfffe, 16), 0xfffe);
+        assert_eq!(reconstruct_sequence_number(0xfffe, 0x0003, 16), 0x1_0003);
+    }
+
+    #[test]
+    fn test_replay_window() {
+        let mut window = ReplayWindow::default();
+        assert!(!window.contains(0));
+        window.insert(0);
+        assert!(window.contains(0));
+
+        window.insert(3);
+        assert!(!window.contains(1));
+        assert!(!window.contains(2));
+        assert!(window.contains(3));
+
+        window.insert(1);
+        assert!(window.contains(1));
+        assert!(!window.contains(2));
+
+        window.insert(100);
+        assert!(window.contains(3));
+        assert!(window.contains(36));
+        assert!(!window.contains(37));
+        assert!(!window.contains(101));
+    }
+}
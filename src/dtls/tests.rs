@@ -0,0 +1,646 @@
+//! Handshakes over UDP loopback against a minimal DTLS 1.3 server built from the record layer
+//! and handshake messages of this crate.
+
+use std::collections::VecDeque;
+use std::net::{SocketAddr, UdpSocket};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use digest::Digest;
+use p256::EncodedPoint;
+use p256::PublicKey;
+use p256::ecdh::EphemeralSecret;
+use rand::rngs::OsRng;
+
+use super::blocking;
+use super::flight::{FragmentHeader, Reassembler, Reassembly, encode_ack};
+use super::record::{APPLICATION_EPOCH, HANDSHAKE_EPOCH, RecordLayer, RecordNumber};
+use super::{DatagramSocket, DtlsConnection};
+use crate::TlsError;
+use crate::alert::{AlertDescription, AlertLevel};
+use crate::buffer::CryptoBuffer;
+use crate::config::{
+    Aes128GcmSha256, Certificate, CryptoProvider, TlsCipherSuite, TlsConfig, TlsContext,
+    UnsecureProvider,
+};
+use crate::connection::sign_certificate_verify;
+use crate::content_types::ContentType;
+use crate::extensions::extension_data::connection_id::ConnectionId;
+use crate::extensions::extension_data::key_share::{KeyShareEntry, KeyShareServerHello};
+use crate::extensions::extension_data::supported_groups::NamedGroup;
+use crate::extensions::extension_data::supported_versions::{DTLS13, SupportedVersionsServerHello};
+use crate::extensions::messages::ServerHelloExtension;
+use crate::handshake::certificate::CertificateRef;
+use crate::handshake::encrypted_extensions::EncryptedExtensions;
+use crate::handshake::server_hello::{HELLO_RETRY_REQUEST_RANDOM, ServerHello};
+use crate::handshake::{DTLS_LEGACY_VERSION, HandshakeType, IncomingHandshake, OutgoingHandshake};
+use crate::key_schedule::KeySchedule;
+use crate::parse_buffer::ParseBuffer;
+
+type CipherSuite = Aes128GcmSha256;
+
+const SERVER_CID: &[u8] = b"server-cid";
+const CLIENT_CID: &[u8] = b"client";
+const COOKIE: &[u8] = b"a cookie for the client";
+
+#[derive(Clone, Copy)]
+struct Options {
+    mtu: usize,
+    hello_retry: bool,
+    connection_id: bool,
+    /// Index of a datagram sent by the server that is lost.
+    drop_sent: Option<usize>,
+    /// Index of a datagram received by the server that is lost.
+    drop_received: Option<usize>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            mtu: 1200,
+            hello_retry: false,
+            connection_id: false,
+            drop_sent: None,
+            drop_received: None,
+        }
+    }
+}
+
+struct TestServer {
+    socket: UdpSocket,
+    options: Options,
+    provider: UnsecureProvider<'static, CipherSuite, OsRng>,
+    cert: &'static [u8],
+    key_schedule: KeySchedule<CipherSuite>,
+    records: RecordLayer<CipherSuite>,
+    reassembler: Reassembler<'static>,
+    /// The messages of the current flight with their epoch and message sequence number.
+    flight: Vec<(u64, u16, Vec<u8>)>,
+    next_message_seq: u16,
+    messages: VecDeque<Vec<u8>>,
+    app_data: VecDeque<Vec<u8>>,
+    acks: Vec<RecordNumber>,
+    connected: bool,
+    closed: bool,
+    sent: usize,
+    received: usize,
+    timeouts: usize,
+}
+
+impl TestServer {
+    fn spawn(options: Options) -> (SocketAddr, JoinHandle<Result<(), TlsError>>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        let cert = pem_parser::pem_to_der(include_str!("../../tests/data/im-server-cert.pem"));
+        let key = pem_parser::pem_to_der(include_str!("../../tests/data/im-server-key.pem"));
+        let cert = cert.leak();
+        let provider = UnsecureProvider::new::<CipherSuite>(OsRng)
+            .with_cert(Certificate::X509(cert))
+            .with_priv_key(key.leak());
+
+        let server = Self {
+            socket,
+            options,
+            provider,
+            cert,
+            key_schedule: KeySchedule::new_server().with_dtls_labels(),
+            records: RecordLayer::new(),
+            reassembler: Reassembler::new(vec![0; 16384].leak()),
+            flight: Vec::new(),
+            next_message_seq: 0,
+            messages: VecDeque::new(),
+            app_data: VecDeque::new(),
+            acks: Vec::new(),
+            connected: false,
+            closed: false,
+            sent: 0,
+            received: 0,
+            timeouts: 0,
+        };
+        (addr, std::thread::spawn(move || server.run()))
+    }
+
+    fn run(mut self) -> Result<(), TlsError> {
+        let mut datagram = [0; 1];
+        let (_, peer) = self.socket.peek_from(&mut datagram).unwrap();
+        self.socket.connect(peer).unwrap();
+
+        self.key_schedule.initialize_early_secret(None)?;
+
+        let mut client_hello = self.next_message()?;
+        if self.options.hello_retry {
+            // The transcript starts with the hash of the first ClientHello
+            let hash = <CipherSuite as TlsCipherSuite>::Hash::digest(&client_hello);
+            let hello_retry_request = hello_retry_request();
+            let mut transcript = <CipherSuite as TlsCipherSuite>::Hash::new();
+            transcript.update([HandshakeType::MessageHash as u8, 0, 0, hash.len() as u8]);
+            transcript.update(hash);
+            transcript.update(&hello_retry_request);
+            self.key_schedule.replace_transcript_hash(transcript);
+
+            self.push_raw(0, hello_retry_request);
+            self.send_flight()?;
+            client_hello = self.next_message()?;
+            self.flight.clear();
+        }
+
+        let IncomingHandshake::ClientHello(client_hello) = IncomingHandshake::<CipherSuite>::read(
+            &mut ParseBuffer::new(&client_hello),
+            self.key_schedule.transcript_hash(),
+        )?
+        else {
+            panic!("expected a ClientHello");
+        };
+        assert!(client_hello.offers_version(DTLS13));
+        if self.options.hello_retry {
+            assert_eq!(client_hello.cookie(), Some(COOKIE));
+        }
+        let client_cid = client_hello.connection_id();
+        assert_eq!(client_cid.is_some(), self.options.connection_id);
+        let client_public_key =
+            PublicKey::from_sec1_bytes(client_hello.key_share(NamedGroup::Secp256r1).unwrap())
+                .unwrap();
+
+        let secret = EphemeralSecret::random(&mut OsRng);
+        let public_key = EncodedPoint::from(&secret.public_key());
+        let mut extensions = heapless::Vec::new();
+        extensions
+            .push(ServerHelloExtension::SupportedVersions(
+                SupportedVersionsServerHello {
+                    selected_version: DTLS13,
+                },
+            ))
+            .unwrap();
+        extensions
+            .push(ServerHelloExtension::KeyShare(KeyShareServerHello(
+                KeyShareEntry {
+                    group: NamedGroup::Secp256r1,
+                    opaque: public_key.as_ref(),
+                },
+            )))
+            .unwrap();
+        if client_cid.is_some() {
+            extensions
+                .push(ServerHelloExtension::ConnectionId(ConnectionId {
+                    cid: SERVER_CID,
+                }))
+                .unwrap();
+        }
+        let server_hello = ServerHello::new([7; 32], &[], CipherSuite::CODE_POINT, extensions);
+        self.push(&OutgoingHandshake::ServerHello(server_hello), 0)?;
+
+        let shared = secret.diffie_hellman(&client_public_key);
+        self.key_schedule
+            .initialize_handshake_secret(shared.raw_secret_bytes())?;
+        let (write_key_schedule, read_key_schedule) = self.key_schedule.as_split();
+        self.records
+            .install_write_epoch(HANDSHAKE_EPOCH, write_key_schedule)?;
+        self.records
+            .install_read_epoch(HANDSHAKE_EPOCH, read_key_schedule)?;
+        if let Some(client_cid) = client_cid {
+            self.records.set_connection_ids(SERVER_CID, client_cid)?;
+        }
+
+        self.push(
+            &OutgoingHandshake::EncryptedExtensions(EncryptedExtensions {
+                extensions: heapless::Vec::new(),
+            }),
+            HANDSHAKE_EPOCH,
+        )?;
+        let cert = Certificate::X509(self.cert);
+        let mut certificate = CertificateRef::with_context(&[]);
+        certificate.add((&cert).into())?;
+        self.push(
+            &OutgoingHandshake::Certificate(certificate),
+            HANDSHAKE_EPOCH,
+        )?;
+        let certificate_verify = sign_certificate_verify(
+            b"TLS 1.3, server CertificateVerify\x00",
+            self.key_schedule.transcript_hash(),
+            self.provider.signer()?,
+        )?;
+        self.push(
+            &OutgoingHandshake::CertificateVerify(certificate_verify),
+            HANDSHAKE_EPOCH,
+        )?;
+        let finished = self.key_schedule.create_finished()?;
+        self.push(&OutgoingHandshake::Finished(finished), HANDSHAKE_EPOCH)?;
+        let traffic_hash = self.key_schedule.transcript_hash().clone();
+        self.send_flight()?;
+
+        let client_finished = self.next_message()?;
+        let IncomingHandshake::Finished(client_finished) = IncomingHandshake::<CipherSuite>::read(
+            &mut ParseBuffer::new(&client_finished),
+            self.key_schedule.transcript_hash(),
+        )?
+        else {
+            panic!("expected a Finished");
+        };
+        assert!(
+            self.key_schedule
+                .read_state()
+                .verify_finished(&client_finished)?
+        );
+
+        self.key_schedule.replace_transcript_hash(traffic_hash);
+        self.key_schedule.initialize_master_secret()?;
+        let (write_key_schedule, read_key_schedule) = self.key_schedule.as_split();
+        self.records
+            .install_write_epoch(APPLICATION_EPOCH, write_key_schedule)?;
+        self.records
+            .install_read_epoch(APPLICATION_EPOCH, read_key_schedule)?;
+        self.flight.clear();
+        self.connected = true;
+        self.send_ack()?;
+
+        // Echo the application data until the client closes the connection
+        while !self.closed {
+            if let Some(data) = self.app_data.pop_front() {
+                let mut datagram = [0; 2048];
+                let (len, _) = self.records.encode(
+                    &mut datagram,
+                    APPLICATION_EPOCH,
+                    ContentType::ApplicationData,
+                    |buf| buf.extend_from_slice(&data),
+                )?;
+                self.send(&datagram[..len]);
+            } else {
+                self.poll()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn push(
+        &mut self,
+        handshake: &OutgoingHandshake<'_, '_, CipherSuite>,
+        epoch: u64,
+    ) -> Result<(), TlsError> {
+        let mut storage = [0; 4096];
+        let mut buf = CryptoBuffer::wrap(&mut storage);
+        handshake.encode(&mut buf)?;
+        let len = buf.len();
+        self.key_schedule.transcript_hash().update(&storage[..len]);
+        self.push_raw(epoch, storage[..len].to_vec());
+        Ok(())
+    }
+
+    fn push_raw(&mut self, epoch: u64, message: Vec<u8>) {
+        self.flight.push((epoch, self.next_message_seq, message));
+        self.next_message_seq += 1;
+    }
+
+    fn send(&mut self, datagram: &[u8]) {
+        if self.options.drop_sent != Some(self.sent) {
+            self.socket.send(datagram).unwrap();
+        }
+        self.sent += 1;
+    }
+
+    /// Send the current flight, packing the fragments in datagrams of at most the MTU.
+    fn send_flight(&mut self) -> Result<(), TlsError> {
+        let mtu = self.options.mtu;
+        let mut datagrams = vec![Vec::new()];
+        for (epoch, message_seq, message) in &self.flight {
+            let body = &message[4..];
+            let max_fragment_len = mtu - self.records.overhead(*epoch) - 12;
+            let mut offset = 0;
+            loop {
+                let fragment_length = (body.len() - offset).min(max_fragment_len);
+                let header = FragmentHeader {
+                    msg_type: message[0],
+                    length: body.len(),
+                    message_seq: *message_seq,
+                    offset,
+                    fragment_length,
+                };
+                let mut record = [0; 2048];
+                let (len, _) =
+                    self.records
+                        .encode(&mut record, *epoch, ContentType::Handshake, |buf| {
+                            header.encode(buf)?;
+                            buf.extend_from_slice(&body[offset..offset + fragment_length])
+                        })?;
+
+                let datagram = datagrams.last_mut().unwrap();
+                if datagram.len() + len > mtu {
+                    datagrams.push(record[..len].to_vec());
+                } else {
+                    datagram.extend_from_slice(&record[..len]);
+                }
+
+                offset += fragment_length;
+                if offset >= body.len() {
+                    break;
+                }
+            }
+        }
+
+        for datagram in datagrams.iter().filter(|d| !d.is_empty()) {
+            self.send(datagram);
+        }
+        Ok(())
+    }
+
+    fn send_ack(&mut self) -> Result<(), TlsError> {
+        let mut datagram = [0; 1024];
+        let acks = &self.acks;
+        let (len, _) =
+            self.records
+                .encode(&mut datagram, APPLICATION_EPOCH, ContentType::Ack, |buf| {
+                    encode_ack(buf, acks)
+                })?;
+        self.send(&datagram[..len]);
+        Ok(())
+    }
+
+    fn next_message(&mut self) -> Result<Vec<u8>, TlsError> {
+        loop {
+            if let Some(message) = self.messages.pop_front() {
+                return Ok(message);
+            }
+            self.poll()?;
+        }
+    }
+
+    /// Receive and process a datagram, retransmitting the last flight if none arrives.
+    fn poll(&mut self) -> Result<(), TlsError> {
+        let mut datagram = [0; 4096];
+        let len = match self.socket.recv(&mut datagram) {
+            Ok(len) => len,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                self.timeouts += 1;
+                assert!(self.timeouts < 50, "client stopped responding");
+                return self.send_flight();
+            }
+            Err(e) => panic!("{e}"),
+        };
+
+        self.received += 1;
+        if self.options.drop_received == Some(self.received - 1) {
+            return Ok(());
+        }
+
+        let mut retransmit = false;
+        let mut pos = 0;
+        while pos < len {
+            let (consumed, record) = self.records.decode(&mut datagram[pos..len]);
+            let start = pos;
+            pos += consumed;
+            let Some(record) = record else {
+                continue;
+            };
+
+            let payload = &datagram[start + record.payload.start..start + record.payload.end];
+            match record.content_type {
+                ContentType::Handshake => {
+                    let mut data = payload;
+                    while !data.is_empty() {
+                        let (header, body, rest) = FragmentHeader::parse(data)?;
+                        data = rest;
+                        match self.reassembler.insert(&header, body)? {
+                            Reassembly::Complete => {
+                                self.messages.push_back(self.reassembler.message().to_vec());
+                                self.reassembler.next();
+                            }
+                            Reassembly::Old => retransmit = true,
+                            Reassembly::Incomplete | Reassembly::Ignored => {}
+                        }
+                    }
+                    if record.number.epoch == HANDSHAKE_EPOCH && !self.acks.contains(&record.number)
+                    {
+                        self.acks.push(record.number);
+                    }
+                }
+                // The client is missing part of the flight
+                ContentType::Ack => retransmit = true,
+                ContentType::Alert => {
+                    if payload[1] == AlertDescription::CloseNotify as u8 {
+                        self.closed = true;
+                    } else {
+                        return Err(TlsError::HandshakeAborted(
+                            AlertLevel::of(payload[0]).unwrap(),
+                            AlertDescription::of(payload[1]).unwrap(),
+                        ));
+                    }
+                }
+                ContentType::ApplicationData => self.app_data.push_back(payload.to_vec()),
+                ContentType::ChangeCipherSpec | ContentType::Invalid => {}
+            }
+        }
+
+        if retransmit {
+            if self.connected {
+                self.send_ack()?;
+            } else {
+                self.send_flight()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn hello_retry_request() -> Vec<u8> {
+    let mut extensions = Vec::new();
+    // supported_versions
+    extensions.extend_from_slice(&[0x00, 0x2b, 0x00, 0x02]);
+    extensions.extend_from_slice(&DTLS13.0.to_be_bytes());
+    // cookie
+    extensions.extend_from_slice(&[0x00, 0x2c]);
+    extensions.extend_from_slice(&(COOKIE.len() as u16 + 2).to_be_bytes());
+    extensions.extend_from_slice(&(COOKIE.len() as u16).to_be_bytes());
+    extensions.extend_from_slice(COOKIE);
+
+    let mut body = Vec::new();
+    body.extend_from_slice(&DTLS_LEGACY_VERSION.to_be_bytes());
+    body.extend_from_slice(&HELLO_RETRY_REQUEST_RANDOM);
+    body.push(0);
+    body.extend_from_slice(&CipherSuite::CODE_POINT.to_be_bytes());
+    body.push(0);
+    body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    body.extend_from_slice(&extensions);
+
+    let mut message = vec![HandshakeType::ServerHello as u8];
+    message.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+    message.extend_from_slice(&body);
+    message
+}
+
+fn client_config(options: Options) -> TlsConfig<'static> {
+    let config = TlsConfig::new()
+        .with_mtu(options.mtu)
+        .with_retransmission_timeout(Duration::from_millis(50));
+    if options.connection_id {
+        config.with_connection_id(CLIENT_CID)
+    } else {
+        config
+    }
+}
+
+fn blocking_echo(options: Options) {
+    let (addr, server) = TestServer::spawn(options);
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.connect(addr).unwrap();
+    let config = client_config(options);
+    let mut read_buf = [0; 16384];
+    let mut write_buf = [0; 4096];
+    let mut dtls = blocking::DtlsConnection::new(socket, &mut read_buf, &mut write_buf);
+    dtls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<CipherSuite>(OsRng),
+    ))
+    .expect("error establishing DTLS connection");
+    assert!(dtls.is_opened());
+
+    let mut buf = [0; 2048];
+    for message in [&b"ping"[..], &[0x55; 100]] {
+        assert_eq!(dtls.write(message).unwrap(), message.len());
+        let len = dtls.read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], message);
+    }
+
+    // Writes are limited to a single datagram
+    let len = dtls.write(&[0xaa; 2048]).unwrap();
+    assert!(len < options.mtu);
+    assert_eq!(dtls.read(&mut buf).unwrap(), len);
+
+    dtls.close().map_err(|(_, e)| e).unwrap();
+    server.join().unwrap().expect("server error");
+}
+
+#[test]
+fn test_blocking_echo() {
+    blocking_echo(Options::default());
+}
+
+#[test]
+fn test_fragmentation() {
+    blocking_echo(Options {
+        mtu: 200,
+        ..Options::default()
+    });
+}
+
+#[test]
+fn test_hello_retry_request_cookie() {
+    blocking_echo(Options {
+        hello_retry: true,
+        ..Options::default()
+    });
+}
+
+#[test]
+fn test_connection_id() {
+    blocking_echo(Options {
+        connection_id: true,
+        mtu: 300,
+        ..Options::default()
+    });
+}
+
+#[test]
+fn test_retransmission() {
+    // The first datagram of the server flight and the client Finished are lost
+    blocking_echo(Options {
+        drop_sent: Some(0),
+        drop_received: Some(1),
+        ..Options::default()
+    });
+}
+
+#[test]
+fn test_handshake_timeout() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.connect(silent.local_addr().unwrap()).unwrap();
+
+    let config = TlsConfig::new().with_retransmission_timeout(Duration::from_millis(1));
+    let mut read_buf = [0; 4096];
+    let mut write_buf = [0; 4096];
+    let mut dtls = blocking::DtlsConnection::new(socket, &mut read_buf, &mut write_buf);
+    let result = dtls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<CipherSuite>(OsRng),
+    ));
+    assert!(matches!(
+        result,
+        Err(TlsError::Io(embedded_io::ErrorKind::TimedOut))
+    ));
+
+    // Every transmission of the ClientHello reached the server
+    let mut datagram = [0; 2048];
+    silent.set_nonblocking(true).unwrap();
+    let mut count = 0;
+    while silent.recv(&mut datagram).is_ok() {
+        count += 1;
+    }
+    assert_eq!(count, 7);
+}
+
+struct TokioSocket(tokio::net::UdpSocket);
+
+impl DatagramSocket for TokioSocket {
+    type Error = std::io::Error;
+
+    async fn send(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        self.0.send(buf).await.map(|_| ())
+    }
+
+    async fn recv(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<Option<usize>, Self::Error> {
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.0.recv(buf))
+                .await
+                .map_or(Ok(None), |result| result.map(Some)),
+            None => self.0.recv(buf).await.map(Some),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_async_echo() {
+    let options = Options {
+        hello_retry: true,
+        connection_id: true,
+        drop_sent: Some(1),
+        ..Options::default()
+    };
+    let (addr, server) = TestServer::spawn(options);
+
+    let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(addr).await.unwrap();
+    let config = client_config(options);
+    let mut read_buf = [0; 16384];
+    let mut write_buf = [0; 4096];
+    let mut dtls = DtlsConnection::new(TokioSocket(socket), &mut read_buf, &mut write_buf);
+    dtls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<CipherSuite>(OsRng),
+    ))
+    .await
+    .expect("error establishing DTLS connection");
+
+    assert_eq!(dtls.write(b"ping").await.unwrap(), 4);
+    let mut buf = [0; 16];
+    let len = dtls.read(&mut buf).await.unwrap();
+    assert_eq!(&buf[..len], b"ping");
+
+    dtls.close().await.map_err(|(_, e)| e).unwrap();
+    tokio::task::spawn_blocking(|| server.join().unwrap())
+        .await
+        .unwrap()
+        .expect("server error");
+}
@@ -17,19 +17,44 @@ use crate::{
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct AlpnProtocolNameList<'a> {
-    pub protocols: &'a [&'a [u8]],
+    pub protocols: AlpnProtocols<'a>,
+}
+
+/// A list of ALPN protocol names.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AlpnProtocols<'a> {
+    /// The protocol names.
+    List(&'a [&'a [u8]]),
+    /// The protocol names, each prefixed with its length as in the `protocol_name_list`.
+    Encoded(&'a [u8]),
+}
+
+impl<'a> AlpnProtocols<'a> {
+    /// Iterate over the protocol names.
+    pub fn iter(self) -> impl Iterator<Item = &'a [u8]> + Clone {
+        let (list, encoded) = match self {
+            Self::List(list) => (list, &[][..]),
+            Self::Encoded(encoded) => (&[][..], encoded),
+        };
+        list.iter().copied().chain(encoded_names(encoded))
+    }
+}
+
+/// Iterate over the length-prefixed protocol names of an encoded `protocol_name_list`.
+pub(crate) fn encoded_names(list: &[u8]) -> impl Iterator<Item = &[u8]> + Clone {
+    let mut buf = ParseBuffer::new(list);
+    core::iter::from_fn(move || {
+        let len = buf.read_u8().ok()? as usize;
+        buf.slice(len).ok().map(|name| name.as_slice())
+    })
 }
 
 impl<'a> AlpnProtocolNameList<'a> {
     pub fn parse(buf: &mut ParseBuffer<'a>) -> Result<Self, ParseError> {
-        // We parse but don't store the individual protocol names in a heapless
-        // container — just validate the wire format. The slice reference is kept
-        // for the lifetime of the parse buffer, but since we can't reconstruct
-        // `&[&[u8]]` from a flat buffer without allocation, we store an empty
-        // slice. Callers that need the parsed protocols (server-side) would need
-        // a different approach; for our client-side use we only need encode().
         let list_len = buf.read_u16()? as usize;
-        let mut list_buf = buf.slice(list_len)?;
+        let list = buf.slice(list_len)?;
+        let mut list_buf = list.clone();
 
         while !list_buf.is_empty() {
             let name_len = list_buf.read_u8()? as usize;
@@ -39,13 +64,15 @@ impl<'a> AlpnProtocolNameList<'a> {
             let _name = list_buf.slice(name_len)?;
         }
 
-        Ok(Self { protocols: &[] })
+        Ok(Self {
+            protocols: AlpnProtocols::Encoded(list.as_slice()),
+        })
     }
 
     pub fn encode(&self, buf: &mut CryptoBuffer) -> Result<(), TlsError> {
         // Outer u16 length prefix for the ProtocolNameList
         buf.with_u16_length(|buf| {
-            for protocol in self.protocols {
+            for protocol in self.protocols.iter() {
                 buf.push(protocol.len() as u8)
                     .map_err(|_| TlsError::EncodeError)?;
                 buf.extend_from_slice(protocol)?;
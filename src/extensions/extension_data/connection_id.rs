@@ -0,0 +1,28 @@
+use crate::{
+    TlsError,
+    buffer::CryptoBuffer,
+    parse_buffer::{ParseBuffer, ParseError},
+};
+
+/// RFC 9146, Section 3.  Specifying the `connection_id` Extension
+///
+/// The connection ID the sender wants the peer to put in the records it sends. An empty
+/// connection ID means the sender supports connection IDs but does not want to receive them.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConnectionId<'a> {
+    pub cid: &'a [u8],
+}
+
+impl<'a> ConnectionId<'a> {
+    pub fn parse(buf: &mut ParseBuffer<'a>) -> Result<Self, ParseError> {
+        let len = buf.read_u8()? as usize;
+        Ok(Self {
+            cid: buf.slice(len)?.as_slice(),
+        })
+    }
+
+    pub fn encode(&self, buf: &mut CryptoBuffer) -> Result<(), TlsError> {
+        buf.with_u8_length(|buf| buf.extend_from_slice(self.cid))
+    }
+}
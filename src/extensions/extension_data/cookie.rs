@@ -0,0 +1,28 @@
+use crate::{
+    TlsError,
+    buffer::CryptoBuffer,
+    parse_buffer::{ParseBuffer, ParseError},
+};
+
+/// RFC 8446, Section 4.2.2.  Cookie
+///
+/// Sent by a server in a `HelloRetryRequest` and echoed by the client in the second
+/// `ClientHello`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Cookie<'a> {
+    pub cookie: &'a [u8],
+}
+
+impl<'a> Cookie<'a> {
+    pub fn parse(buf: &mut ParseBuffer<'a>) -> Result<Self, ParseError> {
+        let len = buf.read_u16()? as usize;
+        Ok(Self {
+            cookie: buf.slice(len)?.as_slice(),
+        })
+    }
+
+    pub fn encode(&self, buf: &mut CryptoBuffer) -> Result<(), TlsError> {
+        buf.with_u16_length(|buf| buf.extend_from_slice(self.cookie))
+    }
+}
@@ -0,0 +1,31 @@
+use crate::{
+    TlsError,
+    buffer::CryptoBuffer,
+    parse_buffer::{ParseBuffer, ParseError},
+};
+
+/// The uncompressed point format, the only one used by TLS 1.3 and RFC 8422.
+#[cfg_attr(not(feature = "tls12"), allow(dead_code))]
+pub const UNCOMPRESSED: u8 = 0;
+
+/// RFC 8422, Section 5.1.2.  Supported Point Formats Extension
+///
+/// Only used by TLS 1.2, where some servers refuse ECDHE without it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EcPointFormats<'a> {
+    pub formats: &'a [u8],
+}
+
+impl<'a> EcPointFormats<'a> {
+    pub fn parse(buf: &mut ParseBuffer<'a>) -> Result<Self, ParseError> {
+        let len = buf.read_u8()? as usize;
+        Ok(Self {
+            formats: buf.slice(len)?.as_slice(),
+        })
+    }
+
+    pub fn encode(&self, buf: &mut CryptoBuffer) -> Result<(), TlsError> {
+        buf.with_u8_length(|buf| buf.extend_from_slice(self.formats))
+    }
+}
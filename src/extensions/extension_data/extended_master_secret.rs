@@ -0,0 +1,28 @@
+use crate::{
+    TlsError,
+    buffer::CryptoBuffer,
+    parse_buffer::{ParseBuffer, ParseError},
+};
+
+/// RFC 7627, Section 5.1.  Extension Negotiation
+///
+/// Binds the TLS 1.2 master secret to the whole handshake. The `extension_data` field of this
+/// extension is empty.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ExtendedMasterSecret;
+
+impl ExtendedMasterSecret {
+    pub fn parse(buf: &mut ParseBuffer) -> Result<Self, ParseError> {
+        if buf.is_empty() {
+            Ok(Self)
+        } else {
+            Err(ParseError::InvalidData)
+        }
+    }
+
+    #[allow(clippy::unnecessary_wraps, clippy::unused_self)]
+    pub fn encode(self, _buf: &mut CryptoBuffer) -> Result<(), TlsError> {
+        Ok(())
+    }
+}
@@ -25,6 +25,12 @@ pub enum MaxFragmentLength {
 }
 
 impl MaxFragmentLength {
+    /// The maximum plaintext fragment length in bytes.
+    #[must_use]
+    pub const fn fragment_len(self) -> usize {
+        1 << (8 + self as usize)
+    }
+
     pub fn parse(buf: &mut ParseBuffer) -> Result<Self, ParseError> {
         match buf.read_u8()? {
             1 => Ok(Self::Bits9),
@@ -1,8 +1,14 @@
 pub mod alpn;
+pub mod connection_id;
+pub mod cookie;
+pub mod ec_point_formats;
+pub mod extended_master_secret;
 pub mod key_share;
 pub mod max_fragment_length;
+pub mod padding;
 pub mod pre_shared_key;
 pub mod psk_key_exchange_modes;
+pub mod renegotiation_info;
 pub mod server_name;
 pub mod signature_algorithms;
 pub mod signature_algorithms_cert;
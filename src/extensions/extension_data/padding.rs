@@ -0,0 +1,37 @@
+use crate::{
+    TlsError,
+    buffer::CryptoBuffer,
+    parse_buffer::{ParseBuffer, ParseError},
+};
+
+/// `ClientHello` padding extension
+///
+/// RFC 7685, Section 3.  Padding Extension
+/// The `extension_data` for the extension consists of an arbitrary
+/// number of zero bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Padding {
+    pub len: usize,
+}
+
+impl Padding {
+    pub fn parse(buf: &mut ParseBuffer) -> Result<Self, ParseError> {
+        let len = buf.remaining();
+        let data = buf.slice(len)?;
+
+        // The client MUST fill the padding extension completely with zero bytes
+        if data.as_slice().iter().any(|b| *b != 0) {
+            return Err(ParseError::InvalidData);
+        }
+
+        Ok(Self { len })
+    }
+
+    pub fn encode(self, buf: &mut CryptoBuffer) -> Result<(), TlsError> {
+        for _ in 0..self.len {
+            buf.push(0).map_err(|_| TlsError::EncodeError)?;
+        }
+        Ok(())
+    }
+}
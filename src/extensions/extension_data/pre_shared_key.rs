@@ -17,6 +17,18 @@ impl<const N: usize> PreSharedKeyClientHello<'_, N> {
         unimplemented!()
     }
 
+    /// The size of the encoded extension data, including the binders.
+    pub fn encoded_len(&self) -> usize {
+        let identities_len: usize = self
+            .identities
+            .iter()
+            .map(|identity| 2 + identity.len() + 4)
+            .sum();
+        let binders_len = (1 + self.hash_size) * self.identities.len();
+
+        2 + identities_len + 2 + binders_len
+    }
+
     pub fn encode(&self, buf: &mut CryptoBuffer) -> Result<(), TlsError> {
         buf.with_u16_length(|buf| {
             for identity in &self.identities {
@@ -0,0 +1,28 @@
+use crate::{
+    TlsError,
+    buffer::CryptoBuffer,
+    parse_buffer::{ParseBuffer, ParseError},
+};
+
+/// RFC 5746, Section 3.2.  Extension Definition
+///
+/// Signals support for secure renegotiation in TLS 1.2. The `renegotiated_connection` field is
+/// empty during the initial handshake, and embedded-tls never renegotiates.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RenegotiationInfo<'a> {
+    pub renegotiated_connection: &'a [u8],
+}
+
+impl<'a> RenegotiationInfo<'a> {
+    pub fn parse(buf: &mut ParseBuffer<'a>) -> Result<Self, ParseError> {
+        let len = buf.read_u8()? as usize;
+        Ok(Self {
+            renegotiated_connection: buf.slice(len)?.as_slice(),
+        })
+    }
+
+    pub fn encode(&self, buf: &mut CryptoBuffer) -> Result<(), TlsError> {
+        buf.with_u8_length(|buf| buf.extend_from_slice(self.renegotiated_connection))
+    }
+}
@@ -46,6 +46,9 @@ pub enum SignatureScheme {
     Sha384BrainpoolP384r1,
     Sha512BrainpoolP512r1,
 
+    /* ShangMi (RFC 8998) */
+    Sm2sigSm3,
+
     /* ML-DSA */
     MlDsa44,
     MlDsa65,
@@ -88,6 +91,8 @@ impl SignatureScheme {
             0x081B => Ok(Self::Sha384BrainpoolP384r1),
             0x081C => Ok(Self::Sha512BrainpoolP512r1),
 
+            0x0708 => Ok(Self::Sm2sigSm3),
+
             0x0904 => Ok(Self::MlDsa44),
             0x0905 => Ok(Self::MlDsa65),
             0x0906 => Ok(Self::MlDsa87),
@@ -129,6 +134,8 @@ impl SignatureScheme {
             Self::Sha384BrainpoolP384r1 => 0x081B,
             Self::Sha512BrainpoolP512r1 => 0x081C,
 
+            Self::Sm2sigSm3 => 0x0708,
+
             Self::MlDsa44 => 0x0904,
             Self::MlDsa65 => 0x0905,
             Self::MlDsa87 => 0x0906,
@@ -140,6 +147,8 @@ impl SignatureScheme {
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct SignatureAlgorithms<const N: usize> {
     pub supported_signature_algorithms: Vec<SignatureScheme, N>,
+    /// GREASE value to advertise ahead of the supported signature algorithms.
+    pub grease: Option<u16>,
 }
 
 impl<const N: usize> SignatureAlgorithms<N> {
@@ -149,11 +158,15 @@ impl<const N: usize> SignatureAlgorithms<N> {
         Ok(Self {
             supported_signature_algorithms: buf
                 .read_list::<_, N>(data_length, SignatureScheme::parse)?,
+            grease: None,
         })
     }
 
     pub fn encode(&self, buf: &mut CryptoBuffer) -> Result<(), TlsError> {
         buf.with_u16_length(|buf| {
+            if let Some(grease) = self.grease {
+                buf.push_u16(grease).map_err(|_| TlsError::EncodeError)?;
+            }
             for &a in &self.supported_signature_algorithms {
                 buf.push_u16(a.as_u16())
                     .map_err(|_| TlsError::EncodeError)?;
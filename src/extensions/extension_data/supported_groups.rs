@@ -16,6 +16,14 @@ pub enum NamedGroup {
     X25519,
     X448,
 
+    /* Brainpool (RFC 8734) */
+    BrainpoolP256r1Tls13,
+    BrainpoolP384r1Tls13,
+    BrainpoolP512r1Tls13,
+
+    /* ShangMi (RFC 8998) */
+    CurveSm2,
+
     /* Finite Field Groups (DHE) */
     Ffdhe2048,
     Ffdhe3072,
@@ -38,6 +46,12 @@ impl NamedGroup {
             0x001D => Ok(Self::X25519),
             0x001E => Ok(Self::X448),
 
+            0x001F => Ok(Self::BrainpoolP256r1Tls13),
+            0x0020 => Ok(Self::BrainpoolP384r1Tls13),
+            0x0021 => Ok(Self::BrainpoolP512r1Tls13),
+
+            0x0029 => Ok(Self::CurveSm2),
+
             0x0100 => Ok(Self::Ffdhe2048),
             0x0101 => Ok(Self::Ffdhe3072),
             0x0102 => Ok(Self::Ffdhe4096),
@@ -52,6 +66,7 @@ impl NamedGroup {
         }
     }
 
+    #[must_use]
     pub fn as_u16(self) -> u16 {
         match self {
             Self::Secp256r1 => 0x0017,
@@ -60,6 +75,12 @@ impl NamedGroup {
             Self::X25519 => 0x001D,
             Self::X448 => 0x001E,
 
+            Self::BrainpoolP256r1Tls13 => 0x001F,
+            Self::BrainpoolP384r1Tls13 => 0x0020,
+            Self::BrainpoolP512r1Tls13 => 0x0021,
+
+            Self::CurveSm2 => 0x0029,
+
             Self::Ffdhe2048 => 0x0100,
             Self::Ffdhe3072 => 0x0101,
             Self::Ffdhe4096 => 0x0102,
@@ -82,6 +103,8 @@ impl NamedGroup {
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct SupportedGroups<const N: usize> {
     pub supported_groups: Vec<NamedGroup, N>,
+    /// GREASE value to advertise ahead of the supported groups.
+    pub grease: Option<u16>,
 }
 
 impl<const N: usize> SupportedGroups<N> {
@@ -90,11 +113,15 @@ impl<const N: usize> SupportedGroups<N> {
 
         Ok(Self {
             supported_groups: buf.read_list::<_, N>(data_length, NamedGroup::parse)?,
+            grease: None,
         })
     }
 
     pub fn encode(&self, buf: &mut CryptoBuffer) -> Result<(), TlsError> {
         buf.with_u16_length(|buf| {
+            if let Some(grease) = self.grease {
+                buf.push_u16(grease).map_err(|_| TlsError::EncodeError)?;
+            }
             for g in &self.supported_groups {
                 g.encode(buf)?;
             }
@@ -7,7 +7,7 @@ use heapless::Vec;
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub struct ProtocolVersion(u16);
+pub struct ProtocolVersion(pub(crate) u16);
 
 impl ProtocolVersion {
     pub fn encode(self, buf: &mut CryptoBuffer) -> Result<(), TlsError> {
@@ -20,6 +20,9 @@ impl ProtocolVersion {
 }
 
 pub const TLS13: ProtocolVersion = ProtocolVersion(0x0304);
+#[cfg(feature = "tls12")]
+pub const TLS12: ProtocolVersion = ProtocolVersion(0x0303);
+pub const DTLS13: ProtocolVersion = ProtocolVersion(0xfefc);
 
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -1,43 +1,47 @@
 macro_rules! extension_group {
-    (pub enum $name:ident$(<$lt:lifetime>)? {
+    (pub enum $name:ident<$lt:lifetime> {
         $($extension:ident($extension_data:ty)),+
     }) => {
         #[derive(Debug, Clone)]
         #[cfg_attr(feature = "defmt", derive(defmt::Format))]
         #[allow(dead_code)] // extension_data may not be used
-        pub enum $name$(<$lt>)? {
-            $($extension($extension_data)),+
+        pub enum $name<$lt> {
+            $($extension($extension_data),)+
+            /// An extension of a type unknown to embedded-tls, kept as opaque data.
+            Unknown(crate::extensions::UnknownExtension<$lt>)
         }
 
         #[allow(dead_code)] // not all methods are used
-        impl$(<$lt>)? $name$(<$lt>)? {
-            pub fn extension_type(&self) -> crate::extensions::ExtensionType {
+        impl<$lt> $name<$lt> {
+            pub fn extension_type(&self) -> u16 {
                 match self {
-                    $(Self::$extension(_) => crate::extensions::ExtensionType::$extension),+
+                    $(Self::$extension(_) => crate::extensions::ExtensionType::$extension as u16,)+
+                    Self::Unknown(extension) => extension.extension_type,
                 }
             }
 
             pub fn encode(&self, buf: &mut crate::buffer::CryptoBuffer) -> Result<(), crate::TlsError> {
-                self.extension_type().encode(buf)?;
+                buf.push_u16(self.extension_type())
+                    .map_err(|_| crate::TlsError::EncodeError)?;
 
                 buf.with_u16_length(|buf| match self {
-                    $(Self::$extension(ext_data) => ext_data.encode(buf)),+
+                    $(Self::$extension(ext_data) => ext_data.encode(buf),)+
+                    Self::Unknown(extension) => buf.extend_from_slice(extension.data),
                 })
             }
 
-            pub fn parse(buf: &mut crate::parse_buffer::ParseBuffer$(<$lt>)?) -> Result<Self, crate::TlsError> {
-                // Consume extension data even if we don't recognize the extension
-                let extension_type = crate::extensions::ExtensionType::parse(buf);
+            pub fn parse(buf: &mut crate::parse_buffer::ParseBuffer<$lt>) -> Result<Self, crate::TlsError> {
+                let extension_type = buf.read_u16().map_err(|_| crate::TlsError::DecodeError)?;
                 let data_len = buf.read_u16().map_err(|_| crate::TlsError::DecodeError)? as usize;
                 let mut ext_data = buf.slice(data_len).map_err(|_| crate::TlsError::DecodeError)?;
 
-                let ext_type = extension_type.map_err(|err| {
-                    warn!("Failed to read extension type: {:?}", err);
-                    match err {
-                        crate::parse_buffer::ParseError::InvalidData => crate::TlsError::UnknownExtensionType,
-                        _ => crate::TlsError::DecodeError,
-                    }
-                })?;
+                let Some(ext_type) = crate::extensions::ExtensionType::from_u16(extension_type) else {
+                    debug!("Read unknown extension type {}", extension_type);
+                    return Ok(Self::Unknown(crate::extensions::UnknownExtension {
+                        extension_type,
+                        data: ext_data.as_slice(),
+                    }));
+                };
 
                 debug!("Read extension type {:?}", ext_type);
                 trace!("Extension data length: {}", data_len);
@@ -64,8 +68,26 @@ macro_rules! extension_group {
                 }
             }
 
+            /// Parse a list of extensions, keeping those of unknown types as
+            /// [`Self::Unknown`], for messages whose extensions respond to the `ClientHello`.
             pub fn parse_vector<const N: usize>(
-                buf: &mut crate::parse_buffer::ParseBuffer$(<$lt>)?,
+                buf: &mut crate::parse_buffer::ParseBuffer<$lt>,
+            ) -> Result<heapless::Vec<Self, N>, crate::TlsError> {
+                Self::parse_list(buf, true)
+            }
+
+            /// Parse a list of extensions, skipping those of unknown types, which RFC 8446
+            /// requires for the extensions of `Certificate`, `CertificateRequest` and
+            /// `NewSessionTicket`.
+            pub fn parse_vector_ignoring_unknown<const N: usize>(
+                buf: &mut crate::parse_buffer::ParseBuffer<$lt>,
+            ) -> Result<heapless::Vec<Self, N>, crate::TlsError> {
+                Self::parse_list(buf, false)
+            }
+
+            fn parse_list<const N: usize>(
+                buf: &mut crate::parse_buffer::ParseBuffer<$lt>,
+                keep_unknown: bool,
             ) -> Result<heapless::Vec<Self, N>, crate::TlsError> {
                 let extensions_len = buf
                     .read_u16()
@@ -77,22 +99,26 @@ macro_rules! extension_group {
 
                 while !ext_buf.is_empty() {
                     trace!("Extension buffer: {}", ext_buf.remaining());
-                    match Self::parse(&mut ext_buf) {
-                        Ok(extension) => {
-                            extensions
-                                .push(extension)
-                                .map_err(|_| crate::TlsError::DecodeError)?;
-                        }
-                        Err(crate::TlsError::UnknownExtensionType) => {
-                            // ignore unrecognized extension type
-                        }
-                        Err(err) => return Err(err),
+                    let extension = Self::parse(&mut ext_buf)?;
+                    if keep_unknown || !matches!(extension, Self::Unknown(_)) {
+                        extensions
+                            .push(extension)
+                            .map_err(|_| crate::TlsError::DecodeError)?;
                     }
                 }
 
                 trace!("Read {} extensions", extensions.len());
                 Ok(extensions)
             }
+
+            /// The extension of an unknown type, if it is one.
+            pub fn unknown(&self) -> Option<&crate::extensions::UnknownExtension<$lt>> {
+                if let Self::Unknown(extension) = self {
+                    Some(extension)
+                } else {
+                    None
+                }
+            }
         }
     };
 }
@@ -1,10 +1,16 @@
 use crate::extensions::{
     extension_data::{
         alpn::AlpnProtocolNameList,
-        key_share::{KeyShareClientHello, KeyShareServerHello},
+        connection_id::ConnectionId,
+        cookie::Cookie,
+        ec_point_formats::EcPointFormats,
+        extended_master_secret::ExtendedMasterSecret,
+        key_share::{KeyShareClientHello, KeyShareHelloRetryRequest, KeyShareServerHello},
         max_fragment_length::MaxFragmentLength,
+        padding::Padding,
         pre_shared_key::{PreSharedKeyClientHello, PreSharedKeyServerHello},
         psk_key_exchange_modes::PskKeyExchangeModes,
+        renegotiation_info::RenegotiationInfo,
         server_name::{ServerNameList, ServerNameResponse},
         signature_algorithms::SignatureAlgorithms,
         signature_algorithms_cert::SignatureAlgorithmsCert,
@@ -19,7 +25,7 @@ use crate::extensions::{
 extension_group! {
     pub enum ClientHelloExtension<'a> {
         ServerName(ServerNameList<'a, 1>),
-        SupportedVersions(SupportedVersionsClientHello<1>),
+        SupportedVersions(SupportedVersionsClientHello<3>),
         SignatureAlgorithms(SignatureAlgorithms<25>),
         SupportedGroups(SupportedGroups<13>),
         KeyShare(KeyShareClientHello<'a, 1>),
@@ -34,22 +40,34 @@ extension_group! {
         SignedCertificateTimestamp(Unimplemented<'a>),
         ClientCertificateType(Unimplemented<'a>),
         ServerCertificateType(Unimplemented<'a>),
-        Padding(Unimplemented<'a>),
+        Padding(Padding),
         EarlyData(Unimplemented<'a>),
-        Cookie(Unimplemented<'a>),
+        Cookie(Cookie<'a>),
         CertificateAuthorities(Unimplemented<'a>),
         OidFilters(Unimplemented<'a>),
-        PostHandshakeAuth(Unimplemented<'a>)
+        PostHandshakeAuth(Unimplemented<'a>),
+        ConnectionId(ConnectionId<'a>),
+        EcPointFormats(EcPointFormats<'a>),
+        ExtendedMasterSecret(ExtendedMasterSecret),
+        RenegotiationInfo(RenegotiationInfo<'a>)
     }
 }
 
-// Source: https://www.rfc-editor.org/rfc/rfc8446#section-4.2 table, rows marked with SH
+// Source: https://www.rfc-editor.org/rfc/rfc8446#section-4.2 table, rows marked with SH,
+// followed by the extensions a TLS 1.2 server may send in its ServerHello
 extension_group! {
     pub enum ServerHelloExtension<'a> {
         KeyShare(KeyShareServerHello<'a>),
         PreSharedKey(PreSharedKeyServerHello),
-        Cookie(Unimplemented<'a>), // temporary so we don't trip up on HelloRetryRequests
-        SupportedVersions(SupportedVersionsServerHello)
+        Cookie(Cookie<'a>),
+        SupportedVersions(SupportedVersionsServerHello),
+        ConnectionId(ConnectionId<'a>),
+        ServerName(ServerNameResponse),
+        MaxFragmentLength(MaxFragmentLength),
+        ApplicationLayerProtocolNegotiation(AlpnProtocolNameList<'a>),
+        EcPointFormats(EcPointFormats<'a>),
+        ExtendedMasterSecret(ExtendedMasterSecret),
+        RenegotiationInfo(RenegotiationInfo<'a>)
     }
 }
 
@@ -99,8 +117,8 @@ extension_group! {
 // Source: https://www.rfc-editor.org/rfc/rfc8446#section-4.2 table, rows marked with HRR
 extension_group! {
     pub enum HelloRetryRequestExtension<'a> {
-        KeyShare(Unimplemented<'a>),
-        Cookie(Unimplemented<'a>),
-        SupportedVersions(Unimplemented<'a>)
+        KeyShare(KeyShareHelloRetryRequest),
+        Cookie(Cookie<'a>),
+        SupportedVersions(SupportedVersionsServerHello)
     }
 }
@@ -1,14 +1,13 @@
-use crate::{
-    TlsError,
-    buffer::CryptoBuffer,
-    parse_buffer::{ParseBuffer, ParseError},
-};
+use crate::parse_buffer::{ParseBuffer, ParseError};
 
 mod extension_group_macro;
 
 pub mod extension_data;
 pub mod messages;
 
+/// Size of the extension type and length fields preceding the extension data.
+pub(crate) const EXTENSION_HEADER_LEN: usize = 4;
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ExtensionType {
@@ -16,6 +15,7 @@ pub enum ExtensionType {
     MaxFragmentLength = 1,
     StatusRequest = 5,
     SupportedGroups = 10,
+    EcPointFormats = 11,
     SignatureAlgorithms = 13,
     UseSrtp = 14,
     Heartbeat = 15,
@@ -24,6 +24,7 @@ pub enum ExtensionType {
     ClientCertificateType = 19,
     ServerCertificateType = 20,
     Padding = 21,
+    ExtendedMasterSecret = 23,
     CompressCertificate = 27,
     PreSharedKey = 41,
     EarlyData = 42,
@@ -35,46 +36,64 @@ pub enum ExtensionType {
     PostHandshakeAuth = 49,
     SignatureAlgorithmsCert = 50,
     KeyShare = 51,
+    ConnectionId = 54,
+    RenegotiationInfo = 0xff01,
+}
+
+/// An extension of a type unknown to embedded-tls, whose data is kept opaque.
+///
+/// RFC 8446, Section 4.2: unknown extensions are ignored in messages that do not respond to
+/// the `ClientHello`, and rejected with an `unsupported_extension` alert in responses, since
+/// a peer must not send an extension that was not offered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UnknownExtension<'a> {
+    pub extension_type: u16,
+    pub data: &'a [u8],
 }
 
 impl ExtensionType {
     pub fn parse(buf: &mut ParseBuffer) -> Result<Self, ParseError> {
-        match buf.read_u16()? {
-            v if v == Self::ServerName as u16 => Ok(Self::ServerName),
-            v if v == Self::MaxFragmentLength as u16 => Ok(Self::MaxFragmentLength),
-            v if v == Self::StatusRequest as u16 => Ok(Self::StatusRequest),
-            v if v == Self::SupportedGroups as u16 => Ok(Self::SupportedGroups),
-            v if v == Self::SignatureAlgorithms as u16 => Ok(Self::SignatureAlgorithms),
-            v if v == Self::UseSrtp as u16 => Ok(Self::UseSrtp),
-            v if v == Self::Heartbeat as u16 => Ok(Self::Heartbeat),
-            v if v == Self::ApplicationLayerProtocolNegotiation as u16 => {
-                Ok(Self::ApplicationLayerProtocolNegotiation)
-            }
-            v if v == Self::SignedCertificateTimestamp as u16 => {
-                Ok(Self::SignedCertificateTimestamp)
-            }
-            v if v == Self::ClientCertificateType as u16 => Ok(Self::ClientCertificateType),
-            v if v == Self::ServerCertificateType as u16 => Ok(Self::ServerCertificateType),
-            v if v == Self::Padding as u16 => Ok(Self::Padding),
-            v if v == Self::CompressCertificate as u16 => Ok(Self::CompressCertificate),
-            v if v == Self::PreSharedKey as u16 => Ok(Self::PreSharedKey),
-            v if v == Self::EarlyData as u16 => Ok(Self::EarlyData),
-            v if v == Self::SupportedVersions as u16 => Ok(Self::SupportedVersions),
-            v if v == Self::Cookie as u16 => Ok(Self::Cookie),
-            v if v == Self::PskKeyExchangeModes as u16 => Ok(Self::PskKeyExchangeModes),
-            v if v == Self::CertificateAuthorities as u16 => Ok(Self::CertificateAuthorities),
-            v if v == Self::OidFilters as u16 => Ok(Self::OidFilters),
-            v if v == Self::PostHandshakeAuth as u16 => Ok(Self::PostHandshakeAuth),
-            v if v == Self::SignatureAlgorithmsCert as u16 => Ok(Self::SignatureAlgorithmsCert),
-            v if v == Self::KeyShare as u16 => Ok(Self::KeyShare),
-            other => {
-                warn!("Read unknown ExtensionType: {}", other);
-                Err(ParseError::InvalidData)
-            }
-        }
+        let value = buf.read_u16()?;
+        Self::from_u16(value).ok_or_else(|| {
+            warn!("Read unknown ExtensionType: {}", value);
+            ParseError::InvalidData
+        })
     }
 
-    pub fn encode(self, buf: &mut CryptoBuffer) -> Result<(), TlsError> {
-        buf.push_u16(self as u16).map_err(|_| TlsError::EncodeError)
+    /// The extension type with the code point `value`, if it is known.
+    pub(crate) fn from_u16(value: u16) -> Option<Self> {
+        Some(match value {
+            v if v == Self::ServerName as u16 => Self::ServerName,
+            v if v == Self::MaxFragmentLength as u16 => Self::MaxFragmentLength,
+            v if v == Self::StatusRequest as u16 => Self::StatusRequest,
+            v if v == Self::SupportedGroups as u16 => Self::SupportedGroups,
+            v if v == Self::EcPointFormats as u16 => Self::EcPointFormats,
+            v if v == Self::SignatureAlgorithms as u16 => Self::SignatureAlgorithms,
+            v if v == Self::UseSrtp as u16 => Self::UseSrtp,
+            v if v == Self::Heartbeat as u16 => Self::Heartbeat,
+            v if v == Self::ApplicationLayerProtocolNegotiation as u16 => {
+                Self::ApplicationLayerProtocolNegotiation
+            }
+            v if v == Self::SignedCertificateTimestamp as u16 => Self::SignedCertificateTimestamp,
+            v if v == Self::ClientCertificateType as u16 => Self::ClientCertificateType,
+            v if v == Self::ServerCertificateType as u16 => Self::ServerCertificateType,
+            v if v == Self::Padding as u16 => Self::Padding,
+            v if v == Self::ExtendedMasterSecret as u16 => Self::ExtendedMasterSecret,
+            v if v == Self::CompressCertificate as u16 => Self::CompressCertificate,
+            v if v == Self::PreSharedKey as u16 => Self::PreSharedKey,
+            v if v == Self::EarlyData as u16 => Self::EarlyData,
+            v if v == Self::SupportedVersions as u16 => Self::SupportedVersions,
+            v if v == Self::Cookie as u16 => Self::Cookie,
+            v if v == Self::PskKeyExchangeModes as u16 => Self::PskKeyExchangeModes,
+            v if v == Self::CertificateAuthorities as u16 => Self::CertificateAuthorities,
+            v if v == Self::OidFilters as u16 => Self::OidFilters,
+            v if v == Self::PostHandshakeAuth as u16 => Self::PostHandshakeAuth,
+            v if v == Self::SignatureAlgorithmsCert as u16 => Self::SignatureAlgorithmsCert,
+            v if v == Self::KeyShare as u16 => Self::KeyShare,
+            v if v == Self::ConnectionId as u16 => Self::ConnectionId,
+            v if v == Self::RenegotiationInfo as u16 => Self::RenegotiationInfo,
+            _ => return None,
+        })
     }
 }
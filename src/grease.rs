@@ -0,0 +1,120 @@
+//! GREASE (Generate Random Extensions And Sustain Extensibility), [RFC 8701].
+//!
+//! GREASE values are reserved code points that a client advertises to make sure servers
+//! and middleboxes correctly ignore values they don't know. Every `ClientHello` picks fresh
+//! values, so broken peers are found early instead of when a new extension is deployed.
+//!
+//! [RFC 8701]: https://www.rfc-editor.org/rfc/rfc8701
+
+use rand_core::RngCore;
+
+use crate::TlsError;
+use crate::buffer::CryptoBuffer;
+
+/// RFC 8701, Section 2: `{0x0A,0x0A}`, `{0x1A,0x1A}`, ... `{0xFA,0xFA}`.
+///
+/// Used for cipher suites, extensions, named groups, signature algorithms and versions.
+const fn value(index: u8) -> u16 {
+    let byte = ((index & 0x0F) << 4) | 0x0A;
+    u16::from_be_bytes([byte, byte])
+}
+
+/// Whether `value` is one of the reserved GREASE values.
+pub(crate) const fn is_grease(value: u16) -> bool {
+    let [high, low] = value.to_be_bytes();
+    high == low && low & 0x0F == 0x0A
+}
+
+/// The GREASE values used in a single `ClientHello`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct Grease {
+    pub cipher_suite: u16,
+    pub group: u16,
+    pub signature_scheme: u16,
+    pub version: u16,
+    /// Two distinct extension code points. RFC 8701, Section 3.1: the client MUST NOT
+    /// advertise the same GREASE extension type more than once.
+    pub extensions: [u16; 2],
+}
+
+impl Grease {
+    pub fn random(rng: &mut impl RngCore) -> Self {
+        let mut indices = [0; 6];
+        rng.fill_bytes(&mut indices);
+
+        let first_extension = value(indices[4]);
+        let mut second_extension = value(indices[5]);
+        if second_extension == first_extension {
+            second_extension = value(indices[5].wrapping_add(1));
+        }
+
+        Self {
+            cipher_suite: value(indices[0]),
+            group: value(indices[1]),
+            signature_scheme: value(indices[2]),
+            version: value(indices[3]),
+            extensions: [first_extension, second_extension],
+        }
+    }
+
+    /// Encode a GREASE extension. Its contents are arbitrary and will be ignored by the server.
+    pub fn encode_extension(
+        extension_type: u16,
+        data: &[u8],
+        buf: &mut CryptoBuffer,
+    ) -> Result<(), TlsError> {
+        buf.push_u16(extension_type)
+            .map_err(|_| TlsError::EncodeError)?;
+        buf.with_u16_length(|buf| buf.extend_from_slice(data))
+            .map_err(|_| TlsError::EncodeError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values() {
+        assert_eq!(value(0), 0x0A0A);
+        assert_eq!(value(1), 0x1A1A);
+        assert_eq!(value(15), 0xFAFA);
+        assert_eq!(value(16), 0x0A0A);
+    }
+
+    #[test]
+    fn recognizes_values() {
+        assert!((0..16).all(|i| is_grease(value(i))));
+        assert!(!is_grease(0x0A1A));
+        assert!(!is_grease(0x0B0B));
+    }
+
+    #[test]
+    fn extensions_are_distinct() {
+        struct Constant;
+
+        impl RngCore for Constant {
+            fn next_u32(&mut self) -> u32 {
+                0
+            }
+
+            fn next_u64(&mut self) -> u64 {
+                0
+            }
+
+            fn fill_bytes(&mut self, dest: &mut [u8]) {
+                dest.fill(0x33);
+            }
+
+            fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+                self.fill_bytes(dest);
+                Ok(())
+            }
+        }
+
+        let grease = Grease::random(&mut Constant);
+        assert_eq!(grease.extensions[0], 0x3A3A);
+        assert_eq!(grease.extensions[1], 0x4A4A);
+    }
+}
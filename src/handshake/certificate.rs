@@ -7,7 +7,6 @@ use heapless::Vec;
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct CertificateRef<'a> {
-    raw_entries: &'a [u8],
     request_context: &'a [u8],
 
     pub entries: Vec<CertificateEntryRef<'a>, 16>,
@@ -16,7 +15,6 @@ pub struct CertificateRef<'a> {
 impl<'a> CertificateRef<'a> {
     pub fn with_context(request_context: &'a [u8]) -> Self {
         Self {
-            raw_entries: &[],
             request_context,
             entries: Vec::new(),
         }
@@ -42,12 +40,50 @@ impl<'a> CertificateRef<'a> {
         let entries = CertificateEntryRef::parse_vector(&mut raw_entries)?;
 
         Ok(Self {
-            raw_entries: raw_entries.as_slice(),
             request_context: request_context.as_slice(),
             entries,
         })
     }
 
+    /// Parse a TLS 1.2 `Certificate` message (RFC 5246, Section 7.4.2), which has no request
+    /// context and no per-entry extensions.
+    #[cfg(feature = "tls12")]
+    pub(crate) fn parse_tls12(buf: &mut ParseBuffer<'a>) -> Result<Self, TlsError> {
+        let entries_len = buf.read_u24().map_err(|_| TlsError::InvalidCertificate)?;
+        let mut raw_entries = buf
+            .slice(entries_len as usize)
+            .map_err(|_| TlsError::InvalidCertificate)?;
+
+        let mut entries = Vec::new();
+        while !raw_entries.is_empty() {
+            let entry_len = raw_entries
+                .read_u24()
+                .map_err(|_| TlsError::InvalidCertificateEntry)?;
+            let cert = raw_entries
+                .slice(entry_len as usize)
+                .map_err(|_| TlsError::InvalidCertificateEntry)?;
+            entries
+                .push(CertificateEntryRef::X509(cert.as_slice()))
+                .map_err(|_| TlsError::DecodeError)?;
+        }
+
+        Ok(Self {
+            request_context: &[],
+            entries,
+        })
+    }
+
+    /// Encode as a TLS 1.2 `Certificate` message.
+    #[cfg(feature = "tls12")]
+    pub(crate) fn encode_tls12(&self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
+        buf.with_u24_length(|buf| {
+            for entry in &self.entries {
+                buf.with_u24_length(|buf| buf.extend_from_slice(entry.data()))?;
+            }
+            Ok(())
+        })
+    }
+
     pub(crate) fn encode(&self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
         buf.with_u8_length(|buf| buf.extend_from_slice(self.request_context))?;
         buf.with_u24_length(|buf| {
@@ -80,7 +116,7 @@ impl<'a> CertificateEntryRef<'a> {
         let entry = CertificateEntryRef::X509(cert.as_slice());
 
         // Validate extensions
-        CertificateExtension::parse_vector::<2>(buf)?;
+        CertificateExtension::parse_vector_ignoring_unknown::<2>(buf)?;
 
         Ok(entry)
     }
@@ -99,6 +135,12 @@ impl<'a> CertificateEntryRef<'a> {
         Ok(result)
     }
 
+    fn data(&self) -> &'a [u8] {
+        match *self {
+            CertificateEntryRef::X509(data) | CertificateEntryRef::RawPublicKey(data) => data,
+        }
+    }
+
     pub(crate) fn encode(&self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
         match *self {
             CertificateEntryRef::RawPublicKey(_key) => {
@@ -147,10 +189,17 @@ impl<'a, const N: usize> TryFrom<CertificateRef<'a>> for Certificate<N> {
         request_context
             .extend_from_slice(cert.request_context)
             .map_err(|_| TlsError::OutOfMemory)?;
+        // Entries are stored in the TLS 1.3 format, without their extensions
         let mut entries_data = Vec::new();
-        entries_data
-            .extend_from_slice(cert.raw_entries)
-            .map_err(|_| TlsError::OutOfMemory)?;
+        for entry in &cert.entries {
+            let data = entry.data();
+            let len = u32::try_from(data.len()).map_err(|_| TlsError::OutOfMemory)?;
+            entries_data
+                .extend_from_slice(&len.to_be_bytes()[1..])
+                .and_then(|()| entries_data.extend_from_slice(data))
+                .and_then(|()| entries_data.extend_from_slice(&[0, 0]))
+                .map_err(|_| TlsError::OutOfMemory)?;
+        }
 
         Ok(Self {
             request_context,
@@ -166,7 +215,6 @@ impl<'a, const N: usize> TryFrom<&'a Certificate<N>> for CertificateRef<'a> {
         let entries =
             CertificateEntryRef::parse_vector(&mut ParseBuffer::from(&cert.entries_data[..]))?;
         Ok(Self {
-            raw_entries: &cert.entries_data[..],
             request_context,
             entries,
         })
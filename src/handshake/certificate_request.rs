@@ -1,12 +1,17 @@
+use crate::TlsError;
+use crate::buffer::CryptoBuffer;
+use crate::extensions::extension_data::signature_algorithms::{
+    SignatureAlgorithms, SignatureScheme,
+};
 use crate::extensions::messages::CertificateRequestExtension;
 use crate::parse_buffer::ParseBuffer;
-use crate::{TlsError, unused};
 use heapless::Vec;
 
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct CertificateRequestRef<'a> {
     pub(crate) request_context: &'a [u8],
+    pub(crate) signature_schemes: Vec<SignatureScheme, 25>,
 }
 
 impl<'a> CertificateRequestRef<'a> {
@@ -19,11 +24,35 @@ impl<'a> CertificateRequestRef<'a> {
             .map_err(|_| TlsError::InvalidCertificateRequest)?;
 
         // Validate extensions
-        let extensions = CertificateRequestExtension::parse_vector::<6>(buf)?;
+        let extensions = CertificateRequestExtension::parse_vector_ignoring_unknown::<6>(buf)?;
+
+        let signature_schemes = extensions
+            .into_iter()
+            .find_map(|extension| match extension {
+                CertificateRequestExtension::SignatureAlgorithms(algorithms) => {
+                    Some(algorithms.supported_signature_algorithms)
+                }
+                _ => None,
+            })
+            .unwrap_or_default();
 
-        unused(extensions);
         Ok(Self {
             request_context: request_context.as_slice(),
+            signature_schemes,
+        })
+    }
+
+    pub(crate) fn encode(&self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
+        buf.with_u8_length(|buf| buf.extend_from_slice(self.request_context))?;
+
+        // Section 4.3.2.  Certificate Request
+        // The "signature_algorithms" extension MUST be specified
+        buf.with_u16_length(|buf| {
+            CertificateRequestExtension::SignatureAlgorithms(SignatureAlgorithms {
+                supported_signature_algorithms: self.signature_schemes.clone(),
+                grease: None,
+            })
+            .encode(buf)
         })
     }
 }
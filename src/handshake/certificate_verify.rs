@@ -32,12 +32,15 @@ impl<'a> CertificateVerifyRef<'a> {
 // ecdsaSHA256 -> 6 bytes (ASN.1 structure) + 32-33 bytes (r) + 32-33 bytes (s) = 70..72 bytes
 // ecdsaSHA384 -> 6 bytes (ASN.1 structure) + 48-49 bytes (r) + 48-49 bytes (s) = 102..104 bytes
 // Ed25519 -> 6 bytes (ASN.1 structure) + 32-33 bytes (r) + 32-33 bytes (s) = 70..72 bytes
+// Ed448 -> 57 bytes (R) + 57 bytes (S) = 114 bytes
 // RSA2048 -> 256 bytes
 // RSA3072 -> 384 bytee
 // RSA4096 -> 512 bytes
 #[cfg(feature = "rsa")]
 const SIGNATURE_SIZE: usize = 512;
-#[cfg(not(feature = "rsa"))]
+#[cfg(all(not(feature = "rsa"), feature = "ed448"))]
+const SIGNATURE_SIZE: usize = 114;
+#[cfg(all(not(feature = "rsa"), not(feature = "ed448")))]
 const SIGNATURE_SIZE: usize = 104;
 
 #[derive(Debug)]
@@ -2,26 +2,44 @@ use core::marker::PhantomData;
 
 use digest::{Digest, OutputSizeUser};
 use heapless::Vec;
-use p256::EncodedPoint;
-use p256::ecdh::EphemeralSecret;
 use p256::elliptic_curve::rand_core::RngCore;
 use typenum::Unsigned;
 
 use crate::TlsError;
+use crate::alert::{AlertDescription, AlertLevel};
 use crate::config::{TlsCipherSuite, TlsConfig};
-use crate::extensions::extension_data::alpn::AlpnProtocolNameList;
+use crate::extensions::extension_data::alpn::{self, AlpnProtocolNameList};
+use crate::extensions::extension_data::connection_id::ConnectionId;
+use crate::extensions::extension_data::cookie::Cookie;
 use crate::extensions::extension_data::key_share::{KeyShareClientHello, KeyShareEntry};
+use crate::extensions::extension_data::padding::Padding;
 use crate::extensions::extension_data::pre_shared_key::PreSharedKeyClientHello;
 use crate::extensions::extension_data::psk_key_exchange_modes::{
     PskKeyExchangeMode, PskKeyExchangeModes,
 };
 use crate::extensions::extension_data::server_name::ServerNameList;
 use crate::extensions::extension_data::signature_algorithms::SignatureAlgorithms;
+use crate::extensions::extension_data::signature_algorithms::SignatureScheme;
 use crate::extensions::extension_data::supported_groups::{NamedGroup, SupportedGroups};
-use crate::extensions::extension_data::supported_versions::{SupportedVersionsClientHello, TLS13};
+use crate::extensions::extension_data::supported_versions::{
+    DTLS13, ProtocolVersion, SupportedVersionsClientHello, TLS13,
+};
+#[cfg(feature = "tls12")]
+use crate::extensions::extension_data::{
+    ec_point_formats::{self, EcPointFormats},
+    extended_master_secret::ExtendedMasterSecret,
+    renegotiation_info::RenegotiationInfo,
+    supported_versions::TLS12,
+};
 use crate::extensions::messages::ClientHelloExtension;
-use crate::handshake::{LEGACY_VERSION, Random};
+use crate::extensions::{EXTENSION_HEADER_LEN, ExtensionType};
+use crate::grease::Grease;
+use crate::handshake::{
+    DTLS_LEGACY_VERSION, HANDSHAKE_HEADER_LEN, LEGACY_VERSION, OutgoingHandshake, Random,
+};
+use crate::key_exchange::{EphemeralSecret, MAX_PUBLIC_KEY_LEN};
 use crate::key_schedule::{HashOutputSize, WriteKeySchedule};
+use crate::parse_buffer::ParseBuffer;
 use crate::{CryptoProvider, buffer::CryptoBuffer};
 
 pub struct ClientHello<'config, CipherSuite>
@@ -29,49 +47,212 @@ where
     CipherSuite: TlsCipherSuite,
 {
     pub(crate) config: &'config TlsConfig<'config>,
-    random: Random,
+    pub(crate) random: Random,
+    pub(crate) legacy_session_id: Option<Random>,
     cipher_suite: PhantomData<CipherSuite>,
-    pub(crate) secret: EphemeralSecret,
+    /// The secret of the key share, which is `None` when only measuring the encoded length.
+    pub(crate) secret: Option<EphemeralSecret>,
+    pub(crate) grease: Option<Grease>,
+    dtls: bool,
+    cookie: Option<&'config [u8]>,
 }
 
 impl<'config, CipherSuite> ClientHello<'config, CipherSuite>
 where
     CipherSuite: TlsCipherSuite,
 {
-    pub fn new<Provider>(config: &'config TlsConfig<'config>, mut provider: Provider) -> Self
+    /// Create a `ClientHello`, generating the key share unless a `secret` generated ahead of
+    /// time for the group of the key share is passed in.
+    pub fn new<Provider>(
+        config: &'config TlsConfig<'config>,
+        mut provider: Provider,
+        secret: Option<EphemeralSecret>,
+    ) -> Result<Self, TlsError>
     where
         Provider: CryptoProvider,
     {
         let mut random = [0; 32];
         provider.rng().fill_bytes(&mut random);
 
+        let legacy_session_id = config.middlebox_compat.then(|| {
+            let mut session_id = [0; 32];
+            provider.rng().fill_bytes(&mut session_id);
+            session_id
+        });
+        let grease = config.grease.then(|| Grease::random(&mut provider.rng()));
+
+        Ok(Self {
+            config,
+            random,
+            legacy_session_id,
+            cipher_suite: PhantomData,
+            secret: Some(config.key_share(secret, &mut provider.rng())?),
+            grease,
+            dtls: false,
+            cookie: None,
+        })
+    }
+
+    /// Create a DTLS 1.3 `ClientHello`.
+    ///
+    /// The random, key share and GREASE values are passed in, as a `ClientHello` sent in
+    /// response to a `HelloRetryRequest` must repeat them, together with the `cookie`.
+    pub(crate) fn dtls(
+        config: &'config TlsConfig<'config>,
+        random: Random,
+        secret: EphemeralSecret,
+        grease: Option<Grease>,
+        cookie: Option<&'config [u8]>,
+    ) -> Self {
         Self {
             config,
             random,
+            // RFC 9147, Section 5: DTLS 1.3 has no middlebox compatibility mode
+            legacy_session_id: None,
             cipher_suite: PhantomData,
-            secret: EphemeralSecret::random(&mut provider.rng()),
+            secret: Some(secret),
+            grease,
+            dtls: true,
+            cookie,
+        }
+    }
+
+    /// Encode the application-defined extensions of the configuration.
+    fn encode_custom_extensions(&self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
+        // RFC 8446, Section 4.2: there MUST NOT be more than one extension of the same type,
+        // and the custom extensions must not take the place of pre_shared_key.
+        if !self.config.custom_extensions_valid() {
+            error!("Custom extensions must have distinct, unknown types");
+            return Err(TlsError::EncodeError);
+        }
+        for extension in self.config.custom_extensions {
+            buf.push_u16(extension.extension_type)
+                .map_err(|_| TlsError::EncodeError)?;
+            buf.with_u16_length(|buf| buf.extend_from_slice(extension.data))
+                .map_err(|_| TlsError::EncodeError)?;
+        }
+        Ok(())
+    }
+
+    /// Encode the extensions only sent in a DTLS `ClientHello`.
+    fn encode_dtls_extensions(&self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
+        if let Some(cookie) = self.cookie {
+            ClientHelloExtension::Cookie(Cookie { cookie }).encode(buf)?;
         }
+
+        if self.dtls
+            && let Some(cid) = self.config.connection_id
+        {
+            ClientHelloExtension::ConnectionId(ConnectionId { cid }).encode(buf)?;
+        }
+        Ok(())
+    }
+
+    fn encode_cipher_suites(&self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
+        if let Some(grease) = &self.grease {
+            buf.push_u16(grease.cipher_suite)?;
+        }
+        buf.push_u16(CipherSuite::CODE_POINT)?;
+
+        #[cfg(feature = "tls12")]
+        if self.offers_tls12() {
+            for code_point in CipherSuite::TLS12_CODE_POINTS {
+                buf.push_u16(*code_point)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Section 4.2.1.  Supported Versions
+    /// Implementations of this specification MUST send this extension in the
+    /// `ClientHello` containing all versions of TLS which they are prepared to
+    /// negotiate
+    fn encode_supported_versions(&self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
+        let mut versions = Vec::new();
+        if let Some(grease) = &self.grease {
+            unwrap!(versions.push(ProtocolVersion(grease.version)).ok());
+        }
+        unwrap!(versions.push(if self.dtls { DTLS13 } else { TLS13 }).ok());
+        #[cfg(feature = "tls12")]
+        if self.offers_tls12() {
+            unwrap!(versions.push(TLS12).ok());
+        }
+        ClientHelloExtension::SupportedVersions(SupportedVersionsClientHello { versions })
+            .encode(buf)
+    }
+
+    /// Whether TLS 1.2 is offered as well. It is never offered over DTLS or with a PSK, which
+    /// the TLS 1.2 fallback does not support.
+    #[cfg(feature = "tls12")]
+    pub(crate) fn offers_tls12(&self) -> bool {
+        !self.dtls && self.config.psk.is_none()
+    }
+
+    /// Encode the extensions a TLS 1.2 server needs to negotiate ECDHE with the extended
+    /// master secret.
+    #[cfg(feature = "tls12")]
+    fn encode_tls12_extensions(buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
+        ClientHelloExtension::EcPointFormats(EcPointFormats {
+            formats: &[ec_point_formats::UNCOMPRESSED],
+        })
+        .encode(buf)?;
+        ClientHelloExtension::ExtendedMasterSecret(ExtendedMasterSecret).encode(buf)?;
+        ClientHelloExtension::RenegotiationInfo(RenegotiationInfo {
+            renegotiated_connection: &[],
+        })
+        .encode(buf)
+    }
+
+    /// The group and public key of the key share.
+    fn key_share(&self) -> Result<(NamedGroup, Vec<u8, MAX_PUBLIC_KEY_LEN>), TlsError> {
+        if let Some(secret) = &self.secret {
+            return Ok((secret.group(), secret.public_key()));
+        }
+
+        // Only the length of the public key matters, see `Self::encoded_len`
+        let group = self.config.key_share_group()?;
+        let len = EphemeralSecret::public_key_len(group).ok_or(TlsError::InvalidKeyShare)?;
+        let mut public_key = Vec::new();
+        public_key
+            .resize(len, 0)
+            .map_err(|_| TlsError::InternalError)?;
+        Ok((group, public_key))
     }
 
     pub(crate) fn encode(&self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
-        let public_key = EncodedPoint::from(&self.secret.public_key());
-        let public_key = public_key.as_ref();
+        let (group, public_key) = self.key_share()?;
 
-        buf.push_u16(LEGACY_VERSION)
+        // The handshake header has already been written by the caller
+        let start = buf.len();
+
+        let legacy_version = if self.dtls {
+            DTLS_LEGACY_VERSION
+        } else {
+            LEGACY_VERSION
+        };
+        buf.push_u16(legacy_version)
             .map_err(|_| TlsError::EncodeError)?;
         buf.extend_from_slice(&self.random)
             .map_err(|_| TlsError::EncodeError)?;
 
-        // session id (empty)
-        buf.push(0).map_err(|_| TlsError::EncodeError)?;
+        // session id, only sent in the middlebox compatibility mode
+        let session_id = self
+            .legacy_session_id
+            .as_ref()
+            .map_or(&[][..], |id| &id[..]);
+        buf.with_u8_length(|buf| buf.extend_from_slice(session_id))?;
+
+        if self.dtls {
+            // RFC 9147, Section 5.3: legacy_cookie MUST be set to a zero-length vector
+            buf.push(0).map_err(|_| TlsError::EncodeError)?;
+        }
 
         // cipher suites (2+)
         //buf.extend_from_slice(&((self.config.cipher_suites.len() * 2) as u16).to_be_bytes());
         //for c in self.config.cipher_suites.iter() {
         //buf.extend_from_slice(&(*c as u16).to_be_bytes());
         //}
-        buf.push_u16(2).map_err(|_| TlsError::EncodeError)?;
-        buf.push_u16(CipherSuite::CODE_POINT)
+        buf.with_u16_length(|buf| self.encode_cipher_suites(buf))
             .map_err(|_| TlsError::EncodeError)?;
 
         // compression methods, 1 byte of 0
@@ -80,17 +261,16 @@ where
 
         // extensions (1+)
         buf.with_u16_length(|buf| {
-            // Section 4.2.1.  Supported Versions
-            // Implementations of this specification MUST send this extension in the
-            // ClientHello containing all versions of TLS which they are prepared to
-            // negotiate
-            ClientHelloExtension::SupportedVersions(SupportedVersionsClientHello {
-                versions: Vec::from_slice(&[TLS13]).unwrap(),
-            })
-            .encode(buf)?;
+            if let Some(grease) = &self.grease {
+                Grease::encode_extension(grease.extensions[0], &[], buf)?;
+            }
+
+            self.encode_supported_versions(buf)?;
+            self.encode_dtls_extensions(buf)?;
 
             ClientHelloExtension::SignatureAlgorithms(SignatureAlgorithms {
                 supported_signature_algorithms: self.config.signature_schemes.clone(),
+                grease: self.grease.map(|grease| grease.signature_scheme),
             })
             .encode(buf)?;
 
@@ -100,6 +280,7 @@ where
 
             ClientHelloExtension::SupportedGroups(SupportedGroups {
                 supported_groups: self.config.named_groups.clone(),
+                grease: self.grease.map(|grease| grease.group),
             })
             .encode(buf)?;
 
@@ -110,8 +291,8 @@ where
 
             ClientHelloExtension::KeyShare(KeyShareClientHello {
                 client_shares: Vec::from_slice(&[KeyShareEntry {
-                    group: NamedGroup::Secp256r1,
-                    opaque: public_key,
+                    group,
+                    opaque: &public_key,
                 }])
                 .unwrap(),
             })
@@ -129,17 +310,47 @@ where
                 .encode(buf)?;
             }
 
+            #[cfg(feature = "tls12")]
+            if self.offers_tls12() {
+                Self::encode_tls12_extensions(buf)?;
+            }
+
+            self.encode_custom_extensions(buf)?;
+
+            if let Some(grease) = &self.grease {
+                // RFC 8701, Section 3.1: one GREASE extension should be non-empty
+                Grease::encode_extension(grease.extensions[1], &[0], buf)?;
+            }
+
+            let psk = self
+                .config
+                .psk
+                .as_ref()
+                .map(|(_, identities)| PreSharedKeyClientHello {
+                    identities: identities.clone(),
+                    hash_size: <CipherSuite::Hash as OutputSizeUser>::output_size(),
+                });
+
+            if let Some(padded_len) = self.config.padding {
+                // RFC 7685, Section 4: the padding extension pads the whole ClientHello, so
+                // it must account for the pre_shared_key extension that follows it.
+                let psk_len = psk
+                    .as_ref()
+                    .map_or(0, |psk| EXTENSION_HEADER_LEN + psk.encoded_len());
+                let unpadded_len = HANDSHAKE_HEADER_LEN + buf.len() - start + psk_len;
+                if unpadded_len < padded_len {
+                    let len = (padded_len - unpadded_len).saturating_sub(EXTENSION_HEADER_LEN);
+                    ClientHelloExtension::Padding(Padding { len }).encode(buf)?;
+                }
+            }
+
             // Section 4.2
             // When multiple extensions of different types are present, the
             // extensions MAY appear in any order, with the exception of
             // "pre_shared_key" which MUST be the last extension in
             // the ClientHello.
-            if let Some((_, identities)) = &self.config.psk {
-                ClientHelloExtension::PreSharedKey(PreSharedKeyClientHello {
-                    identities: identities.clone(),
-                    hash_size: <CipherSuite::Hash as OutputSizeUser>::output_size(),
-                })
-                .encode(buf)?;
+            if let Some(psk) = psk {
+                ClientHelloExtension::PreSharedKey(psk).encode(buf)?;
             }
 
             Ok(())
@@ -148,6 +359,33 @@ where
         Ok(())
     }
 
+    /// The length of the `ClientHello` encoded for `config`, handshake header included.
+    ///
+    /// The `ClientHello` is encoded into a counting buffer, with placeholders for the random
+    /// values, which do not change its length.
+    pub(crate) fn encoded_len(config: &'config TlsConfig<'config>) -> Result<usize, TlsError> {
+        let hello = Self {
+            config,
+            random: [0; 32],
+            legacy_session_id: config.middlebox_compat.then_some([0; 32]),
+            cipher_suite: PhantomData,
+            secret: None,
+            grease: config.grease.then_some(Grease {
+                cipher_suite: 0,
+                group: 0,
+                signature_scheme: 0,
+                version: 0,
+                extensions: [0, 0],
+            }),
+            dtls: false,
+            cookie: None,
+        };
+
+        let mut buf = CryptoBuffer::counting();
+        OutgoingHandshake::ClientHello(hello).encode(&mut buf)?;
+        Ok(buf.len())
+    }
+
     pub fn finalize(
         &self,
         enc_buf: &mut [u8],
@@ -186,3 +424,453 @@ where
         Ok(())
     }
 }
+
+/// A `ClientHello` received by a server connection.
+///
+/// The extensions a server needs are kept as raw data and interpreted on demand, so unknown
+/// (e.g. GREASE) values offered by the client are skipped instead of failing the handshake.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ClientHelloRef<'a> {
+    /// The whole handshake message, including the handshake header.
+    pub(crate) raw: &'a [u8],
+    pub(crate) random: Random,
+    pub(crate) legacy_session_id: &'a [u8],
+    cipher_suites: &'a [u8],
+    supported_versions: &'a [u8],
+    signature_algorithms: &'a [u8],
+    supported_groups: &'a [u8],
+    key_shares: &'a [u8],
+    psk_key_exchange_modes: &'a [u8],
+    alpn_protocols: Option<&'a [u8]>,
+    cookie: Option<&'a [u8]>,
+    connection_id: Option<&'a [u8]>,
+    psk_identities: &'a [u8],
+    psk_binders: &'a [u8],
+}
+
+impl<'a> ClientHelloRef<'a> {
+    pub fn parse(buf: &mut ParseBuffer<'a>) -> Result<ClientHelloRef<'a>, TlsError> {
+        let version = buf.read_u16().map_err(|_| TlsError::InvalidHandshake)?;
+
+        let mut random = [0; 32];
+        buf.fill(&mut random)?;
+
+        let session_id_len = buf
+            .read_u8()
+            .map_err(|_| TlsError::InvalidSessionIdLength)?;
+        if session_id_len > 32 {
+            return Err(TlsError::InvalidSessionIdLength);
+        }
+        let legacy_session_id = buf
+            .slice(session_id_len as usize)
+            .map_err(|_| TlsError::InvalidSessionIdLength)?;
+
+        if version == DTLS_LEGACY_VERSION {
+            // RFC 9147, Section 5.3: a DTLS 1.3 server MUST ignore the legacy_cookie
+            let legacy_cookie_len = buf.read_u8()? as usize;
+            buf.slice(legacy_cookie_len)?;
+        }
+
+        let cipher_suites_len = buf.read_u16()? as usize;
+        let cipher_suites = buf.slice(cipher_suites_len)?;
+
+        // Section 4.1.2.  Client Hello
+        // For every TLS 1.3 ClientHello, this vector MUST contain exactly one byte, set to
+        // zero
+        let compression_methods_len = buf.read_u8()? as usize;
+        if buf.slice(compression_methods_len)?.as_slice() != [0] {
+            return Err(TlsError::AbortHandshake(
+                AlertLevel::Fatal,
+                AlertDescription::IllegalParameter,
+            ));
+        }
+
+        let mut hello = Self {
+            raw: &[],
+            random,
+            legacy_session_id: legacy_session_id.as_slice(),
+            cipher_suites: cipher_suites.as_slice(),
+            supported_versions: &[],
+            signature_algorithms: &[],
+            supported_groups: &[],
+            key_shares: &[],
+            psk_key_exchange_modes: &[],
+            alpn_protocols: None,
+            cookie: None,
+            connection_id: None,
+            psk_identities: &[],
+            psk_binders: &[],
+        };
+
+        let extensions_len = buf
+            .read_u16()
+            .map_err(|_| TlsError::InvalidExtensionsLength)?;
+        let mut extensions = buf
+            .slice(extensions_len as usize)
+            .map_err(|_| TlsError::InvalidExtensionsLength)?;
+
+        while !extensions.is_empty() {
+            let extension_type = ExtensionType::parse(&mut extensions).ok();
+            let data_len = extensions.read_u16()? as usize;
+            let mut data = extensions.slice(data_len)?;
+
+            match extension_type {
+                Some(ExtensionType::SupportedVersions) => {
+                    let len = data.read_u8()? as usize;
+                    hello.supported_versions = data.slice(len)?.as_slice();
+                }
+                Some(ExtensionType::SignatureAlgorithms) => {
+                    let len = data.read_u16()? as usize;
+                    hello.signature_algorithms = data.slice(len)?.as_slice();
+                }
+                Some(ExtensionType::SupportedGroups) => {
+                    let len = data.read_u16()? as usize;
+                    hello.supported_groups = data.slice(len)?.as_slice();
+                }
+                Some(ExtensionType::KeyShare) => {
+                    let len = data.read_u16()? as usize;
+                    hello.key_shares = data.slice(len)?.as_slice();
+                }
+                Some(ExtensionType::PskKeyExchangeModes) => {
+                    let len = data.read_u8()? as usize;
+                    hello.psk_key_exchange_modes = data.slice(len)?.as_slice();
+                }
+                Some(ExtensionType::ApplicationLayerProtocolNegotiation) => {
+                    let len = data.read_u16()? as usize;
+                    hello.alpn_protocols = Some(data.slice(len)?.as_slice());
+                }
+                Some(ExtensionType::Cookie) => {
+                    hello.cookie = Some(Cookie::parse(&mut data)?.cookie);
+                }
+                Some(ExtensionType::ConnectionId) => {
+                    hello.connection_id = Some(ConnectionId::parse(&mut data)?.cid);
+                }
+                Some(ExtensionType::PreSharedKey) => {
+                    // Section 4.2.11.  Pre-Shared Key Extension
+                    // The "pre_shared_key" extension MUST be the last extension in the
+                    // ClientHello [..].  Servers MUST check that it is the last extension
+                    // and otherwise fail the handshake with an "illegal_parameter" alert.
+                    if !extensions.is_empty() {
+                        return Err(TlsError::AbortHandshake(
+                            AlertLevel::Fatal,
+                            AlertDescription::IllegalParameter,
+                        ));
+                    }
+                    hello.parse_pre_shared_key(&mut data)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(hello)
+    }
+
+    fn parse_pre_shared_key(&mut self, data: &mut ParseBuffer<'a>) -> Result<(), TlsError> {
+        let len = data.read_u16()? as usize;
+        self.psk_identities = data.slice(len)?.as_slice();
+        let len = data.read_u16()? as usize;
+        self.psk_binders = data.slice(len)?.as_slice();
+
+        let identities = Self::psk_identity_list(self.psk_identities).count();
+        if identities != Self::psk_binder_list(self.psk_binders).count() {
+            return Err(TlsError::AbortHandshake(
+                AlertLevel::Fatal,
+                AlertDescription::IllegalParameter,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether the client offers the cipher suite with the given code point.
+    pub(crate) fn offers_cipher_suite(&self, code_point: u16) -> bool {
+        u16_list(self.cipher_suites).any(|suite| suite == code_point)
+    }
+
+    /// Whether the client offers TLS 1.3 in the `supported_versions` extension.
+    pub(crate) fn offers_tls13(&self) -> bool {
+        self.offers_version(TLS13)
+    }
+
+    pub(crate) fn offers_version(&self, version: ProtocolVersion) -> bool {
+        u16_list(self.supported_versions).any(|offered| offered == version.0)
+    }
+
+    /// Whether the client offers `group` in the `supported_groups` extension.
+    pub(crate) fn offers_group(&self, group: NamedGroup) -> bool {
+        u16_list(self.supported_groups).any(|offered| offered == group.as_u16())
+    }
+
+    /// The cookie echoed from a `HelloRetryRequest`.
+    pub(crate) fn cookie(&self) -> Option<&'a [u8]> {
+        self.cookie
+    }
+
+    /// The connection ID the client wants to receive, if it offered connection IDs.
+    #[allow(dead_code)]
+    pub(crate) fn connection_id(&self) -> Option<&'a [u8]> {
+        self.connection_id
+    }
+
+    /// The signature schemes offered by the client, skipping unknown ones.
+    pub(crate) fn signature_schemes(&self) -> impl Iterator<Item = SignatureScheme> + 'a {
+        u16_list(self.signature_algorithms).filter_map(|scheme| {
+            SignatureScheme::parse(&mut ParseBuffer::new(&scheme.to_be_bytes())).ok()
+        })
+    }
+
+    pub(crate) fn offers_psk_dhe_ke(&self) -> bool {
+        self.psk_key_exchange_modes
+            .contains(&(PskKeyExchangeMode::PskDheKe as u8))
+    }
+
+    /// The key share the client sent for `group`, if any.
+    pub(crate) fn key_share(&self, group: NamedGroup) -> Option<&'a [u8]> {
+        let mut buf = ParseBuffer::new(self.key_shares);
+        core::iter::from_fn(|| KeyShareEntry::parse(&mut buf).ok())
+            .find(|entry| entry.group == group)
+            .map(|entry| entry.opaque)
+    }
+
+    /// The protocols offered in the ALPN extension, or `None` if the extension was not sent.
+    pub(crate) fn alpn_protocols(&self) -> Option<impl Iterator<Item = &'a [u8]> + Clone> {
+        Some(alpn::encoded_names(self.alpn_protocols?))
+    }
+
+    /// The PSK identities offered by the client, together with their binders.
+    pub(crate) fn psk_offers(&self) -> impl Iterator<Item = (&'a [u8], &'a [u8])> {
+        Self::psk_identity_list(self.psk_identities).zip(Self::psk_binder_list(self.psk_binders))
+    }
+
+    fn psk_identity_list(identities: &'a [u8]) -> impl Iterator<Item = &'a [u8]> {
+        let mut buf = ParseBuffer::new(identities);
+        core::iter::from_fn(move || {
+            let len = buf.read_u16().ok()? as usize;
+            let identity = buf.slice(len).ok()?;
+            // NOTE: Ticket age is not used for external PSKs
+            buf.read_u32().ok()?;
+            Some(identity.as_slice())
+        })
+    }
+
+    fn psk_binder_list(binders: &'a [u8]) -> impl Iterator<Item = &'a [u8]> {
+        let mut buf = ParseBuffer::new(binders);
+        core::iter::from_fn(move || {
+            let len = buf.read_u8().ok()? as usize;
+            buf.slice(len).ok().map(|binder| binder.as_slice())
+        })
+    }
+
+    /// The part of the message covered by the PSK binders, which is everything up to the
+    /// binder list at the very end.
+    pub(crate) fn psk_binder_transcript(&self) -> &'a [u8] {
+        &self.raw[..self.raw.len() - 2 - self.psk_binders.len()]
+    }
+}
+
+fn u16_list(data: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    data.as_chunks::<2>()
+        .0
+        .iter()
+        .copied()
+        .map(u16::from_be_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Aes128GcmSha256;
+    use crate::config::{CustomExtension, MaxFragmentLength, UnsecureProvider};
+    use rand::rngs::OsRng;
+
+    const CUSTOM_EXTENSIONS: &[CustomExtension<'static>] = &[
+        CustomExtension {
+            extension_type: 0xff00,
+            data: &[1, 2, 3],
+        },
+        CustomExtension {
+            extension_type: 0xfe00,
+            data: &[],
+        },
+    ];
+
+    fn encode_into(config: &TlsConfig<'_>, data: &mut [u8]) -> Result<usize, TlsError> {
+        let provider = UnsecureProvider::new::<Aes128GcmSha256>(OsRng);
+        let hello = ClientHello::<Aes128GcmSha256>::new(config, provider, None)?;
+        let mut buf = CryptoBuffer::wrap(data);
+        hello.encode(&mut buf)?;
+        Ok(buf.len())
+    }
+
+    fn encode(config: &TlsConfig<'_>) -> usize {
+        HANDSHAKE_HEADER_LEN + encode_into(config, &mut [0; 1024]).unwrap()
+    }
+
+    #[test]
+    fn encoded_len_matches_encoding() {
+        let configs = [
+            TlsConfig::new(),
+            TlsConfig::new()
+                .with_server_name("example.com")
+                .with_alpn(&[b"h2", b"http/1.1"])
+                .with_max_fragment_length(MaxFragmentLength::Bits10),
+            TlsConfig::new().enable_grease().with_padding(512),
+            TlsConfig::new().enable_middlebox_compatibility(),
+            TlsConfig::new()
+                .with_psk(&[0; 32], &[b"identity", b"other identity"])
+                .with_padding(300),
+            TlsConfig::new()
+                .with_custom_extensions(CUSTOM_EXTENSIONS)
+                .with_psk(&[0; 32], &[b"identity"])
+                .enable_grease()
+                .with_padding(512),
+        ];
+
+        for config in &configs {
+            assert_eq!(
+                encode(config),
+                ClientHello::<Aes128GcmSha256>::encoded_len(config).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn encoded_len_matches_encoding_for_each_group() {
+        let groups = [
+            NamedGroup::Secp256r1,
+            NamedGroup::CurveSm2,
+            NamedGroup::BrainpoolP256r1Tls13,
+            NamedGroup::BrainpoolP384r1Tls13,
+            NamedGroup::X448,
+        ];
+
+        for group in groups.iter().filter(|g| EphemeralSecret::supports(**g)) {
+            let config = TlsConfig::builder()
+                .with_named_groups(core::slice::from_ref(group))
+                .build()
+                .unwrap();
+            assert_eq!(
+                encode(&config),
+                ClientHello::<Aes128GcmSha256>::encoded_len(&config).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn encoded_len_matches_encoding_for_dtls() {
+        let cid = [1, 2, 3, 4];
+        let config = TlsConfig::new()
+            .with_connection_id(&cid)
+            .enable_grease()
+            .with_psk(&[0; 32], &[b"identity"]);
+        let cookie = [0x55; 20];
+        let secret =
+            EphemeralSecret::generate(config.key_share_group().unwrap(), &mut OsRng).unwrap();
+        let grease = Some(Grease::random(&mut OsRng));
+        let hello = ClientHello::<Aes128GcmSha256>::dtls(
+            &config,
+            [0x42; 32],
+            secret,
+            grease,
+            Some(&cookie),
+        );
+
+        let mut data = [0; 1024];
+        let mut buf = CryptoBuffer::wrap(&mut data);
+        OutgoingHandshake::ClientHello(hello)
+            .encode(&mut buf)
+            .unwrap();
+        let encoded = buf.len();
+
+        // The DTLS `ClientHello` measured without a key share secret, as `encoded_len` does
+        let placeholder = ClientHello::<Aes128GcmSha256> {
+            secret: None,
+            ..ClientHello::dtls(
+                &config,
+                [0; 32],
+                EphemeralSecret::generate(config.key_share_group().unwrap(), &mut OsRng).unwrap(),
+                grease,
+                Some(&cookie),
+            )
+        };
+        let mut buf = CryptoBuffer::counting();
+        OutgoingHandshake::ClientHello(placeholder)
+            .encode(&mut buf)
+            .unwrap();
+        assert_eq!(encoded, buf.len());
+        assert!(encoded > ClientHello::<Aes128GcmSha256>::encoded_len(&config).unwrap());
+    }
+
+    #[test]
+    fn grease_and_padding() {
+        let config = TlsConfig::new().enable_grease().with_padding(512);
+        let provider = UnsecureProvider::new::<Aes128GcmSha256>(OsRng);
+        let hello = ClientHello::<Aes128GcmSha256>::new(&config, provider, None).unwrap();
+        let grease = hello.grease.unwrap();
+
+        let mut data = [0; 1024];
+        let mut buf = CryptoBuffer::wrap(&mut data);
+        hello.encode(&mut buf).unwrap();
+        let len = buf.len();
+        let data = &data[..len];
+        assert_eq!(HANDSHAKE_HEADER_LEN + len, 512);
+
+        // The GREASE cipher suite comes first, after the version, random and session id
+        assert_eq!(data[37..39], grease.cipher_suite.to_be_bytes());
+        let contains = |value: u16| data.windows(2).any(|w| w == value.to_be_bytes());
+        for value in [
+            grease.cipher_suite,
+            grease.group,
+            grease.signature_scheme,
+            grease.version,
+            grease.extensions[0],
+            grease.extensions[1],
+        ] {
+            assert!(crate::grease::is_grease(value));
+            assert!(contains(value));
+        }
+    }
+
+    #[test]
+    fn custom_extensions_before_pre_shared_key() {
+        let config = TlsConfig::new()
+            .with_custom_extensions(CUSTOM_EXTENSIONS)
+            .with_psk(&[0; 32], &[b"identity"]);
+        let mut data = [0; 1024];
+        let len = encode_into(&config, &mut data).unwrap();
+        let data = &data[..len];
+
+        let position = |needle: &[u8]| data.windows(needle.len()).position(|w| w == needle);
+        let custom = position(&[0xff, 0x00, 0x00, 0x03, 1, 2, 3]).unwrap();
+        let empty_custom = position(&[0xfe, 0x00, 0x00, 0x00]).unwrap();
+        let identity = position(b"identity").unwrap();
+        assert!(custom < empty_custom);
+        assert!(empty_custom < identity);
+    }
+
+    #[test]
+    fn custom_extensions_of_known_types_are_rejected() {
+        for extension_type in [
+            ExtensionType::PreSharedKey as u16,
+            ExtensionType::Padding as u16,
+            0x2a2a,
+        ] {
+            let extensions = [CustomExtension {
+                extension_type,
+                data: &[],
+            }];
+            let config = TlsConfig::new().with_custom_extensions(&extensions);
+            assert!(matches!(
+                encode_into(&config, &mut [0; 1024]),
+                Err(TlsError::EncodeError)
+            ));
+        }
+
+        let duplicates = [CUSTOM_EXTENSIONS[0], CUSTOM_EXTENSIONS[0]];
+        let config = TlsConfig::new().with_custom_extensions(&duplicates);
+        assert!(matches!(
+            encode_into(&config, &mut [0; 1024]),
+            Err(TlsError::EncodeError)
+        ));
+    }
+}
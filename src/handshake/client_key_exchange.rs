@@ -0,0 +1,17 @@
+use crate::TlsError;
+use crate::buffer::CryptoBuffer;
+
+/// RFC 8422, Section 5.7.  Client Key Exchange
+///
+/// The client's ephemeral ECDH public key, as an uncompressed point.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ClientKeyExchange<'a> {
+    pub public_key: &'a [u8],
+}
+
+impl ClientKeyExchange<'_> {
+    pub(crate) fn encode(&self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
+        buf.with_u8_length(|buf| buf.extend_from_slice(self.public_key))
+    }
+}
@@ -1,19 +1,63 @@
-use core::marker::PhantomData;
+use heapless::Vec;
 
+use crate::extensions::UnknownExtension;
 use crate::extensions::messages::EncryptedExtensionsExtension;
 
 use crate::TlsError;
+use crate::buffer::CryptoBuffer;
 use crate::parse_buffer::ParseBuffer;
 
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct EncryptedExtensions<'a> {
-    _todo: PhantomData<&'a ()>,
+    pub(crate) extensions: Vec<EncryptedExtensionsExtension<'a>, 16>,
 }
 
 impl<'a> EncryptedExtensions<'a> {
     pub fn parse(buf: &mut ParseBuffer<'a>) -> Result<EncryptedExtensions<'a>, TlsError> {
-        EncryptedExtensionsExtension::parse_vector::<16>(buf)?;
-        Ok(EncryptedExtensions { _todo: PhantomData })
+        let extensions = EncryptedExtensionsExtension::parse_vector::<16>(buf)?;
+        Ok(EncryptedExtensions { extensions })
+    }
+
+    pub(crate) fn encode(&self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
+        buf.with_u16_length(|buf| {
+            for extension in &self.extensions {
+                extension.encode(buf)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// The extensions whose type is unknown to embedded-tls.
+    pub(crate) fn unknown_extensions(&self) -> impl Iterator<Item = &UnknownExtension<'a>> {
+        self.extensions
+            .iter()
+            .filter_map(EncryptedExtensionsExtension::unknown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_extensions() {
+        let data = [
+            0, 19, // extensions length
+            0, 16, 0, 5, 0, 3, 2, b'h', b'2', // application_layer_protocol_negotiation
+            0xff, 0x00, 0, 2, 1, 2, // unknown
+            0xfe, 0x00, 0, 0, // unknown
+        ];
+        let mut buf = ParseBuffer::new(&data);
+        let extensions = EncryptedExtensions::parse(&mut buf).unwrap();
+        assert!(buf.is_empty());
+        assert_eq!(extensions.extensions.len(), 3);
+
+        let mut unknown = extensions
+            .unknown_extensions()
+            .map(|e| (e.extension_type, e.data));
+        assert_eq!(unknown.next(), Some((0xff00, &[1, 2][..])));
+        assert_eq!(unknown.next(), Some((0xfe00, &[][..])));
+        assert_eq!(unknown.next(), None);
     }
 }
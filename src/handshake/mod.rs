@@ -1,18 +1,22 @@
 //use p256::elliptic_curve::AffinePoint;
 use crate::TlsError;
 use crate::config::TlsCipherSuite;
-use crate::handshake::certificate::CertificateRef;
+use crate::handshake::certificate::{CertificateEntryRef, CertificateRef};
 use crate::handshake::certificate_request::CertificateRequestRef;
 use crate::handshake::certificate_verify::{CertificateVerify, CertificateVerifyRef};
-use crate::handshake::client_hello::ClientHello;
+use crate::handshake::client_hello::{ClientHello, ClientHelloRef};
+#[cfg(feature = "tls12")]
+use crate::handshake::client_key_exchange::ClientKeyExchange;
 use crate::handshake::encrypted_extensions::EncryptedExtensions;
 use crate::handshake::finished::Finished;
 use crate::handshake::new_session_ticket::NewSessionTicket;
-use crate::handshake::server_hello::ServerHello;
+use crate::handshake::server_hello::{HelloRetryRequest, ServerHello};
 use crate::key_schedule::HashOutputSize;
 use crate::parse_buffer::{ParseBuffer, ParseError};
 use crate::{buffer::CryptoBuffer, key_schedule::WriteKeySchedule};
 use core::fmt::{Debug, Formatter};
+#[cfg(feature = "tls12")]
+use generic_array::typenum::U12;
 use sha2::Digest;
 
 pub mod binder;
@@ -20,26 +24,40 @@ pub mod certificate;
 pub mod certificate_request;
 pub mod certificate_verify;
 pub mod client_hello;
+#[cfg(feature = "tls12")]
+pub mod client_key_exchange;
 pub mod encrypted_extensions;
 pub mod finished;
 pub mod new_session_ticket;
 pub mod server_hello;
+#[cfg(feature = "tls12")]
+pub mod server_key_exchange;
 
 const LEGACY_VERSION: u16 = 0x0303;
 
-type Random = [u8; 32];
+/// The `legacy_version` of a DTLS 1.3 `ClientHello`, which is DTLS 1.2.
+pub(crate) const DTLS_LEGACY_VERSION: u16 = 0xfefd;
+
+/// Size of the handshake type and length fields preceding each handshake message.
+pub(crate) const HANDSHAKE_HEADER_LEN: usize = 4;
+
+pub(crate) type Random = [u8; 32];
 
 #[derive(Debug, Copy, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum HandshakeType {
+    HelloRequest = 0,
     ClientHello = 1,
     ServerHello = 2,
     NewSessionTicket = 4,
     EndOfEarlyData = 5,
     EncryptedExtensions = 8,
     Certificate = 11,
+    ServerKeyExchange = 12,
     CertificateRequest = 13,
+    ServerHelloDone = 14,
     CertificateVerify = 15,
+    ClientKeyExchange = 16,
     Finished = 20,
     KeyUpdate = 24,
     MessageHash = 254,
@@ -48,14 +66,18 @@ pub enum HandshakeType {
 impl HandshakeType {
     pub fn parse(buf: &mut ParseBuffer) -> Result<Self, ParseError> {
         match buf.read_u8()? {
+            0 => Ok(HandshakeType::HelloRequest),
             1 => Ok(HandshakeType::ClientHello),
             2 => Ok(HandshakeType::ServerHello),
             4 => Ok(HandshakeType::NewSessionTicket),
             5 => Ok(HandshakeType::EndOfEarlyData),
             8 => Ok(HandshakeType::EncryptedExtensions),
             11 => Ok(HandshakeType::Certificate),
+            12 => Ok(HandshakeType::ServerKeyExchange),
             13 => Ok(HandshakeType::CertificateRequest),
+            14 => Ok(HandshakeType::ServerHelloDone),
             15 => Ok(HandshakeType::CertificateVerify),
+            16 => Ok(HandshakeType::ClientKeyExchange),
             20 => Ok(HandshakeType::Finished),
             24 => Ok(HandshakeType::KeyUpdate),
             254 => Ok(HandshakeType::MessageHash),
@@ -64,36 +86,70 @@ impl HandshakeType {
     }
 }
 
+/// Handshake messages sent to the peer, by either a client or a server connection.
 #[allow(clippy::large_enum_variant)]
-pub enum ClientHandshake<'config, 'a, CipherSuite>
+pub enum OutgoingHandshake<'config, 'a, CipherSuite>
 where
     CipherSuite: TlsCipherSuite,
 {
-    ClientCert(CertificateRef<'a>),
-    ClientCertVerify(CertificateVerify),
+    Certificate(CertificateRef<'a>),
+    CertificateVerify(CertificateVerify),
     ClientHello(ClientHello<'config, CipherSuite>),
     Finished(Finished<HashOutputSize<CipherSuite>>),
+    ServerHello(ServerHello<'a>),
+    HelloRetryRequest(HelloRetryRequest<'a>),
+    EncryptedExtensions(EncryptedExtensions<'a>),
+    CertificateRequest(CertificateRequestRef<'a>),
+    #[cfg(feature = "tls12")]
+    ClientKeyExchange(ClientKeyExchange<'a>),
+    /// A TLS 1.2 client `Certificate`. It is always empty, as the TLS 1.2 `CertificateVerify`
+    /// signs all handshake messages, which are not kept around.
+    #[cfg(feature = "tls12")]
+    Tls12ClientCert(CertificateRef<'a>),
+    #[cfg(feature = "tls12")]
+    Tls12Finished(Finished<U12>),
 }
 
-impl<CipherSuite> ClientHandshake<'_, '_, CipherSuite>
+impl<CipherSuite> OutgoingHandshake<'_, '_, CipherSuite>
 where
     CipherSuite: TlsCipherSuite,
 {
     fn handshake_type(&self) -> HandshakeType {
         match self {
-            ClientHandshake::ClientHello(_) => HandshakeType::ClientHello,
-            ClientHandshake::Finished(_) => HandshakeType::Finished,
-            ClientHandshake::ClientCert(_) => HandshakeType::Certificate,
-            ClientHandshake::ClientCertVerify(_) => HandshakeType::CertificateVerify,
+            OutgoingHandshake::ClientHello(_) => HandshakeType::ClientHello,
+            OutgoingHandshake::Finished(_) => HandshakeType::Finished,
+            OutgoingHandshake::Certificate(_) => HandshakeType::Certificate,
+            OutgoingHandshake::CertificateVerify(_) => HandshakeType::CertificateVerify,
+            OutgoingHandshake::ServerHello(_) | OutgoingHandshake::HelloRetryRequest(_) => {
+                HandshakeType::ServerHello
+            }
+            OutgoingHandshake::EncryptedExtensions(_) => HandshakeType::EncryptedExtensions,
+            OutgoingHandshake::CertificateRequest(_) => HandshakeType::CertificateRequest,
+            #[cfg(feature = "tls12")]
+            OutgoingHandshake::ClientKeyExchange(_) => HandshakeType::ClientKeyExchange,
+            #[cfg(feature = "tls12")]
+            OutgoingHandshake::Tls12ClientCert(_) => HandshakeType::Certificate,
+            #[cfg(feature = "tls12")]
+            OutgoingHandshake::Tls12Finished(_) => HandshakeType::Finished,
         }
     }
 
     fn encode_inner(&self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
         match self {
-            ClientHandshake::ClientHello(inner) => inner.encode(buf),
-            ClientHandshake::Finished(inner) => inner.encode(buf),
-            ClientHandshake::ClientCert(inner) => inner.encode(buf),
-            ClientHandshake::ClientCertVerify(inner) => inner.encode(buf),
+            OutgoingHandshake::ClientHello(inner) => inner.encode(buf),
+            OutgoingHandshake::Finished(inner) => inner.encode(buf),
+            OutgoingHandshake::Certificate(inner) => inner.encode(buf),
+            OutgoingHandshake::CertificateVerify(inner) => inner.encode(buf),
+            OutgoingHandshake::ServerHello(inner) => inner.encode(buf),
+            OutgoingHandshake::HelloRetryRequest(inner) => inner.encode(buf),
+            OutgoingHandshake::EncryptedExtensions(inner) => inner.encode(buf),
+            OutgoingHandshake::CertificateRequest(inner) => inner.encode(buf),
+            #[cfg(feature = "tls12")]
+            OutgoingHandshake::ClientKeyExchange(inner) => inner.encode(buf),
+            #[cfg(feature = "tls12")]
+            OutgoingHandshake::Tls12ClientCert(inner) => inner.encode_tls12(buf),
+            #[cfg(feature = "tls12")]
+            OutgoingHandshake::Tls12Finished(inner) => inner.encode(buf),
         }
     }
 
@@ -111,7 +167,7 @@ where
         write_key_schedule: &mut WriteKeySchedule<CipherSuite>,
     ) -> Result<(), TlsError> {
         let enc_buf = buf.as_mut_slice();
-        if let ClientHandshake::ClientHello(hello) = self {
+        if let OutgoingHandshake::ClientHello(hello) = self {
             hello.finalize(enc_buf, transcript, write_key_schedule)
         } else {
             transcript.update(enc_buf);
@@ -126,62 +182,77 @@ where
     }
 }
 
+/// Handshake messages received from the peer, by either a client or a server connection.
 #[allow(clippy::large_enum_variant)]
-pub enum ServerHandshake<'a, CipherSuite: TlsCipherSuite> {
+pub enum IncomingHandshake<'a, CipherSuite: TlsCipherSuite> {
+    ClientHello(ClientHelloRef<'a>),
     ServerHello(ServerHello<'a>),
     EncryptedExtensions(EncryptedExtensions<'a>),
     NewSessionTicket(NewSessionTicket<'a>),
     Certificate(CertificateRef<'a>),
+    /// An entry of a `Certificate` message streamed entry by entry.
+    CertificateEntry(CertificateEntryRef<'a>),
+    /// The end of a `Certificate` message streamed entry by entry.
+    CertificateEnd,
     CertificateRequest(CertificateRequestRef<'a>),
     CertificateVerify(CertificateVerifyRef<'a>),
     Finished(Finished<HashOutputSize<CipherSuite>>),
 }
 
-impl<CipherSuite: TlsCipherSuite> ServerHandshake<'_, CipherSuite> {
+impl<CipherSuite: TlsCipherSuite> IncomingHandshake<'_, CipherSuite> {
     #[allow(dead_code)]
     pub fn handshake_type(&self) -> HandshakeType {
         match self {
-            ServerHandshake::ServerHello(_) => HandshakeType::ServerHello,
-            ServerHandshake::EncryptedExtensions(_) => HandshakeType::EncryptedExtensions,
-            ServerHandshake::NewSessionTicket(_) => HandshakeType::NewSessionTicket,
-            ServerHandshake::Certificate(_) => HandshakeType::Certificate,
-            ServerHandshake::CertificateRequest(_) => HandshakeType::CertificateRequest,
-            ServerHandshake::CertificateVerify(_) => HandshakeType::CertificateVerify,
-            ServerHandshake::Finished(_) => HandshakeType::Finished,
+            IncomingHandshake::ClientHello(_) => HandshakeType::ClientHello,
+            IncomingHandshake::ServerHello(_) => HandshakeType::ServerHello,
+            IncomingHandshake::EncryptedExtensions(_) => HandshakeType::EncryptedExtensions,
+            IncomingHandshake::NewSessionTicket(_) => HandshakeType::NewSessionTicket,
+            IncomingHandshake::Certificate(_)
+            | IncomingHandshake::CertificateEntry(_)
+            | IncomingHandshake::CertificateEnd => HandshakeType::Certificate,
+            IncomingHandshake::CertificateRequest(_) => HandshakeType::CertificateRequest,
+            IncomingHandshake::CertificateVerify(_) => HandshakeType::CertificateVerify,
+            IncomingHandshake::Finished(_) => HandshakeType::Finished,
         }
     }
 }
 
-impl<CipherSuite: TlsCipherSuite> Debug for ServerHandshake<'_, CipherSuite> {
+impl<CipherSuite: TlsCipherSuite> Debug for IncomingHandshake<'_, CipherSuite> {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
-            ServerHandshake::ServerHello(inner) => Debug::fmt(inner, f),
-            ServerHandshake::EncryptedExtensions(inner) => Debug::fmt(inner, f),
-            ServerHandshake::Certificate(inner) => Debug::fmt(inner, f),
-            ServerHandshake::CertificateRequest(inner) => Debug::fmt(inner, f),
-            ServerHandshake::CertificateVerify(inner) => Debug::fmt(inner, f),
-            ServerHandshake::Finished(inner) => Debug::fmt(inner, f),
-            ServerHandshake::NewSessionTicket(inner) => Debug::fmt(inner, f),
+            IncomingHandshake::ClientHello(inner) => Debug::fmt(inner, f),
+            IncomingHandshake::ServerHello(inner) => Debug::fmt(inner, f),
+            IncomingHandshake::EncryptedExtensions(inner) => Debug::fmt(inner, f),
+            IncomingHandshake::Certificate(inner) => Debug::fmt(inner, f),
+            IncomingHandshake::CertificateEntry(inner) => Debug::fmt(inner, f),
+            IncomingHandshake::CertificateEnd => f.write_str("CertificateEnd"),
+            IncomingHandshake::CertificateRequest(inner) => Debug::fmt(inner, f),
+            IncomingHandshake::CertificateVerify(inner) => Debug::fmt(inner, f),
+            IncomingHandshake::Finished(inner) => Debug::fmt(inner, f),
+            IncomingHandshake::NewSessionTicket(inner) => Debug::fmt(inner, f),
         }
     }
 }
 
 #[cfg(feature = "defmt")]
-impl<'a, CipherSuite: TlsCipherSuite> defmt::Format for ServerHandshake<'a, CipherSuite> {
+impl<'a, CipherSuite: TlsCipherSuite> defmt::Format for IncomingHandshake<'a, CipherSuite> {
     fn format(&self, f: defmt::Formatter<'_>) {
         match self {
-            ServerHandshake::ServerHello(inner) => defmt::write!(f, "{}", inner),
-            ServerHandshake::EncryptedExtensions(inner) => defmt::write!(f, "{}", inner),
-            ServerHandshake::Certificate(inner) => defmt::write!(f, "{}", inner),
-            ServerHandshake::CertificateRequest(inner) => defmt::write!(f, "{}", inner),
-            ServerHandshake::CertificateVerify(inner) => defmt::write!(f, "{}", inner),
-            ServerHandshake::Finished(inner) => defmt::write!(f, "{}", inner),
-            ServerHandshake::NewSessionTicket(inner) => defmt::write!(f, "{}", inner),
+            IncomingHandshake::ClientHello(inner) => defmt::write!(f, "{}", inner),
+            IncomingHandshake::ServerHello(inner) => defmt::write!(f, "{}", inner),
+            IncomingHandshake::EncryptedExtensions(inner) => defmt::write!(f, "{}", inner),
+            IncomingHandshake::Certificate(inner) => defmt::write!(f, "{}", inner),
+            IncomingHandshake::CertificateEntry(inner) => defmt::write!(f, "{}", inner),
+            IncomingHandshake::CertificateEnd => defmt::write!(f, "CertificateEnd"),
+            IncomingHandshake::CertificateRequest(inner) => defmt::write!(f, "{}", inner),
+            IncomingHandshake::CertificateVerify(inner) => defmt::write!(f, "{}", inner),
+            IncomingHandshake::Finished(inner) => defmt::write!(f, "{}", inner),
+            IncomingHandshake::NewSessionTicket(inner) => defmt::write!(f, "{}", inner),
         }
     }
 }
 
-impl<'a, CipherSuite: TlsCipherSuite> ServerHandshake<'a, CipherSuite> {
+impl<'a, CipherSuite: TlsCipherSuite> IncomingHandshake<'a, CipherSuite> {
     pub fn read(
         buf: &mut ParseBuffer<'a>,
         digest: &mut CipherSuite::Hash,
@@ -190,8 +261,14 @@ impl<'a, CipherSuite: TlsCipherSuite> ServerHandshake<'a, CipherSuite> {
         let mut handshake = Self::parse(buf)?;
         let handshake_end = buf.offset();
 
-        if let ServerHandshake::Finished(finished) = &mut handshake {
-            finished.hash.replace(digest.clone().finalize());
+        match &mut handshake {
+            IncomingHandshake::Finished(finished) => {
+                finished.hash.replace(digest.clone().finalize());
+            }
+            IncomingHandshake::ClientHello(hello) => {
+                hello.raw = &buf.as_slice()[handshake_start..handshake_end];
+            }
+            _ => {}
         }
 
         digest.update(&buf.as_slice()[handshake_start..handshake_end]);
@@ -199,6 +276,40 @@ impl<'a, CipherSuite: TlsCipherSuite> ServerHandshake<'a, CipherSuite> {
         Ok(handshake)
     }
 
+    /// Read the start of a streamed `Certificate` message, up to its first entry.
+    pub(crate) fn read_certificate_start(
+        buf: &mut ParseBuffer<'a>,
+        digest: &mut CipherSuite::Hash,
+    ) -> Result<(), TlsError> {
+        let start = buf.offset();
+        let handshake_type = HandshakeType::parse(buf).map_err(|_| TlsError::InvalidHandshake)?;
+        if !matches!(handshake_type, HandshakeType::Certificate) {
+            return Err(TlsError::InvalidHandshake);
+        }
+        let content_len = buf.read_u24().map_err(|_| TlsError::InvalidHandshake)?;
+        let request_context_len = buf.read_u8().map_err(|_| TlsError::InvalidCertificate)?;
+        buf.slice(request_context_len as usize)
+            .map_err(|_| TlsError::InvalidCertificate)?;
+        let entries_len = buf.read_u24().map_err(|_| TlsError::InvalidCertificate)?;
+        if content_len != 1 + u32::from(request_context_len) + 3 + entries_len {
+            return Err(TlsError::DecodeError);
+        }
+
+        digest.update(&buf.as_slice()[start..buf.offset()]);
+        Ok(())
+    }
+
+    /// Read an entry of a streamed `Certificate` message.
+    pub(crate) fn read_certificate_entry(
+        buf: &mut ParseBuffer<'a>,
+        digest: &mut CipherSuite::Hash,
+    ) -> Result<Self, TlsError> {
+        let start = buf.offset();
+        let entry = CertificateEntryRef::parse(buf)?;
+        digest.update(&buf.as_slice()[start..buf.offset()]);
+        Ok(IncomingHandshake::CertificateEntry(entry))
+    }
+
     fn parse(buf: &mut ParseBuffer<'a>) -> Result<Self, TlsError> {
         let handshake_type = HandshakeType::parse(buf).map_err(|_| TlsError::InvalidHandshake)?;
 
@@ -207,26 +318,30 @@ impl<'a, CipherSuite: TlsCipherSuite> ServerHandshake<'a, CipherSuite> {
         let content_len = buf.read_u24().map_err(|_| TlsError::InvalidHandshake)?;
 
         let handshake = match handshake_type {
-            //HandshakeType::ClientHello => {}
-            HandshakeType::ServerHello => ServerHandshake::ServerHello(ServerHello::parse(buf)?),
+            HandshakeType::ClientHello => {
+                IncomingHandshake::ClientHello(ClientHelloRef::parse(buf)?)
+            }
+            HandshakeType::ServerHello => IncomingHandshake::ServerHello(ServerHello::parse(buf)?),
             HandshakeType::NewSessionTicket => {
-                ServerHandshake::NewSessionTicket(NewSessionTicket::parse(buf)?)
+                IncomingHandshake::NewSessionTicket(NewSessionTicket::parse(buf)?)
             }
             //HandshakeType::EndOfEarlyData => {}
             HandshakeType::EncryptedExtensions => {
-                ServerHandshake::EncryptedExtensions(EncryptedExtensions::parse(buf)?)
+                IncomingHandshake::EncryptedExtensions(EncryptedExtensions::parse(buf)?)
+            }
+            HandshakeType::Certificate => {
+                IncomingHandshake::Certificate(CertificateRef::parse(buf)?)
             }
-            HandshakeType::Certificate => ServerHandshake::Certificate(CertificateRef::parse(buf)?),
 
             HandshakeType::CertificateRequest => {
-                ServerHandshake::CertificateRequest(CertificateRequestRef::parse(buf)?)
+                IncomingHandshake::CertificateRequest(CertificateRequestRef::parse(buf)?)
             }
 
             HandshakeType::CertificateVerify => {
-                ServerHandshake::CertificateVerify(CertificateVerifyRef::parse(buf)?)
+                IncomingHandshake::CertificateVerify(CertificateVerifyRef::parse(buf)?)
             }
             HandshakeType::Finished => {
-                ServerHandshake::Finished(Finished::parse(buf, content_len)?)
+                IncomingHandshake::Finished(Finished::parse(buf, content_len)?)
             }
             //HandshakeType::KeyUpdate => {}
             //HandshakeType::MessageHash => {}
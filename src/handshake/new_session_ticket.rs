@@ -25,9 +25,30 @@ impl<'a> NewSessionTicket<'a> {
             .slice(ticket_length as usize)
             .map_err(|_| TlsError::InvalidTicketLength)?;
 
-        let extensions = NewSessionTicketExtension::parse_vector::<1>(buf)?;
+        let extensions = NewSessionTicketExtension::parse_vector_ignoring_unknown::<1>(buf)?;
 
         unused((lifetime, age_add, nonce, ticket, extensions));
         Ok(Self { _todo: PhantomData })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_unknown_extensions() {
+        let data = [
+            0, 0, 0, 60, // ticket_lifetime
+            0, 0, 0, 0, // ticket_age_add
+            1, 0, // ticket_nonce
+            0, 1, 0, // ticket
+            0, 9, // extensions length
+            0x0a, 0x0a, 0, 0, // GREASE
+            0xfe, 0x00, 0, 1, 0, // unknown
+        ];
+        let mut buf = ParseBuffer::new(&data);
+        NewSessionTicket::parse(&mut buf).unwrap();
+        assert!(buf.is_empty());
+    }
+}
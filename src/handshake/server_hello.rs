@@ -1,26 +1,56 @@
 use heapless::Vec;
 
+use crate::TlsError;
+use crate::buffer::CryptoBuffer;
 use crate::cipher_suites::CipherSuite;
 use crate::crypto_engine::CryptoEngine;
+use crate::extensions::UnknownExtension;
 use crate::extensions::extension_data::key_share::KeyShareEntry;
-use crate::extensions::messages::ServerHelloExtension;
+use crate::extensions::extension_data::supported_groups::NamedGroup;
+use crate::extensions::extension_data::supported_versions::ProtocolVersion;
+use crate::extensions::messages::{HelloRetryRequestExtension, ServerHelloExtension};
+use crate::handshake::{LEGACY_VERSION, Random};
+use crate::key_exchange::{EphemeralSecret, SharedSecret};
 use crate::parse_buffer::ParseBuffer;
-use crate::{TlsError, unused};
-use p256::PublicKey;
-use p256::ecdh::{EphemeralSecret, SharedSecret};
+
+/// The `random` of a `ServerHello` that is actually a `HelloRetryRequest`, which is
+/// SHA-256("HelloRetryRequest") (RFC 8446, Section 4.1.3).
+pub(crate) const HELLO_RETRY_REQUEST_RANDOM: Random = [
+    0xcf, 0x21, 0xad, 0x74, 0xe5, 0x9a, 0x61, 0x11, 0xbe, 0x1d, 0x8c, 0x02, 0x1e, 0x65, 0xb8, 0x91,
+    0xc2, 0xa2, 0x11, 0x16, 0x7a, 0xbb, 0x8c, 0x5e, 0x07, 0x9e, 0x09, 0xe2, 0xc8, 0xa8, 0x33, 0x9c,
+];
 
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ServerHello<'a> {
-    extensions: Vec<ServerHelloExtension<'a>, 4>,
+    legacy_version: u16,
+    random: Random,
+    legacy_session_id: &'a [u8],
+    cipher_suite: u16,
+    extensions: Vec<ServerHelloExtension<'a>, 8>,
 }
 
 impl<'a> ServerHello<'a> {
+    pub(crate) fn new(
+        random: Random,
+        legacy_session_id: &'a [u8],
+        cipher_suite: u16,
+        extensions: Vec<ServerHelloExtension<'a>, 8>,
+    ) -> Self {
+        Self {
+            legacy_version: LEGACY_VERSION,
+            random,
+            legacy_session_id,
+            cipher_suite,
+            extensions,
+        }
+    }
+
     pub fn parse(buf: &mut ParseBuffer<'a>) -> Result<ServerHello<'a>, TlsError> {
         //let mut buf = ParseBuffer::new(&buf[0..content_length]);
         //let mut buf = ParseBuffer::new(&buf);
 
-        let _version = buf.read_u16().map_err(|_| TlsError::InvalidHandshake)?;
+        let legacy_version = buf.read_u16().map_err(|_| TlsError::InvalidHandshake)?;
 
         let mut random = [0; 32];
         buf.fill(&mut random)?;
@@ -43,14 +73,109 @@ impl<'a> ServerHello<'a> {
         buf.read_u8()?;
 
         let extensions = ServerHelloExtension::parse_vector(buf)?;
+        reject_unknown(extensions.iter().filter_map(ServerHelloExtension::unknown))?;
 
         // debug!("server random {:x}", random);
         // debug!("server session-id {:x}", session_id.as_slice());
         debug!("server cipher_suite {:?}", cipher_suite);
         debug!("server extensions {:?}", extensions);
 
-        unused(session_id);
-        Ok(Self { extensions })
+        Ok(Self {
+            legacy_version,
+            random,
+            legacy_session_id: session_id.as_slice(),
+            cipher_suite: cipher_suite as u16,
+            extensions,
+        })
+    }
+
+    pub(crate) fn encode(&self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
+        buf.push_u16(self.legacy_version)?;
+        buf.extend_from_slice(&self.random)?;
+
+        // Section 4.1.3.  Server Hello
+        // legacy_session_id_echo:  The contents of the client's
+        // legacy_session_id field.
+        buf.with_u8_length(|buf| buf.extend_from_slice(self.legacy_session_id))?;
+        buf.push_u16(self.cipher_suite)?;
+
+        // legacy_compression_method
+        buf.push(0)?;
+
+        buf.with_u16_length(|buf| {
+            for extension in &self.extensions {
+                extension.encode(buf)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Whether the `ServerHello` message `body` (without the handshake header) is a
+    /// `HelloRetryRequest`.
+    pub(crate) fn is_hello_retry_request(body: &[u8]) -> bool {
+        body.get(2..34) == Some(&HELLO_RETRY_REQUEST_RANDOM[..])
+    }
+
+    pub(crate) fn cipher_suite(&self) -> u16 {
+        self.cipher_suite
+    }
+
+    /// The `legacy_session_id_echo` field, which must match the `ClientHello` session ID.
+    pub(crate) fn legacy_session_id(&self) -> &'a [u8] {
+        self.legacy_session_id
+    }
+
+    /// The `legacy_version` field, which is the negotiated version in TLS 1.2.
+    #[cfg(feature = "tls12")]
+    pub(crate) fn legacy_version(&self) -> u16 {
+        self.legacy_version
+    }
+
+    #[cfg(feature = "tls12")]
+    pub(crate) fn random(&self) -> &Random {
+        &self.random
+    }
+
+    /// Whether the server agreed to use the extended master secret (RFC 7627).
+    #[cfg(feature = "tls12")]
+    pub(crate) fn extended_master_secret(&self) -> bool {
+        self.extensions
+            .iter()
+            .any(|e| matches!(e, ServerHelloExtension::ExtendedMasterSecret(_)))
+    }
+
+    /// The `renegotiated_connection` of the `renegotiation_info` extension, if present.
+    #[cfg(feature = "tls12")]
+    pub(crate) fn renegotiation_info(&self) -> Option<&'a [u8]> {
+        self.extensions.iter().find_map(|e| {
+            if let ServerHelloExtension::RenegotiationInfo(info) = e {
+                Some(info.renegotiated_connection)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The version selected in the `supported_versions` extension.
+    pub(crate) fn selected_version(&self) -> Option<ProtocolVersion> {
+        self.extensions.iter().find_map(|e| {
+            if let ServerHelloExtension::SupportedVersions(versions) = e {
+                Some(versions.selected_version)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The connection ID the server wants to receive, if it negotiated connection IDs.
+    pub(crate) fn connection_id(&self) -> Option<&'a [u8]> {
+        self.extensions.iter().find_map(|e| {
+            if let ServerHelloExtension::ConnectionId(connection_id) = e {
+                Some(connection_id.cid)
+            } else {
+                None
+            }
+        })
     }
 
     pub fn key_share(&self) -> Option<&KeyShareEntry<'_>> {
@@ -63,21 +188,191 @@ impl<'a> ServerHello<'a> {
         })
     }
 
-    pub fn calculate_shared_secret(&self, secret: &EphemeralSecret) -> Option<SharedSecret> {
-        let server_key_share = self.key_share()?;
-        let server_public_key = PublicKey::from_sec1_bytes(server_key_share.opaque).ok()?;
-        Some(secret.diffie_hellman(&server_public_key))
+    /// Compute the shared secret with the server key share, which must be for the group of
+    /// the key share sent in the `ClientHello`.
+    pub(crate) fn calculate_shared_secret(
+        &self,
+        secret: &EphemeralSecret,
+    ) -> Result<SharedSecret, TlsError> {
+        let server_key_share = self.key_share().ok_or(TlsError::InvalidKeyShare)?;
+        if server_key_share.group != secret.group() {
+            warn!(
+                "Server key share is for {:?} instead of {:?}",
+                server_key_share.group,
+                secret.group()
+            );
+            return Err(TlsError::InvalidKeyShare);
+        }
+        secret.diffie_hellman(server_key_share.opaque)
     }
 
     #[allow(dead_code)]
-    pub fn initialize_crypto_engine(&self, secret: &EphemeralSecret) -> Option<CryptoEngine> {
+    pub(crate) fn initialize_crypto_engine(
+        &self,
+        secret: &EphemeralSecret,
+    ) -> Option<CryptoEngine> {
         let server_key_share = self.key_share()?;
 
         let group = server_key_share.group;
-
-        let server_public_key = PublicKey::from_sec1_bytes(server_key_share.opaque).ok()?;
-        let shared = secret.diffie_hellman(&server_public_key);
+        let shared = secret.diffie_hellman(server_key_share.opaque).ok()?;
 
         Some(CryptoEngine::new(group, shared))
     }
 }
+
+/// RFC 8446, Section 4.2: the server only sends extensions the client offered, and the client
+/// MUST abort the handshake with an `unsupported_extension` alert otherwise.
+fn reject_unknown<'a>(
+    mut extensions: impl Iterator<Item = &'a UnknownExtension<'a>>,
+) -> Result<(), TlsError> {
+    if let Some(extension) = extensions.next() {
+        warn!("Unsolicited extension type {}", extension.extension_type);
+        return Err(TlsError::UnknownExtensionType);
+    }
+    Ok(())
+}
+
+/// A `HelloRetryRequest`, which is sent as a `ServerHello` with a special random.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HelloRetryRequest<'a> {
+    legacy_session_id: &'a [u8],
+    cipher_suite: u16,
+    extensions: Vec<HelloRetryRequestExtension<'a>, 3>,
+}
+
+impl<'a> HelloRetryRequest<'a> {
+    pub(crate) fn new(
+        legacy_session_id: &'a [u8],
+        cipher_suite: u16,
+        extensions: Vec<HelloRetryRequestExtension<'a>, 3>,
+    ) -> Self {
+        Self {
+            legacy_session_id,
+            cipher_suite,
+            extensions,
+        }
+    }
+
+    pub fn parse(buf: &mut ParseBuffer<'a>) -> Result<HelloRetryRequest<'a>, TlsError> {
+        let _version = buf.read_u16().map_err(|_| TlsError::InvalidHandshake)?;
+
+        let mut random = [0; 32];
+        buf.fill(&mut random)?;
+        if random != HELLO_RETRY_REQUEST_RANDOM {
+            return Err(TlsError::InvalidHandshake);
+        }
+
+        let session_id_length = buf
+            .read_u8()
+            .map_err(|_| TlsError::InvalidSessionIdLength)?;
+        let session_id = buf
+            .slice(session_id_length as usize)
+            .map_err(|_| TlsError::InvalidSessionIdLength)?;
+
+        let cipher_suite = buf.read_u16().map_err(|_| TlsError::InvalidCipherSuite)?;
+
+        // skip compression method, it's 0.
+        buf.read_u8()?;
+
+        let extensions = HelloRetryRequestExtension::parse_vector(buf)?;
+        reject_unknown(
+            extensions
+                .iter()
+                .filter_map(HelloRetryRequestExtension::unknown),
+        )?;
+
+        debug!("hello retry request extensions {:?}", extensions);
+
+        Ok(Self {
+            legacy_session_id: session_id.as_slice(),
+            cipher_suite,
+            extensions,
+        })
+    }
+
+    pub(crate) fn encode(&self, buf: &mut CryptoBuffer<'_>) -> Result<(), TlsError> {
+        buf.push_u16(LEGACY_VERSION)?;
+        buf.extend_from_slice(&HELLO_RETRY_REQUEST_RANDOM)?;
+        buf.with_u8_length(|buf| buf.extend_from_slice(self.legacy_session_id))?;
+        buf.push_u16(self.cipher_suite)?;
+
+        // legacy_compression_method
+        buf.push(0)?;
+
+        buf.with_u16_length(|buf| {
+            for extension in &self.extensions {
+                extension.encode(buf)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub(crate) fn cipher_suite(&self) -> u16 {
+        self.cipher_suite
+    }
+
+    pub(crate) fn selected_version(&self) -> Option<ProtocolVersion> {
+        self.extensions.iter().find_map(|e| {
+            if let HelloRetryRequestExtension::SupportedVersions(versions) = e {
+                Some(versions.selected_version)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The group the server wants a key share for, if it did not accept the offered one.
+    pub(crate) fn selected_group(&self) -> Option<NamedGroup> {
+        self.extensions.iter().find_map(|e| {
+            if let HelloRetryRequestExtension::KeyShare(key_share) = e {
+                Some(key_share.selected_group)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The cookie to echo in the second `ClientHello`.
+    pub(crate) fn cookie(&self) -> Option<&'a [u8]> {
+        self.extensions.iter().find_map(|e| {
+            if let HelloRetryRequestExtension::Cookie(cookie) = e {
+                Some(cookie.cookie)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extensions::extension_data::supported_versions::TLS13;
+
+    fn server_hello(extensions: &[u8]) -> std::vec::Vec<u8> {
+        let mut data = std::vec![0x03, 0x03];
+        data.extend_from_slice(&[0; 32]);
+        data.extend_from_slice(&[0, 0x13, 0x01, 0]);
+        data.extend_from_slice(&u16::try_from(extensions.len()).unwrap().to_be_bytes());
+        data.extend_from_slice(extensions);
+        data
+    }
+
+    #[test]
+    fn known_extensions() {
+        // supported_versions selecting TLS 1.3
+        let data = server_hello(&[0, 43, 0, 2, 0x03, 0x04]);
+        let hello = ServerHello::parse(&mut ParseBuffer::new(&data)).unwrap();
+        assert_eq!(hello.selected_version(), Some(TLS13));
+    }
+
+    #[test]
+    fn unsolicited_extension() {
+        let data = server_hello(&[0, 43, 0, 2, 0x03, 0x04, 0x1a, 0x1a, 0, 0]);
+        assert!(matches!(
+            ServerHello::parse(&mut ParseBuffer::new(&data)),
+            Err(TlsError::UnknownExtensionType)
+        ));
+    }
+}
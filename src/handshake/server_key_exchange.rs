@@ -0,0 +1,46 @@
+use crate::TlsError;
+use crate::extensions::extension_data::supported_groups::NamedGroup;
+use crate::handshake::certificate_verify::CertificateVerifyRef;
+use crate::parse_buffer::ParseBuffer;
+
+/// The `ECCurveType` of a named curve (RFC 8422, Section 5.4).
+const NAMED_CURVE: u8 = 3;
+
+/// RFC 8422, Section 5.4.  Server Key Exchange
+///
+/// The server's ephemeral ECDH public key, signed together with both hello randoms.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ServerKeyExchange<'a> {
+    pub group: NamedGroup,
+    pub public_key: &'a [u8],
+    /// The encoded `ServerECDHParams`, which are covered by the signature.
+    pub params: &'a [u8],
+    pub signature: CertificateVerifyRef<'a>,
+}
+
+impl<'a> ServerKeyExchange<'a> {
+    pub fn parse(buf: &mut ParseBuffer<'a>) -> Result<Self, TlsError> {
+        let start = buf.offset();
+
+        if buf.read_u8()? != NAMED_CURVE {
+            return Err(TlsError::InvalidKeyShare);
+        }
+        let group = NamedGroup::parse(buf).map_err(|_| TlsError::InvalidKeyShare)?;
+        let len = buf.read_u8()?;
+        let public_key = buf
+            .slice(len as usize)
+            .map_err(|_| TlsError::InvalidKeyShare)?
+            .as_slice();
+
+        let params = &buf.as_slice()[start..buf.offset()];
+        let signature = CertificateVerifyRef::parse(buf)?;
+
+        Ok(Self {
+            group,
+            public_key,
+            params,
+            signature,
+        })
+    }
+}
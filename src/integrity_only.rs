@@ -0,0 +1,162 @@
+//! Integrity-only record protection, for the cipher suites of [RFC 9150].
+//!
+//! Records are authenticated with an HMAC but sent in plaintext, for deployments where the
+//! control traffic must remain inspectable. The HMAC is wrapped in the AEAD interface used by
+//! the other cipher suites, so that records are protected the same way: the "ciphertext" is
+//! the plaintext itself, and the HMAC is the tag.
+//!
+//! The HMAC key and the per-record nonce are derived by the key schedule like the AEAD key and
+//! nonce, with `key_length` and `iv_length` set to the length of the hash.
+//!
+//! [RFC 9150]: https://www.rfc-editor.org/rfc/rfc9150
+
+use aes_gcm::aead::generic_array::GenericArray;
+use aes_gcm::aead::{AeadCore, AeadInPlace, Error, Key, KeyInit, KeySizeUser, Nonce, Tag};
+use digest::core_api::BlockSizeUser;
+use digest::{Digest, OutputSizeUser};
+use hmac::{Mac, SimpleHmac};
+use typenum::U0;
+use zeroize::Zeroize;
+
+/// The record protection of the integrity-only cipher suites, an HMAC with `Hash` presented as
+/// an AEAD.
+///
+/// Following [RFC 9150, Section 5](https://www.rfc-editor.org/rfc/rfc9150#section-5), the tag
+/// of a record is the HMAC of the per-record nonce, the additional data (the record header) and
+/// the `TLSInnerPlaintext`, keyed with the traffic `write_key`.
+pub struct HmacIntegrity<Hash: OutputSizeUser> {
+    key: GenericArray<u8, Hash::OutputSize>,
+}
+
+impl<Hash> HmacIntegrity<Hash>
+where
+    Hash: Digest + BlockSizeUser,
+{
+    fn mac(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        record: &[u8],
+    ) -> Result<SimpleHmac<Hash>, Error> {
+        let mut mac = <SimpleHmac<Hash> as Mac>::new_from_slice(&self.key).map_err(|_| Error)?;
+        mac.update(nonce);
+        mac.update(associated_data);
+        mac.update(record);
+        Ok(mac)
+    }
+}
+
+impl<Hash: OutputSizeUser> KeySizeUser for HmacIntegrity<Hash> {
+    type KeySize = Hash::OutputSize;
+}
+
+impl<Hash: OutputSizeUser> KeyInit for HmacIntegrity<Hash> {
+    fn new(key: &Key<Self>) -> Self {
+        Self { key: key.clone() }
+    }
+}
+
+impl<Hash: OutputSizeUser> AeadCore for HmacIntegrity<Hash> {
+    type NonceSize = Hash::OutputSize;
+    type TagSize = Hash::OutputSize;
+    type CiphertextOverhead = U0;
+}
+
+impl<Hash> AeadInPlace for HmacIntegrity<Hash>
+where
+    Hash: Digest + BlockSizeUser,
+{
+    fn encrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<Tag<Self>, Error> {
+        let mac = self.mac(nonce, associated_data, buffer)?;
+        Ok(mac.finalize().into_bytes())
+    }
+
+    fn decrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &Tag<Self>,
+    ) -> Result<(), Error> {
+        let mac = self.mac(nonce, associated_data, buffer)?;
+        mac.verify(tag).map_err(|_| Error)
+    }
+}
+
+impl<Hash: OutputSizeUser> Drop for HmacIntegrity<Hash> {
+    fn drop(&mut self) {
+        self.key.as_mut_slice().zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sha256Sha256;
+    use crate::buffer::CryptoBuffer;
+    use crate::connection::encrypt;
+    use crate::key_schedule::KeySchedule;
+    use sha2::Sha256;
+
+    #[test]
+    fn plaintext_is_authenticated() {
+        let cipher = HmacIntegrity::<Sha256>::new(&[1; 32].into());
+        let nonce = [2; 32].into();
+        let header = [23, 3, 3, 0, 37];
+
+        let mut record = *b"hello";
+        let tag = cipher
+            .encrypt_in_place_detached(&nonce, &header, &mut record)
+            .unwrap();
+        assert_eq!(&record, b"hello");
+
+        let mut expected = <SimpleHmac<Sha256> as Mac>::new_from_slice(&[1; 32]).unwrap();
+        expected.update(&[2; 32]);
+        expected.update(&header);
+        expected.update(b"hello");
+        assert_eq!(tag, expected.finalize().into_bytes());
+
+        cipher
+            .decrypt_in_place_detached(&nonce, &header, &mut record, &tag)
+            .unwrap();
+
+        record[0] ^= 1;
+        assert!(
+            cipher
+                .decrypt_in_place_detached(&nonce, &header, &mut record, &tag)
+                .is_err()
+        );
+    }
+
+    /// Records protected with the client handshake traffic secret of a key exchange with the
+    /// shared secret `11 11 .. 11` and an empty transcript, computed independently following
+    /// RFC 8446, Section 7 and RFC 9150, Section 5.
+    #[test]
+    fn protected_record_known_answer() {
+        let mut key_schedule = KeySchedule::<Sha256Sha256>::new();
+        key_schedule.initialize_early_secret(None).unwrap();
+        key_schedule
+            .initialize_handshake_secret(&[0x11; 32])
+            .unwrap();
+
+        let expected: [&[u8]; 2] = [
+            b"hello\x17\x2e\x99\x1f\x41\x54\xf2\xf3\xfe\x65\xa0\xa1\xb7\x08\xb2\x31\xfd\
+              \x01\x2c\x61\x77\x5f\x88\x66\xe2\xc2\xc8\xc8\x0e\xb0\x94\x83\x19",
+            b"hello\x17\xd3\x55\x99\xe6\x20\x2b\xee\xfc\x5c\xe7\x48\x27\x6b\x02\x05\x82\
+              \x7b\x77\xca\x45\x25\x6a\x74\xae\x8f\x9c\xa4\x52\x6f\x7d\x6c\x84",
+        ];
+        for expected in expected {
+            let mut record = [0; 64];
+            record[..6].copy_from_slice(b"hello\x17");
+            let mut buf = CryptoBuffer::wrap_with_pos(&mut record, 6);
+            encrypt(key_schedule.write_state(), &mut buf).unwrap();
+            assert_eq!(buf.as_slice(), expected);
+            key_schedule.write_state().increment_counter();
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! The ephemeral key exchange of the TLS 1.3 handshake, over the named groups for which key
+//! shares can be generated.
+
+use heapless::Vec;
+use rand_core::CryptoRngCore;
+use zeroize::Zeroize;
+
+use crate::TlsError;
+use crate::extensions::extension_data::supported_groups::NamedGroup;
+
+/// The largest public key of the supported groups, an uncompressed point on a 256-bit curve,
+/// or on a 512-bit curve with brainpool.
+pub(crate) const MAX_PUBLIC_KEY_LEN: usize = if cfg!(feature = "brainpool") { 129 } else { 65 };
+
+/// The largest shared secret of the supported groups, 64 bytes with brainpool or 56 bytes with
+/// X448.
+const MAX_SHARED_SECRET_LEN: usize = if cfg!(feature = "brainpool") {
+    64
+} else if cfg!(feature = "x448") {
+    56
+} else {
+    32
+};
+
+/// The ephemeral secret of a key share.
+pub(crate) enum EphemeralSecret {
+    Secp256r1(p256::ecdh::EphemeralSecret),
+    /// The SM2 curve of RFC 8998.
+    #[cfg(feature = "shangmi")]
+    CurveSm2(sm2::elliptic_curve::ecdh::EphemeralSecret<sm2::Sm2>),
+    /// The brainpool curves of RFC 8734.
+    #[cfg(feature = "brainpool")]
+    BrainpoolP256r1(bp256::r1::SecretKey),
+    #[cfg(feature = "brainpool")]
+    BrainpoolP384r1(bp384::r1::SecretKey),
+    #[cfg(feature = "brainpool")]
+    BrainpoolP512r1(crate::bp512::SecretKey),
+    /// The X448 function of RFC 7748.
+    #[cfg(feature = "x448")]
+    X448(ed448_goldilocks_plus::x448::ReusableSecret),
+}
+
+impl EphemeralSecret {
+    /// The length of the public key of `group`, or `None` if key shares cannot be generated
+    /// for it.
+    pub(crate) const fn public_key_len(group: NamedGroup) -> Option<usize> {
+        match group {
+            NamedGroup::Secp256r1 => Some(65),
+            #[cfg(feature = "shangmi")]
+            NamedGroup::CurveSm2 => Some(65),
+            #[cfg(feature = "brainpool")]
+            NamedGroup::BrainpoolP256r1Tls13 => Some(65),
+            #[cfg(feature = "brainpool")]
+            NamedGroup::BrainpoolP384r1Tls13 => Some(97),
+            #[cfg(feature = "brainpool")]
+            NamedGroup::BrainpoolP512r1Tls13 => Some(129),
+            #[cfg(feature = "x448")]
+            NamedGroup::X448 => Some(56),
+            _ => None,
+        }
+    }
+
+    /// Whether key shares can be generated for `group`.
+    pub(crate) const fn supports(group: NamedGroup) -> bool {
+        Self::public_key_len(group).is_some()
+    }
+
+    /// Generate a secret for `group`, if supported.
+    pub(crate) fn generate(group: NamedGroup, rng: &mut impl CryptoRngCore) -> Option<Self> {
+        match group {
+            NamedGroup::Secp256r1 => {
+                Some(Self::Secp256r1(p256::ecdh::EphemeralSecret::random(rng)))
+            }
+            #[cfg(feature = "shangmi")]
+            NamedGroup::CurveSm2 => Some(Self::CurveSm2(
+                sm2::elliptic_curve::ecdh::EphemeralSecret::random(rng),
+            )),
+            #[cfg(feature = "brainpool")]
+            NamedGroup::BrainpoolP256r1Tls13 => {
+                Some(Self::BrainpoolP256r1(brainpool::generate(rng)))
+            }
+            #[cfg(feature = "brainpool")]
+            NamedGroup::BrainpoolP384r1Tls13 => {
+                Some(Self::BrainpoolP384r1(brainpool::generate(rng)))
+            }
+            #[cfg(feature = "brainpool")]
+            NamedGroup::BrainpoolP512r1Tls13 => {
+                Some(Self::BrainpoolP512r1(brainpool::generate(rng)))
+            }
+            #[cfg(feature = "x448")]
+            NamedGroup::X448 => {
+                let mut bytes = [0; 56];
+                rng.fill_bytes(&mut bytes);
+                let secret = ed448_goldilocks_plus::x448::ReusableSecret::from(bytes);
+                bytes.zeroize();
+                Some(Self::X448(secret))
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn group(&self) -> NamedGroup {
+        match self {
+            Self::Secp256r1(_) => NamedGroup::Secp256r1,
+            #[cfg(feature = "shangmi")]
+            Self::CurveSm2(_) => NamedGroup::CurveSm2,
+            #[cfg(feature = "brainpool")]
+            Self::BrainpoolP256r1(_) => NamedGroup::BrainpoolP256r1Tls13,
+            #[cfg(feature = "brainpool")]
+            Self::BrainpoolP384r1(_) => NamedGroup::BrainpoolP384r1Tls13,
+            #[cfg(feature = "brainpool")]
+            Self::BrainpoolP512r1(_) => NamedGroup::BrainpoolP512r1Tls13,
+            #[cfg(feature = "x448")]
+            Self::X448(_) => NamedGroup::X448,
+        }
+    }
+
+    /// The public key sent in the key share, an uncompressed SEC1 point for the elliptic
+    /// curves, or the u-coordinate for X448.
+    pub(crate) fn public_key(&self) -> Vec<u8, MAX_PUBLIC_KEY_LEN> {
+        let public_key = match self {
+            Self::Secp256r1(secret) => {
+                Vec::from_slice(p256::EncodedPoint::from(secret.public_key()).as_bytes())
+            }
+            #[cfg(feature = "shangmi")]
+            Self::CurveSm2(secret) => {
+                Vec::from_slice(sm2::EncodedPoint::from(secret.public_key()).as_bytes())
+            }
+            #[cfg(feature = "brainpool")]
+            Self::BrainpoolP256r1(secret) => brainpool::public_key(secret),
+            #[cfg(feature = "brainpool")]
+            Self::BrainpoolP384r1(secret) => brainpool::public_key(secret),
+            #[cfg(feature = "brainpool")]
+            Self::BrainpoolP512r1(secret) => brainpool::public_key(secret),
+            #[cfg(feature = "x448")]
+            Self::X448(secret) => {
+                Vec::from_slice(ed448_goldilocks_plus::x448::PublicKey::from(secret).as_bytes())
+            }
+        };
+        unwrap!(public_key.ok())
+    }
+
+    /// Compute the shared secret with the public key of the peer's key share.
+    pub(crate) fn diffie_hellman(&self, peer_public_key: &[u8]) -> Result<SharedSecret, TlsError> {
+        match self {
+            Self::Secp256r1(secret) => {
+                let public_key = p256::PublicKey::from_sec1_bytes(peer_public_key)
+                    .map_err(|_| TlsError::InvalidKeyShare)?;
+                SharedSecret::new(secret.diffie_hellman(&public_key).raw_secret_bytes())
+            }
+            #[cfg(feature = "shangmi")]
+            Self::CurveSm2(secret) => {
+                let public_key = sm2::PublicKey::from_sec1_bytes(peer_public_key)
+                    .map_err(|_| TlsError::InvalidKeyShare)?;
+                SharedSecret::new(secret.diffie_hellman(&public_key).raw_secret_bytes())
+            }
+            #[cfg(feature = "brainpool")]
+            Self::BrainpoolP256r1(secret) => brainpool::diffie_hellman(secret, peer_public_key),
+            #[cfg(feature = "brainpool")]
+            Self::BrainpoolP384r1(secret) => brainpool::diffie_hellman(secret, peer_public_key),
+            #[cfg(feature = "brainpool")]
+            Self::BrainpoolP512r1(secret) => brainpool::diffie_hellman(secret, peer_public_key),
+            #[cfg(feature = "x448")]
+            Self::X448(secret) => {
+                let public_key: [u8; 56] = peer_public_key
+                    .try_into()
+                    .map_err(|_| TlsError::InvalidKeyShare)?;
+                let shared = secret.diffie_hellman(&public_key.into());
+                // RFC 8446, Section 7.4.2: abort on the all-zero value of a low-order point
+                if !shared.was_contributory() {
+                    return Err(TlsError::InvalidKeyShare);
+                }
+                SharedSecret::new(shared.as_bytes())
+            }
+        }
+    }
+}
+
+/// The brainpool curves are only implemented on a newer elliptic-curve release than the other
+/// curves, with an incompatible `rand_core`.
+#[cfg(feature = "brainpool")]
+mod brainpool {
+    use bp256::elliptic_curve::point::AffineCoordinates;
+    use bp256::elliptic_curve::sec1::{FromSec1Point, ModulusSize, ToSec1Point};
+    use bp256::elliptic_curve::{
+        AffinePoint, CurveArithmetic, CurveGroup, FieldBytes, FieldBytesSize, PublicKey, SecretKey,
+    };
+    use heapless::{CapacityError, Vec};
+    use rand_core::CryptoRngCore;
+    use zeroize::Zeroize;
+
+    use super::{MAX_PUBLIC_KEY_LEN, SharedSecret};
+    use crate::TlsError;
+
+    /// Generate a secret key by rejection sampling, drawing the scalars from `rng`.
+    pub(super) fn generate<C: CurveArithmetic>(rng: &mut impl CryptoRngCore) -> SecretKey<C> {
+        let mut bytes = FieldBytes::<C>::default();
+        loop {
+            rng.fill_bytes(&mut bytes);
+            let secret = SecretKey::from_bytes(&bytes);
+            bytes.as_mut_slice().zeroize();
+            if let Ok(secret) = secret {
+                return secret;
+            }
+        }
+    }
+
+    pub(super) fn public_key<C>(
+        secret: &SecretKey<C>,
+    ) -> Result<Vec<u8, MAX_PUBLIC_KEY_LEN>, CapacityError>
+    where
+        C: CurveArithmetic,
+        AffinePoint<C>: FromSec1Point<C> + ToSec1Point<C>,
+        FieldBytesSize<C>: ModulusSize,
+    {
+        Vec::from_slice(secret.public_key().to_sec1_point(false).as_bytes())
+    }
+
+    pub(super) fn diffie_hellman<C>(
+        secret: &SecretKey<C>,
+        peer_public_key: &[u8],
+    ) -> Result<SharedSecret, TlsError>
+    where
+        C: CurveArithmetic,
+        AffinePoint<C>: FromSec1Point<C> + ToSec1Point<C>,
+        FieldBytesSize<C>: ModulusSize,
+    {
+        let public_key = PublicKey::<C>::from_sec1_bytes(peer_public_key)
+            .map_err(|_| TlsError::InvalidKeyShare)?;
+        let point = (public_key.to_projective() * *secret.to_nonzero_scalar()).to_affine();
+        SharedSecret::new(&point.x())
+    }
+}
+
+/// The shared secret of a key exchange, the input of the handshake secret. Zeroized when
+/// dropped.
+pub(crate) struct SharedSecret(Vec<u8, MAX_SHARED_SECRET_LEN>);
+
+impl SharedSecret {
+    fn new(secret: &[u8]) -> Result<Self, TlsError> {
+        Vec::from_slice(secret)
+            .map(Self)
+            .map_err(|_| TlsError::InvalidKeyShare)
+    }
+
+    pub(crate) fn raw_secret_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Drop for SharedSecret {
+    fn drop(&mut self) {
+        self.0.as_mut_slice().zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    fn agree(group: NamedGroup) {
+        let client = EphemeralSecret::generate(group, &mut OsRng).unwrap();
+        let server = EphemeralSecret::generate(group, &mut OsRng).unwrap();
+        assert_eq!(client.group(), group);
+        assert_eq!(
+            client.public_key().len(),
+            EphemeralSecret::public_key_len(group).unwrap()
+        );
+
+        let client_shared = client.diffie_hellman(&server.public_key()).unwrap();
+        let server_shared = server.diffie_hellman(&client.public_key()).unwrap();
+        assert_eq!(
+            client_shared.raw_secret_bytes(),
+            server_shared.raw_secret_bytes()
+        );
+    }
+
+    #[test]
+    fn secp256r1() {
+        agree(NamedGroup::Secp256r1);
+    }
+
+    #[cfg(feature = "shangmi")]
+    #[test]
+    fn curve_sm2() {
+        agree(NamedGroup::CurveSm2);
+    }
+
+    #[cfg(feature = "brainpool")]
+    #[test]
+    fn brainpool_p256r1() {
+        agree(NamedGroup::BrainpoolP256r1Tls13);
+    }
+
+    #[cfg(feature = "brainpool")]
+    #[test]
+    fn brainpool_p384r1() {
+        agree(NamedGroup::BrainpoolP384r1Tls13);
+    }
+
+    #[cfg(feature = "brainpool")]
+    #[test]
+    fn brainpool_p512r1() {
+        agree(NamedGroup::BrainpoolP512r1Tls13);
+    }
+
+    #[cfg(feature = "x448")]
+    #[test]
+    fn x448() {
+        agree(NamedGroup::X448);
+    }
+
+    #[cfg(feature = "x448")]
+    #[test]
+    fn x448_low_order_point() {
+        let secret = EphemeralSecret::generate(NamedGroup::X448, &mut OsRng).unwrap();
+        assert!(matches!(
+            secret.diffie_hellman(&[0; 56]),
+            Err(TlsError::InvalidKeyShare)
+        ));
+        assert!(matches!(
+            secret.diffie_hellman(&[9; 32]),
+            Err(TlsError::InvalidKeyShare)
+        ));
+    }
+
+    #[test]
+    fn unsupported_group() {
+        assert!(!EphemeralSecret::supports(NamedGroup::Ffdhe2048));
+        assert!(EphemeralSecret::generate(NamedGroup::Ffdhe2048, &mut OsRng).is_none());
+    }
+
+    #[test]
+    fn invalid_public_key() {
+        let secret = EphemeralSecret::generate(NamedGroup::Secp256r1, &mut OsRng).unwrap();
+        assert!(matches!(
+            secret.diffie_hellman(&[4; 65]),
+            Err(TlsError::InvalidKeyShare)
+        ));
+    }
+}
@@ -0,0 +1,225 @@
+//! Logging of the secrets of a connection in the NSS key log format, the format of the files
+//! named by `SSLKEYLOGFILE`, so that captures of its traffic can be decrypted by tools such as
+//! Wireshark.
+//!
+//! Anyone holding the log can decrypt the connection, so it is only meant for debugging.
+
+#[cfg(feature = "key-log")]
+use heapless::Vec;
+#[cfg(feature = "key-log")]
+use zeroize::Zeroize;
+
+use crate::handshake::Random;
+
+/// Receives the secrets of a connection as they are derived, see
+/// [`TlsContext::with_key_log`](crate::TlsContext::with_key_log).
+#[cfg(feature = "key-log")]
+pub trait KeyLog {
+    /// Log the secret named `label` in the NSS key log format, such as
+    /// `CLIENT_HANDSHAKE_TRAFFIC_SECRET`, of the connection whose `ClientHello` carried
+    /// `client_random`.
+    fn log(&mut self, label: &str, client_random: &[u8], secret: &[u8]);
+}
+
+/// A borrowed [`KeyLog`] in the [`TlsContext`](crate::TlsContext).
+#[cfg(feature = "key-log")]
+pub(crate) struct KeyLogRef<'a>(pub(crate) &'a mut (dyn KeyLog + Send));
+
+#[cfg(feature = "key-log")]
+impl core::fmt::Debug for KeyLogRef<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("KeyLog")
+    }
+}
+
+#[cfg(all(feature = "key-log", feature = "defmt"))]
+impl defmt::Format for KeyLogRef<'_> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "KeyLog");
+    }
+}
+
+/// Writes the secrets as lines of the NSS key log format: the label, the client random and the
+/// secret, hex encoded and separated by spaces.
+#[cfg(all(feature = "key-log", feature = "std"))]
+pub struct NssKeyLog<W: std::io::Write> {
+    writer: W,
+}
+
+#[cfg(all(feature = "key-log", feature = "std"))]
+impl<W: std::io::Write> NssKeyLog<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(all(feature = "key-log", feature = "std"))]
+impl NssKeyLog<std::fs::File> {
+    /// Append to the file named by the `SSLKEYLOGFILE` environment variable, or `None` if it
+    /// is not set or cannot be opened.
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var_os("SSLKEYLOGFILE")?;
+        let file = std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .inspect_err(|_| warn!("Failed to open SSLKEYLOGFILE"))
+            .ok()?;
+        Some(Self::new(file))
+    }
+}
+
+#[cfg(all(feature = "key-log", feature = "std"))]
+impl<W: std::io::Write> KeyLog for NssKeyLog<W> {
+    fn log(&mut self, label: &str, client_random: &[u8], secret: &[u8]) {
+        let result = writeln!(
+            self.writer,
+            "{label} {} {}",
+            Hex(client_random),
+            Hex(secret)
+        )
+        .and_then(|()| self.writer.flush());
+        if result.is_err() {
+            warn!("Failed to write the key log");
+        }
+    }
+}
+
+#[cfg(all(feature = "key-log", feature = "std"))]
+struct Hex<'a>(&'a [u8]);
+
+#[cfg(all(feature = "key-log", feature = "std"))]
+impl core::fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+/// The name of the secret derived with the HKDF or PRF `label` in the NSS key log format.
+#[cfg(feature = "key-log")]
+fn nss_label(label: &[u8]) -> Option<&'static str> {
+    match label {
+        b"c hs traffic" => Some("CLIENT_HANDSHAKE_TRAFFIC_SECRET"),
+        b"s hs traffic" => Some("SERVER_HANDSHAKE_TRAFFIC_SECRET"),
+        b"c ap traffic" => Some("CLIENT_TRAFFIC_SECRET_0"),
+        b"s ap traffic" => Some("SERVER_TRAFFIC_SECRET_0"),
+        b"exp master" => Some("EXPORTER_SECRET"),
+        // TLS 1.2 only has the master secret
+        b"extended master secret" => Some("CLIENT_RANDOM"),
+        _ => None,
+    }
+}
+
+/// The most secrets derived between two calls to [`PendingSecrets::flush`], those of a whole
+/// handshake.
+#[cfg(feature = "key-log")]
+const MAX_PENDING_SECRETS: usize = 5;
+
+/// The longest secret, the SHA-384 traffic secrets and the TLS 1.2 master secret.
+#[cfg(feature = "key-log")]
+const MAX_SECRET_LEN: usize = 48;
+
+/// The secrets derived by the key schedule that were not logged yet. Zeroized when logged or
+/// dropped.
+#[cfg(feature = "key-log")]
+pub(crate) struct PendingSecrets {
+    client_random: Random,
+    secrets: Vec<(&'static str, Vec<u8, MAX_SECRET_LEN>), MAX_PENDING_SECRETS>,
+}
+
+#[cfg(feature = "key-log")]
+impl PendingSecrets {
+    pub(crate) const fn new() -> Self {
+        Self {
+            client_random: [0; 32],
+            secrets: Vec::new(),
+        }
+    }
+
+    pub(crate) fn set_client_random(&mut self, client_random: Random) {
+        self.client_random = client_random;
+    }
+
+    /// Queue the `secret` derived with `label`, if it is one of the NSS key log.
+    pub(crate) fn push(&mut self, label: &[u8], secret: &[u8]) {
+        let (Some(label), Ok(secret)) = (nss_label(label), Vec::from_slice(secret)) else {
+            return;
+        };
+        if let Err((_, mut secret)) = self.secrets.push((label, secret)) {
+            warn!("Too many secrets to log");
+            secret.as_mut_slice().zeroize();
+        }
+    }
+
+    /// Pass the queued secrets to `key_log`, if any, and wipe them.
+    pub(crate) fn flush(&mut self, mut key_log: Option<&mut dyn KeyLog>) {
+        for (label, secret) in &mut self.secrets {
+            if let Some(key_log) = key_log.as_deref_mut() {
+                key_log.log(label, &self.client_random, secret);
+            }
+            secret.as_mut_slice().zeroize();
+        }
+        self.secrets.clear();
+    }
+}
+
+#[cfg(feature = "key-log")]
+impl Drop for PendingSecrets {
+    fn drop(&mut self) {
+        self.flush(None);
+    }
+}
+
+/// Without the `key-log` feature, secrets are never queued.
+#[cfg(not(feature = "key-log"))]
+pub(crate) struct PendingSecrets;
+
+#[cfg(not(feature = "key-log"))]
+#[allow(clippy::unused_self)]
+impl PendingSecrets {
+    pub(crate) const fn new() -> Self {
+        Self
+    }
+
+    #[inline]
+    pub(crate) fn set_client_random(&mut self, _client_random: Random) {}
+
+    #[inline]
+    pub(crate) fn push(&mut self, _label: &[u8], _secret: &[u8]) {}
+}
+
+#[cfg(all(test, feature = "key-log"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nss_key_log_format() {
+        let mut pending = PendingSecrets::new();
+        pending.set_client_random([0xab; 32]);
+        pending.push(b"c hs traffic", &[1, 2, 3]);
+        pending.push(b"derived", &[4, 5, 6]);
+        pending.push(b"s ap traffic", &[0xff]);
+
+        let mut key_log = NssKeyLog::new(std::vec::Vec::new());
+        pending.flush(Some(&mut key_log));
+        let log = String::from_utf8(key_log.into_inner()).unwrap();
+        let random = "ab".repeat(32);
+        assert_eq!(
+            log,
+            format!(
+                "CLIENT_HANDSHAKE_TRAFFIC_SECRET {random} 010203\n\
+                 SERVER_TRAFFIC_SECRET_0 {random} ff\n"
+            )
+        );
+
+        // The secrets are only logged once
+        let mut key_log = NssKeyLog::new(std::vec::Vec::new());
+        pending.flush(Some(&mut key_log));
+        assert!(key_log.into_inner().is_empty());
+    }
+}
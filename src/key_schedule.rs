@@ -1,11 +1,16 @@
 use crate::handshake::binder::PskBinder;
 use crate::handshake::finished::Finished;
+use crate::handshake::{HandshakeType, Random};
+#[cfg(feature = "key-log")]
+use crate::key_log::KeyLog;
+use crate::key_log::PendingSecrets;
 use crate::{TlsError, config::TlsCipherSuite};
 use digest::OutputSizeUser;
 use digest::generic_array::ArrayLength;
 use hmac::{Mac, SimpleHmac};
 use sha2::Digest;
 use sha2::digest::generic_array::{GenericArray, typenum::Unsigned};
+use zeroize::Zeroize;
 
 pub type HashOutputSize<CipherSuite> =
     <<CipherSuite as TlsCipherSuite>::Hash as OutputSizeUser>::OutputSize;
@@ -25,20 +30,42 @@ where
     CipherSuite: TlsCipherSuite,
 {
     Uninitialized,
-    Initialized(Hkdf<CipherSuite>),
+    Initialized(Hkdf<CipherSuite>, &'static [u8]),
 }
 
+/// Prefix of the HKDF labels of TLS 1.3.
+const TLS13_LABEL_PREFIX: &[u8] = b"tls13 ";
+
+/// Prefix of the HKDF labels of DTLS 1.3 (RFC 9147, Section 5.9).
+const DTLS13_LABEL_PREFIX: &[u8] = b"dtls13";
+
+/// The longest `HkdfLabel` of an exporter: the length, then the label and the context, each
+/// at most 255 bytes and prefixed with their length.
+const MAX_EXPORTER_HKDF_LABEL_LEN: usize = 2 + 1 + 255 + 1 + 255;
+
 impl<CipherSuite> Secret<CipherSuite>
 where
     CipherSuite: TlsCipherSuite,
 {
-    fn replace(&mut self, secret: Hkdf<CipherSuite>) {
-        *self = Self::Initialized(secret);
+    fn replace(&mut self, secret: Hkdf<CipherSuite>, label_prefix: &'static [u8]) {
+        *self = Self::Initialized(secret, label_prefix);
+    }
+
+    /// Overwrite the HKDF state with one derived from an all-zero key, and uninitialize.
+    fn zeroize(&mut self) {
+        if let Secret::Initialized(secret, _) = self
+            && let Ok(zeroed) = Hkdf::<CipherSuite>::from_prk(&HashArray::<CipherSuite>::default())
+        {
+            // SAFETY: `secret` is valid for writes, and the state it holds owns no resources,
+            // so it does not need to be dropped
+            unsafe { core::ptr::write_volatile(secret, zeroed) };
+        }
+        *self = Secret::Uninitialized;
     }
 
-    fn as_ref(&self) -> Result<&Hkdf<CipherSuite>, TlsError> {
+    fn as_ref(&self) -> Result<(&Hkdf<CipherSuite>, &'static [u8]), TlsError> {
         match self {
-            Secret::Initialized(secret) => Ok(secret),
+            Secret::Initialized(secret, label_prefix) => Ok((secret, label_prefix)),
             Secret::Uninitialized => Err(TlsError::InternalError),
         }
     }
@@ -49,17 +76,18 @@ where
         context_type: ContextType<CipherSuite>,
     ) -> Result<GenericArray<u8, N>, TlsError> {
         //info!("make label {:?} {}", label, len);
+        let (secret, label_prefix) = self.as_ref()?;
         let mut hkdf_label = heapless_typenum::Vec::<u8, LabelBufferSize<CipherSuite>>::new();
         hkdf_label
             .extend_from_slice(&N::to_u16().to_be_bytes())
             .map_err(|()| TlsError::InternalError)?;
 
-        let label_len = 6 + label.len() as u8;
+        let label_len = (label_prefix.len() + label.len()) as u8;
         hkdf_label
             .extend_from_slice(&label_len.to_be_bytes())
             .map_err(|()| TlsError::InternalError)?;
         hkdf_label
-            .extend_from_slice(b"tls13 ")
+            .extend_from_slice(label_prefix)
             .map_err(|()| TlsError::InternalError)?;
         hkdf_label
             .extend_from_slice(label)
@@ -81,12 +109,44 @@ where
 
         let mut okm = GenericArray::default();
         //info!("label {:x?}", label);
-        self.as_ref()?
+        secret
             .expand(&hkdf_label, &mut okm)
             .map_err(|_| TlsError::CryptoError)?;
         //info!("expand {:x?}", okm);
         Ok(okm)
     }
+
+    /// `HKDF-Expand-Label` into `okm`, for labels and output lengths chosen by the application.
+    fn expand_label_into(
+        &self,
+        label: &[u8],
+        context: &[u8],
+        okm: &mut [u8],
+    ) -> Result<(), TlsError> {
+        let (secret, label_prefix) = self.as_ref()?;
+        let okm_len = u16::try_from(okm.len()).map_err(|_| TlsError::CryptoError)?;
+        let label_len =
+            u8::try_from(label_prefix.len() + label.len()).map_err(|_| TlsError::EncodeError)?;
+        let context_len = u8::try_from(context.len()).map_err(|_| TlsError::EncodeError)?;
+
+        let mut hkdf_label = heapless::Vec::<u8, MAX_EXPORTER_HKDF_LABEL_LEN>::new();
+        for part in [
+            &okm_len.to_be_bytes()[..],
+            &[label_len],
+            label_prefix,
+            label,
+            &[context_len],
+            context,
+        ] {
+            hkdf_label
+                .extend_from_slice(part)
+                .map_err(|_| TlsError::InternalError)?;
+        }
+
+        secret
+            .expand(&hkdf_label, okm)
+            .map_err(|_| TlsError::CryptoError)
+    }
 }
 
 pub struct SharedState<CipherSuite>
@@ -95,6 +155,7 @@ where
 {
     secret: HashArray<CipherSuite>,
     hkdf: Secret<CipherSuite>,
+    label_prefix: &'static [u8],
 }
 
 impl<CipherSuite> SharedState<CipherSuite>
@@ -105,12 +166,13 @@ where
         Self {
             secret: GenericArray::default(),
             hkdf: Secret::Uninitialized,
+            label_prefix: TLS13_LABEL_PREFIX,
         }
     }
 
     fn initialize(&mut self, ikm: &[u8]) {
         let (secret, hkdf) = Hkdf::<CipherSuite>::extract(Some(self.secret.as_ref()), ikm);
-        self.hkdf.replace(hkdf);
+        self.hkdf.replace(hkdf, self.label_prefix);
         self.secret = secret;
     }
 
@@ -127,6 +189,22 @@ where
         self.secret = self.derive_secret(b"derived", ContextType::empty_hash())?;
         Ok(())
     }
+
+    fn zeroize(&mut self) {
+        self.secret.as_mut_slice().zeroize();
+        self.hkdf.zeroize();
+    }
+}
+
+/// How records are handled once the server picked TLS 1.2.
+#[cfg(feature = "tls12")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) enum Tls12Records {
+    /// Records are in plaintext, and handshake records are passed on undecoded.
+    Plaintext,
+    /// Records are protected with the TLS 1.2 AEAD construction (RFC 5288).
+    Protected,
 }
 
 pub(crate) struct KeyScheduleState<CipherSuite>
@@ -137,6 +215,8 @@ where
     counter: u64,
     key: KeyArray<CipherSuite>,
     iv: IvArray<CipherSuite>,
+    #[cfg(feature = "tls12")]
+    tls12: Option<Tls12Records>,
 }
 
 impl<CipherSuite> KeyScheduleState<CipherSuite>
@@ -149,6 +229,8 @@ where
             counter: 0,
             key: KeyArray::<CipherSuite>::default(),
             iv: IvArray::<CipherSuite>::default(),
+            #[cfg(feature = "tls12")]
+            tls12: None,
         }
     }
 
@@ -167,17 +249,28 @@ where
         Ok(KeySchedule::<CipherSuite>::get_nonce(self.counter, iv))
     }
 
+    /// The DTLS 1.3 record number encryption key (RFC 9147, Section 4.2.3).
+    pub fn get_sn_key(&self) -> Result<KeyArray<CipherSuite>, TlsError> {
+        self.traffic_secret
+            .make_expanded_hkdf_label(b"sn", ContextType::None)
+    }
+
+    /// Derive the traffic secret with `label`, and the keys from it. The secret is returned to
+    /// be logged.
     fn calculate_traffic_secret(
         &mut self,
         label: &[u8],
         shared: &mut SharedState<CipherSuite>,
         transcript_hash: &CipherSuite::Hash,
-    ) -> Result<(), TlsError> {
+    ) -> Result<HashArray<CipherSuite>, TlsError> {
         let secret = shared.derive_secret(label, ContextType::transcript_hash(transcript_hash))?;
         let traffic_secret =
             Hkdf::<CipherSuite>::from_prk(&secret).map_err(|_| TlsError::InternalError)?;
 
-        self.traffic_secret.replace(traffic_secret);
+        self.traffic_secret
+            .replace(traffic_secret, shared.label_prefix);
+        // The lengths are those of the cipher suite: for the integrity-only suites of RFC 9150,
+        // the HMAC key and the nonce are as long as the hash
         self.key = self
             .traffic_secret
             .make_expanded_hkdf_label(b"key", ContextType::None)?;
@@ -185,12 +278,35 @@ where
             .traffic_secret
             .make_expanded_hkdf_label(b"iv", ContextType::None)?;
         self.counter = 0;
-        Ok(())
+        Ok(secret)
     }
 
     pub fn increment_counter(&mut self) {
         self.counter = unwrap!(self.counter.checked_add(1));
     }
+
+    fn zeroize(&mut self) {
+        self.traffic_secret.zeroize();
+        self.key.as_mut_slice().zeroize();
+        self.iv.as_mut_slice().zeroize();
+    }
+
+    #[cfg(feature = "tls12")]
+    fn set_tls12_records(&mut self, records: Option<Tls12Records>) {
+        if records == Some(Tls12Records::Protected) {
+            self.counter = 0;
+        }
+        self.tls12 = records;
+    }
+
+    /// Install the TLS 1.2 key and implicit nonce part (`salt`). The IV is the salt followed by
+    /// zeroes, so that [`Self::get_nonce`] starts with the salt and ends with the sequence number.
+    #[cfg(feature = "tls12")]
+    fn set_tls12_keys(&mut self, key: &[u8], salt: &[u8]) {
+        self.key.copy_from_slice(key);
+        self.iv.fill(0);
+        self.iv[..salt.len()].copy_from_slice(salt);
+    }
 }
 
 enum ContextType<CipherSuite>
@@ -225,6 +341,9 @@ where
     shared: SharedState<CipherSuite>,
     client_state: WriteKeySchedule<CipherSuite>,
     server_state: ReadKeySchedule<CipherSuite>,
+    is_server: bool,
+    exporter_secret: Secret<CipherSuite>,
+    pending_secrets: PendingSecrets,
 }
 
 impl<CipherSuite> KeySchedule<CipherSuite>
@@ -242,17 +361,87 @@ where
                 state: KeyScheduleState::new(),
                 transcript_hash: <CipherSuite::Hash as Digest>::new(),
             },
+            is_server: false,
+            exporter_secret: Secret::Uninitialized,
+            pending_secrets: PendingSecrets::new(),
         }
     }
 
+    /// Create a key schedule for the server side of a connection.
+    ///
+    /// The write state then uses the server traffic secrets and the read state the client
+    /// traffic secrets, so that [`Self::create_finished`] creates the server `Finished`
+    /// and [`ReadKeySchedule::verify_finished`] verifies the client `Finished`.
+    pub fn new_server() -> Self {
+        Self {
+            is_server: true,
+            ..Self::new()
+        }
+    }
+
+    /// Wipe the secrets and return to the initial state, for a new handshake.
+    pub(crate) fn reset(&mut self) {
+        self.shared.zeroize();
+        self.client_state.state.zeroize();
+        self.client_state.binder_key.zeroize();
+        self.server_state.state.zeroize();
+        self.exporter_secret.zeroize();
+
+        let label_prefix = self.shared.label_prefix;
+        *self = Self {
+            is_server: self.is_server,
+            ..Self::new()
+        };
+        self.shared.label_prefix = label_prefix;
+    }
+
+    /// Derive the secrets with the DTLS 1.3 labels instead of the TLS 1.3 ones.
+    pub(crate) fn with_dtls_labels(mut self) -> Self {
+        self.shared.label_prefix = DTLS13_LABEL_PREFIX;
+        self
+    }
+
     pub(crate) fn transcript_hash(&mut self) -> &mut CipherSuite::Hash {
         &mut self.server_state.transcript_hash
     }
 
+    /// Set the random of the `ClientHello`, which identifies the connection in the key log.
+    pub(crate) fn set_client_random(&mut self, client_random: Random) {
+        self.pending_secrets.set_client_random(client_random);
+    }
+
+    /// Queue a `secret` derived outside of the key schedule with `label` for the key log.
+    #[cfg(feature = "tls12")]
+    pub(crate) fn log_secret(&mut self, label: &[u8], secret: &[u8]) {
+        self.pending_secrets.push(label, secret);
+    }
+
+    /// Pass the secrets derived since the last call to `key_log`, if any.
+    #[cfg(feature = "key-log")]
+    pub(crate) fn flush_key_log(&mut self, key_log: Option<&mut dyn KeyLog>) {
+        self.pending_secrets.flush(key_log);
+    }
+
     pub(crate) fn replace_transcript_hash(&mut self, hash: CipherSuite::Hash) {
         self.server_state.transcript_hash = hash;
     }
 
+    /// Replace the first `ClientHello` in the transcript with a `message_hash` message holding
+    /// its hash, before the transcript continues with a `HelloRetryRequest` (RFC 8446,
+    /// Section 4.4.1).
+    pub(crate) fn hash_client_hello(&mut self) {
+        let client_hello_hash = self.transcript_hash().clone().finalize();
+        let mut transcript = CipherSuite::Hash::new();
+        transcript.update([
+            HandshakeType::MessageHash as u8,
+            0,
+            0,
+            client_hello_hash.len() as u8,
+        ]);
+        transcript.update(&client_hello_hash);
+        self.replace_transcript_hash(transcript);
+    }
+
     pub fn as_split(
         &mut self,
     ) -> (
@@ -270,9 +459,7 @@ where
         &mut self.server_state
     }
 
-    pub fn create_client_finished(
-        &self,
-    ) -> Result<Finished<HashOutputSize<CipherSuite>>, TlsError> {
+    pub fn create_finished(&self) -> Result<Finished<HashOutputSize<CipherSuite>>, TlsError> {
         let key = self
             .client_state
             .state
@@ -293,7 +480,7 @@ where
         Ok(Finished { verify, hash: None })
     }
 
-    fn get_nonce(counter: u64, iv: &IvArray<CipherSuite>) -> IvArray<CipherSuite> {
+    pub(crate) fn get_nonce(counter: u64, iv: &IvArray<CipherSuite>) -> IvArray<CipherSuite> {
         //info!("counter = {} {:x?}", counter, &counter.to_be_bytes(),);
         let counter = Self::pad::<CipherSuite::IvLen>(&counter.to_be_bytes());
 
@@ -346,6 +533,7 @@ where
             .derive_secret(b"ext binder", ContextType::empty_hash())?;
         self.client_state.binder_key.replace(
             Hkdf::<CipherSuite>::from_prk(&binder_key).map_err(|_| TlsError::InternalError)?,
+            self.shared.label_prefix,
         );
         self.shared.derived()
     }
@@ -364,25 +552,84 @@ where
         //info!("Derive keys, hash: {:x?}", context);
 
         self.calculate_traffic_secrets(b"c ap traffic", b"s ap traffic")?;
+
+        let mut exporter_secret = self.shared.derive_secret(
+            b"exp master",
+            ContextType::transcript_hash(&self.server_state.transcript_hash),
+        )?;
+        self.exporter_secret.replace(
+            Hkdf::<CipherSuite>::from_prk(&exporter_secret).map_err(|_| TlsError::InternalError)?,
+            self.shared.label_prefix,
+        );
+        self.pending_secrets.push(b"exp master", &exporter_secret);
+        exporter_secret.as_mut_slice().zeroize();
+
         self.shared.derived()
     }
 
+    /// Fill `out` with keying material exported with `label` and `context` (RFC 8446,
+    /// Section 7.5).
+    pub(crate) fn export_keying_material(
+        &self,
+        label: &[u8],
+        context: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), TlsError> {
+        #[cfg(feature = "tls12")]
+        if self.server_state.state.tls12.is_some() {
+            warn!("Keying material exporters are not supported with TLS 1.2");
+            return Err(TlsError::Unimplemented);
+        }
+        if matches!(self.exporter_secret, Secret::Uninitialized) {
+            return Err(TlsError::MissingHandshake);
+        }
+
+        // Derive-Secret(exporter_master_secret, label, "")
+        let empty_hash = <CipherSuite::Hash as Digest>::new().finalize();
+        let mut secret = HashArray::<CipherSuite>::default();
+        self.exporter_secret
+            .expand_label_into(label, &empty_hash, &mut secret)?;
+        let mut derived = Secret::<CipherSuite>::Uninitialized;
+        derived.replace(
+            Hkdf::<CipherSuite>::from_prk(&secret).map_err(|_| TlsError::InternalError)?,
+            self.shared.label_prefix,
+        );
+        secret.as_mut_slice().zeroize();
+
+        let context_hash = <CipherSuite::Hash as Digest>::new()
+            .chain_update(context)
+            .finalize();
+        let result = derived.expand_label_into(b"exporter", &context_hash, out);
+        derived.zeroize();
+        result
+    }
+
     fn calculate_traffic_secrets(
         &mut self,
         client_label: &[u8],
         server_label: &[u8],
     ) -> Result<(), TlsError> {
-        self.client_state.state.calculate_traffic_secret(
-            client_label,
+        let (write_label, read_label) = if self.is_server {
+            (server_label, client_label)
+        } else {
+            (client_label, server_label)
+        };
+
+        let mut write_secret = self.client_state.state.calculate_traffic_secret(
+            write_label,
             &mut self.shared,
             &self.server_state.transcript_hash,
         )?;
+        self.pending_secrets.push(write_label, &write_secret);
+        write_secret.as_mut_slice().zeroize();
 
-        self.server_state.state.calculate_traffic_secret(
-            server_label,
+        let mut read_secret = self.server_state.state.calculate_traffic_secret(
+            read_label,
             &mut self.shared,
             &self.server_state.transcript_hash,
         )?;
+        self.pending_secrets.push(read_label, &read_secret);
+        read_secret.as_mut_slice().zeroize();
 
         Ok(())
     }
@@ -420,6 +667,36 @@ where
         self.state.get_nonce()
     }
 
+    pub(crate) fn get_iv(&self) -> Result<&IvArray<CipherSuite>, TlsError> {
+        self.state.get_iv()
+    }
+
+    pub(crate) fn get_sn_key(&self) -> Result<KeyArray<CipherSuite>, TlsError> {
+        self.state.get_sn_key()
+    }
+
+    #[cfg(feature = "tls12")]
+    pub(crate) fn tls12_records(&self) -> Option<Tls12Records> {
+        self.state.tls12
+    }
+
+    /// Switch to TLS 1.2 records, resetting the sequence number when they become protected.
+    #[cfg(feature = "tls12")]
+    pub(crate) fn set_tls12_records(&mut self, records: Option<Tls12Records>) {
+        self.state.set_tls12_records(records);
+    }
+
+    #[cfg(feature = "tls12")]
+    pub(crate) fn set_tls12_keys(&mut self, key: &[u8], salt: &[u8]) {
+        self.state.set_tls12_keys(key, salt);
+    }
+
+    /// The TLS 1.2 sequence number of the next record.
+    #[cfg(feature = "tls12")]
+    pub(crate) fn sequence_number(&self) -> u64 {
+        self.state.counter
+    }
+
     pub fn create_psk_binder(
         &self,
         transcript_hash: &CipherSuite::Hash,
@@ -437,6 +714,25 @@ where
         let verify = hmac.finalize().into_bytes();
         Ok(PskBinder { verify })
     }
+
+    /// Check a binder received in a `ClientHello` in constant time.
+    pub(crate) fn verify_psk_binder(
+        &self,
+        transcript_hash: &CipherSuite::Hash,
+        binder: &[u8],
+    ) -> Result<bool, TlsError> {
+        let key = self
+            .binder_key
+            .make_expanded_hkdf_label::<HashOutputSize<CipherSuite>>(
+                b"finished",
+                ContextType::None,
+            )?;
+
+        let mut hmac = SimpleHmac::<CipherSuite::Hash>::new_from_slice(&key)
+            .map_err(|_| TlsError::CryptoError)?;
+        Mac::update(&mut hmac, &transcript_hash.clone().finalize());
+        Ok(hmac.verify_slice(binder).is_ok())
+    }
 }
 
 pub struct ReadKeySchedule<CipherSuite>
@@ -467,7 +763,37 @@ where
         self.state.get_nonce()
     }
 
-    pub fn verify_server_finished(
+    pub(crate) fn get_iv(&self) -> Result<&IvArray<CipherSuite>, TlsError> {
+        self.state.get_iv()
+    }
+
+    pub(crate) fn get_sn_key(&self) -> Result<KeyArray<CipherSuite>, TlsError> {
+        self.state.get_sn_key()
+    }
+
+    #[cfg(feature = "tls12")]
+    pub(crate) fn tls12_records(&self) -> Option<Tls12Records> {
+        self.state.tls12
+    }
+
+    /// Switch to TLS 1.2 records, resetting the sequence number when they become protected.
+    #[cfg(feature = "tls12")]
+    pub(crate) fn set_tls12_records(&mut self, records: Option<Tls12Records>) {
+        self.state.set_tls12_records(records);
+    }
+
+    #[cfg(feature = "tls12")]
+    pub(crate) fn set_tls12_keys(&mut self, key: &[u8], salt: &[u8]) {
+        self.state.set_tls12_keys(key, salt);
+    }
+
+    /// The TLS 1.2 sequence number of the next record.
+    #[cfg(feature = "tls12")]
+    pub(crate) fn sequence_number(&self) -> u64 {
+        self.state.counter
+    }
+
+    pub fn verify_finished(
         &self,
         finished: &Finished<HashOutputSize<CipherSuite>>,
     ) -> Result<bool, TlsError> {
@@ -0,0 +1,140 @@
+use core::fmt;
+
+use heapless::Vec;
+use heapless::vec::VecView;
+use rand_core::CryptoRngCore;
+
+use crate::extensions::extension_data::supported_groups::NamedGroup;
+use crate::key_exchange::EphemeralSecret;
+
+/// An ephemeral key share generated ahead of the handshake, see [`KeySharePool`].
+///
+/// A key share cannot be cloned: it is moved into the handshake that uses it, and zeroized
+/// when dropped. A handshake only uses a key share of the group it sends a key share for, see
+/// [`TlsConfigBuilder::with_named_groups`](crate::TlsConfigBuilder::with_named_groups).
+pub struct KeyShare(pub(crate) EphemeralSecret);
+
+impl KeyShare {
+    /// Generate a new [`NamedGroup::Secp256r1`] key share.
+    #[must_use]
+    pub fn generate(rng: &mut impl CryptoRngCore) -> Self {
+        Self(unwrap!(EphemeralSecret::generate(
+            NamedGroup::Secp256r1,
+            rng
+        )))
+    }
+
+    /// Generate a new key share for `group`, or `None` if key shares cannot be generated for
+    /// it.
+    #[must_use]
+    pub fn generate_for(group: NamedGroup, rng: &mut impl CryptoRngCore) -> Option<Self> {
+        EphemeralSecret::generate(group, rng).map(Self)
+    }
+
+    /// The group of the key share.
+    #[must_use]
+    pub fn group(&self) -> NamedGroup {
+        self.0.group()
+    }
+}
+
+impl fmt::Debug for KeyShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("KeyShare")
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for KeyShare {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "KeyShare");
+    }
+}
+
+/// Up to `N` key shares generated ahead of the handshakes, for instance while the device is
+/// idle, so that opening a connection does not wait for the key generation.
+///
+/// Each handshake takes one key share out of the pool, see
+/// [`TlsContext::with_key_shares`](crate::TlsContext::with_key_shares). When the pool has no
+/// key share for the group of the handshake, the key share is generated during the handshake
+/// instead, and the key shares of other groups stay in the pool.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct KeySharePool<const N: usize> {
+    shares: Vec<KeyShare, N>,
+}
+
+impl<const N: usize> KeySharePool<N> {
+    /// Create an empty pool.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { shares: Vec::new() }
+    }
+
+    /// Generate key shares until the pool is full.
+    pub fn fill(&mut self, rng: &mut impl CryptoRngCore) {
+        while !self.shares.is_full() {
+            unwrap!(self.shares.push(KeyShare::generate(rng)).ok());
+        }
+    }
+
+    /// Add a key share to the pool, returning it back if the pool is full.
+    pub fn push(&mut self, key_share: KeyShare) -> Result<(), KeyShare> {
+        self.shares.push(key_share)
+    }
+
+    /// Take a key share out of the pool.
+    #[must_use]
+    pub fn take(&mut self) -> Option<KeyShare> {
+        self.shares.pop()
+    }
+
+    /// The number of key shares in the pool.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.shares.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.shares.is_empty()
+    }
+
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.shares.is_full()
+    }
+
+    /// Borrow the key shares regardless of the capacity of the pool.
+    pub(crate) fn as_mut_view(&mut self) -> &mut VecView<KeyShare> {
+        self.shares.as_mut_view()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn fill_and_take() {
+        let mut pool = KeySharePool::<2>::new();
+        assert!(pool.is_empty());
+
+        pool.fill(&mut OsRng);
+        assert!(pool.is_full());
+        assert!(pool.push(KeyShare::generate(&mut OsRng)).is_err());
+
+        assert!(pool.take().is_some());
+        assert!(pool.take().is_some());
+        assert!(pool.take().is_none());
+        assert_eq!(pool.len(), 0);
+    }
+
+    #[test]
+    fn generate_for_group() {
+        let key_share = KeyShare::generate_for(NamedGroup::Secp256r1, &mut OsRng).unwrap();
+        assert_eq!(key_share.group(), NamedGroup::Secp256r1);
+        assert!(KeyShare::generate_for(NamedGroup::Ffdhe2048, &mut OsRng).is_none());
+    }
+}
@@ -54,28 +54,52 @@ use parse_buffer::ParseError;
 pub mod alert;
 mod application_data;
 pub mod blocking;
+#[cfg(feature = "brainpool")]
+mod bp512;
 mod buffer;
 mod change_cipher_spec;
 mod cipher_suites;
+mod client;
 mod common;
 mod config;
 mod connection;
 mod content_types;
 mod crypto_engine;
+pub mod dtls;
 mod extensions;
 pub mod flush_policy;
+mod grease;
 mod handshake;
+#[cfg(feature = "integrity-only")]
+mod integrity_only;
+mod key_exchange;
+mod key_log;
 mod key_schedule;
+mod key_share_pool;
+pub mod owned;
 mod parse_buffer;
 pub mod read_buffer;
 mod record;
+mod record_buffer;
 mod record_reader;
+mod server_connection;
+#[cfg(feature = "tls12")]
+mod tls12;
 mod write_buffer;
 
+pub use client::TlsClient;
 pub use config::UnsecureProvider;
 pub use extensions::extension_data::signature_algorithms::SignatureScheme;
 pub use handshake::certificate_verify::CertificateVerify;
+#[cfg(feature = "integrity-only")]
+pub use integrity_only::HmacIntegrity;
+#[cfg(feature = "key-log")]
+pub use key_log::KeyLog;
+#[cfg(all(feature = "key-log", feature = "std"))]
+pub use key_log::NssKeyLog;
+pub use key_share_pool::{KeyShare, KeySharePool};
 pub use rand_core::{CryptoRng, CryptoRngCore};
+pub use record_buffer::{RecordBuffer, RecordBuffers};
 
 #[cfg(feature = "webpki")]
 pub mod webpki;
@@ -101,6 +125,7 @@ pub enum TlsError {
     IoError,
     InternalError,
     InvalidRecord,
+    BadRecordMac,
     UnknownContentType,
     InvalidNonceLength,
     InvalidTicketLength,
@@ -125,6 +150,16 @@ pub enum TlsError {
     CryptoError,
     EncodeError,
     DecodeError,
+    /// The buffer shared by a half-duplex connection still holds data of the other direction:
+    /// the received data must be read before writing, and the written data flushed before
+    /// reading. A half-duplex connection cannot be split either.
+    BufferInUse,
+    /// The read buffer is smaller than the size required by the configuration, see
+    /// [`min_read_buffer_size`].
+    ReadBufferTooSmall(usize),
+    /// The write buffer is smaller than the size required by the configuration, see
+    /// [`min_write_buffer_size`].
+    WriteBufferTooSmall(usize),
     Io(embedded_io::ErrorKind),
 }
 
@@ -147,6 +182,58 @@ impl core::fmt::Display for TlsError {
 
 impl core::error::Error for TlsError {}
 
+impl TlsError {
+    /// The alert to send to the peer before tearing down the connection because of this error.
+    ///
+    /// Returns `None` if no alert should be sent: the connection is already closed, the peer
+    /// aborted it with an alert of its own, or the transport failed.
+    #[must_use]
+    pub fn alert_description(&self) -> Option<alert::AlertDescription> {
+        use alert::AlertDescription;
+
+        match self {
+            Self::ConnectionClosed
+            | Self::MissingHandshake
+            | Self::HandshakeAborted(..)
+            | Self::IoError
+            | Self::BufferInUse
+            | Self::ReadBufferTooSmall(_)
+            | Self::WriteBufferTooSmall(_)
+            | Self::Io(_) => None,
+            Self::AbortHandshake(_, description) => Some(*description),
+            Self::InvalidRecord | Self::UnknownContentType | Self::InvalidHandshake => {
+                Some(AlertDescription::UnexpectedMessage)
+            }
+            Self::BadRecordMac => Some(AlertDescription::BadRecordMac),
+            Self::InvalidNonceLength
+            | Self::InvalidTicketLength
+            | Self::InvalidExtensionsLength
+            | Self::InvalidSessionIdLength
+            | Self::InvalidCertificateRequest
+            | Self::ParseError(_)
+            | Self::DecodeError => Some(AlertDescription::DecodeError),
+            Self::UnknownExtensionType => Some(AlertDescription::UnsupportedExtension),
+            Self::InvalidCipherSuite | Self::InvalidKeyShare | Self::InvalidSignatureScheme => {
+                Some(AlertDescription::IllegalParameter)
+            }
+            Self::InvalidSupportedVersions => Some(AlertDescription::ProtocolVersion),
+            Self::InvalidSignature => Some(AlertDescription::DecryptError),
+            Self::InvalidCertificate | Self::InvalidCertificateEntry => {
+                Some(AlertDescription::BadCertificate)
+            }
+            Self::Unimplemented
+            | Self::InternalError
+            | Self::InsufficientSpace
+            | Self::InvalidApplicationData
+            | Self::InvalidPrivateKey
+            | Self::UnableToInitializeCryptoEngine
+            | Self::OutOfMemory
+            | Self::CryptoError
+            | Self::EncodeError => Some(AlertDescription::InternalError),
+        }
+    }
+}
+
 #[cfg(feature = "std")]
 mod stdlib {
     use crate::config::TlsClock;
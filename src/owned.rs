@@ -0,0 +1,414 @@
+//! A TLS connection owning its record buffers, and a configuration owning its data.
+//!
+//! Unlike [`crate::TlsConnection`], which borrows its buffers, the buffers are stored inline, so
+//! the connection can be kept in a `static` cell, moved to another task or stored alongside
+//! other connections without tying them to the lifetime of the buffers.
+//!
+//! Likewise, [`TlsConfig`] copies the server name, ALPN protocols and pre-shared key that
+//! [`crate::TlsConfig`] borrows, so a configuration built at runtime can be kept in a `static`.
+use crate::TlsError;
+use crate::alert::{AlertDescription, AlertLevel};
+use crate::asynch::{TlsReader, TlsWriter};
+use crate::config::{
+    ConfigError, CryptoProvider, CustomExtension, MAX_CONNECTION_ID_LEN, TlsCipherSuite, TlsContext,
+};
+use crate::extensions::extension_data::alpn::AlpnProtocols;
+use crate::flush_policy::FlushPolicy;
+use crate::read_buffer::ReadBuffer;
+use embedded_io::ErrorType;
+use embedded_io_async::{BufRead, Read as AsyncRead, Write as AsyncWrite};
+use heapless::{String, Vec};
+
+/// Type representing an async TLS connection owning a read buffer of `RX` bytes and a write
+/// buffer of `TX` bytes.
+///
+/// See [`crate::TlsConnection::new`] for how to size the buffers.
+pub struct TlsConnection<Socket, CipherSuite, const RX: usize, const TX: usize>
+where
+    Socket: AsyncRead + AsyncWrite,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    inner: crate::TlsConnection<'static, Socket, CipherSuite, [u8; RX], [u8; TX]>,
+}
+
+impl<Socket, CipherSuite, const RX: usize, const TX: usize>
+    TlsConnection<Socket, CipherSuite, RX, TX>
+where
+    Socket: AsyncRead + AsyncWrite,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    /// Create a new TLS connection with the async I/O implementation.
+    pub fn new(delegate: Socket) -> Self {
+        Self::from_parts(delegate, [0; RX], [0; TX])
+    }
+
+    /// Create a new TLS connection from the parts returned by [`Self::into_parts`].
+    pub fn from_parts(
+        delegate: Socket,
+        record_read_buf: [u8; RX],
+        record_write_buf: [u8; TX],
+    ) -> Self {
+        Self {
+            inner: crate::TlsConnection::from_buffers(delegate, record_read_buf, record_write_buf),
+        }
+    }
+
+    /// Reset the connection to its initial state over the new transport `delegate`, returning
+    /// the previous one, so that [`Self::open`] can perform a new handshake.
+    ///
+    /// See [`crate::TlsConnection::reset`].
+    pub fn reset(&mut self, delegate: Socket) -> Socket {
+        self.inner.reset(delegate)
+    }
+
+    /// Reset the connection, and return the transport and the read and write buffers.
+    pub fn into_parts(self) -> (Socket, [u8; RX], [u8; TX]) {
+        self.inner.into_parts()
+    }
+
+    pub fn is_opened(&mut self) -> bool {
+        self.inner.is_opened()
+    }
+
+    /// Returns a reference to the current flush policy.
+    #[inline]
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.inner.flush_policy()
+    }
+
+    /// Replace the current flush policy with the provided one.
+    #[inline]
+    pub fn set_flush_policy(&mut self, policy: FlushPolicy) {
+        self.inner.set_flush_policy(policy);
+    }
+
+    /// Fill `out` with keying material exported from the connection with `label` and
+    /// `context`.
+    ///
+    /// See [`crate::TlsConnection::export_keying_material`].
+    pub fn export_keying_material(
+        &self,
+        label: &[u8],
+        context: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), TlsError> {
+        self.inner.export_keying_material(label, context, out)
+    }
+
+    /// Open a TLS connection, performing the handshake with the configuration provided when
+    /// creating the connection instance.
+    ///
+    /// See [`crate::TlsConnection::open`].
+    pub async fn open<Provider>(
+        &mut self,
+        context: TlsContext<'_, Provider>,
+    ) -> Result<(), TlsError>
+    where
+        Provider: CryptoProvider<CipherSuite = CipherSuite>,
+    {
+        self.inner.open(context).await
+    }
+
+    /// Encrypt and send the provided slice over the connection.
+    ///
+    /// See [`crate::TlsConnection::write`].
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, TlsError> {
+        self.inner.write(buf).await
+    }
+
+    /// Force all previously written, buffered bytes to be encoded into a tls record and written
+    /// to the connection.
+    pub async fn flush(&mut self) -> Result<(), TlsError> {
+        self.inner.flush().await
+    }
+
+    /// Read and decrypt data filling the provided slice.
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, TlsError> {
+        self.inner.read(buf).await
+    }
+
+    /// Reads buffered data. If nothing is in memory, it'll wait for a TLS record and process it.
+    pub async fn read_buffered(&mut self) -> Result<ReadBuffer<'_>, TlsError> {
+        self.inner.read_buffered().await
+    }
+
+    /// Send an alert to the server.
+    ///
+    /// See [`crate::TlsConnection::send_alert`].
+    pub async fn send_alert(
+        &mut self,
+        level: AlertLevel,
+        description: AlertDescription,
+    ) -> Result<(), TlsError> {
+        self.inner.send_alert(level, description).await
+    }
+
+    /// Send `close_notify` to the server, closing the write side of the connection.
+    pub async fn shutdown_write(&mut self) -> Result<(), TlsError> {
+        self.inner.shutdown_write().await
+    }
+
+    /// Gracefully close a connection instance, returning the ownership of the I/O provider.
+    ///
+    /// See [`crate::TlsConnection::shutdown`].
+    pub async fn shutdown(self) -> Result<Socket, (Socket, TlsError)> {
+        self.inner.shutdown().await
+    }
+
+    /// Close a connection instance, returning the ownership of the async I/O provider.
+    pub async fn close(self) -> Result<Socket, (Socket, TlsError)> {
+        self.inner.close().await
+    }
+
+    /// Split the connection into a reader and a writer that can be used concurrently.
+    pub fn split(
+        &mut self,
+    ) -> (
+        TlsReader<'_, Socket, CipherSuite>,
+        TlsWriter<'_, Socket, CipherSuite>,
+    )
+    where
+        Socket: Clone,
+    {
+        self.inner.split()
+    }
+}
+
+impl<Socket, CipherSuite, const RX: usize, const TX: usize> ErrorType
+    for TlsConnection<Socket, CipherSuite, RX, TX>
+where
+    Socket: AsyncRead + AsyncWrite,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    type Error = TlsError;
+}
+
+impl<Socket, CipherSuite, const RX: usize, const TX: usize> AsyncRead
+    for TlsConnection<Socket, CipherSuite, RX, TX>
+where
+    Socket: AsyncRead + AsyncWrite,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        TlsConnection::read(self, buf).await
+    }
+}
+
+impl<Socket, CipherSuite, const RX: usize, const TX: usize> BufRead
+    for TlsConnection<Socket, CipherSuite, RX, TX>
+where
+    Socket: AsyncRead + AsyncWrite,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    async fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+        self.inner.fill_buf().await
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+    }
+}
+
+impl<Socket, CipherSuite, const RX: usize, const TX: usize> AsyncWrite
+    for TlsConnection<Socket, CipherSuite, RX, TX>
+where
+    Socket: AsyncRead + AsyncWrite,
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        TlsConnection::write(self, buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        TlsConnection::flush(self).await
+    }
+}
+
+/// A [`crate::TlsConfig`] owning the server name, ALPN protocols and pre-shared key.
+///
+/// The server name holds up to `SERVER_NAME` bytes, and the ALPN protocols up to `ALPN` bytes,
+/// counting one extra byte per protocol. The pre-shared key holds up to `PSK` bytes, and each
+/// of its identities up to `PSK_IDENTITY` bytes.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TlsConfig<
+    const SERVER_NAME: usize,
+    const ALPN: usize,
+    const PSK: usize,
+    const PSK_IDENTITY: usize,
+> {
+    config: crate::TlsConfig<'static>,
+    server_name: Option<String<SERVER_NAME>>,
+    alpn_protocols: Option<Vec<u8, ALPN>>,
+    psk: Option<(Vec<u8, PSK>, Vec<Vec<u8, PSK_IDENTITY>, 4>)>,
+    connection_id: Option<Vec<u8, MAX_CONNECTION_ID_LEN>>,
+}
+
+impl<const SERVER_NAME: usize, const ALPN: usize, const PSK: usize, const PSK_IDENTITY: usize>
+    TlsConfig<SERVER_NAME, ALPN, PSK, PSK_IDENTITY>
+{
+    /// Copy `config`, including the data it borrows.
+    ///
+    /// Fails with [`ConfigError::InsufficientCapacity`] if the data does not fit. Custom
+    /// extensions are not copied: `config` must have none, or the call fails with
+    /// [`ConfigError::CustomExtensionsNotCopied`], see [`Self::with_custom_extensions`].
+    pub fn new(config: &crate::TlsConfig<'_>) -> Result<Self, ConfigError> {
+        if !config.custom_extensions.is_empty() {
+            return Err(ConfigError::CustomExtensionsNotCopied);
+        }
+
+        let server_name = config
+            .server_name
+            .map(|name| String::try_from(name).map_err(|_| ConfigError::InsufficientCapacity))
+            .transpose()?;
+
+        let alpn_protocols = config
+            .alpn_protocols
+            .map(|protocols| {
+                let mut encoded = Vec::new();
+                for protocol in protocols.iter() {
+                    let len = u8::try_from(protocol.len())
+                        .map_err(|_| ConfigError::InvalidAlpnProtocols)?;
+                    encoded
+                        .push(len)
+                        .map_err(|_| ConfigError::InsufficientCapacity)?;
+                    encoded
+                        .extend_from_slice(protocol)
+                        .map_err(|_| ConfigError::InsufficientCapacity)?;
+                }
+                Ok(encoded)
+            })
+            .transpose()?;
+
+        let psk = config
+            .psk
+            .as_ref()
+            .map(|(psk, identities)| {
+                let psk = Vec::from_slice(psk).map_err(|_| ConfigError::InsufficientCapacity)?;
+                let identities = identities
+                    .iter()
+                    .map(|identity| Vec::from_slice(identity))
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| ConfigError::InsufficientCapacity)?;
+                Ok((psk, identities))
+            })
+            .transpose()?;
+
+        let connection_id = config
+            .connection_id
+            .map(|cid| Vec::from_slice(cid).map_err(|_| ConfigError::InsufficientCapacity))
+            .transpose()?;
+
+        Ok(Self {
+            config: crate::TlsConfig {
+                server_name: None,
+                alpn_protocols: None,
+                psk: None,
+                signature_schemes: config.signature_schemes.clone(),
+                named_groups: config.named_groups.clone(),
+                max_fragment_length: config.max_fragment_length,
+                padding: config.padding,
+                grease: config.grease,
+                middlebox_compat: config.middlebox_compat,
+                client_auth: config.client_auth,
+                connection_id: None,
+                custom_extensions: &[],
+                mtu: config.mtu,
+                retransmission_timeout: config.retransmission_timeout,
+            },
+            server_name,
+            alpn_protocols,
+            psk,
+            connection_id,
+        })
+    }
+
+    /// Send the `static` custom `extensions` in the `ClientHello`, see
+    /// [`crate::TlsConfig::with_custom_extensions`].
+    #[must_use]
+    pub fn with_custom_extensions(
+        mut self,
+        extensions: &'static [CustomExtension<'static>],
+    ) -> Self {
+        self.config.custom_extensions = extensions;
+        self
+    }
+
+    /// Borrow the configuration as a [`crate::TlsConfig`]. A [`TlsContext`] can also be
+    /// created from the owned configuration directly.
+    pub fn as_config(&self) -> crate::TlsConfig<'_> {
+        crate::TlsConfig {
+            server_name: self.server_name.as_deref(),
+            alpn_protocols: self.alpn_protocols.as_deref().map(AlpnProtocols::Encoded),
+            psk: self.psk.as_ref().map(|(psk, identities)| {
+                (
+                    psk.as_slice(),
+                    identities.iter().map(Vec::as_slice).collect(),
+                )
+            }),
+            connection_id: self.connection_id.as_deref(),
+            ..self.config.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Aes128GcmSha256;
+    use crate::buffer::CryptoBuffer;
+    use crate::config::{MaxFragmentLength, UnsecureProvider};
+    use crate::handshake::client_hello::ClientHello;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn encode(config: &crate::TlsConfig<'_>, data: &mut [u8]) -> usize {
+        let provider = UnsecureProvider::new::<Aes128GcmSha256>(StdRng::seed_from_u64(0));
+        let hello = ClientHello::<Aes128GcmSha256>::new(config, provider, None).unwrap();
+        let mut buf = CryptoBuffer::wrap(data);
+        hello.encode(&mut buf).unwrap();
+        buf.len()
+    }
+
+    #[test]
+    fn same_client_hello_as_borrowed_config() {
+        let config = crate::TlsConfig::new()
+            .with_server_name("example.com")
+            .with_alpn(&[b"h2", b"http/1.1"])
+            .with_psk(&[0; 32], &[b"identity", b"other identity"])
+            .with_max_fragment_length(MaxFragmentLength::Bits10)
+            .enable_grease();
+        let owned = TlsConfig::<16, 16, 32, 16>::new(&config).unwrap();
+
+        let mut borrowed_hello = [0; 1024];
+        let mut owned_hello = [0; 1024];
+        let len = encode(&config, &mut borrowed_hello);
+        assert_eq!(len, encode(&owned.as_config(), &mut owned_hello));
+        assert_eq!(borrowed_hello[..len], owned_hello[..len]);
+    }
+
+    #[test]
+    fn insufficient_capacity() {
+        let config = crate::TlsConfig::new().with_server_name("example.com");
+        assert_eq!(
+            TlsConfig::<8, 0, 0, 0>::new(&config).unwrap_err(),
+            ConfigError::InsufficientCapacity
+        );
+
+        let config = crate::TlsConfig::new().with_alpn(&[b"h2", b"http/1.1"]);
+        assert_eq!(
+            TlsConfig::<0, 8, 0, 0>::new(&config).unwrap_err(),
+            ConfigError::InsufficientCapacity
+        );
+
+        let extensions = [CustomExtension {
+            extension_type: 0xff00,
+            data: &[],
+        }];
+        let config = crate::TlsConfig::new().with_custom_extensions(&extensions);
+        assert_eq!(
+            TlsConfig::<0, 0, 0, 0>::new(&config).unwrap_err(),
+            ConfigError::CustomExtensionsNotCopied
+        );
+    }
+}
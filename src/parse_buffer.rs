@@ -9,6 +9,7 @@ pub enum ParseError {
     InvalidData,
 }
 
+#[derive(Clone)]
 pub struct ParseBuffer<'b> {
     pos: usize,
     buffer: &'b [u8],
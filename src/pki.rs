@@ -1,9 +1,15 @@
 use crate::TlsError;
 use crate::config::{Certificate, TlsCipherSuite, TlsClock, TlsVerifier};
-#[cfg(feature = "p384")]
+#[cfg(any(feature = "p384", feature = "brainpool"))]
 use crate::der_certificate::ECDSA_SHA384;
+#[cfg(feature = "ed448")]
+use crate::der_certificate::ED448;
 #[cfg(feature = "ed25519")]
 use crate::der_certificate::ED25519;
+#[cfg(feature = "shangmi")]
+use crate::der_certificate::SM2_SM3;
+#[cfg(feature = "brainpool")]
+use crate::der_certificate::{BRAINPOOL_P256R1, BRAINPOOL_P384R1, BRAINPOOL_P512R1, ECDSA_SHA512};
 use crate::der_certificate::{
     DecodedCertificate, ECDSA_SHA256, HOSTNAME_MAXLEN, MAX_SAN_DNS_NAMES, Time,
     extract_common_name, extract_san_dns_names,
@@ -12,9 +18,7 @@ use crate::der_certificate::{
 use crate::der_certificate::{RSA_PKCS1_SHA256, RSA_PKCS1_SHA384, RSA_PKCS1_SHA512};
 use crate::extensions::extension_data::signature_algorithms::SignatureScheme;
 use crate::handshake::{
-    certificate::{
-        Certificate as OwnedCertificate, CertificateEntryRef, CertificateRef as ServerCertificate,
-    },
+    certificate::{CertificateEntryRef, CertificateRef as ServerCertificate},
     certificate_verify::CertificateVerifyRef,
 };
 use crate::parse_buffer::ParseError;
@@ -28,37 +32,89 @@ pub struct CertificateNames {
     pub san_dns_names: heapless::Vec<heapless::String<HOSTNAME_MAXLEN>, MAX_SAN_DNS_NAMES>,
 }
 
-pub struct CertificateChain<'a> {
-    prev: &'a CertificateEntryRef<'a>,
-    chain: &'a ServerCertificate<'a>,
-    idx: isize,
+/// A certificate chain verified incrementally, as its entries are received from the
+/// end-entity certificate up to the one issued by the CA.
+///
+/// Only the last entry received is kept, until the next one proves it was issued by it.
+pub struct CertificateChain<const CERT_SIZE: usize> {
+    pending: Vec<u8, CERT_SIZE>,
+    public_key: Vec<u8, CERT_SIZE>,
+    len: usize,
 }
 
-impl<'a> CertificateChain<'a> {
-    pub fn new(ca: &'a CertificateEntryRef, chain: &'a ServerCertificate<'a>) -> Self {
+impl<const CERT_SIZE: usize> CertificateChain<CERT_SIZE> {
+    #[must_use]
+    pub fn new() -> Self {
         Self {
-            prev: ca,
-            chain,
-            idx: chain.entries.len() as isize - 1,
+            pending: Vec::new(),
+            public_key: Vec::new(),
+            len: 0,
         }
     }
-}
 
-impl<'a> Iterator for CertificateChain<'a> {
-    type Item = (&'a CertificateEntryRef<'a>, &'a CertificateEntryRef<'a>);
+    /// Add the next entry of the chain, verifying that it issued the previous entry.
+    ///
+    /// Returns the names of the end-entity certificate once it has been verified.
+    pub fn push(
+        &mut self,
+        entry: &CertificateEntryRef,
+        now: Option<u64>,
+    ) -> Result<Option<CertificateNames>, TlsError> {
+        let CertificateEntryRef::X509(certificate) = *entry else {
+            return Err(TlsError::DecodeError);
+        };
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.idx < 0 {
-            return None;
-        }
+        let names = if self.len > 0 {
+            let names = verify_certificate(entry, &CertificateEntryRef::X509(&self.pending), now)?;
+            (self.len == 1).then_some(names)
+        } else {
+            self.public_key = Vec::from_slice(public_key(certificate)?).map_err(|_| {
+                error!("Public key too large for buffer");
+                TlsError::InsufficientSpace
+            })?;
+            None
+        };
 
-        let cur = &self.chain.entries[self.idx as usize];
-        let out = (self.prev, cur);
+        self.pending = Vec::from_slice(certificate).map_err(|_| {
+            error!(
+                "Certificate too large for buffer. Size: {} Buffer size: {}",
+                certificate.len(),
+                CERT_SIZE
+            );
+            TlsError::InsufficientSpace
+        })?;
+        self.len += 1;
+        Ok(names)
+    }
 
-        self.prev = cur;
-        self.idx -= 1;
+    /// Verify that the last entry of the chain was issued by the `ca` certificate.
+    ///
+    /// Returns the names of the end-entity certificate if it is the only entry of the chain.
+    pub fn finish(
+        &mut self,
+        ca: &CertificateEntryRef,
+        now: Option<u64>,
+    ) -> Result<Option<CertificateNames>, TlsError> {
+        if self.len == 0 {
+            return Err(TlsError::InvalidCertificate);
+        }
+        let names = verify_certificate(ca, &CertificateEntryRef::X509(&self.pending), now)?;
+        let end_entity = self.len == 1;
+        self.len = 0;
+        self.pending.clear();
+        Ok(end_entity.then_some(names))
+    }
 
-        Some(out)
+    /// The public key of the end-entity certificate.
+    #[must_use]
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+}
+
+impl<const CERT_SIZE: usize> Default for CertificateChain<CERT_SIZE> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -70,7 +126,8 @@ where
     ca: Certificate<&'a [u8]>,
     host: Option<heapless::String<64>>,
     certificate_transcript: Option<CipherSuite::Hash>,
-    certificate: Option<OwnedCertificate<CERT_SIZE>>,
+    chain: CertificateChain<CERT_SIZE>,
+    client_certificates: bool,
     _clock: PhantomData<Clock>,
 }
 
@@ -85,10 +142,19 @@ where
             ca,
             host: None,
             certificate_transcript: None,
-            certificate: None,
+            chain: CertificateChain::new(),
+            client_certificates: false,
             _clock: PhantomData,
         }
     }
+
+    /// Verify client certificates for a [`TlsServerConnection`](crate::TlsServerConnection)
+    /// instead of server certificates.
+    #[must_use]
+    pub fn for_client_certificates(mut self) -> Self {
+        self.client_certificates = true;
+        self
+    }
 }
 
 impl<CipherSuite, Clock, const CERT_SIZE: usize> TlsVerifier<CipherSuite>
@@ -109,31 +175,44 @@ where
         transcript: &CipherSuite::Hash,
         cert: ServerCertificate,
     ) -> Result<(), TlsError> {
-        let mut names = CertificateNames {
-            common_name: None,
-            san_dns_names: heapless::Vec::new(),
-        };
-
-        for (p, q) in CertificateChain::new(&(&self.ca).into(), &cert) {
-            names = verify_certificate(p, q, Clock::now())?;
+        for entry in cert.entries {
+            self.verify_certificate_entry(entry)?;
         }
+        self.verify_certificate_chain(transcript)
+    }
 
-        if !tls_hostname_match(&names, &self.host) {
-            error!(
-                "Hostname ({:?}) does not match certificate names (CN={:?}, SANs={:?})",
-                self.host, names.common_name, names.san_dns_names
-            );
-            return Err(TlsError::InvalidCertificate);
-        }
+    fn streams_certificates(&self) -> bool {
+        true
+    }
 
-        self.certificate.replace(cert.try_into()?);
+    fn verify_certificate_entry(&mut self, entry: CertificateEntryRef) -> Result<(), TlsError> {
+        let names = self.chain.push(&entry, Clock::now())?;
+        self.verify_names(names)
+    }
+
+    fn verify_certificate_chain(&mut self, transcript: &CipherSuite::Hash) -> Result<(), TlsError> {
+        let names = self.chain.finish(&(&self.ca).into(), Clock::now())?;
+        self.verify_names(names)?;
         self.certificate_transcript.replace(transcript.clone());
         Ok(())
     }
 
     fn verify_signature(&mut self, verify: CertificateVerifyRef) -> Result<(), TlsError> {
+        // RFC 8446, Section 4.4.3: RSASSA-PKCS1-v1_5 is only signed in TLS 1.2
+        if matches!(
+            verify.signature_scheme,
+            SignatureScheme::RsaPkcs1Sha256
+                | SignatureScheme::RsaPkcs1Sha384
+                | SignatureScheme::RsaPkcs1Sha512
+        ) {
+            return Err(TlsError::InvalidSignatureScheme);
+        }
         let handshake_hash = unwrap!(self.certificate_transcript.take());
-        let ctx_str = b"TLS 1.3, server CertificateVerify\x00";
+        let ctx_str: &[u8] = if self.client_certificates {
+            b"TLS 1.3, client CertificateVerify\x00"
+        } else {
+            b"TLS 1.3, server CertificateVerify\x00"
+        };
         let mut msg: Vec<u8, 146> = Vec::new();
         msg.resize(64, 0x20).map_err(|_| TlsError::EncodeError)?;
         msg.extend_from_slice(ctx_str)
@@ -141,35 +220,64 @@ where
         msg.extend_from_slice(&handshake_hash.finalize())
             .map_err(|_| TlsError::EncodeError)?;
 
-        let certificate = unwrap!(self.certificate.as_ref()).try_into()?;
-        verify_signature(&msg[..], &certificate, &verify)?;
+        verify_signature(&msg[..], self.chain.public_key(), &verify)?;
         Ok(())
     }
-}
 
-fn verify_signature(
-    message: &[u8],
-    certificate: &ServerCertificate,
-    verify: &CertificateVerifyRef,
-) -> Result<(), TlsError> {
-    let verified;
+    #[cfg(feature = "tls12")]
+    fn verify_tls12_signature(
+        &mut self,
+        message: &[u8],
+        verify: CertificateVerifyRef,
+    ) -> Result<(), TlsError> {
+        if self.chain.public_key().is_empty() {
+            return Err(TlsError::InvalidHandshake);
+        }
+        verify_signature(message, self.chain.public_key(), &verify)
+    }
+}
 
-    let certificate =
-        if let Some(CertificateEntryRef::X509(certificate)) = certificate.entries.first() {
-            certificate
-        } else {
-            return Err(TlsError::DecodeError);
-        };
+impl<CipherSuite, Clock, const CERT_SIZE: usize> CertVerifier<'_, CipherSuite, Clock, CERT_SIZE>
+where
+    CipherSuite: TlsCipherSuite,
+    Clock: TlsClock,
+{
+    /// Check the hostname against the end-entity certificate names, once verified.
+    fn verify_names(&self, names: Option<CertificateNames>) -> Result<(), TlsError> {
+        // Client certificates are not bound to a hostname
+        if let Some(names) = names
+            && !self.client_certificates
+            && !tls_hostname_match(&names, &self.host)
+        {
+            error!(
+                "Hostname ({:?}) does not match certificate names (CN={:?}, SANs={:?})",
+                self.host, names.common_name, names.san_dns_names
+            );
+            return Err(TlsError::InvalidCertificate);
+        }
+        Ok(())
+    }
+}
 
+/// The subject public key of a DER encoded certificate.
+fn public_key(certificate: &[u8]) -> Result<&[u8], TlsError> {
     let certificate =
         DecodedCertificate::from_der(certificate).map_err(|_| TlsError::DecodeError)?;
 
-    let public_key = certificate
+    certificate
         .tbs_certificate
         .subject_public_key_info
         .public_key
         .as_bytes()
-        .ok_or(TlsError::DecodeError)?;
+        .ok_or(TlsError::DecodeError)
+}
+
+fn verify_signature(
+    message: &[u8],
+    public_key: &[u8],
+    verify: &CertificateVerifyRef,
+) -> Result<(), TlsError> {
+    let verified;
 
     match verify.signature_scheme {
         SignatureScheme::EcdsaSecp256r1Sha256 => {
@@ -199,6 +307,19 @@ fn verify_signature(
                 Signature::try_from(verify.signature).map_err(|_| TlsError::DecodeError)?;
             verified = verifying_key.verify(message, &signature).is_ok();
         }
+        #[cfg(feature = "ed448")]
+        SignatureScheme::Ed448 => verified = verify_ed448(public_key, message, verify.signature)?,
+        #[cfg(feature = "brainpool")]
+        SignatureScheme::Sha256BrainpoolP256r1
+        | SignatureScheme::Sha384BrainpoolP384r1
+        | SignatureScheme::Sha512BrainpoolP512r1 => {
+            verified = Brainpool::verify_scheme(verify, public_key, message)?;
+        }
+        #[cfg(feature = "shangmi")]
+        SignatureScheme::Sm2sigSm3 => {
+            // RFC 8998, Section 3.2.1: the distinguishing identifier of TLS 1.3
+            verified = verify_sm2("TLSv1.3", public_key, message, verify.signature)?;
+        }
         #[cfg(feature = "rsa")]
         SignatureScheme::RsaPssRsaeSha256 => {
             use rsa::{
@@ -252,6 +373,18 @@ fn verify_signature(
                 Signature::try_from(verify.signature).map_err(|_| TlsError::DecodeError)?;
             verified = verifying_key.verify(message, &signature).is_ok();
         }
+        #[cfg(feature = "rsa")]
+        SignatureScheme::RsaPkcs1Sha256 => {
+            verified = verify_rsa_pkcs1::<sha2::Sha256>(public_key, message, verify.signature)?;
+        }
+        #[cfg(feature = "rsa")]
+        SignatureScheme::RsaPkcs1Sha384 => {
+            verified = verify_rsa_pkcs1::<sha2::Sha384>(public_key, message, verify.signature)?;
+        }
+        #[cfg(feature = "rsa")]
+        SignatureScheme::RsaPkcs1Sha512 => {
+            verified = verify_rsa_pkcs1::<sha2::Sha512>(public_key, message, verify.signature)?;
+        }
         _ => {
             error!(
                 "InvalidSignatureScheme: {:?} Are you missing a feature?",
@@ -267,6 +400,149 @@ fn verify_signature(
     Ok(())
 }
 
+/// Verify an RSASSA-PKCS1-v1_5 `signature` of `message`, hashed with `Hash`. Only signed by
+/// TLS 1.2 servers, in `ServerKeyExchange`.
+#[cfg(feature = "rsa")]
+fn verify_rsa_pkcs1<Hash>(
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<bool, TlsError>
+where
+    Hash: Digest + rsa::pkcs8::AssociatedOid,
+{
+    use rsa::{
+        RsaPublicKey,
+        pkcs1::DecodeRsaPublicKey,
+        pkcs1v15::{Signature, VerifyingKey},
+        signature::Verifier,
+    };
+
+    let public_key = RsaPublicKey::from_pkcs1_der(public_key).map_err(|_| TlsError::DecodeError)?;
+    let verifying_key = VerifyingKey::<Hash>::new(public_key);
+    let signature = Signature::try_from(signature).map_err(|_| TlsError::DecodeError)?;
+    Ok(verifying_key.verify(message, &signature).is_ok())
+}
+
+/// Verify an Ed448 `signature` of `message`, in the pure mode of RFC 8032 with an empty context.
+#[cfg(feature = "ed448")]
+fn verify_ed448(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<bool, TlsError> {
+    use ed448_goldilocks_plus::{Signature, VerifyingKey};
+
+    let public_key = public_key.try_into().map_err(|_| TlsError::DecodeError)?;
+    let verifying_key = VerifyingKey::from_bytes(public_key).map_err(|_| TlsError::DecodeError)?;
+    let signature = Signature::from_slice(signature).map_err(|_| TlsError::DecodeError)?;
+    Ok(verifying_key.verify_raw(&signature, message).is_ok())
+}
+
+/// The brainpool curves of RFC 8734 that ECDSA signatures are verified on.
+#[cfg(feature = "brainpool")]
+#[derive(Clone, Copy)]
+enum Brainpool {
+    P256r1,
+    P384r1,
+    P512r1,
+}
+
+#[cfg(feature = "brainpool")]
+impl Brainpool {
+    /// The brainpool curve of the public key of `certificate`, if any.
+    fn of(certificate: &DecodedCertificate) -> Option<Self> {
+        let curve = certificate
+            .tbs_certificate
+            .subject_public_key_info
+            .algorithm
+            .parameters?
+            .decode_as::<der::asn1::ObjectIdentifier>()
+            .ok()?;
+        match curve {
+            BRAINPOOL_P256R1 => Some(Self::P256r1),
+            BRAINPOOL_P384R1 => Some(Self::P384r1),
+            BRAINPOOL_P512R1 => Some(Self::P512r1),
+            _ => None,
+        }
+    }
+
+    /// Verify a `CertificateVerify` signature of `message` with one of the brainpool schemes.
+    fn verify_scheme(
+        verify: &CertificateVerifyRef,
+        public_key: &[u8],
+        message: &[u8],
+    ) -> Result<bool, TlsError> {
+        match verify.signature_scheme {
+            SignatureScheme::Sha256BrainpoolP256r1 => {
+                Self::P256r1.verify::<sha2::Sha256>(public_key, message, verify.signature)
+            }
+            SignatureScheme::Sha384BrainpoolP384r1 => {
+                Self::P384r1.verify::<sha2::Sha384>(public_key, message, verify.signature)
+            }
+            SignatureScheme::Sha512BrainpoolP512r1 => {
+                Self::P512r1.verify::<sha2::Sha512>(public_key, message, verify.signature)
+            }
+            _ => Err(TlsError::InvalidSignatureScheme),
+        }
+    }
+
+    /// Verify the DER encoded ECDSA `signature` of `message`, hashed with `Hash`.
+    fn verify<Hash: Digest>(
+        self,
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, TlsError> {
+        use brainpool_ecdsa::{Signature, VerifyingKey, signature::hazmat::PrehashVerifier};
+
+        let prehash = Hash::digest(message);
+        match self {
+            Self::P256r1 => {
+                let verifying_key =
+                    VerifyingKey::<bp256::BrainpoolP256r1>::from_sec1_bytes(public_key)
+                        .map_err(|_| TlsError::DecodeError)?;
+                let signature = Signature::from_der(signature)
+                    .map_err(|_| TlsError::ParseError(ParseError::InvalidData))?;
+                Ok(verifying_key.verify_prehash(&prehash, &signature).is_ok())
+            }
+            Self::P384r1 => {
+                let verifying_key =
+                    VerifyingKey::<bp384::BrainpoolP384r1>::from_sec1_bytes(public_key)
+                        .map_err(|_| TlsError::DecodeError)?;
+                let signature = Signature::from_der(signature)
+                    .map_err(|_| TlsError::ParseError(ParseError::InvalidData))?;
+                Ok(verifying_key.verify_prehash(&prehash, &signature).is_ok())
+            }
+            Self::P512r1 => {
+                let verifying_key =
+                    VerifyingKey::<crate::bp512::BrainpoolP512r1>::from_sec1_bytes(public_key)
+                        .map_err(|_| TlsError::DecodeError)?;
+                let signature = Signature::from_der(signature)
+                    .map_err(|_| TlsError::ParseError(ParseError::InvalidData))?;
+                Ok(verifying_key.verify_prehash(&prehash, &signature).is_ok())
+            }
+        }
+    }
+}
+
+/// Verify an SM2 `signature` of `message` with the distinguishing identifier `distid`. The
+/// signature is DER encoded like an ECDSA signature.
+#[cfg(feature = "shangmi")]
+fn verify_sm2(
+    distid: &str,
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<bool, TlsError> {
+    use sm2::dsa::{Signature, VerifyingKey, signature::Verifier};
+
+    let verifying_key =
+        VerifyingKey::from_sec1_bytes(distid, public_key).map_err(|_| TlsError::DecodeError)?;
+    let (r, s) = ecdsa::Signature::<sm2::Sm2>::from_der(signature)
+        .map_err(|_| TlsError::ParseError(ParseError::InvalidData))?
+        .split_bytes();
+    let signature =
+        Signature::from_scalars(r, s).map_err(|_| TlsError::ParseError(ParseError::InvalidData))?;
+    Ok(verifying_key.verify(message, &signature).is_ok())
+}
+
 fn get_certificate_tlv_bytes<'a>(input: &[u8]) -> der::Result<&[u8]> {
     use der::{Decode, Reader, SliceReader};
 
@@ -334,6 +610,25 @@ fn verify_certificate(
             get_certificate_tlv_bytes(certificate).map_err(|_| TlsError::DecodeError)?;
 
         match parsed_certificate.signature_algorithm {
+            // RFC 8734: brainpool keys sign with the same ECDSA algorithms as the NIST curves
+            #[cfg(feature = "brainpool")]
+            algorithm @ (ECDSA_SHA256 | ECDSA_SHA384 | ECDSA_SHA512)
+                if Brainpool::of(&ca_certificate).is_some() =>
+            {
+                let curve = Brainpool::of(&ca_certificate).ok_or(TlsError::DecodeError)?;
+                let signature = parsed_certificate
+                    .signature
+                    .as_bytes()
+                    .ok_or(TlsError::ParseError(ParseError::InvalidData))?;
+
+                verified = if algorithm == ECDSA_SHA256 {
+                    curve.verify::<sha2::Sha256>(ca_public_key, certificate_data, signature)?
+                } else if algorithm == ECDSA_SHA384 {
+                    curve.verify::<sha2::Sha384>(ca_public_key, certificate_data, signature)?
+                } else {
+                    curve.verify::<sha2::Sha512>(ca_public_key, certificate_data, signature)?
+                };
+            }
             ECDSA_SHA256 => {
                 use p256::ecdsa::{Signature, VerifyingKey, signature::Verifier};
                 let verifying_key = VerifyingKey::from_sec1_bytes(ca_public_key)
@@ -382,6 +677,28 @@ fn verify_certificate(
 
                 verified = verifying_key.verify(certificate_data, &signature).is_ok();
             }
+            #[cfg(feature = "ed448")]
+            ED448 => {
+                let signature = parsed_certificate
+                    .signature
+                    .as_bytes()
+                    .ok_or(TlsError::ParseError(ParseError::InvalidData))?;
+                verified = verify_ed448(ca_public_key, certificate_data, signature)?;
+            }
+            #[cfg(feature = "shangmi")]
+            SM2_SM3 => {
+                let signature = parsed_certificate
+                    .signature
+                    .as_bytes()
+                    .ok_or(TlsError::ParseError(ParseError::InvalidData))?;
+                // GM/T 0009: certificates are signed with the default distinguishing identifier
+                verified = verify_sm2(
+                    "1234567812345678",
+                    ca_public_key,
+                    certificate_data,
+                    signature,
+                )?;
+            }
             #[cfg(feature = "rsa")]
             a if a == RSA_PKCS1_SHA256 => {
                 use rsa::{
@@ -689,4 +1006,89 @@ mod tests {
         assert!(!tls_hostname_match_impl(b"example!.com", b"example!.com"));
         assert!(!tls_hostname_match_impl(b"example.com", b"exa mple.com"));
     }
+
+    #[cfg(feature = "shangmi")]
+    #[test]
+    fn sm2_signature() {
+        use super::{CertificateVerifyRef, SignatureScheme, TlsError, verify_signature};
+        use sm2::dsa::{Signature, SigningKey, signature::Signer};
+
+        let secret_key = sm2::SecretKey::random(&mut rand::rngs::OsRng);
+        let public_key = sm2::EncodedPoint::from(secret_key.public_key());
+        let message = b"TLS 1.3, server CertificateVerify";
+
+        let sign = |distid| {
+            let signature: Signature = SigningKey::new(distid, &secret_key).unwrap().sign(message);
+            ecdsa::Signature::<sm2::Sm2>::from_scalars(signature.r_bytes(), signature.s_bytes())
+                .unwrap()
+                .to_der()
+        };
+        let verify = |signature: &[u8]| {
+            verify_signature(
+                message,
+                public_key.as_bytes(),
+                &CertificateVerifyRef {
+                    signature_scheme: SignatureScheme::Sm2sigSm3,
+                    signature,
+                },
+            )
+        };
+
+        assert!(verify(sign("TLSv1.3").as_bytes()).is_ok());
+        assert!(matches!(
+            verify(sign("1234567812345678").as_bytes()),
+            Err(TlsError::InvalidSignature)
+        ));
+    }
+
+    #[cfg(feature = "rsa")]
+    #[test]
+    fn tls13_rejects_pkcs1() {
+        use super::{CertVerifier, CertificateVerifyRef, SignatureScheme, TlsError};
+        use crate::config::{Certificate, NoClock, TlsVerifier};
+
+        let mut verifier =
+            CertVerifier::<crate::Aes128GcmSha256, NoClock, 4096>::new(Certificate::X509(&[]));
+        assert!(matches!(
+            verifier.verify_signature(CertificateVerifyRef {
+                signature_scheme: SignatureScheme::RsaPkcs1Sha256,
+                signature: &[],
+            }),
+            Err(TlsError::InvalidSignatureScheme)
+        ));
+    }
+
+    #[cfg(feature = "brainpool")]
+    #[test]
+    fn brainpool_signature() {
+        use super::{CertificateVerifyRef, SignatureScheme, TlsError, verify_signature};
+        use brainpool_ecdsa::{Signature, SigningKey, signature::hazmat::PrehashSigner};
+        use sha2::{Digest, Sha256};
+
+        let signing_key = SigningKey::<bp256::BrainpoolP256r1>::from_slice(&[7; 32]).unwrap();
+        let public_key = signing_key.verifying_key().to_sec1_point(false);
+        let message = b"TLS 1.3, server CertificateVerify";
+
+        let sign = |message: &[u8]| {
+            let signature: Signature<_> =
+                signing_key.sign_prehash(&Sha256::digest(message)).unwrap();
+            signature.to_der()
+        };
+        let verify = |signature: &[u8]| {
+            verify_signature(
+                message,
+                public_key.as_bytes(),
+                &CertificateVerifyRef {
+                    signature_scheme: SignatureScheme::Sha256BrainpoolP256r1,
+                    signature,
+                },
+            )
+        };
+
+        assert!(verify(sign(message).as_bytes()).is_ok());
+        assert!(matches!(
+            verify(sign(b"another message").as_bytes()),
+            Err(TlsError::InvalidSignature)
+        ));
+    }
 }
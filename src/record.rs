@@ -4,8 +4,10 @@ use crate::change_cipher_spec::ChangeCipherSpec;
 use crate::config::{TlsCipherSuite, TlsConfig};
 use crate::content_types::ContentType;
 use crate::handshake::client_hello::ClientHello;
-use crate::handshake::{ClientHandshake, ServerHandshake};
+use crate::handshake::{IncomingHandshake, OutgoingHandshake};
+use crate::key_exchange::EphemeralSecret;
 use crate::key_schedule::WriteKeySchedule;
+use crate::record_reader::{Fragment, reassemble_handshake};
 use crate::{CryptoProvider, buffer::CryptoBuffer};
 use crate::{
     alert::{Alert, AlertDescription, AlertLevel},
@@ -21,8 +23,10 @@ where
     // N: ArrayLength<u8>,
     CipherSuite: TlsCipherSuite,
 {
-    Handshake(ClientHandshake<'config, 'a, CipherSuite>, Encrypted),
+    Handshake(OutgoingHandshake<'config, 'a, CipherSuite>, Encrypted),
     Alert(Alert, Encrypted),
+    /// Sent by TLS 1.2 connections, and in the TLS 1.3 middlebox compatibility mode.
+    ChangeCipherSpec(ChangeCipherSpec),
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -31,6 +35,7 @@ pub enum ClientRecordHeader {
     Handshake(Encrypted),
     Alert(Encrypted),
     ApplicationData,
+    ChangeCipherSpec,
 }
 
 impl ClientRecordHeader {
@@ -40,16 +45,18 @@ impl ClientRecordHeader {
                 encrypted
             }
             ClientRecordHeader::ApplicationData => true,
+            ClientRecordHeader::ChangeCipherSpec => false,
         }
     }
 
     pub fn header_content_type(self) -> ContentType {
         match self {
             Self::Handshake(false) => ContentType::Handshake,
-            Self::Alert(false) => ContentType::ChangeCipherSpec,
+            Self::Alert(false) => ContentType::Alert,
             Self::Handshake(true) | Self::Alert(true) | Self::ApplicationData => {
                 ContentType::ApplicationData
             }
+            Self::ChangeCipherSpec => ContentType::ChangeCipherSpec,
         }
     }
 
@@ -58,12 +65,16 @@ impl ClientRecordHeader {
             Self::Handshake(_) => ContentType::Handshake,
             Self::Alert(_) => ContentType::Alert,
             Self::ApplicationData => ContentType::ApplicationData,
+            Self::ChangeCipherSpec => ContentType::ChangeCipherSpec,
         }
     }
 
     pub fn version(self) -> [u8; 2] {
         match self {
-            Self::Handshake(true) | Self::Alert(true) | Self::ApplicationData => [0x03, 0x03],
+            Self::Handshake(true)
+            | Self::Alert(true)
+            | Self::ApplicationData
+            | Self::ChangeCipherSpec => [0x03, 0x03],
             Self::Handshake(false) | Self::Alert(false) => [0x03, 0x01],
         }
     }
@@ -87,20 +98,22 @@ where
         match self {
             ClientRecord::Handshake(_, encrypted) => ClientRecordHeader::Handshake(*encrypted),
             ClientRecord::Alert(_, encrypted) => ClientRecordHeader::Alert(*encrypted),
+            ClientRecord::ChangeCipherSpec(_) => ClientRecordHeader::ChangeCipherSpec,
         }
     }
 
     pub fn client_hello<Provider>(
         config: &'config TlsConfig<'config>,
         provider: &mut Provider,
-    ) -> Self
+        secret: Option<EphemeralSecret>,
+    ) -> Result<Self, TlsError>
     where
         Provider: CryptoProvider,
     {
-        ClientRecord::Handshake(
-            ClientHandshake::ClientHello(ClientHello::new(config, provider)),
+        Ok(ClientRecord::Handshake(
+            OutgoingHandshake::ClientHello(ClientHello::new(config, provider, secret)?),
             false,
-        )
+        ))
     }
 
     pub fn close_notify(opened: bool) -> Self {
@@ -116,6 +129,7 @@ where
         match self {
             ClientRecord::Handshake(handshake, _) => handshake.encode(buf)?,
             ClientRecord::Alert(alert, _) => alert.encode(buf)?,
+            ClientRecord::ChangeCipherSpec(ccs) => ccs.encode(buf)?,
         };
 
         Ok(buf.len() - record_length_marker)
@@ -132,7 +146,7 @@ where
                 handshake.finalize(buf, transcript, write_key_schedule)
             }
             ClientRecord::Handshake(_, true) => {
-                ClientHandshake::<CipherSuite>::finalize_encrypted(buf, transcript);
+                OutgoingHandshake::<CipherSuite>::finalize_encrypted(buf, transcript);
                 Ok(())
             }
             _ => Ok(()),
@@ -144,7 +158,7 @@ where
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[allow(clippy::large_enum_variant)]
 pub enum ServerRecord<'a, CipherSuite: TlsCipherSuite> {
-    Handshake(ServerHandshake<'a, CipherSuite>),
+    Handshake(IncomingHandshake<'a, CipherSuite>),
     ChangeCipherSpec(ChangeCipherSpec),
     Alert(Alert),
     ApplicationData(ApplicationData<'a>),
@@ -189,36 +203,61 @@ impl<'a, CipherSuite: TlsCipherSuite> ServerRecord<'a, CipherSuite> {
         }
     }
 
+    /// Decode a record, whose `data` starts with the bytes held of a handshake message split
+    /// across the previous records.
+    ///
+    /// Returns `None` if the record only holds more of the split message.
     pub fn decode(
         header: RecordHeader,
         data: &'a mut [u8],
+        fragment: &'a mut Fragment,
         digest: &mut CipherSuite::Hash,
-    ) -> Result<ServerRecord<'a, CipherSuite>, TlsError> {
-        assert_eq!(header.content_length(), data.len());
+    ) -> Result<Option<ServerRecord<'a, CipherSuite>>, TlsError> {
+        assert_eq!(fragment.held + header.content_length(), data.len());
         match header.content_type() {
-            ContentType::Invalid => Err(TlsError::Unimplemented),
-            ContentType::ChangeCipherSpec => Ok(ServerRecord::ChangeCipherSpec(
+            // ACKs only exist in DTLS 1.3
+            ContentType::Invalid | ContentType::Ack => Err(TlsError::UnknownContentType),
+            // Handshake messages must not be interleaved with other records (RFC 8446, Section 5.1)
+            ContentType::ChangeCipherSpec | ContentType::Alert if fragment.held > 0 => {
+                Err(TlsError::InvalidRecord)
+            }
+            ContentType::ChangeCipherSpec => Ok(Some(ServerRecord::ChangeCipherSpec(
                 ChangeCipherSpec::read(data)?,
-            )),
+            ))),
             ContentType::Alert => {
                 let mut parse = ParseBuffer::new(data);
                 let alert = Alert::parse(&mut parse)?;
-                Ok(ServerRecord::Alert(alert))
+                Ok(Some(ServerRecord::Alert(alert)))
             }
             ContentType::Handshake => {
-                let mut parse = ParseBuffer::new(data);
-                Ok(ServerRecord::Handshake(ServerHandshake::read(
+                let len = data.len();
+                let messages = reassemble_handshake(data, len, fragment)?;
+                if messages.is_empty() {
+                    return Ok(None);
+                }
+                let mut parse = ParseBuffer::new(messages);
+                Ok(Some(ServerRecord::Handshake(IncomingHandshake::read(
                     &mut parse, digest,
-                )?))
-            }
-            ContentType::ApplicationData => {
-                let buf = CryptoBuffer::wrap_with_pos(data, data.len());
-                Ok(ServerRecord::ApplicationData(ApplicationData::new(
-                    buf, header,
-                )))
+                )?)))
             }
+            ContentType::ApplicationData => Ok(Some(Self::undecoded(header, data, fragment))),
         }
     }
 
+    /// Wrap the record `data` as is, to be decrypted or decoded later. The bytes held of a split
+    /// handshake message are skipped.
+    pub(crate) fn undecoded(
+        header: RecordHeader,
+        data: &'a mut [u8],
+        fragment: &'a mut Fragment,
+    ) -> Self {
+        let buf = CryptoBuffer::wrap_with_pos(data, data.len()).offset(fragment.held);
+        ServerRecord::ApplicationData(ApplicationData {
+            header,
+            data: buf,
+            fragment: Some(fragment),
+        })
+    }
+
     //pub fn parse<D: Digest>(buf: &[u8]) -> Result<Self, TlsError> {}
 }
@@ -0,0 +1,40 @@
+/// The storage of a record buffer: a slice borrowed for `'a`, or an array owned by the
+/// connection, see [`owned::TlsConnection`](crate::owned::TlsConnection).
+///
+/// This trait is sealed and cannot be implemented outside of embedded-tls.
+pub trait RecordBuffer<'a>: AsRef<[u8]> + AsMut<[u8]> + sealed::Sealed<'a> {}
+
+impl<'a> RecordBuffer<'a> for &'a mut [u8] {}
+
+impl<const N: usize> RecordBuffer<'_> for [u8; N] {}
+
+/// The record buffers of a connection borrowing its buffers, returned by `into_parts` to be
+/// passed back to `from_parts`.
+#[derive(Debug)]
+pub enum RecordBuffers<'a> {
+    /// The read and write buffers.
+    FullDuplex(&'a mut [u8], &'a mut [u8]),
+    /// The single buffer of a half-duplex connection.
+    HalfDuplex(&'a mut [u8]),
+}
+
+pub(crate) mod sealed {
+    pub trait Sealed<'a> {
+        /// Replace the borrowed slice, which the two directions of a half-duplex connection
+        /// lend each other, returning the previous one. Owned storage is never shared, and
+        /// returns `None`.
+        fn replace(&mut self, buf: &'a mut [u8]) -> Option<&'a mut [u8]>;
+    }
+
+    impl<'a> Sealed<'a> for &'a mut [u8] {
+        fn replace(&mut self, buf: &'a mut [u8]) -> Option<&'a mut [u8]> {
+            Some(core::mem::replace(self, buf))
+        }
+    }
+
+    impl<'a, const N: usize> Sealed<'a> for [u8; N] {
+        fn replace(&mut self, _buf: &'a mut [u8]) -> Option<&'a mut [u8]> {
+            None
+        }
+    }
+}
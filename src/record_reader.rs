@@ -1,19 +1,37 @@
+use crate::content_types::ContentType;
+use crate::handshake::HandshakeType;
 use crate::key_schedule::ReadKeySchedule;
+#[cfg(feature = "tls12")]
+use crate::key_schedule::Tls12Records;
 use embedded_io::{Error, Read as BlockingRead};
 use embedded_io_async::Read as AsyncRead;
+use zeroize::Zeroize;
 
 use crate::{
     TlsError,
     config::TlsCipherSuite,
     record::{RecordHeader, ServerRecord},
+    record_buffer::RecordBuffer,
 };
 
-pub struct RecordReader<'a> {
-    pub(crate) buf: &'a mut [u8],
+/// Reassembles the records received from the server.
+///
+/// Bytes are received into [`Self::rx_buffer`], which only ever asks for the rest of the
+/// current record, so nothing beyond it is read from the transport.
+///
+/// Handshake messages split across records are reassembled in the same buffer: the start of the
+/// message is held right before the next record, so a message must fit in the buffer along with
+/// the record completing it. Streamed `Certificate` messages only need their largest entry to fit.
+pub struct RecordReader<B> {
+    pub(crate) buf: B,
     /// The number of decoded bytes in the buffer
     decoded: usize,
     /// The number of read but not yet decoded bytes in the buffer
     pending: usize,
+    /// The split handshake message held right before the pending bytes
+    fragment: Fragment,
+    /// The header of the record being received
+    header: PendingHeader,
 }
 
 pub struct RecordReaderBorrowMut<'a> {
@@ -22,230 +40,354 @@ pub struct RecordReaderBorrowMut<'a> {
     decoded: &'a mut usize,
     /// The number of read but not yet decoded bytes in the buffer
     pending: &'a mut usize,
+    /// The split handshake message held right before the pending bytes
+    fragment: &'a mut Fragment,
+    /// The header of the record being received
+    header: &'a mut PendingHeader,
 }
 
-impl<'a> RecordReader<'a> {
-    pub fn new(buf: &'a mut [u8]) -> Self {
-        if buf.len() < 16640 {
-            warn!("Read buffer is smaller than 16640 bytes, which may cause problems!");
-        }
+/// A handshake message split across records, whose start is held in the read buffer.
+#[derive(Default)]
+pub(crate) struct Fragment {
+    /// The number of bytes held
+    pub(crate) held: usize,
+    /// The number of bytes of the streamed `Certificate` message that were not processed yet,
+    /// held bytes included
+    streamed: usize,
+    /// Whether the entries of `Certificate` messages are processed as they are received, so only
+    /// the entry being received is held instead of the whole message
+    stream_certificates: bool,
+}
+
+/// The handshake messages of a record, split by [`reassemble_streamed_handshake`].
+pub(crate) struct HandshakeMessages<'a> {
+    /// The entries continuing the `Certificate` message streamed from the previous records
+    pub(crate) continued: &'a [u8],
+    /// Whether `continued` completes the streamed `Certificate` message
+    pub(crate) continued_last: bool,
+    /// The complete handshake messages
+    pub(crate) complete: &'a [u8],
+    /// The start of a `Certificate` message streamed from this record: its header, request
+    /// context and the entries received so far
+    pub(crate) started: &'a [u8],
+}
+
+/// A record header, which is received apart from the record content.
+#[derive(Default)]
+struct PendingHeader {
+    buf: [u8; RecordHeader::LEN],
+    len: usize,
+}
+
+impl PendingHeader {
+    fn is_complete(&self) -> bool {
+        self.len == RecordHeader::LEN
+    }
+
+    fn content_length(&self) -> usize {
+        u16::from_be_bytes([self.buf[3], self.buf[4]]) as usize
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> RecordReader<B> {
+    pub fn new(buf: B) -> Self {
         Self {
             buf,
             decoded: 0,
             pending: 0,
+            fragment: Fragment::default(),
+            header: PendingHeader::default(),
         }
     }
 
     pub fn reborrow_mut(&mut self) -> RecordReaderBorrowMut<'_> {
         RecordReaderBorrowMut {
-            buf: self.buf,
+            buf: self.buf.as_mut(),
             decoded: &mut self.decoded,
             pending: &mut self.pending,
+            fragment: &mut self.fragment,
+            header: &mut self.header,
         }
     }
 
-    pub async fn read<'m, CipherSuite: TlsCipherSuite>(
-        &'m mut self,
-        transport: &mut impl AsyncRead,
-        key_schedule: &mut ReadKeySchedule<CipherSuite>,
-    ) -> Result<ServerRecord<'m, CipherSuite>, TlsError> {
-        read(
-            self.buf,
-            &mut self.decoded,
-            &mut self.pending,
-            transport,
-            key_schedule,
-        )
-        .await
+    /// Whether a whole record has been received.
+    pub fn is_complete(&self) -> bool {
+        self.header.is_complete() && self.pending >= self.header.content_length()
     }
 
-    pub fn read_blocking<'m, CipherSuite: TlsCipherSuite>(
-        &'m mut self,
-        transport: &mut impl BlockingRead,
-        key_schedule: &mut ReadKeySchedule<CipherSuite>,
-    ) -> Result<ServerRecord<'m, CipherSuite>, TlsError> {
-        read_blocking(
-            self.buf,
-            &mut self.decoded,
-            &mut self.pending,
-            transport,
-            key_schedule,
-        )
+    /// The buffer to receive the rest of the current record into, which is empty once the
+    /// record is complete.
+    pub fn rx_buffer(&mut self) -> &mut [u8] {
+        self.reborrow_mut().into_rx_buffer()
     }
-}
 
-impl RecordReaderBorrowMut<'_> {
-    pub async fn read<'m, CipherSuite: TlsCipherSuite>(
+    /// Zeroize the buffer and discard the records being received.
+    pub(crate) fn reset(&mut self) {
+        self.buf.as_mut().zeroize();
+        self.decoded = 0;
+        self.pending = 0;
+        self.fragment.held = 0;
+        self.fragment.streamed = 0;
+        self.header = PendingHeader::default();
+    }
+
+    /// Whether no record is being received, so the buffer only holds decoded records.
+    pub(crate) fn is_idle(&self) -> bool {
+        self.pending == 0 && self.header.len == 0 && self.fragment.held == 0
+    }
+
+    /// `len` bytes were received into [`Self::rx_buffer`].
+    ///
+    /// Once the header is complete, the record is made contiguous in the buffer, which may move
+    /// it over the records that were decoded before: these must no longer be in use.
+    pub fn receive(&mut self, len: usize) -> Result<(), TlsError> {
+        self.reborrow_mut().receive(len)
+    }
+
+    /// Process the entries of the `Certificate` messages as they are received, see
+    /// [`TlsVerifier::streams_certificates`](crate::config::TlsVerifier::streams_certificates).
+    pub(crate) fn set_certificate_streaming(&mut self, enabled: bool) {
+        self.fragment.stream_certificates = enabled;
+    }
+
+    /// Decode the received record, if it is complete.
+    ///
+    /// Returns `None` if the record is incomplete, or only holds the start of a handshake
+    /// message.
+    pub fn next_record<'m, CipherSuite: TlsCipherSuite>(
         &'m mut self,
-        transport: &mut impl AsyncRead,
         key_schedule: &mut ReadKeySchedule<CipherSuite>,
-    ) -> Result<ServerRecord<'m, CipherSuite>, TlsError> {
-        read(
-            self.buf,
-            self.decoded,
-            self.pending,
-            transport,
-            key_schedule,
-        )
-        .await
+    ) -> Result<Option<ServerRecord<'m, CipherSuite>>, TlsError> {
+        self.reborrow_mut().into_next_record(key_schedule)
     }
 
+    #[cfg(test)]
     pub fn read_blocking<'m, CipherSuite: TlsCipherSuite>(
         &'m mut self,
         transport: &mut impl BlockingRead,
         key_schedule: &mut ReadKeySchedule<CipherSuite>,
     ) -> Result<ServerRecord<'m, CipherSuite>, TlsError> {
-        read_blocking(
-            self.buf,
-            self.decoded,
-            self.pending,
-            transport,
-            key_schedule,
-        )
+        self.reborrow_mut()
+            .into_read_blocking(transport, key_schedule)
     }
 }
 
-pub async fn read<'m, CipherSuite: TlsCipherSuite>(
-    buf: &'m mut [u8],
-    decoded: &mut usize,
-    pending: &mut usize,
-    transport: &mut impl AsyncRead,
-    key_schedule: &mut ReadKeySchedule<CipherSuite>,
-) -> Result<ServerRecord<'m, CipherSuite>, TlsError> {
-    let header: RecordHeader = next_record_header(transport).await?;
-
-    advance(buf, decoded, pending, transport, header.content_length()).await?;
-    consume(
-        buf,
-        decoded,
-        pending,
-        header,
-        key_schedule.transcript_hash(),
-    )
-}
+impl<'a, B: RecordBuffer<'a>> RecordReader<B> {
+    /// Take the buffer out, to lend it to the write side of a half-duplex connection. The
+    /// decoded records are discarded.
+    pub(crate) fn take_buf(&mut self) -> &'a mut [u8] {
+        self.decoded = 0;
+        self.pending = 0;
+        self.fragment.held = 0;
+        self.header = PendingHeader::default();
+        self.buf.replace(&mut []).unwrap_or_default()
+    }
 
-pub fn read_blocking<'m, CipherSuite: TlsCipherSuite>(
-    buf: &'m mut [u8],
-    decoded: &mut usize,
-    pending: &mut usize,
-    transport: &mut impl BlockingRead,
-    key_schedule: &mut ReadKeySchedule<CipherSuite>,
-) -> Result<ServerRecord<'m, CipherSuite>, TlsError> {
-    let header: RecordHeader = next_record_header_blocking(transport)?;
-
-    advance_blocking(buf, decoded, pending, transport, header.content_length())?;
-    consume(
-        buf,
-        decoded,
-        pending,
-        header,
-        key_schedule.transcript_hash(),
-    )
+    /// Give the buffer taken by [`Self::take_buf`] back. Owned storage, which is never
+    /// shared, keeps its own buffer.
+    pub(crate) fn set_buf(&mut self, buf: &'a mut [u8]) {
+        self.buf.replace(buf);
+    }
 }
 
-async fn next_record_header(transport: &mut impl AsyncRead) -> Result<RecordHeader, TlsError> {
-    let mut buf: [u8; RecordHeader::LEN] = [0; RecordHeader::LEN];
-    let mut total_read: usize = 0;
-    while total_read != RecordHeader::LEN {
-        let read: usize = transport
-            .read(&mut buf[total_read..])
-            .await
-            .map_err(|e| TlsError::Io(e.kind()))?;
-        if read == 0 {
-            return Err(TlsError::IoError);
+impl<'a> RecordReaderBorrowMut<'a> {
+    fn reborrow_mut(&mut self) -> RecordReaderBorrowMut<'_> {
+        RecordReaderBorrowMut {
+            buf: self.buf,
+            decoded: self.decoded,
+            pending: self.pending,
+            fragment: self.fragment,
+            header: self.header,
         }
-        total_read += read;
     }
-    RecordHeader::decode(buf)
-}
 
-fn next_record_header_blocking(
-    transport: &mut impl BlockingRead,
-) -> Result<RecordHeader, TlsError> {
-    let mut buf: [u8; RecordHeader::LEN] = [0; RecordHeader::LEN];
-    let mut total_read: usize = 0;
-    while total_read != RecordHeader::LEN {
-        let read: usize = transport
-            .read(&mut buf[total_read..])
-            .map_err(|e| TlsError::Io(e.kind()))?;
-        if read == 0 {
-            return Err(TlsError::IoError);
+    fn is_complete(&self) -> bool {
+        self.header.is_complete() && *self.pending >= self.header.content_length()
+    }
+
+    /// Whether the received record only holds more of a split handshake message, so it does not
+    /// decode to a server record.
+    fn is_fragment<CipherSuite: TlsCipherSuite>(
+        &self,
+        #[cfg_attr(not(feature = "tls12"), allow(unused_variables))] key_schedule: &ReadKeySchedule<
+            CipherSuite,
+        >,
+    ) -> bool {
+        // TLS 1.2 handshake records are reassembled by the TLS 1.2 state machine
+        #[cfg(feature = "tls12")]
+        if key_schedule.tls12_records().is_some() {
+            return false;
         }
-        total_read += read;
+
+        let start = *self.decoded - self.fragment.held;
+        let end = *self.decoded + self.header.content_length();
+        ContentType::of(self.header.buf[0]) == Some(ContentType::Handshake)
+            && complete_messages_len(&self.buf[start..end]) == 0
     }
-    RecordHeader::decode(buf)
-}
 
-async fn advance(
-    buf: &mut [u8],
-    decoded: &mut usize,
-    pending: &mut usize,
-    transport: &mut impl AsyncRead,
-    amount: usize,
-) -> Result<(), TlsError> {
-    ensure_contiguous(buf, decoded, pending, amount)?;
-
-    let mut remain: usize = amount;
-    while *pending < amount {
-        let read = transport
-            .read(&mut buf[*decoded + *pending..][..remain])
-            .await
-            .map_err(|e| TlsError::Io(e.kind()))?;
-        if read == 0 {
-            return Err(TlsError::IoError);
+    fn into_rx_buffer(self) -> &'a mut [u8] {
+        if !self.header.is_complete() {
+            return &mut self.header.buf[self.header.len..];
         }
-        remain -= read;
-        *pending += read;
+
+        // The record was made contiguous in the buffer when its header was received
+        let end = usize::min(*self.decoded + self.header.content_length(), self.buf.len());
+        let start = usize::min(*self.decoded + *self.pending, end);
+        &mut self.buf[start..end]
     }
 
-    Ok(())
-}
+    fn receive(&mut self, len: usize) -> Result<(), TlsError> {
+        if self.header.is_complete() {
+            *self.pending += len;
+            return Ok(());
+        }
 
-fn advance_blocking(
-    buf: &mut [u8],
-    decoded: &mut usize,
-    pending: &mut usize,
-    transport: &mut impl BlockingRead,
-    amount: usize,
-) -> Result<(), TlsError> {
-    ensure_contiguous(buf, decoded, pending, amount)?;
-
-    let mut remain: usize = amount;
-    while *pending < amount {
-        let read = transport
-            .read(&mut buf[*decoded + *pending..][..remain])
-            .map_err(|e| TlsError::Io(e.kind()))?;
-        if read == 0 {
-            return Err(TlsError::IoError);
+        self.header.len += len;
+        if self.header.is_complete() {
+            let header = RecordHeader::decode(self.header.buf)?;
+            ensure_contiguous(
+                self.buf,
+                self.decoded,
+                self.pending,
+                self.fragment,
+                header.content_length(),
+            )?;
         }
-        remain -= read;
-        *pending += read;
+        Ok(())
     }
 
-    Ok(())
+    fn into_next_record<CipherSuite: TlsCipherSuite>(
+        self,
+        key_schedule: &mut ReadKeySchedule<CipherSuite>,
+    ) -> Result<Option<ServerRecord<'a, CipherSuite>>, TlsError> {
+        if !self.is_complete() {
+            return Ok(None);
+        }
+
+        let header = RecordHeader::decode(self.header.buf)?;
+        self.header.len = 0;
+        consume(
+            self.buf,
+            self.decoded,
+            self.pending,
+            self.fragment,
+            header,
+            key_schedule,
+        )
+    }
+
+    async fn into_read<CipherSuite: TlsCipherSuite>(
+        mut self,
+        transport: &mut impl AsyncRead,
+        key_schedule: &mut ReadKeySchedule<CipherSuite>,
+    ) -> Result<ServerRecord<'a, CipherSuite>, TlsError> {
+        loop {
+            while !self.is_complete() {
+                let read = transport
+                    .read(self.reborrow_mut().into_rx_buffer())
+                    .await
+                    .map_err(|e| TlsError::Io(e.kind()))?;
+                if read == 0 {
+                    return Err(TlsError::IoError);
+                }
+                self.receive(read)?;
+            }
+
+            if !self.is_fragment(key_schedule) {
+                break;
+            }
+            self.reborrow_mut().into_next_record(key_schedule)?;
+        }
+
+        self.into_next_record(key_schedule)?
+            .ok_or(TlsError::InternalError)
+    }
+
+    fn into_read_blocking<CipherSuite: TlsCipherSuite>(
+        mut self,
+        transport: &mut impl BlockingRead,
+        key_schedule: &mut ReadKeySchedule<CipherSuite>,
+    ) -> Result<ServerRecord<'a, CipherSuite>, TlsError> {
+        loop {
+            while !self.is_complete() {
+                let read = transport
+                    .read(self.reborrow_mut().into_rx_buffer())
+                    .map_err(|e| TlsError::Io(e.kind()))?;
+                if read == 0 {
+                    return Err(TlsError::IoError);
+                }
+                self.receive(read)?;
+            }
+
+            if !self.is_fragment(key_schedule) {
+                break;
+            }
+            self.reborrow_mut().into_next_record(key_schedule)?;
+        }
+
+        self.into_next_record(key_schedule)?
+            .ok_or(TlsError::InternalError)
+    }
+
+    pub async fn read<'m, CipherSuite: TlsCipherSuite>(
+        &'m mut self,
+        transport: &mut impl AsyncRead,
+        key_schedule: &mut ReadKeySchedule<CipherSuite>,
+    ) -> Result<ServerRecord<'m, CipherSuite>, TlsError> {
+        self.reborrow_mut().into_read(transport, key_schedule).await
+    }
+
+    pub fn read_blocking<'m, CipherSuite: TlsCipherSuite>(
+        &'m mut self,
+        transport: &mut impl BlockingRead,
+        key_schedule: &mut ReadKeySchedule<CipherSuite>,
+    ) -> Result<ServerRecord<'m, CipherSuite>, TlsError> {
+        self.reborrow_mut()
+            .into_read_blocking(transport, key_schedule)
+    }
 }
 
 fn consume<'m, CipherSuite: TlsCipherSuite>(
     buf: &'m mut [u8],
     decoded: &mut usize,
     pending: &mut usize,
+    fragment: &'m mut Fragment,
     header: RecordHeader,
-    digest: &mut CipherSuite::Hash,
-) -> Result<ServerRecord<'m, CipherSuite>, TlsError> {
+    key_schedule: &mut ReadKeySchedule<CipherSuite>,
+) -> Result<Option<ServerRecord<'m, CipherSuite>>, TlsError> {
     let content_len = header.content_length();
 
-    let slice = &mut buf[*decoded..][..content_len];
+    // The record is decoded along with the held start of a split handshake message
+    let slice = &mut buf[*decoded - fragment.held..][..fragment.held + content_len];
 
     *decoded += content_len;
     *pending -= content_len;
 
-    ServerRecord::decode(header, slice, digest)
+    // TLS 1.2 handshake records may hold several messages, and protected records keep their
+    // content type in the header, so they are decoded by the TLS 1.2 state machine instead.
+    #[cfg(feature = "tls12")]
+    match key_schedule.tls12_records() {
+        Some(Tls12Records::Plaintext) if header.content_type() == ContentType::Handshake => {
+            return Ok(Some(ServerRecord::undecoded(header, slice, fragment)));
+        }
+        // Handshake messages must not be interleaved with other records
+        Some(Tls12Records::Protected) if fragment.held > 0 => {
+            return Err(TlsError::InvalidRecord);
+        }
+        Some(Tls12Records::Protected) => {
+            return Ok(Some(ServerRecord::undecoded(header, slice, fragment)));
+        }
+        _ => {}
+    }
+
+    ServerRecord::decode(header, slice, fragment, key_schedule.transcript_hash())
 }
 
 fn ensure_contiguous(
     buf: &mut [u8],
     decoded: &mut usize,
     pending: &mut usize,
+    fragment: &Fragment,
     len: usize,
 ) -> Result<(), TlsError> {
     if *decoded + len > buf.len() {
@@ -257,18 +399,165 @@ fn ensure_contiguous(
             );
             return Err(TlsError::InsufficientSpace);
         }
-        buf.copy_within(*decoded..*decoded + *pending, 0);
-        *decoded = 0;
+        let start = *decoded - fragment.held;
+        if fragment.held + len > buf.len() {
+            let held = &buf[start..*decoded];
+            if fragment.streamed > 0 {
+                error!(
+                    "Certificate too large for buffer. Size: {} Buffer size: {}",
+                    entry_len(held).unwrap_or(fragment.held + len),
+                    buf.len()
+                );
+            } else {
+                error!(
+                    "Handshake message too large for buffer. Size: {} Buffer size: {}",
+                    message_len(held).unwrap_or(fragment.held + len),
+                    buf.len()
+                );
+            }
+            return Err(TlsError::InsufficientSpace);
+        }
+        buf.copy_within(start..*decoded + *pending, 0);
+        *decoded = fragment.held;
     }
 
     Ok(())
 }
 
+/// The length of the handshake message starting `data`, including its header.
+fn message_len(data: &[u8]) -> Option<usize> {
+    match data {
+        [_, a, b, c, ..] => Some(4 + u32::from_be_bytes([0, *a, *b, *c]) as usize),
+        _ => None,
+    }
+}
+
+/// The length of the complete handshake messages at the start of `data`.
+fn complete_messages_len(data: &[u8]) -> usize {
+    let mut len = 0;
+    while let Some(message_len) = message_len(&data[len..]) {
+        if len + message_len > data.len() {
+            break;
+        }
+        len += message_len;
+    }
+    len
+}
+
+/// The length of the TLS 1.3 certificate entry starting `data`, up to its extensions.
+fn entry_len(data: &[u8]) -> Option<usize> {
+    match data {
+        [a, b, c, ..] => Some(3 + u32::from_be_bytes([0, *a, *b, *c]) as usize + 2),
+        _ => None,
+    }
+}
+
+/// The length of the complete TLS 1.3 certificate entries at the start of `data`.
+fn complete_entries_len(data: &[u8]) -> usize {
+    let mut len = 0;
+    while let Some(entry_len) = entry_len(&data[len..]) {
+        let Some(&[a, b]) = data.get(len + entry_len - 2..len + entry_len) else {
+            break;
+        };
+        let entry_len = entry_len + u16::from_be_bytes([a, b]) as usize;
+        if len + entry_len > data.len() {
+            break;
+        }
+        len += entry_len;
+    }
+    len
+}
+
+/// The length of the start of the `Certificate` message starting `data`, up to its first
+/// entry, and the length of the whole message.
+fn certificate_start_len(data: &[u8]) -> Option<(usize, usize)> {
+    if data.first() != Some(&(HandshakeType::Certificate as u8)) {
+        return None;
+    }
+    let context_len = *data.get(4)? as usize;
+    let start_len = 4 + 1 + context_len + 3;
+    (data.len() >= start_len).then_some((start_len, message_len(data)?))
+}
+
+/// Hold the bytes of `data` from `start` to `len`, by moving them to the end of `data`, right
+/// before the next record in the read buffer.
+fn hold(data: &mut [u8], start: usize, len: usize, fragment: &mut Fragment) {
+    let end = data.len();
+    fragment.held = len - start;
+    data.copy_within(start..len, end - fragment.held);
+}
+
+/// Split the complete handshake messages off the first `len` bytes of `data`, which start with
+/// the held part of a split message.
+///
+/// The trailing incomplete message is held until the record completing it is received.
+pub(crate) fn reassemble_handshake<'d>(
+    data: &'d mut [u8],
+    len: usize,
+    fragment: &mut Fragment,
+) -> Result<&'d [u8], TlsError> {
+    // Only encrypted TLS 1.3 records stream certificates
+    if fragment.streamed > 0 {
+        return Err(TlsError::InvalidRecord);
+    }
+
+    let complete = complete_messages_len(&data[..len]);
+    hold(data, complete, len, fragment);
+    Ok(&data[..complete])
+}
+
+/// Split the handshake messages off the first `len` bytes of `data` like
+/// [`reassemble_handshake`], but stream the `Certificate` messages if enabled: their complete
+/// entries are split off as well, and only the trailing incomplete entry is held.
+pub(crate) fn reassemble_streamed_handshake<'d>(
+    data: &'d mut [u8],
+    len: usize,
+    fragment: &mut Fragment,
+) -> Result<HandshakeMessages<'d>, TlsError> {
+    let mut continued = 0;
+    let mut continued_last = false;
+    if fragment.streamed > 0 {
+        let available = usize::min(fragment.streamed, len);
+        continued = complete_entries_len(&data[..available]);
+        // The entries must end with the message
+        if fragment.streamed <= len && continued < available {
+            return Err(TlsError::DecodeError);
+        }
+        fragment.streamed -= continued;
+        continued_last = fragment.streamed == 0;
+    }
+
+    let mut complete = continued;
+    let mut started = continued;
+    if fragment.streamed == 0 {
+        complete += complete_messages_len(&data[continued..len]);
+        started = complete;
+        if fragment.stream_certificates
+            && let Some((start_len, message_len)) = certificate_start_len(&data[complete..len])
+        {
+            let entries_len = complete_entries_len(&data[complete + start_len..len]);
+            started += start_len + entries_len;
+            fragment.streamed = message_len - start_len - entries_len;
+        }
+    }
+
+    hold(data, started, len, fragment);
+    let (continued, rest) = data.split_at(continued);
+    let (complete, rest) = rest.split_at(complete - continued.len());
+    Ok(HandshakeMessages {
+        continued,
+        continued_last,
+        complete,
+        started: &rest[..started - complete.len() - continued.len()],
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use core::convert::Infallible;
 
     use super::*;
+    use crate::handshake::IncomingHandshake;
     use crate::{Aes128GcmSha256, content_types::ContentType, key_schedule::KeySchedule};
 
     struct ChunkRead<'a>(&'a [u8], usize);
@@ -476,4 +765,119 @@ mod tests {
             assert_eq!(0, reader.pending);
         }
     }
+
+    /// An application data record, followed by a `Finished` message split across three
+    /// handshake records.
+    fn split_finished() -> std::vec::Vec<u8> {
+        let mut records = std::vec![ContentType::ApplicationData as u8, 0x03, 0x03, 0x00, 0x0a];
+        records.extend_from_slice(&[0xaa; 10]);
+        records.extend_from_slice(&[ContentType::Handshake as u8, 0x03, 0x03, 0x00, 0x03]);
+        records.extend_from_slice(&[20, 0x00, 0x00]);
+        records.extend_from_slice(&[ContentType::Handshake as u8, 0x03, 0x03, 0x00, 21]);
+        records.push(32);
+        records.extend(0..20);
+        records.extend_from_slice(&[ContentType::Handshake as u8, 0x03, 0x03, 0x00, 12]);
+        records.extend(20..32);
+        records
+    }
+
+    #[test]
+    fn can_reassemble_handshake_message() {
+        let records = split_finished();
+        let mut transport = records.as_slice();
+
+        // The last record only fits once the held message is moved to the start
+        let mut buf = [0; 40];
+        let mut reader = RecordReader::new(&mut buf);
+        let mut key_schedule = KeySchedule::<Aes128GcmSha256>::new();
+
+        assert!(matches!(
+            reader.read_blocking(&mut transport, key_schedule.read_state()),
+            Ok(ServerRecord::ApplicationData(_))
+        ));
+
+        if let ServerRecord::Handshake(IncomingHandshake::Finished(finished)) = reader
+            .read_blocking(&mut transport, key_schedule.read_state())
+            .unwrap()
+        {
+            assert!(finished.verify.iter().copied().eq(0..32));
+        } else {
+            panic!("Wrong server record");
+        }
+
+        assert_eq!(0, reader.fragment.held);
+        assert!(transport.is_empty());
+    }
+
+    #[test]
+    fn can_stream_certificate_entries() {
+        // Certificate message with an empty context and two entries, followed by a Finished
+        let entry = |cert: u8| [0, 0, 3, cert, cert, cert, 0, 0];
+        let mut message = std::vec![11, 0, 0, 20, 0, 0, 0, 16];
+        message.extend_from_slice(&entry(1));
+        message.extend_from_slice(&entry(2));
+        let finished = [20, 0, 0, 1, 0xff];
+
+        let mut fragment = Fragment {
+            stream_certificates: true,
+            ..Fragment::default()
+        };
+
+        // The first record ends within the second entry
+        let mut data = message[..20].to_vec();
+        let messages = reassemble_streamed_handshake(&mut data, 20, &mut fragment).unwrap();
+        assert!(messages.continued.is_empty());
+        assert!(messages.complete.is_empty());
+        assert_eq!(&message[..16], messages.started);
+        assert_eq!(4, fragment.held);
+        assert_eq!(8, fragment.streamed);
+
+        // The next record completes the message and carries the Finished
+        let mut data = message[16..].to_vec();
+        data.extend_from_slice(&finished);
+        let len = data.len();
+        let messages = reassemble_streamed_handshake(&mut data, len, &mut fragment).unwrap();
+        assert_eq!(&entry(2), messages.continued);
+        assert!(messages.continued_last);
+        assert_eq!(&finished, messages.complete);
+        assert!(messages.started.is_empty());
+        assert_eq!(0, fragment.held);
+        assert_eq!(0, fragment.streamed);
+    }
+
+    #[test]
+    fn streamed_entries_must_end_with_message() {
+        let mut fragment = Fragment {
+            streamed: 4,
+            stream_certificates: true,
+            ..Fragment::default()
+        };
+
+        // A 3 byte entry left, where the message ends after 4 bytes
+        let mut data = [0, 0, 1, 0xff, 0, 0, 20, 0, 0];
+        assert!(matches!(
+            reassemble_streamed_handshake(&mut data, 9, &mut fragment),
+            Err(TlsError::DecodeError)
+        ));
+    }
+
+    #[test]
+    fn handshake_message_must_fit_buffer() {
+        let records = split_finished();
+        let mut transport = records.as_slice();
+
+        let mut buf = [0; 30];
+        let mut reader = RecordReader::new(&mut buf);
+        let mut key_schedule = KeySchedule::<Aes128GcmSha256>::new();
+
+        assert!(
+            reader
+                .read_blocking(&mut transport, key_schedule.read_state())
+                .is_ok()
+        );
+        assert!(matches!(
+            reader.read_blocking(&mut transport, key_schedule.read_state()),
+            Err(TlsError::InsufficientSpace)
+        ));
+    }
 }
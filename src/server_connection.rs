@@ -0,0 +1,865 @@
+use crate::alert::{AlertDescription, AlertLevel};
+use crate::client::TlsClient;
+use crate::config::{TlsCipherSuite, TlsConfig};
+use crate::connection::{decrypt_record, sign_certificate_verify};
+use crate::extensions::extension_data::alpn::{AlpnProtocolNameList, AlpnProtocols};
+use crate::extensions::extension_data::cookie::Cookie;
+use crate::extensions::extension_data::key_share::{
+    KeyShareEntry, KeyShareHelloRetryRequest, KeyShareServerHello,
+};
+use crate::extensions::extension_data::pre_shared_key::PreSharedKeyServerHello;
+use crate::extensions::extension_data::signature_algorithms::SignatureScheme;
+use crate::extensions::extension_data::supported_groups::NamedGroup;
+use crate::extensions::extension_data::supported_versions::{SupportedVersionsServerHello, TLS13};
+use crate::extensions::messages::{
+    EncryptedExtensionsExtension, HelloRetryRequestExtension, ServerHelloExtension,
+};
+use crate::handshake::certificate::CertificateRef;
+use crate::handshake::certificate_request::CertificateRequestRef;
+use crate::handshake::client_hello::ClientHelloRef;
+use crate::handshake::encrypted_extensions::EncryptedExtensions;
+use crate::handshake::server_hello::{HelloRetryRequest, ServerHello};
+use crate::handshake::{IncomingHandshake, OutgoingHandshake, Random};
+use crate::key_exchange::{EphemeralSecret, MAX_PUBLIC_KEY_LEN, SharedSecret};
+use crate::key_schedule::KeySchedule;
+use crate::record::{ClientRecord, ServerRecord};
+use crate::record_buffer::RecordBuffer;
+use crate::write_buffer::WriteBuffer;
+use crate::{CryptoProvider, TlsError, TlsVerifier};
+use digest::Digest;
+use heapless::Vec;
+use rand_core::RngCore;
+
+pub struct Handshake<CipherSuite>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    traffic_hash: Option<CipherSuite::Hash>,
+    client_key_share: Option<(NamedGroup, Vec<u8, MAX_PUBLIC_KEY_LEN>)>,
+    legacy_session_id: Vec<u8, 32>,
+    signature_schemes: Vec<SignatureScheme, 25>,
+    selected_psk: Option<u16>,
+    alpn_protocol: Option<usize>,
+    /// The secret agreed with the client, until the `ServerHello` has been encoded.
+    shared_secret: Option<SharedSecret>,
+    /// The group asked for in a `HelloRetryRequest`, if one was sent.
+    retry_group: Option<NamedGroup>,
+    /// The cookie of the `HelloRetryRequest`, which the second `ClientHello` must echo.
+    cookie: Option<Random>,
+    /// The transcript up to the `HelloRetryRequest`, which the PSK binders of the second
+    /// `ClientHello` are computed over.
+    retry_transcript: Option<CipherSuite::Hash>,
+    certificate_requested: bool,
+    certificate_received: bool,
+    certificate_verify_pending: bool,
+}
+
+impl<CipherSuite> Handshake<CipherSuite>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    pub fn new() -> Handshake<CipherSuite> {
+        Handshake {
+            traffic_hash: None,
+            client_key_share: None,
+            legacy_session_id: Vec::new(),
+            signature_schemes: Vec::new(),
+            selected_psk: None,
+            alpn_protocol: None,
+            shared_secret: None,
+            retry_group: None,
+            cookie: None,
+            retry_transcript: None,
+            certificate_requested: false,
+            certificate_received: false,
+            certificate_verify_pending: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum State {
+    ClientHello,
+    HelloRetryRequest,
+    ServerHello,
+    EncryptedExtensions,
+    CertificateRequest,
+    ServerCert,
+    ServerCertVerify,
+    ServerFinished,
+    ClientVerify,
+    ApplicationData,
+}
+
+/// The server side of the handshake, independent of the transport.
+///
+/// It runs over the record layer of a [`TlsClient`], which carries the application data once
+/// the handshake is complete: the records to send are taken from
+/// [`TlsClient::pending_transmit`], and the bytes received from the client are copied into
+/// [`TlsClient::rx_buffer`].
+pub(crate) struct ServerHandshake<CipherSuite>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    state: State,
+    handshake: Handshake<CipherSuite>,
+}
+
+impl<CipherSuite> ServerHandshake<CipherSuite>
+where
+    CipherSuite: TlsCipherSuite + 'static,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            state: State::ClientHello,
+            handshake: Handshake::new(),
+        }
+    }
+
+    /// Whether the handshake has not completed yet.
+    pub(crate) fn is_handshaking(&self) -> bool {
+        self.state != State::ApplicationData
+    }
+
+    /// Whether the handshake waits for more bytes from the client.
+    pub(crate) fn wants_read<'a, Rx, Tx>(&self, client: &TlsClient<'a, CipherSuite, Rx, Tx>) -> bool
+    where
+        Rx: RecordBuffer<'a>,
+        Tx: RecordBuffer<'a>,
+    {
+        self.state.is_reading()
+            && client.pending_transmit().is_empty()
+            && !client.record_reader.is_complete()
+    }
+
+    /// Make progress on the handshake: process the received records, and encode the records
+    /// of the server flight.
+    ///
+    /// Returns once a record is pending transmission, more bytes must be received (see
+    /// [`Self::wants_read`]) or the handshake is complete.
+    pub(crate) fn handle<'a, Rx, Tx, Provider>(
+        &mut self,
+        client: &mut TlsClient<'a, CipherSuite, Rx, Tx>,
+        config: &TlsConfig,
+        crypto_provider: &mut Provider,
+    ) -> Result<(), TlsError>
+    where
+        Rx: RecordBuffer<'a>,
+        Tx: RecordBuffer<'a>,
+        Provider: CryptoProvider<CipherSuite = CipherSuite>,
+    {
+        while client.pending_transmit().is_empty() && self.is_handshaking() {
+            let state = self.state;
+            let result = if state.is_reading() {
+                client.claim_read_buffer()?;
+                match client
+                    .record_reader
+                    .next_record(client.key_schedule.read_state())
+                {
+                    Ok(Some(record)) => state.process_record(
+                        &mut self.handshake,
+                        &mut client.key_schedule,
+                        config,
+                        crypto_provider,
+                        record,
+                    ),
+                    Ok(None) => return Ok(()),
+                    Err(e) => Err(e),
+                }
+            } else {
+                client.claim_write_buffer(false)?;
+                state
+                    .write_record(
+                        &mut self.handshake,
+                        &mut client.key_schedule,
+                        config,
+                        crypto_provider,
+                        &mut client.record_write_buf,
+                    )
+                    .map(|(next, tx)| (next, tx.len()))
+                    .and_then(|(next, len)| {
+                        client.set_pending_transmit(len);
+                        client.key_schedule.write_state().increment_counter();
+                        state.record_written(next, &mut self.handshake, &mut client.key_schedule)
+                    })
+            };
+            self.state = result.map_err(|e| client.abort_handshake(e, state.encrypts_alerts()))?;
+            trace!("State {:?} -> {:?}", state, self.state);
+        }
+
+        if !self.is_handshaking() && !client.is_opened() {
+            client.set_opened();
+        }
+        Ok(())
+    }
+}
+
+impl State {
+    /// Whether this state waits for a record from the client.
+    fn is_reading(self) -> bool {
+        matches!(self, State::ClientHello | State::ClientVerify)
+    }
+
+    /// Whether an alert sent in this state is protected by the handshake traffic keys.
+    fn encrypts_alerts(self) -> bool {
+        !matches!(
+            self,
+            State::ClientHello | State::HelloRetryRequest | State::ServerHello
+        )
+    }
+
+    /// Process a record received from the client, see [`Self::is_reading`].
+    fn process_record<Provider>(
+        self,
+        handshake: &mut Handshake<Provider::CipherSuite>,
+        key_schedule: &mut KeySchedule<Provider::CipherSuite>,
+        config: &TlsConfig,
+        crypto_provider: &mut Provider,
+        record: ServerRecord<'_, Provider::CipherSuite>,
+    ) -> Result<State, TlsError>
+    where
+        Provider: CryptoProvider,
+    {
+        match self {
+            State::ClientHello => process_client_hello(handshake, key_schedule, config, record),
+            State::ClientVerify => {
+                process_client_verify(handshake, key_schedule, crypto_provider, record)
+            }
+            _ => Err(TlsError::InternalError),
+        }
+    }
+
+    /// Called once the record encoded by [`Self::write_record`] is pending transmission, with
+    /// the state it returned.
+    fn record_written<CipherSuite>(
+        self,
+        next: State,
+        handshake: &mut Handshake<CipherSuite>,
+        key_schedule: &mut KeySchedule<CipherSuite>,
+    ) -> Result<State, TlsError>
+    where
+        CipherSuite: TlsCipherSuite,
+    {
+        if self == State::HelloRetryRequest {
+            handshake
+                .retry_transcript
+                .replace(key_schedule.transcript_hash().clone());
+        }
+        if self == State::ServerHello {
+            let shared = handshake
+                .shared_secret
+                .take()
+                .ok_or(TlsError::InvalidHandshake)?;
+            key_schedule.initialize_handshake_secret(shared.raw_secret_bytes())?;
+        }
+        Ok(next)
+    }
+
+    /// Encode the next record of the server flight.
+    fn write_record<'r, Provider>(
+        self,
+        handshake: &mut Handshake<Provider::CipherSuite>,
+        key_schedule: &mut KeySchedule<Provider::CipherSuite>,
+        config: &TlsConfig,
+        crypto_provider: &mut Provider,
+        tx_buf: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+    ) -> Result<(State, &'r [u8]), TlsError>
+    where
+        Provider: CryptoProvider,
+    {
+        match self {
+            State::HelloRetryRequest => {
+                hello_retry_request(handshake, key_schedule, crypto_provider, tx_buf)
+            }
+            State::ServerHello => server_hello(handshake, key_schedule, crypto_provider, tx_buf),
+            State::EncryptedExtensions => {
+                encrypted_extensions(handshake, key_schedule, config, tx_buf)
+            }
+            State::CertificateRequest => {
+                certificate_request(handshake, key_schedule, config, tx_buf)
+            }
+            State::ServerCert => server_cert(key_schedule, crypto_provider, tx_buf),
+            State::ServerCertVerify => {
+                server_cert_verify(handshake, key_schedule, crypto_provider, tx_buf)
+            }
+            State::ServerFinished => server_finished(handshake, key_schedule, tx_buf),
+            _ => Err(TlsError::InternalError),
+        }
+    }
+}
+
+fn process_client_hello<CipherSuite>(
+    handshake: &mut Handshake<CipherSuite>,
+    key_schedule: &mut KeySchedule<CipherSuite>,
+    config: &TlsConfig,
+    record: ServerRecord<'_, CipherSuite>,
+) -> Result<State, TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    match record {
+        ServerRecord::Handshake(IncomingHandshake::ClientHello(client_hello)) => {
+            trace!("********* ClientHello");
+            if !client_hello.offers_tls13() {
+                return Err(TlsError::AbortHandshake(
+                    AlertLevel::Fatal,
+                    AlertDescription::ProtocolVersion,
+                ));
+            }
+
+            if !client_hello.offers_cipher_suite(CipherSuite::CODE_POINT) {
+                warn!("Client does not offer the configured cipher suite");
+                return Err(TlsError::AbortHandshake(
+                    AlertLevel::Fatal,
+                    AlertDescription::HandshakeFailure,
+                ));
+            }
+
+            handshake.legacy_session_id = Vec::from_slice(client_hello.legacy_session_id)
+                .map_err(|_| TlsError::InvalidSessionIdLength)?;
+
+            let Some((group, key_share)) = select_key_share(handshake, &client_hello, config)?
+            else {
+                // Section 4.1.1.  Cryptographic Negotiation
+                // If the server selects an (EC)DHE group and the client did not offer a
+                // compatible "key_share" extension in the initial ClientHello, the server
+                // MUST respond with a HelloRetryRequest message.
+                let group = config
+                    .named_groups
+                    .iter()
+                    .copied()
+                    .filter(|group| EphemeralSecret::supports(*group))
+                    .find(|group| client_hello.offers_group(*group))
+                    .ok_or_else(|| {
+                        warn!("Client does not support any of the configured groups");
+                        TlsError::AbortHandshake(
+                            AlertLevel::Fatal,
+                            AlertDescription::HandshakeFailure,
+                        )
+                    })?;
+                handshake.retry_group.replace(group);
+                key_schedule.hash_client_hello();
+                return Ok(State::HelloRetryRequest);
+            };
+            let key_share = Vec::from_slice(key_share).map_err(|_| TlsError::InvalidKeyShare)?;
+
+            handshake.client_key_share.replace((group, key_share));
+            key_schedule.set_client_random(client_hello.random);
+            handshake.signature_schemes = client_hello
+                .signature_schemes()
+                .take(handshake.signature_schemes.capacity())
+                .collect();
+            let transcript = handshake.retry_transcript.take();
+            handshake.selected_psk = select_psk(&client_hello, key_schedule, config, transcript)?;
+            handshake.alpn_protocol = select_alpn_protocol(&client_hello, config)?;
+
+            if handshake.selected_psk.is_none() {
+                key_schedule.initialize_early_secret(None)?;
+            }
+
+            Ok(State::ServerHello)
+        }
+        ServerRecord::Handshake(_) => Err(TlsError::InvalidHandshake),
+        // The client may send a ChangeCipherSpec before its second ClientHello in the
+        // middlebox compatibility mode
+        ServerRecord::ChangeCipherSpec(_) if handshake.retry_group.is_some() => {
+            Ok(State::ClientHello)
+        }
+        ServerRecord::Alert(alert) => {
+            Err(TlsError::HandshakeAborted(alert.level, alert.description))
+        }
+        _ => Err(TlsError::InvalidRecord),
+    }
+}
+
+/// Find the key share for the first of our groups that the client sent one for.
+///
+/// After a `HelloRetryRequest`, the client must have sent a key share for the group it asked
+/// for, and echoed its cookie. Returns `None` if the client needs to be asked for a key share.
+fn select_key_share<'a, CipherSuite>(
+    handshake: &Handshake<CipherSuite>,
+    client_hello: &ClientHelloRef<'a>,
+    config: &TlsConfig,
+) -> Result<Option<(NamedGroup, &'a [u8])>, TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    let Some(group) = handshake.retry_group else {
+        return Ok(config
+            .named_groups
+            .iter()
+            .filter(|group| EphemeralSecret::supports(**group))
+            .find_map(|group| Some((*group, client_hello.key_share(*group)?))));
+    };
+
+    // Section 4.1.2.  Client Hello
+    // If a "cookie" extension was provided in the HelloRetryRequest, the client MUST include
+    // it, and if a "key_share" extension was provided, replace its list of shares with a
+    // single share for the indicated group.
+    let key_share = client_hello.key_share(group);
+    if key_share.is_none() || client_hello.cookie() != handshake.cookie.as_ref().map(|c| &c[..]) {
+        warn!("Second ClientHello does not follow the HelloRetryRequest");
+        return Err(TlsError::AbortHandshake(
+            AlertLevel::Fatal,
+            AlertDescription::IllegalParameter,
+        ));
+    }
+    Ok(key_share.map(|key_share| (group, key_share)))
+}
+
+/// Pick the first offered PSK identity that we know, and check its binder.
+///
+/// The binder covers `transcript`, the messages preceding the `ClientHello` if it follows a
+/// `HelloRetryRequest`. The early secret is initialized with the selected key.
+fn select_psk<CipherSuite>(
+    client_hello: &ClientHelloRef,
+    key_schedule: &mut KeySchedule<CipherSuite>,
+    config: &TlsConfig,
+    transcript: Option<CipherSuite::Hash>,
+) -> Result<Option<u16>, TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    let Some((psk, identities)) = &config.psk else {
+        return Ok(None);
+    };
+
+    // Section 4.2.9.  Pre-Shared Key Exchange Modes
+    // This extension also restricts the modes for use with PSK resumption.
+    // Servers SHOULD NOT send NewSessionTicket with tickets that are not
+    // compatible with the advertised modes;
+    if !client_hello.offers_psk_dhe_ke() {
+        return Ok(None);
+    }
+
+    let Some((index, binder)) =
+        client_hello
+            .psk_offers()
+            .enumerate()
+            .find_map(|(index, (identity, binder))| {
+                identities.contains(&identity).then_some((index, binder))
+            })
+    else {
+        return Ok(None);
+    };
+
+    key_schedule.initialize_early_secret(Some(psk))?;
+
+    let transcript = transcript
+        .unwrap_or_else(<CipherSuite::Hash as Digest>::new)
+        .chain_update(client_hello.psk_binder_transcript());
+    if !key_schedule
+        .write_state()
+        .verify_psk_binder(&transcript, binder)?
+    {
+        warn!("PSK binder verification failed");
+        return Err(TlsError::AbortHandshake(
+            AlertLevel::Fatal,
+            AlertDescription::DecryptError,
+        ));
+    }
+
+    Ok(Some(index as u16))
+}
+
+/// Pick the first of our ALPN protocols that the client offers.
+fn select_alpn_protocol(
+    client_hello: &ClientHelloRef,
+    config: &TlsConfig,
+) -> Result<Option<usize>, TlsError> {
+    let (Some(protocols), Some(offered)) = (config.alpn_protocols, client_hello.alpn_protocols())
+    else {
+        return Ok(None);
+    };
+
+    // RFC 7301, Section 3.2.  Protocol Selection
+    // In the event that the server supports no protocols that the client advertises, then
+    // the server SHALL respond with a fatal "no_application_protocol" alert.
+    protocols
+        .iter()
+        .position(|protocol| offered.clone().any(|offered| offered == protocol))
+        .map(Some)
+        .ok_or(TlsError::AbortHandshake(
+            AlertLevel::Fatal,
+            AlertDescription::NoApplicationProtocol,
+        ))
+}
+
+fn hello_retry_request<'r, Provider>(
+    handshake: &mut Handshake<Provider::CipherSuite>,
+    key_schedule: &mut KeySchedule<Provider::CipherSuite>,
+    crypto_provider: &mut Provider,
+    tx_buf: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+) -> Result<(State, &'r [u8]), TlsError>
+where
+    Provider: CryptoProvider,
+{
+    let selected_group = handshake.retry_group.ok_or(TlsError::InvalidHandshake)?;
+
+    let mut cookie = [0; 32];
+    crypto_provider.rng().fill_bytes(&mut cookie);
+    let cookie = handshake.cookie.insert(cookie);
+
+    let mut extensions = Vec::new();
+    unwrap!(
+        extensions
+            .push(HelloRetryRequestExtension::SupportedVersions(
+                SupportedVersionsServerHello {
+                    selected_version: TLS13
+                }
+            ))
+            .ok()
+    );
+    unwrap!(
+        extensions
+            .push(HelloRetryRequestExtension::KeyShare(
+                KeyShareHelloRetryRequest { selected_group }
+            ))
+            .ok()
+    );
+    unwrap!(
+        extensions
+            .push(HelloRetryRequestExtension::Cookie(Cookie { cookie }))
+            .ok()
+    );
+
+    let hello_retry_request = HelloRetryRequest::new(
+        &handshake.legacy_session_id,
+        Provider::CipherSuite::CODE_POINT,
+        extensions,
+    );
+
+    let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
+    tx_buf
+        .write_record(
+            &ClientRecord::Handshake(
+                OutgoingHandshake::HelloRetryRequest(hello_retry_request),
+                false,
+            ),
+            write_key_schedule,
+            Some(read_key_schedule),
+        )
+        .map(|slice| (State::ClientHello, slice))
+}
+
+fn server_hello<'r, Provider>(
+    handshake: &mut Handshake<Provider::CipherSuite>,
+    key_schedule: &mut KeySchedule<Provider::CipherSuite>,
+    crypto_provider: &mut Provider,
+    tx_buf: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+) -> Result<(State, &'r [u8]), TlsError>
+where
+    Provider: CryptoProvider,
+{
+    let (group, client_key_share) = handshake
+        .client_key_share
+        .take()
+        .ok_or(TlsError::InvalidHandshake)?;
+
+    let mut random = [0; 32];
+    crypto_provider.rng().fill_bytes(&mut random);
+
+    let secret = EphemeralSecret::generate(group, &mut crypto_provider.rng())
+        .ok_or(TlsError::InvalidKeyShare)?;
+    let public_key = secret.public_key();
+    let shared = secret.diffie_hellman(&client_key_share)?;
+
+    let mut extensions = Vec::new();
+    unwrap!(
+        extensions
+            .push(ServerHelloExtension::SupportedVersions(
+                SupportedVersionsServerHello {
+                    selected_version: TLS13,
+                }
+            ))
+            .ok()
+    );
+    unwrap!(
+        extensions
+            .push(ServerHelloExtension::KeyShare(KeyShareServerHello(
+                KeyShareEntry {
+                    group,
+                    opaque: &public_key,
+                }
+            )))
+            .ok()
+    );
+    if let Some(selected_identity) = handshake.selected_psk {
+        unwrap!(
+            extensions
+                .push(ServerHelloExtension::PreSharedKey(
+                    PreSharedKeyServerHello { selected_identity }
+                ))
+                .ok()
+        );
+    }
+
+    let server_hello = ServerHello::new(
+        random,
+        &handshake.legacy_session_id,
+        Provider::CipherSuite::CODE_POINT,
+        extensions,
+    );
+
+    let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
+    let slice = tx_buf.write_record(
+        &ClientRecord::Handshake(OutgoingHandshake::ServerHello(server_hello), false),
+        write_key_schedule,
+        Some(read_key_schedule),
+    )?;
+
+    handshake.shared_secret.replace(shared);
+    Ok((State::EncryptedExtensions, slice))
+}
+
+fn encrypted_extensions<'r, CipherSuite>(
+    handshake: &mut Handshake<CipherSuite>,
+    key_schedule: &mut KeySchedule<CipherSuite>,
+    config: &TlsConfig,
+    tx_buf: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+) -> Result<(State, &'r [u8]), TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    let mut extensions = Vec::new();
+    let protocol = handshake
+        .alpn_protocol
+        .zip(config.alpn_protocols)
+        .and_then(|(index, protocols)| protocols.iter().nth(index));
+    if let Some(protocol) = &protocol {
+        unwrap!(
+            extensions
+                .push(
+                    EncryptedExtensionsExtension::ApplicationLayerProtocolNegotiation(
+                        AlpnProtocolNameList {
+                            protocols: AlpnProtocols::List(core::slice::from_ref(protocol)),
+                        }
+                    )
+                )
+                .ok()
+        );
+    }
+
+    let next_state = if handshake.selected_psk.is_some() {
+        State::ServerFinished
+    } else if config.client_auth {
+        State::CertificateRequest
+    } else {
+        State::ServerCert
+    };
+
+    let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
+    tx_buf
+        .write_record(
+            &ClientRecord::Handshake(
+                OutgoingHandshake::EncryptedExtensions(EncryptedExtensions { extensions }),
+                true,
+            ),
+            write_key_schedule,
+            Some(read_key_schedule),
+        )
+        .map(|slice| (next_state, slice))
+}
+
+fn certificate_request<'r, CipherSuite>(
+    handshake: &mut Handshake<CipherSuite>,
+    key_schedule: &mut KeySchedule<CipherSuite>,
+    config: &TlsConfig,
+    tx_buf: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+) -> Result<(State, &'r [u8]), TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    handshake.certificate_requested = true;
+
+    let certificate_request = CertificateRequestRef {
+        request_context: &[],
+        signature_schemes: config.signature_schemes.clone(),
+    };
+
+    let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
+    tx_buf
+        .write_record(
+            &ClientRecord::Handshake(
+                OutgoingHandshake::CertificateRequest(certificate_request),
+                true,
+            ),
+            write_key_schedule,
+            Some(read_key_schedule),
+        )
+        .map(|slice| (State::ServerCert, slice))
+}
+
+fn server_cert<'r, Provider>(
+    key_schedule: &mut KeySchedule<Provider::CipherSuite>,
+    crypto_provider: &mut Provider,
+    tx_buf: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+) -> Result<(State, &'r [u8]), TlsError>
+where
+    Provider: CryptoProvider,
+{
+    // Declare cert before certificate so owned data outlives the CertificateRef that borrows it
+    let cert = crypto_provider.server_cert().ok_or_else(|| {
+        error!("No server certificate available");
+        TlsError::InternalError
+    })?;
+    let mut certificate = CertificateRef::with_context(&[]);
+    certificate.add((&cert).into())?;
+
+    let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
+    tx_buf
+        .write_record(
+            &ClientRecord::Handshake(OutgoingHandshake::Certificate(certificate), true),
+            write_key_schedule,
+            Some(read_key_schedule),
+        )
+        .map(|slice| (State::ServerCertVerify, slice))
+}
+
+fn server_cert_verify<'r, Provider>(
+    handshake: &mut Handshake<Provider::CipherSuite>,
+    key_schedule: &mut KeySchedule<Provider::CipherSuite>,
+    crypto_provider: &mut Provider,
+    tx_buf: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+) -> Result<(State, &'r [u8]), TlsError>
+where
+    Provider: CryptoProvider,
+{
+    let signer = crypto_provider.signer()?;
+    if !handshake.signature_schemes.contains(&signer.1) {
+        warn!("Client does not accept signature scheme {:?}", signer.1);
+        return Err(TlsError::AbortHandshake(
+            AlertLevel::Fatal,
+            AlertDescription::HandshakeFailure,
+        ));
+    }
+
+    let ctx_str = b"TLS 1.3, server CertificateVerify\x00";
+    let certificate_verify =
+        sign_certificate_verify(ctx_str, key_schedule.transcript_hash(), signer)?;
+
+    let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
+    tx_buf
+        .write_record(
+            &ClientRecord::Handshake(
+                OutgoingHandshake::CertificateVerify(certificate_verify),
+                true,
+            ),
+            write_key_schedule,
+            Some(read_key_schedule),
+        )
+        .map(|slice| (State::ServerFinished, slice))
+}
+
+fn server_finished<'r, CipherSuite>(
+    handshake: &mut Handshake<CipherSuite>,
+    key_schedule: &mut KeySchedule<CipherSuite>,
+    tx_buf: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+) -> Result<(State, &'r [u8]), TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    let server_finished = key_schedule
+        .create_finished()
+        .map_err(|_| TlsError::InvalidHandshake)?;
+
+    let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
+    let slice = tx_buf.write_record(
+        &ClientRecord::Handshake(OutgoingHandshake::Finished(server_finished), true),
+        write_key_schedule,
+        Some(read_key_schedule),
+    )?;
+
+    // The application traffic secrets are derived from the transcript up to the server Finished
+    handshake
+        .traffic_hash
+        .replace(read_key_schedule.transcript_hash().clone());
+
+    Ok((State::ClientVerify, slice))
+}
+
+fn process_client_verify<Provider>(
+    handshake: &mut Handshake<Provider::CipherSuite>,
+    key_schedule: &mut KeySchedule<Provider::CipherSuite>,
+    crypto_provider: &mut Provider,
+    record: ServerRecord<'_, Provider::CipherSuite>,
+) -> Result<State, TlsError>
+where
+    Provider: CryptoProvider,
+{
+    let mut state = State::ClientVerify;
+    decrypt_record(key_schedule.read_state(), record, |key_schedule, record| {
+        match record {
+            ServerRecord::Handshake(client_handshake) => match client_handshake {
+                IncomingHandshake::Certificate(certificate)
+                    if handshake.certificate_requested && !handshake.certificate_received =>
+                {
+                    handshake.certificate_received = true;
+
+                    // Section 4.4.2.4.  Receiving a Certificate Message
+                    // If the client does not send any certificates (i.e., it sends an empty
+                    // Certificate message), the server MAY at its discretion either continue
+                    // the handshake without client authentication or abort the handshake
+                    // with a "certificate_required" alert.
+                    if certificate.entries.is_empty() {
+                        return Err(TlsError::AbortHandshake(
+                            AlertLevel::Fatal,
+                            AlertDescription::CertificateRequired,
+                        ));
+                    }
+
+                    let transcript = key_schedule.transcript_hash();
+                    if let Ok(verifier) = crypto_provider.verifier() {
+                        verifier.verify_certificate(transcript, certificate)?;
+                        debug!("Certificate verified!");
+                    } else {
+                        debug!("Certificate verification skipped due to no verifier!");
+                    }
+                    handshake.certificate_verify_pending = true;
+                }
+                IncomingHandshake::CertificateVerify(verify)
+                    if handshake.certificate_verify_pending =>
+                {
+                    if let Ok(verifier) = crypto_provider.verifier() {
+                        verifier.verify_signature(verify)?;
+                        debug!("Signature verified!");
+                    } else {
+                        debug!("Signature verification skipped due to no verifier!");
+                    }
+                    handshake.certificate_verify_pending = false;
+                }
+                IncomingHandshake::Finished(finished)
+                    if handshake.certificate_requested == handshake.certificate_received
+                        && !handshake.certificate_verify_pending =>
+                {
+                    if !key_schedule.verify_finished(&finished)? {
+                        warn!("Client signature verification failed");
+                        return Err(TlsError::InvalidSignature);
+                    }
+                    state = State::ApplicationData;
+                }
+                _ => return Err(TlsError::InvalidHandshake),
+            },
+            ServerRecord::ChangeCipherSpec(_) => {}
+            ServerRecord::Alert(alert) => {
+                return Err(TlsError::HandshakeAborted(alert.level, alert.description));
+            }
+            ServerRecord::ApplicationData(_) => return Err(TlsError::InvalidRecord),
+        }
+
+        Ok(())
+    })?;
+
+    if state == State::ApplicationData {
+        key_schedule.replace_transcript_hash(
+            handshake
+                .traffic_hash
+                .take()
+                .ok_or(TlsError::InvalidHandshake)?,
+        );
+        key_schedule.initialize_master_secret()?;
+    }
+
+    Ok(state)
+}
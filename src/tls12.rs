@@ -0,0 +1,657 @@
+//! TLS 1.2 fallback of the client (RFC 5246).
+//!
+//! Only the ECDHE-ECDSA and ECDHE-RSA suites with AES-GCM (RFC 5289) over secp256r1 are
+//! supported, and the server must agree to the extended master secret (RFC 7627). Session
+//! resumption and renegotiation are not supported.
+
+use aes_gcm::aead::{AeadInPlace, KeyInit};
+use digest::generic_array::GenericArray;
+use digest::generic_array::typenum::{U12, Unsigned};
+use digest::{Digest, OutputSizeUser};
+use hmac::{Mac, SimpleHmac};
+
+use crate::TlsError;
+use crate::alert::{Alert, AlertDescription, AlertLevel};
+use crate::application_data::ApplicationData;
+use crate::buffer::CryptoBuffer;
+use crate::change_cipher_spec::ChangeCipherSpec;
+use crate::config::{CryptoProvider, TlsCipherSuite, TlsVerifier};
+use crate::connection::{Handshake, State, process_tls13_server_hello};
+use crate::content_types::ContentType;
+use crate::extensions::extension_data::supported_groups::NamedGroup;
+use crate::handshake::certificate::CertificateRef;
+use crate::handshake::client_key_exchange::ClientKeyExchange;
+use crate::handshake::finished::Finished;
+use crate::handshake::server_key_exchange::ServerKeyExchange;
+use crate::handshake::{HandshakeType, IncomingHandshake, OutgoingHandshake, Random};
+use crate::key_exchange::{EphemeralSecret, MAX_PUBLIC_KEY_LEN};
+use crate::key_schedule::{KeySchedule, ReadKeySchedule, Tls12Records, WriteKeySchedule};
+use crate::parse_buffer::ParseBuffer;
+use crate::record::{ClientRecord, ClientRecordHeader, RecordHeader, ServerRecord};
+use crate::write_buffer::WriteBuffer;
+
+const VERSION: [u8; 2] = [0x03, 0x03];
+
+/// The end of the server random when a TLS 1.3 server negotiated TLS 1.2 (RFC 8446,
+/// Section 4.1.3), which a client that offered TLS 1.3 must reject.
+const DOWNGRADE_SENTINEL: &[u8] = b"DOWNGRD\x01";
+
+/// Length of the explicit part of the AES-GCM nonce (RFC 5288, Section 3).
+const EXPLICIT_NONCE_LEN: usize = 8;
+
+/// Length of the implicit part of the AES-GCM nonce, derived from the key block.
+const SALT_LEN: usize = 4;
+
+const MASTER_SECRET_LEN: usize = 48;
+
+/// Length of the `verify_data` of a TLS 1.2 `Finished` message.
+const VERIFY_DATA_LEN: usize = 12;
+
+/// The TLS 1.2 specific handshake state.
+pub(crate) struct Tls12Handshake {
+    pub(crate) client_random: Random,
+    server_random: Random,
+    server_key: Option<heapless::Vec<u8, MAX_PUBLIC_KEY_LEN>>,
+    certificate_requested: bool,
+    master_secret: [u8; MASTER_SECRET_LEN],
+}
+
+impl Tls12Handshake {
+    pub(crate) fn new() -> Self {
+        Self {
+            client_random: [0; 32],
+            server_random: [0; 32],
+            server_key: None,
+            certificate_requested: false,
+            master_secret: [0; MASTER_SECRET_LEN],
+        }
+    }
+}
+
+/// The TLS 1.2 pseudorandom function (RFC 5246, Section 5), using the cipher suite hash.
+///
+/// The seed is the concatenation of `seeds`.
+fn prf<CipherSuite>(
+    secret: &[u8],
+    label: &[u8],
+    seeds: &[&[u8]],
+    out: &mut [u8],
+) -> Result<(), TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    let hmac = || {
+        <SimpleHmac<CipherSuite::Hash> as Mac>::new_from_slice(secret)
+            .map_err(|_| TlsError::CryptoError)
+    };
+
+    // A(1) = HMAC_hash(secret, label + seed)
+    let mut mac = hmac()?;
+    mac.update(label);
+    for seed in seeds {
+        mac.update(seed);
+    }
+    let mut a = mac.finalize().into_bytes();
+
+    for chunk in out.chunks_mut(<CipherSuite::Hash as OutputSizeUser>::OutputSize::USIZE) {
+        let mut mac = hmac()?;
+        mac.update(&a);
+        mac.update(label);
+        for seed in seeds {
+            mac.update(seed);
+        }
+        chunk.copy_from_slice(&mac.finalize().into_bytes()[..chunk.len()]);
+
+        let mut mac = hmac()?;
+        mac.update(&a);
+        a = mac.finalize().into_bytes();
+    }
+
+    Ok(())
+}
+
+/// Process the first handshake record of the server, once TLS 1.2 was offered.
+///
+/// The record is passed undecoded, as a TLS 1.2 server usually sends its whole flight in it.
+pub(crate) fn process_server_hello<Provider>(
+    handshake: &mut Handshake<Provider::CipherSuite>,
+    key_schedule: &mut KeySchedule<Provider::CipherSuite>,
+    crypto_provider: &mut Provider,
+    record: ApplicationData,
+) -> Result<State, TlsError>
+where
+    Provider: CryptoProvider,
+{
+    if record.header.content_type() != ContentType::Handshake {
+        return Err(TlsError::InvalidRecord);
+    }
+
+    let mut buf = ParseBuffer::new(record.into_handshake_messages()?);
+    if buf.is_empty() {
+        // The ServerHello is split across records
+        return Ok(State::ServerHello);
+    }
+    let IncomingHandshake::ServerHello(server_hello) =
+        IncomingHandshake::<Provider::CipherSuite>::read(&mut buf, key_schedule.transcript_hash())?
+    else {
+        return Err(TlsError::InvalidHandshake);
+    };
+
+    if server_hello.selected_version().is_some() {
+        key_schedule.read_state().set_tls12_records(None);
+        // The rest of a TLS 1.3 server flight is encrypted
+        if !buf.is_empty() {
+            return Err(TlsError::InvalidHandshake);
+        }
+        return process_tls13_server_hello(handshake, key_schedule, &server_hello);
+    }
+
+    trace!("********* TLS 1.2 ServerHello");
+    if server_hello.legacy_version() != u16::from_be_bytes(VERSION) {
+        return Err(TlsError::AbortHandshake(
+            AlertLevel::Fatal,
+            AlertDescription::ProtocolVersion,
+        ));
+    }
+    if server_hello.random().ends_with(DOWNGRADE_SENTINEL)
+        || server_hello
+            .renegotiation_info()
+            .is_some_and(|info| !info.is_empty())
+    {
+        return Err(TlsError::AbortHandshake(
+            AlertLevel::Fatal,
+            AlertDescription::IllegalParameter,
+        ));
+    }
+    if !Provider::CipherSuite::TLS12_CODE_POINTS.contains(&server_hello.cipher_suite()) {
+        return Err(TlsError::InvalidCipherSuite);
+    }
+    if !server_hello.extended_master_secret() {
+        warn!("Server does not support the extended master secret");
+        return Err(TlsError::AbortHandshake(
+            AlertLevel::Fatal,
+            AlertDescription::HandshakeFailure,
+        ));
+    }
+
+    key_schedule
+        .write_state()
+        .set_tls12_records(Some(Tls12Records::Plaintext));
+    handshake.tls12.server_random = *server_hello.random();
+
+    process_server_flight(handshake, key_schedule, crypto_provider, &mut buf)
+}
+
+/// Process a handshake record of the server, up to `ServerHelloDone`.
+pub(crate) fn process_server_record<Provider>(
+    handshake: &mut Handshake<Provider::CipherSuite>,
+    key_schedule: &mut KeySchedule<Provider::CipherSuite>,
+    crypto_provider: &mut Provider,
+    record: ServerRecord<'_, Provider::CipherSuite>,
+) -> Result<State, TlsError>
+where
+    Provider: CryptoProvider,
+{
+    match record {
+        ServerRecord::ApplicationData(record)
+            if record.header.content_type() == ContentType::Handshake =>
+        {
+            let mut buf = ParseBuffer::new(record.into_handshake_messages()?);
+            process_server_flight(handshake, key_schedule, crypto_provider, &mut buf)
+        }
+        ServerRecord::Alert(alert) => {
+            Err(TlsError::HandshakeAborted(alert.level, alert.description))
+        }
+        _ => Err(TlsError::InvalidRecord),
+    }
+}
+
+/// Process the `Certificate`, `ServerKeyExchange`, `CertificateRequest` and `ServerHelloDone`
+/// complete messages in `buf`.
+fn process_server_flight<Provider>(
+    handshake: &mut Handshake<Provider::CipherSuite>,
+    key_schedule: &mut KeySchedule<Provider::CipherSuite>,
+    crypto_provider: &mut Provider,
+    buf: &mut ParseBuffer,
+) -> Result<State, TlsError>
+where
+    Provider: CryptoProvider,
+{
+    let mut state = State::Tls12ServerFlight;
+
+    while !buf.is_empty() {
+        if state != State::Tls12ServerFlight {
+            return Err(TlsError::InvalidHandshake);
+        }
+
+        let start = buf.offset();
+        let handshake_type = HandshakeType::parse(buf).map_err(|_| TlsError::InvalidHandshake)?;
+        let len = buf.read_u24().map_err(|_| TlsError::DecodeError)?;
+        let mut body = buf.slice(len as usize).map_err(|_| TlsError::DecodeError)?;
+        key_schedule
+            .transcript_hash()
+            .update(&buf.as_slice()[start..buf.offset()]);
+
+        trace!("TLS 1.2 handshake = {:?}", handshake_type);
+        match handshake_type {
+            HandshakeType::Certificate => {
+                let certificate = CertificateRef::parse_tls12(&mut body)?;
+                if let Ok(verifier) = crypto_provider.verifier() {
+                    verifier.verify_certificate(key_schedule.transcript_hash(), certificate)?;
+                    debug!("Certificate verified!");
+                } else {
+                    debug!("Certificate verification skipped due to no verifier!");
+                }
+            }
+            HandshakeType::ServerKeyExchange => {
+                let server_key_exchange = ServerKeyExchange::parse(&mut body)?;
+                // The key share of the ClientHello is reused, so it must be for secp256r1 too
+                let group = handshake.secret.as_ref().map(EphemeralSecret::group);
+                if server_key_exchange.group != NamedGroup::Secp256r1
+                    || group != Some(NamedGroup::Secp256r1)
+                {
+                    return Err(TlsError::InvalidKeyShare);
+                }
+                let server_key = heapless::Vec::from_slice(server_key_exchange.public_key)
+                    .map_err(|_| TlsError::InvalidKeyShare)?;
+
+                // client_random + server_random + ServerECDHParams (RFC 8422, Section 5.4)
+                let mut message: heapless::Vec<u8, { 32 + 32 + 4 + 65 }> = heapless::Vec::new();
+                message
+                    .extend_from_slice(&handshake.tls12.client_random)
+                    .and_then(|()| message.extend_from_slice(&handshake.tls12.server_random))
+                    .and_then(|()| message.extend_from_slice(server_key_exchange.params))
+                    .map_err(|_| TlsError::InvalidKeyShare)?;
+
+                if let Ok(verifier) = crypto_provider.verifier() {
+                    verifier.verify_tls12_signature(&message, server_key_exchange.signature)?;
+                    debug!("Signature verified!");
+                } else {
+                    debug!("Signature verification skipped due to no verifier!");
+                }
+                handshake.tls12.server_key = Some(server_key);
+            }
+            HandshakeType::CertificateRequest => {
+                handshake.tls12.certificate_requested = true;
+            }
+            HandshakeType::ServerHelloDone => {
+                if handshake.tls12.server_key.is_none() {
+                    return Err(TlsError::InvalidHandshake);
+                }
+                state = if handshake.tls12.certificate_requested {
+                    State::Tls12ClientCert
+                } else {
+                    State::Tls12ClientKeyExchange
+                };
+            }
+            _ => return Err(TlsError::InvalidHandshake),
+        }
+    }
+
+    Ok(state)
+}
+
+/// Send an empty client `Certificate`, as client authentication is not supported with TLS 1.2.
+pub(crate) fn client_cert<'r, CipherSuite>(
+    key_schedule: &mut KeySchedule<CipherSuite>,
+    buffer: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+) -> Result<(State, &'r [u8]), TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    warn!("Client authentication is not supported with TLS 1.2, sending no certificate");
+    let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
+
+    buffer
+        .write_record(
+            &ClientRecord::Handshake(
+                OutgoingHandshake::Tls12ClientCert(CertificateRef::with_context(&[])),
+                false,
+            ),
+            write_key_schedule,
+            Some(read_key_schedule),
+        )
+        .map(|slice| (State::Tls12ClientKeyExchange, slice))
+}
+
+/// Send the `ClientKeyExchange` and derive the master secret and the traffic keys.
+pub(crate) fn client_key_exchange<'r, CipherSuite>(
+    handshake: &mut Handshake<CipherSuite>,
+    key_schedule: &mut KeySchedule<CipherSuite>,
+    buffer: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+) -> Result<(State, &'r [u8]), TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    let secret = handshake.secret.take().ok_or(TlsError::InvalidHandshake)?;
+    let server_key = handshake
+        .tls12
+        .server_key
+        .take()
+        .ok_or(TlsError::InvalidHandshake)?;
+    let public_key = secret.public_key();
+
+    let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
+    let slice = buffer.write_record(
+        &ClientRecord::Handshake(
+            OutgoingHandshake::ClientKeyExchange(ClientKeyExchange {
+                public_key: &public_key,
+            }),
+            false,
+        ),
+        write_key_schedule,
+        Some(read_key_schedule),
+    )?;
+
+    // RFC 7627, Section 4: the session hash covers the handshake up to the ClientKeyExchange
+    let session_hash = key_schedule.transcript_hash().clone().finalize();
+    let shared = secret.diffie_hellman(&server_key)?;
+    let tls12 = &mut handshake.tls12;
+    prf::<CipherSuite>(
+        shared.raw_secret_bytes(),
+        b"extended master secret",
+        &[&session_hash],
+        &mut tls12.master_secret,
+    )?;
+    key_schedule.log_secret(b"extended master secret", &tls12.master_secret);
+
+    let key_len = CipherSuite::KeyLen::USIZE;
+    let mut key_block = [0; 2 * 32 + 2 * SALT_LEN];
+    let key_block = &mut key_block[..2 * (key_len + SALT_LEN)];
+    prf::<CipherSuite>(
+        &tls12.master_secret,
+        b"key expansion",
+        &[&tls12.server_random, &tls12.client_random],
+        key_block,
+    )?;
+
+    let (client_key, rest) = key_block.split_at(key_len);
+    let (server_key, rest) = rest.split_at(key_len);
+    let (client_salt, server_salt) = rest.split_at(SALT_LEN);
+    key_schedule
+        .write_state()
+        .set_tls12_keys(client_key, client_salt);
+    key_schedule
+        .read_state()
+        .set_tls12_keys(server_key, server_salt);
+    key_block.fill(0);
+
+    Ok((State::Tls12ChangeCipherSpec, slice))
+}
+
+pub(crate) fn change_cipher_spec<'r, CipherSuite>(
+    key_schedule: &mut KeySchedule<CipherSuite>,
+    buffer: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+) -> Result<(State, &'r [u8]), TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
+
+    buffer
+        .write_record(
+            &ClientRecord::ChangeCipherSpec(ChangeCipherSpec::new()),
+            write_key_schedule,
+            Some(read_key_schedule),
+        )
+        .map(|slice| (State::Tls12ClientFinished, slice))
+}
+
+/// Send the client `Finished`, which is the first protected record.
+pub(crate) fn client_finished<'r, CipherSuite>(
+    handshake: &Handshake<CipherSuite>,
+    key_schedule: &mut KeySchedule<CipherSuite>,
+    buffer: &'r mut WriteBuffer<impl AsRef<[u8]> + AsMut<[u8]>>,
+) -> Result<(State, &'r [u8]), TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    let mut finished = Finished::<U12> {
+        verify: GenericArray::default(),
+        hash: None,
+    };
+    prf::<CipherSuite>(
+        &handshake.tls12.master_secret,
+        b"client finished",
+        &[&key_schedule.transcript_hash().clone().finalize()],
+        &mut finished.verify,
+    )?;
+
+    key_schedule
+        .write_state()
+        .set_tls12_records(Some(Tls12Records::Protected));
+    let (write_key_schedule, read_key_schedule) = key_schedule.as_split();
+
+    buffer
+        .write_record(
+            &ClientRecord::Handshake(OutgoingHandshake::Tls12Finished(finished), true),
+            write_key_schedule,
+            Some(read_key_schedule),
+        )
+        .map(|slice| (State::Tls12ServerFinished, slice))
+}
+
+/// Process the server `ChangeCipherSpec` and `Finished`.
+pub(crate) fn process_server_finished<CipherSuite>(
+    handshake: &mut Handshake<CipherSuite>,
+    key_schedule: &mut KeySchedule<CipherSuite>,
+    record: ServerRecord<'_, CipherSuite>,
+) -> Result<State, TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    let read_key_schedule = key_schedule.read_state();
+    match record {
+        ServerRecord::ChangeCipherSpec(_)
+            if read_key_schedule.tls12_records() == Some(Tls12Records::Plaintext) =>
+        {
+            read_key_schedule.set_tls12_records(Some(Tls12Records::Protected));
+            Ok(State::Tls12ServerFinished)
+        }
+        ServerRecord::ApplicationData(ApplicationData {
+            header, mut data, ..
+        }) if read_key_schedule.tls12_records() == Some(Tls12Records::Protected)
+            && header.content_type() == ContentType::Handshake =>
+        {
+            let data = open(read_key_schedule, &header, &mut data)?;
+            let mut expected = [0; VERIFY_DATA_LEN];
+            prf::<CipherSuite>(
+                &handshake.tls12.master_secret,
+                b"server finished",
+                &[&read_key_schedule.transcript_hash().clone().finalize()],
+                &mut expected,
+            )?;
+            handshake.tls12.master_secret.fill(0);
+
+            match data {
+                [20, 0, 0, 12, verify_data @ ..] if constant_time_eq(verify_data, &expected) => {
+                    Ok(State::ApplicationData)
+                }
+                [20, ..] => {
+                    warn!("Server signature verification failed");
+                    Err(TlsError::InvalidSignature)
+                }
+                _ => Err(TlsError::InvalidHandshake),
+            }
+        }
+        ServerRecord::Alert(alert) => {
+            Err(TlsError::HandshakeAborted(alert.level, alert.description))
+        }
+        _ => Err(TlsError::InvalidRecord),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Finish a record once TLS 1.2 was negotiated, protecting it if the keys are in use.
+///
+/// `buf` holds the record header followed by the plaintext.
+pub(crate) fn close_record<'a, CipherSuite>(
+    key_schedule: &WriteKeySchedule<CipherSuite>,
+    records: Tls12Records,
+    header: ClientRecordHeader,
+    mut buf: CryptoBuffer<'a>,
+) -> Result<CryptoBuffer<'a>, TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    buf.as_mut_slice()[1..3].copy_from_slice(&VERSION);
+    if records == Tls12Records::Plaintext {
+        return Ok(buf);
+    }
+
+    let content_type = header.trailer_content_type();
+    let sequence_number = key_schedule.sequence_number().to_be_bytes();
+    let len = buf.len() - RecordHeader::LEN;
+
+    // Make room for the explicit nonce, which is the sequence number
+    buf.extend_from_slice(&[0; EXPLICIT_NONCE_LEN])?;
+    let record = buf.as_mut_slice();
+    record.copy_within(
+        RecordHeader::LEN..RecordHeader::LEN + len,
+        RecordHeader::LEN + 8,
+    );
+    record[0] = content_type as u8;
+    record[RecordHeader::LEN..][..EXPLICIT_NONCE_LEN].copy_from_slice(&sequence_number);
+
+    let additional_data = additional_data(sequence_number, content_type, len);
+    let mut payload = buf.offset(RecordHeader::LEN + EXPLICIT_NONCE_LEN);
+    <CipherSuite::Cipher as KeyInit>::new(key_schedule.get_key()?)
+        .encrypt_in_place(&key_schedule.get_nonce()?, &additional_data, &mut payload)
+        .map_err(|_| TlsError::InvalidApplicationData)?;
+
+    Ok(payload.rewind())
+}
+
+/// Decrypt a protected record in place, returning its plaintext.
+fn open<'a, CipherSuite>(
+    key_schedule: &mut ReadKeySchedule<CipherSuite>,
+    header: &RecordHeader,
+    data: &'a mut CryptoBuffer,
+) -> Result<&'a [u8], TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    let tag_len = <CipherSuite::Cipher as aes_gcm::AeadCore>::TagSize::USIZE;
+    let record = data.as_mut_slice();
+    if record.len() < EXPLICIT_NONCE_LEN + tag_len {
+        return Err(TlsError::BadRecordMac);
+    }
+    let (explicit_nonce, payload) = record.split_at_mut(EXPLICIT_NONCE_LEN);
+
+    let mut nonce = key_schedule.get_iv()?.clone();
+    nonce[SALT_LEN..].copy_from_slice(explicit_nonce);
+
+    let len = payload.len() - tag_len;
+    let (ciphertext, tag) = payload.split_at_mut(len);
+    let additional_data = additional_data(
+        key_schedule.sequence_number().to_be_bytes(),
+        header.content_type(),
+        len,
+    );
+    <CipherSuite::Cipher as KeyInit>::new(key_schedule.get_key()?)
+        .decrypt_in_place_detached(
+            &nonce,
+            &additional_data,
+            ciphertext,
+            aes_gcm::Tag::<<CipherSuite::Cipher as aes_gcm::AeadCore>::TagSize>::from_slice(tag),
+        )
+        .map_err(|_| TlsError::BadRecordMac)?;
+    key_schedule.increment_counter();
+
+    Ok(&data.as_slice()[EXPLICIT_NONCE_LEN..][..len])
+}
+
+/// The additional data of a protected record (RFC 5246, Section 6.2.3.3).
+fn additional_data(sequence_number: [u8; 8], content_type: ContentType, len: usize) -> [u8; 13] {
+    let mut additional_data = [0; 13];
+    additional_data[..8].copy_from_slice(&sequence_number);
+    additional_data[8] = content_type as u8;
+    additional_data[9..11].copy_from_slice(&VERSION);
+    additional_data[11..].copy_from_slice(&(len as u16).to_be_bytes());
+    additional_data
+}
+
+/// Decrypt a protected record received after the handshake.
+pub(crate) fn decrypt_record<CipherSuite>(
+    key_schedule: &mut ReadKeySchedule<CipherSuite>,
+    record: ServerRecord<'_, CipherSuite>,
+    mut cb: impl FnMut(
+        &mut ReadKeySchedule<CipherSuite>,
+        ServerRecord<'_, CipherSuite>,
+    ) -> Result<(), TlsError>,
+) -> Result<(), TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    let ServerRecord::ApplicationData(ApplicationData {
+        header, mut data, ..
+    }) = record
+    else {
+        return cb(key_schedule, record);
+    };
+
+    let content_type = header.content_type();
+    let len = open(key_schedule, &header, &mut data)?.len();
+    let mut data = data.offset(EXPLICIT_NONCE_LEN);
+    data.truncate(len);
+
+    trace!("Decrypting: content type = {:?}", content_type);
+    match content_type {
+        ContentType::ApplicationData => {
+            cb(
+                key_schedule,
+                ServerRecord::ApplicationData(ApplicationData::new(data, header)),
+            )?;
+        }
+        ContentType::Alert => {
+            let alert = Alert::parse(&mut ParseBuffer::new(data.as_slice()))?;
+            cb(key_schedule, ServerRecord::Alert(alert))?;
+        }
+        ContentType::Handshake => match data.as_slice() {
+            // Renegotiation is not supported, so HelloRequest is ignored (RFC 5246,
+            // Section 7.4.1.1)
+            [0, 0, 0, 0] => {}
+            _ => return Err(TlsError::InvalidHandshake),
+        },
+        _ => return Err(TlsError::UnknownContentType),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Aes128GcmSha256;
+
+    #[test]
+    fn prf_sha256() {
+        // Widely used TLS 1.2 PRF test vector, which OpenSSL's TLS1-PRF KDF reproduces
+        let secret = [
+            0x9b, 0xbe, 0x43, 0x6b, 0xa9, 0x40, 0xf0, 0x17, 0xb1, 0x76, 0x52, 0x84, 0x9a, 0x71,
+            0xdb, 0x35,
+        ];
+        let seed = [
+            0xa0, 0xba, 0x9f, 0x93, 0x6c, 0xda, 0x31, 0x18, 0x27, 0xa6, 0xf7, 0x96, 0xff, 0xd5,
+            0x19, 0x8c,
+        ];
+        let expected = [
+            0xe3, 0xf2, 0x29, 0xba, 0x72, 0x7b, 0xe1, 0x7b, 0x8d, 0x12, 0x26, 0x20, 0x55, 0x7c,
+            0xd4, 0x53, 0xc2, 0xaa, 0xb2, 0x1d, 0x07, 0xc3, 0xd4, 0x95, 0x32, 0x9b, 0x52, 0xd4,
+            0xe6, 0x1e, 0xdb, 0x5a, 0x6b, 0x30, 0x17, 0x91, 0xe9, 0x0d, 0x35, 0xc9, 0xc9, 0xa4,
+            0x6b, 0x4e, 0x14, 0xba, 0xf9, 0xaf, 0x0f, 0xa0, 0x22, 0xf7, 0x07, 0x7d, 0xef, 0x17,
+            0xab, 0xfd, 0x37, 0x97, 0xc0, 0x56, 0x4b, 0xab, 0x4f, 0xbc, 0x91, 0x66, 0x6e, 0x9d,
+            0xef, 0x9b, 0x97, 0xfc, 0xe3, 0x4f, 0x79, 0x67, 0x89, 0xba, 0xa4, 0x80, 0x82, 0xd1,
+            0x22, 0xee, 0x42, 0xc5, 0xa7, 0x2e, 0x5a, 0x51, 0x10, 0xff, 0xf7, 0x01, 0x87, 0x34,
+            0x7b, 0x66,
+        ];
+
+        let mut out = [0; 100];
+        prf::<Aes128GcmSha256>(&secret, b"test label", &[&seed[..8], &seed[8..]], &mut out)
+            .unwrap();
+        assert_eq!(expected, out);
+    }
+}
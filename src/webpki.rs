@@ -57,6 +57,9 @@ impl TryInto<&'static webpki::SignatureAlgorithm> for SignatureScheme {
             SignatureScheme::Sha256BrainpoolP256r1
             | SignatureScheme::Sha384BrainpoolP384r1
             | SignatureScheme::Sha512BrainpoolP512r1 => Err(TlsError::InvalidSignatureScheme),
+
+            /* ShangMi */
+            SignatureScheme::Sm2sigSm3 => Err(TlsError::InvalidSignatureScheme),
         }
     }
 }
@@ -106,6 +109,9 @@ impl TryInto<&'static webpki::SignatureAlgorithm> for SignatureScheme {
             SignatureScheme::Sha256BrainpoolP256r1 => Err(TlsError::InvalidSignatureScheme),
             SignatureScheme::Sha384BrainpoolP384r1 => Err(TlsError::InvalidSignatureScheme),
             SignatureScheme::Sha512BrainpoolP512r1 => Err(TlsError::InvalidSignatureScheme),
+
+            /* ShangMi */
+            SignatureScheme::Sm2sigSm3 => Err(TlsError::InvalidSignatureScheme),
         }
     }
 }
@@ -127,6 +133,7 @@ where
     host: Option<heapless::String<64>>,
     certificate_transcript: Option<CipherSuite::Hash>,
     certificate: Option<OwnedCertificate<CERT_SIZE>>,
+    client_certificates: bool,
     _clock: PhantomData<Clock>,
 }
 
@@ -142,9 +149,18 @@ where
             host: None,
             certificate_transcript: None,
             certificate: None,
+            client_certificates: false,
             _clock: PhantomData,
         }
     }
+
+    /// Verify client certificates for a [`TlsServerConnection`](crate::TlsServerConnection)
+    /// instead of server certificates.
+    #[must_use]
+    pub fn for_client_certificates(mut self) -> Self {
+        self.client_certificates = true;
+        self
+    }
 }
 
 impl<CipherSuite, Clock, const CERT_SIZE: usize> TlsVerifier<CipherSuite>
@@ -165,7 +181,12 @@ where
         transcript: &CipherSuite::Hash,
         cert: ServerCertificate,
     ) -> Result<(), TlsError> {
-        verify_certificate(self.host.as_deref(), &self.ca, &cert, Clock::now())?;
+        let usage = if self.client_certificates {
+            webpki::KeyUsage::client_auth()
+        } else {
+            webpki::KeyUsage::server_auth()
+        };
+        verify_certificate(self.host.as_deref(), &self.ca, &cert, Clock::now(), usage)?;
         self.certificate.replace(cert.try_into()?);
         self.certificate_transcript.replace(transcript.clone());
         Ok(())
@@ -173,7 +194,11 @@ where
 
     fn verify_signature(&mut self, verify: CertificateVerifyRef) -> Result<(), TlsError> {
         let handshake_hash = unwrap!(self.certificate_transcript.take());
-        let ctx_str = b"TLS 1.3, server CertificateVerify\x00";
+        let ctx_str: &[u8] = if self.client_certificates {
+            b"TLS 1.3, client CertificateVerify\x00"
+        } else {
+            b"TLS 1.3, server CertificateVerify\x00"
+        };
         let mut msg: Vec<u8, 130> = Vec::new();
         msg.resize(64, 0x20).map_err(|_| TlsError::EncodeError)?;
         msg.extend_from_slice(ctx_str)
@@ -185,6 +210,20 @@ where
         verify_signature(&msg[..], &certificate, &verify)?;
         Ok(())
     }
+
+    #[cfg(feature = "tls12")]
+    fn verify_tls12_signature(
+        &mut self,
+        message: &[u8],
+        verify: CertificateVerifyRef,
+    ) -> Result<(), TlsError> {
+        let certificate = self
+            .certificate
+            .as_ref()
+            .ok_or(TlsError::InvalidHandshake)?
+            .try_into()?;
+        verify_signature(message, &certificate, &verify)
+    }
 }
 
 fn verify_signature(
@@ -228,6 +267,7 @@ fn verify_certificate(
     ca: &Certificate<&[u8]>,
     certificate: &ServerCertificate,
     now: Option<u64>,
+    usage: webpki::KeyUsage,
 ) -> Result<(), TlsError> {
     let mut verified = false;
     let mut host_verified = false;
@@ -254,14 +294,7 @@ fn verify_certificate(
                     webpki::Time::from_seconds_since_unix_epoch(0)
                 };
                 info!("Certificate is loaded!");
-                match cert.verify_for_usage(
-                    ALL_SIGALGS,
-                    &[trust],
-                    &[],
-                    time,
-                    webpki::KeyUsage::server_auth(),
-                    &[],
-                ) {
+                match cert.verify_for_usage(ALL_SIGALGS, &[trust], &[], time, usage, &[]) {
                     Ok(()) => verified = true,
                     Err(e) => {
                         warn!("Error verifying certificate: {:?}", e);
@@ -1,14 +1,20 @@
+use core::ops::Range;
+
+use zeroize::Zeroize;
+
 use crate::{
     TlsError,
+    alert::Alert,
     buffer::CryptoBuffer,
     config::{TLS_RECORD_OVERHEAD, TlsCipherSuite},
     connection::encrypt,
     key_schedule::{ReadKeySchedule, WriteKeySchedule},
     record::{ClientRecord, ClientRecordHeader},
+    record_buffer::RecordBuffer,
 };
 
-pub struct WriteBuffer<'a> {
-    buffer: &'a mut [u8],
+pub struct WriteBuffer<B> {
+    buffer: B,
     pos: usize,
     current_header: Option<ClientRecordHeader>,
 }
@@ -25,10 +31,10 @@ pub(crate) struct WriteBufferBorrowMut<'a> {
     current_header: &'a mut Option<ClientRecordHeader>,
 }
 
-impl<'a> WriteBuffer<'a> {
-    pub fn new(buffer: &'a mut [u8]) -> Self {
+impl<B: AsRef<[u8]> + AsMut<[u8]>> WriteBuffer<B> {
+    pub fn new(buffer: B) -> Self {
         debug_assert!(
-            buffer.len() > TLS_RECORD_OVERHEAD,
+            buffer.as_ref().len() > TLS_RECORD_OVERHEAD,
             "The write buffer must be sufficiently large to include the tls record overhead"
         );
         Self {
@@ -40,7 +46,7 @@ impl<'a> WriteBuffer<'a> {
 
     pub(crate) fn reborrow_mut(&mut self) -> WriteBufferBorrowMut<'_> {
         WriteBufferBorrowMut {
-            buffer: self.buffer,
+            buffer: self.buffer.as_mut(),
             pos: &mut self.pos,
             current_header: &mut self.current_header,
         }
@@ -48,7 +54,7 @@ impl<'a> WriteBuffer<'a> {
 
     pub(crate) fn reborrow(&self) -> WriteBufferBorrow<'_> {
         WriteBufferBorrow {
-            buffer: self.buffer,
+            buffer: self.buffer.as_ref(),
             pos: &self.pos,
             current_header: &self.current_header,
         }
@@ -58,6 +64,28 @@ impl<'a> WriteBuffer<'a> {
         self.reborrow().is_full()
     }
 
+    /// Zeroize the buffer and discard the partially built record.
+    pub(crate) fn reset(&mut self) {
+        self.buffer.as_mut().zeroize();
+        self.pos = 0;
+        self.current_header = None;
+    }
+
+    pub(crate) fn into_buf(self) -> B {
+        self.buffer
+    }
+
+    /// The size of the buffer.
+    pub(crate) fn capacity(&self) -> usize {
+        self.buffer.as_ref().len()
+    }
+
+    /// Part of the records encoded by [`Self::close_record`], [`Self::write_record`] and
+    /// [`Self::write_alert`], which start at the beginning of the buffer.
+    pub(crate) fn encoded(&self, range: Range<usize>) -> &[u8] {
+        &self.buffer.as_ref()[range]
+    }
+
     pub fn append(&mut self, buf: &[u8]) -> usize {
         self.reborrow_mut().append(buf)
     }
@@ -82,7 +110,7 @@ impl<'a> WriteBuffer<'a> {
         CipherSuite: TlsCipherSuite,
     {
         close_record(
-            self.buffer,
+            self.buffer.as_mut(),
             &mut self.pos,
             &mut self.current_header,
             write_key_schedule,
@@ -99,7 +127,7 @@ impl<'a> WriteBuffer<'a> {
         CipherSuite: TlsCipherSuite,
     {
         write_record(
-            self.buffer,
+            self.buffer.as_mut(),
             &mut self.pos,
             &mut self.current_header,
             record,
@@ -107,6 +135,40 @@ impl<'a> WriteBuffer<'a> {
             read_key_schedule,
         )
     }
+
+    /// Encode `alert` into an encrypted record of its own, discarding any partially built record.
+    pub(crate) fn write_alert<CipherSuite>(
+        &mut self,
+        alert: &Alert,
+        write_key_schedule: &mut WriteKeySchedule<CipherSuite>,
+    ) -> Result<&[u8], TlsError>
+    where
+        CipherSuite: TlsCipherSuite,
+    {
+        write_alert(
+            self.buffer.as_mut(),
+            &mut self.pos,
+            &mut self.current_header,
+            alert,
+            write_key_schedule,
+        )
+    }
+}
+
+impl<'a, B: RecordBuffer<'a>> WriteBuffer<B> {
+    /// Take the buffer out, to lend it to the read side of a half-duplex connection. The
+    /// partially built record is discarded.
+    pub(crate) fn take_buf(&mut self) -> &'a mut [u8] {
+        self.pos = 0;
+        self.current_header = None;
+        self.buffer.replace(&mut []).unwrap_or_default()
+    }
+
+    /// Give the buffer taken by [`Self::take_buf`] back. Owned storage, which is never
+    /// shared, keeps its own buffer.
+    pub(crate) fn set_buf(&mut self, buf: &'a mut [u8]) {
+        self.buffer.replace(buf);
+    }
 }
 
 impl WriteBufferBorrow<'_> {
@@ -169,6 +231,24 @@ impl WriteBufferBorrowMut<'_> {
         start_record(self.buffer, self.pos, self.current_header, header)
     }
 
+    /// Encode `alert` into an encrypted record of its own, discarding any partially built record.
+    pub(crate) fn write_alert<CipherSuite>(
+        &mut self,
+        alert: &Alert,
+        write_key_schedule: &mut WriteKeySchedule<CipherSuite>,
+    ) -> Result<&[u8], TlsError>
+    where
+        CipherSuite: TlsCipherSuite,
+    {
+        write_alert(
+            self.buffer,
+            self.pos,
+            self.current_header,
+            alert,
+            write_key_schedule,
+        )
+    }
+
     pub fn close_record<CipherSuite>(
         &mut self,
         write_key_schedule: &mut WriteKeySchedule<CipherSuite>,
@@ -232,6 +312,11 @@ where
 
     let header = current_header.take().unwrap();
     with_buffer(buffer, pos, |mut buf| {
+        #[cfg(feature = "tls12")]
+        if let Some(records) = write_key_schedule.tls12_records() {
+            return crate::tls12::close_record(write_key_schedule, records, header, buf);
+        }
+
         if !header.is_encrypted() {
             return Ok(buf);
         }
@@ -285,3 +370,24 @@ where
     })?;
     close_record(buffer, pos, current_header, write_key_schedule)
 }
+
+fn write_alert<'a, CipherSuite>(
+    buffer: &'a mut [u8],
+    pos: &mut usize,
+    current_header: &mut Option<ClientRecordHeader>,
+    alert: &Alert,
+    write_key_schedule: &mut WriteKeySchedule<CipherSuite>,
+) -> Result<&'a [u8], TlsError>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    *pos = 0;
+    *current_header = None;
+
+    start_record(buffer, pos, current_header, ClientRecordHeader::Alert(true))?;
+    with_buffer(buffer, pos, |mut buf| {
+        alert.encode(&mut buf)?;
+        Ok(buf)
+    })?;
+    close_record(buffer, pos, current_header, write_key_schedule)
+}
@@ -0,0 +1,141 @@
+#![cfg(all(feature = "brainpool", feature = "rustpki"))]
+use embedded_io_adapters::tokio_1::FromTokio;
+use embedded_tls::pki::CertVerifier;
+use embedded_tls::*;
+use openssl::ssl;
+use rand::rngs::OsRng;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Once;
+use std::time::SystemTime;
+use tokio::net::TcpStream;
+
+static INIT: Once = Once::new();
+
+fn init_log() {
+    INIT.call_once(|| {
+        env_logger::init();
+    });
+}
+
+/// Verifies the brainpoolP512r1 certificate of the OpenSSL server.
+struct BrainpoolProvider<'a> {
+    rng: OsRng,
+    verifier: CertVerifier<'a, Aes128GcmSha256, SystemTime, 4096>,
+}
+
+impl CryptoProvider for BrainpoolProvider<'_> {
+    type CipherSuite = Aes128GcmSha256;
+    type Signature = p256::ecdsa::DerSignature;
+
+    fn rng(&mut self) -> impl CryptoRngCore {
+        &mut self.rng
+    }
+
+    fn verifier(&mut self) -> Result<&mut impl TlsVerifier<Aes128GcmSha256>, TlsError> {
+        Ok(&mut self.verifier)
+    }
+}
+
+/// Start an OpenSSL server that only speaks TLS 1.3 over the brainpool `group`, and echoes the
+/// first message it receives.
+fn setup_openssl(group: &str) -> (SocketAddr, std::thread::JoinHandle<()>) {
+    init_log();
+
+    let mut builder = ssl::SslAcceptor::mozilla_modern_v5(ssl::SslMethod::tls_server()).unwrap();
+    builder
+        .set_private_key_file("tests/data/bp512-server-key.pem", ssl::SslFiletype::PEM)
+        .unwrap();
+    builder
+        .set_certificate_chain_file("tests/data/bp512-server-cert.pem")
+        .unwrap();
+    builder.set_groups_list(group).unwrap();
+    let acceptor = builder.build();
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("cannot listen on port");
+    let addr = listener
+        .local_addr()
+        .expect("error retrieving socket address");
+
+    let h = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        if let Ok(mut conn) = acceptor.accept(stream) {
+            let mut buf = [0; 64];
+            let len = conn.read(&mut buf[..]).unwrap();
+            conn.write_all(&buf[..len]).unwrap();
+            let _ = conn.shutdown();
+        }
+    });
+    (addr, h)
+}
+
+async fn ping(group: NamedGroup, openssl_group: &str) {
+    // OpenSSL 3.2 added the brainpool groups of RFC 8734
+    if openssl::version::number() < 0x3020_0000 {
+        eprintln!(
+            "skipped, {} has no brainpool groups for TLS 1.3",
+            openssl::version::version()
+        );
+        return;
+    }
+
+    let (addr, h) = setup_openssl(openssl_group);
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let ca = pem_parser::pem_to_der(include_str!("data/bp512-ca-cert.pem"));
+    let groups = [group];
+    let mut read_record_buffer = [0; 16640];
+    let mut write_record_buffer = [0; 16640];
+    let config = TlsConfig::builder()
+        .with_server_name("localhost")
+        .with_signature_schemes(&[SignatureScheme::Sha512BrainpoolP512r1])
+        .with_named_groups(&groups)
+        .build()
+        .unwrap();
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    tls.open(TlsContext::new(
+        &config,
+        BrainpoolProvider {
+            rng: OsRng,
+            verifier: CertVerifier::new(Certificate::X509(&ca)),
+        },
+    ))
+    .await
+    .expect("error establishing TLS connection");
+
+    tls.write(b"ping").await.unwrap();
+    tls.flush().await.unwrap();
+
+    let mut rx = [0; 4];
+    let len = tls.read(&mut rx).await.unwrap();
+    assert_eq!(b"ping", &rx[..len]);
+
+    tls.close()
+        .await
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+    h.join().unwrap();
+}
+
+#[tokio::test]
+async fn test_brainpool_p256r1() {
+    ping(NamedGroup::BrainpoolP256r1Tls13, "brainpoolP256r1tls13").await;
+}
+
+#[tokio::test]
+async fn test_brainpool_p384r1() {
+    ping(NamedGroup::BrainpoolP384r1Tls13, "brainpoolP384r1tls13").await;
+}
+
+#[tokio::test]
+async fn test_brainpool_p512r1() {
+    ping(NamedGroup::BrainpoolP512r1Tls13, "brainpoolP512r1tls13").await;
+}
@@ -172,3 +172,47 @@ async fn test_client_certificate_auth() {
         .map_err(|(_, e)| e)
         .expect("error closing session");
 }
+
+#[tokio::test]
+async fn test_client_certificate_missing() {
+    use embedded_tls::alert::{AlertDescription, AlertLevel};
+    use embedded_tls::*;
+    use tokio::net::TcpStream;
+    let addr = setup();
+
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let config = TlsConfig::new().with_server_name("factbird.com");
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    let mut provider = Provider {
+        rng: OsRng,
+        priv_key: &[],
+        client_cert: None,
+    };
+    tls.open(TlsContext::new(&config, &mut provider))
+        .await
+        .expect("error establishing TLS connection");
+
+    // The server only rejects the empty certificate after our Finished, so the alert
+    // shows up on the first read.
+    let mut rx_buf = [0; 4096];
+    let err = tls
+        .read(&mut rx_buf)
+        .await
+        .expect_err("server must reject client");
+    assert!(matches!(
+        err,
+        TlsError::HandshakeAborted(AlertLevel::Fatal, AlertDescription::CertificateRequired)
+    ));
+    assert!(!tls.is_opened());
+}
@@ -396,3 +396,764 @@ fn test_blocking_ping_nocopy_bufread() {
         .map_err(|(_, e)| e)
         .expect("error closing session");
 }
+
+#[tokio::test]
+async fn test_ping_grease_padding() {
+    use embedded_tls::*;
+    use tokio::net::TcpStream;
+
+    let addr = setup();
+
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let config = TlsConfig::new()
+        .with_server_name("localhost")
+        .enable_grease()
+        .with_padding(512);
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .await
+    .expect("error establishing TLS connection");
+
+    tls.write_all(b"ping").await.expect("error writing data");
+    tls.flush().await.expect("error flushing data");
+
+    let mut rx_buf = [0; 4096];
+    let sz = tls.read(&mut rx_buf).await.expect("error reading data");
+    assert_eq!(b"ping", &rx_buf[..sz]);
+
+    tls.close()
+        .await
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+}
+
+#[tokio::test]
+async fn test_ping_middlebox_compatibility() {
+    use embedded_tls::*;
+    use tokio::net::TcpStream;
+
+    let addr = setup();
+
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let config = TlsConfig::new()
+        .with_server_name("localhost")
+        .enable_middlebox_compatibility();
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .await
+    .expect("error establishing TLS connection");
+
+    tls.write_all(b"ping").await.expect("error writing data");
+    tls.flush().await.expect("error flushing data");
+
+    let mut rx_buf = [0; 4096];
+    let sz = tls.read(&mut rx_buf).await.expect("error reading data");
+    assert_eq!(b"ping", &rx_buf[..sz]);
+
+    tls.close()
+        .await
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+}
+
+#[tokio::test]
+async fn test_ping_precomputed_key_share() {
+    use embedded_tls::*;
+    use tokio::net::TcpStream;
+
+    let addr = setup();
+
+    let mut pool = KeySharePool::<2>::new();
+    pool.fill(&mut OsRng);
+
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    for remaining in [1, 0] {
+        let stream = TcpStream::connect(addr)
+            .await
+            .expect("error connecting to server");
+
+        let mut tls = TlsConnection::new(
+            FromTokio::new(stream),
+            &mut read_record_buffer,
+            &mut write_record_buffer,
+        );
+        tls.open(
+            TlsContext::new(&config, UnsecureProvider::new::<Aes128GcmSha256>(OsRng))
+                .with_key_shares(&mut pool),
+        )
+        .await
+        .expect("error establishing TLS connection");
+        assert_eq!(pool.len(), remaining);
+
+        tls.write_all(b"ping").await.expect("error writing data");
+        tls.flush().await.expect("error flushing data");
+
+        let mut rx_buf = [0; 4096];
+        let sz = tls.read(&mut rx_buf).await.expect("error reading data");
+        assert_eq!(b"ping", &rx_buf[..sz]);
+
+        tls.close()
+            .await
+            .map_err(|(_, e)| e)
+            .expect("error closing session");
+    }
+}
+
+#[tokio::test]
+async fn test_send_alert() {
+    use embedded_tls::alert::{AlertDescription, AlertLevel};
+    use embedded_tls::*;
+    use tokio::net::TcpStream;
+
+    let addr = setup();
+
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .await
+    .expect("error establishing TLS connection");
+
+    tls.send_alert(AlertLevel::Warning, AlertDescription::UserCanceled)
+        .await
+        .expect("error sending alert");
+    assert!(tls.is_opened());
+
+    tls.send_alert(AlertLevel::Fatal, AlertDescription::AccessDenied)
+        .await
+        .expect("error sending alert");
+    assert!(!tls.is_opened());
+    assert!(matches!(
+        tls.write(b"ping").await,
+        Err(TlsError::MissingHandshake)
+    ));
+}
+
+#[tokio::test]
+async fn test_half_close() {
+    use embedded_tls::*;
+    use tokio::net::TcpStream;
+
+    let addr = setup();
+
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .await
+    .expect("error establishing TLS connection");
+
+    tls.write_all(b"ping").await.expect("error writing data");
+    tls.shutdown_write().await.expect("error shutting down");
+
+    let mut rx_buf = [0; 4096];
+    let sz = tls.read(&mut rx_buf).await.expect("error reading data");
+    assert_eq!(b"ping", &rx_buf[..sz]);
+
+    let sz = tls.read(&mut rx_buf).await.expect("error reading EOF");
+    assert_eq!(0, sz);
+
+    tls.shutdown()
+        .await
+        .map_err(|(_, e)| e)
+        .expect("error shutting down");
+}
+
+#[test]
+fn test_blocking_graceful_shutdown() {
+    use embedded_tls::blocking::*;
+    use std::net::TcpStream;
+
+    let addr = setup();
+    let stream = TcpStream::connect(addr).expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls: TlsConnection<FromStd<TcpStream>, Aes128GcmSha256> = TlsConnection::new(
+        FromStd::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .expect("error establishing TLS connection");
+
+    // The echo is discarded while waiting for the server's close_notify
+    tls.write(b"ping").expect("error writing data");
+
+    tls.shutdown()
+        .map_err(|(_, e)| e)
+        .expect("error shutting down");
+}
+
+#[test]
+fn test_sans_io_ping() {
+    use embedded_tls::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let addr = setup();
+    let mut stream = TcpStream::connect(addr).expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let config = TlsConfig::new().with_server_name("localhost");
+    let mut provider = UnsecureProvider::new::<Aes128GcmSha256>(OsRng);
+
+    let mut tls: TlsClient<Aes128GcmSha256> =
+        TlsClient::new(&mut read_record_buffer, &mut write_record_buffer);
+
+    while tls.is_handshaking() {
+        tls.handle_handshake(&config, &mut provider)
+            .expect("error establishing TLS connection");
+        let tx = tls.pending_transmit();
+        stream.write_all(tx).expect("error sending data");
+        let len = tx.len();
+        tls.transmitted(len);
+        if tls.wants_read() {
+            let len = stream.read(tls.rx_buffer()).expect("error receiving data");
+            tls.receive(len).expect("error receiving data");
+        }
+    }
+    assert!(tls.is_opened());
+
+    assert_eq!(4, tls.write(b"ping").expect("error writing data"));
+    tls.flush().expect("error flushing data");
+    let tx = tls.pending_transmit();
+    stream.write_all(tx).expect("error sending data");
+    let len = tx.len();
+    tls.transmitted(len);
+
+    let mut rx_buf = [0; 4096];
+    let sz = loop {
+        if let Some(sz) = tls.read(&mut rx_buf).expect("error reading data") {
+            break sz;
+        }
+        let len = stream.read(tls.rx_buffer()).expect("error receiving data");
+        tls.receive(len).expect("error receiving data");
+    };
+    assert_eq!(b"ping", &rx_buf[..sz]);
+
+    tls.close().expect("error closing session");
+    stream
+        .write_all(tls.pending_transmit())
+        .expect("error sending data");
+}
+
+#[tokio::test]
+async fn test_half_duplex_ping() {
+    use embedded_tls::*;
+    use tokio::net::TcpStream;
+
+    let addr = setup();
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut record_buffer = [0; 16384];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls = TlsConnection::new_half_duplex(FromTokio::new(stream), &mut record_buffer);
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .await
+    .expect("error establishing TLS connection");
+
+    for _ in 0..2 {
+        tls.write(b"ping").await.expect("error writing data");
+        tls.flush().await.expect("error flushing data");
+
+        let mut rx_buf = [0; 4];
+        let sz = tls.read(&mut rx_buf).await.expect("error reading data");
+        assert_eq!(b"ping", &rx_buf[..sz]);
+    }
+
+    tls.close()
+        .await
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+}
+
+#[tokio::test]
+async fn test_reset_and_reconnect() {
+    use embedded_tls::*;
+    use tokio::net::TcpStream;
+
+    let addr = setup();
+    let connect = || async {
+        FromTokio::new(
+            TcpStream::connect(addr)
+                .await
+                .expect("error connecting to server"),
+        )
+    };
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let mut tls = TlsConnection::new(
+        connect().await,
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    for i in 0..3 {
+        if i == 1 {
+            tls.shutdown_write().await.expect("error closing session");
+            tls.reset(connect().await);
+        } else if i == 2 {
+            let (_, buffers) = tls.into_parts();
+            let RecordBuffers::FullDuplex(read_buf, write_buf) = buffers else {
+                panic!("expected full-duplex buffers");
+            };
+            assert!(read_buf.iter().chain(write_buf.iter()).all(|b| *b == 0));
+            tls = TlsConnection::from_parts(
+                connect().await,
+                RecordBuffers::FullDuplex(read_buf, write_buf),
+            );
+        }
+
+        tls.open(TlsContext::new(
+            &config,
+            UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+        ))
+        .await
+        .expect("error establishing TLS connection");
+
+        tls.write(b"ping").await.expect("error writing data");
+        tls.flush().await.expect("error flushing data");
+
+        let mut rx_buf = [0; 4];
+        let sz = tls.read(&mut rx_buf).await.expect("error reading data");
+        assert_eq!(b"ping", &rx_buf[..sz]);
+    }
+
+    tls.close()
+        .await
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+}
+
+#[tokio::test]
+async fn test_half_duplex_into_parts() {
+    use embedded_tls::*;
+    use tokio::net::TcpStream;
+
+    let addr = setup();
+    let connect = || async {
+        FromTokio::new(
+            TcpStream::connect(addr)
+                .await
+                .expect("error connecting to server"),
+        )
+    };
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut record_buffer = [0; 16384];
+    let mut tls = TlsConnection::new_half_duplex(connect().await, &mut record_buffer);
+
+    for i in 0..2 {
+        if i == 1 {
+            // The buffer is held by the read side after reading
+            let (_, buffers) = tls.into_parts();
+            let RecordBuffers::HalfDuplex(record_buf) = buffers else {
+                panic!("expected a half-duplex buffer");
+            };
+            assert_eq!(record_buf.len(), 16384);
+            assert!(record_buf.iter().all(|b| *b == 0));
+            tls = TlsConnection::from_parts(connect().await, RecordBuffers::HalfDuplex(record_buf));
+        }
+
+        tls.open(TlsContext::new(
+            &config,
+            UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+        ))
+        .await
+        .expect("error establishing TLS connection");
+
+        tls.write(b"ping").await.expect("error writing data");
+        tls.flush().await.expect("error flushing data");
+
+        let mut rx_buf = [0; 4];
+        let sz = tls.read(&mut rx_buf).await.expect("error reading data");
+        assert_eq!(b"ping", &rx_buf[..sz]);
+    }
+
+    tls.close()
+        .await
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+}
+
+#[tokio::test]
+async fn test_owned_ping() {
+    use embedded_tls::*;
+    use tokio::net::TcpStream;
+
+    let addr = setup();
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut tls: owned::TlsConnection<_, Aes128GcmSha256, 16640, 4096> =
+        owned::TlsConnection::new(FromTokio::new(stream));
+
+    // The connection does not borrow anything, so it can be moved to another task
+    tokio::spawn(async move {
+        let mut material = [[0; 32]; 2];
+        assert!(matches!(
+            tls.export_keying_material(b"EXPORTER-test", b"", &mut material[0]),
+            Err(TlsError::MissingHandshake)
+        ));
+
+        let config = TlsConfig::new().with_server_name("localhost");
+        tls.open(TlsContext::new(
+            &config,
+            UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+        ))
+        .await
+        .expect("error establishing TLS connection");
+
+        tls.export_keying_material(b"EXPORTER-test", b"", &mut material[0])
+            .expect("error exporting keying material");
+        tls.export_keying_material(b"EXPORTER-test", b"context", &mut material[1])
+            .expect("error exporting keying material");
+        assert_ne!(material[0], [0; 32]);
+        assert_ne!(material[0], material[1]);
+
+        tls.write(b"ping").await.expect("error writing data");
+        tls.flush().await.expect("error flushing data");
+
+        let mut rx_buf = [0; 4];
+        let sz = tls.read(&mut rx_buf).await.expect("error reading data");
+        assert_eq!(b"ping", &rx_buf[..sz]);
+
+        tls.close()
+            .await
+            .map_err(|(_, e)| e)
+            .expect("error closing session");
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_owned_config() {
+    use embedded_tls::*;
+    use std::sync::OnceLock;
+    use tokio::net::TcpStream;
+
+    static CONFIG: OnceLock<owned::TlsConfig<32, 16, 0, 0>> = OnceLock::new();
+
+    // The server name is only known at runtime, and is copied into the static config
+    let server_name = std::string::String::from("localhost");
+    let config = CONFIG.get_or_init(|| {
+        owned::TlsConfig::new(&TlsConfig::new().with_server_name(&server_name)).unwrap()
+    });
+    drop(server_name);
+
+    let addr = setup();
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+    tls.open(TlsContext::new(
+        config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .await
+    .expect("error establishing TLS connection");
+
+    tls.write(b"ping").await.expect("error writing data");
+    tls.flush().await.expect("error flushing data");
+
+    let mut rx_buf = [0; 4];
+    let sz = tls.read(&mut rx_buf).await.expect("error reading data");
+    assert_eq!(b"ping", &rx_buf[..sz]);
+
+    tls.close()
+        .await
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+}
+
+#[tokio::test]
+async fn test_half_duplex_misuse() {
+    use embedded_tls::*;
+    use tokio::net::TcpStream;
+
+    let addr = setup();
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut record_buffer = [0; 16384];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls = TlsConnection::new_half_duplex(FromTokio::new(stream), &mut record_buffer);
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .await
+    .expect("error establishing TLS connection");
+
+    // Reading before flushing
+    tls.write(b"ping").await.expect("error writing data");
+    let mut rx_buf = [0; 2];
+    assert!(matches!(
+        tls.read(&mut rx_buf).await,
+        Err(TlsError::BufferInUse)
+    ));
+    tls.flush().await.expect("error flushing data");
+
+    // Writing before reading everything
+    assert_eq!(2, tls.read(&mut rx_buf).await.expect("error reading data"));
+    assert_eq!(b"pi", &rx_buf);
+    assert!(matches!(
+        tls.write(b"ping").await,
+        Err(TlsError::BufferInUse)
+    ));
+    assert_eq!(2, tls.read(&mut rx_buf).await.expect("error reading data"));
+    assert_eq!(b"ng", &rx_buf);
+
+    tls.write(b"ping").await.expect("error writing data");
+    tls.close()
+        .await
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+}
+
+#[test]
+fn test_blocking_half_duplex_ping() {
+    use embedded_tls::blocking::*;
+    use std::net::TcpStream;
+
+    let addr = setup();
+    let stream = TcpStream::connect(addr).expect("error connecting to server");
+
+    let mut record_buffer = [0; 16384];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls: TlsConnection<FromStd<TcpStream>, Aes128GcmSha256> =
+        TlsConnection::new_half_duplex(FromStd::new(stream), &mut record_buffer);
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .expect("error establishing TLS connection");
+
+    for _ in 0..2 {
+        tls.write(b"ping").expect("error writing data");
+        tls.flush().expect("error flushing data");
+
+        let mut rx_buf = [0; 4];
+        let sz = tls.read(&mut rx_buf).expect("error reading data");
+        assert_eq!(b"ping", &rx_buf[..sz]);
+    }
+
+    tls.close()
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+}
+
+#[test]
+fn test_buffer_sizes() {
+    use embedded_tls::*;
+
+    const READ: usize = min_read_buffer_size::<Aes128GcmSha256>(None, 0);
+    const READ_MFL: usize =
+        min_read_buffer_size::<Aes128GcmSha256>(Some(MaxFragmentLength::Bits9), 0);
+    assert!(READ_MFL < READ);
+    assert!(READ <= 16640);
+    assert!(min_read_buffer_size::<Aes128GcmSha256>(None, 2048) > READ + 2048);
+
+    let config = TlsConfig::new().with_server_name("localhost");
+    let hello_len = config.client_hello_len::<Aes128GcmSha256>().unwrap();
+    assert!(min_write_buffer_size::<Aes128GcmSha256>(hello_len) > hello_len);
+    assert!(
+        config
+            .with_padding(512)
+            .client_hello_len::<Aes128GcmSha256>()
+            .unwrap()
+            >= 512,
+        "padding counts"
+    );
+}
+
+#[tokio::test]
+async fn test_buffers_too_small() {
+    use embedded_tls::*;
+    use tokio::net::TcpStream;
+
+    let addr = setup();
+
+    // The padded ClientHello does not fit
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 256];
+    let config = TlsConfig::new()
+        .with_server_name("localhost")
+        .with_padding(512);
+    let required = min_write_buffer_size::<Aes128GcmSha256>(
+        config.client_hello_len::<Aes128GcmSha256>().unwrap(),
+    );
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+    let result = tls
+        .open(TlsContext::new(
+            &config,
+            UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+        ))
+        .await;
+    assert!(matches!(result, Err(TlsError::WriteBufferTooSmall(r)) if r == required));
+
+    // A record of the max fragment length does not fit
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+    let mut read_record_buffer = [0; 512];
+    let mut write_record_buffer = [0; 16384];
+    let config = TlsConfig::new()
+        .with_server_name("localhost")
+        .with_max_fragment_length(MaxFragmentLength::Bits9);
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+    let result = tls
+        .open(TlsContext::new(
+            &config,
+            UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+        ))
+        .await;
+    assert!(matches!(
+        result,
+        Err(TlsError::ReadBufferTooSmall(r))
+            if r == min_read_buffer_size::<Aes128GcmSha256>(Some(MaxFragmentLength::Bits9), 0)
+    ));
+}
+
+#[tokio::test]
+async fn test_ping_custom_extensions() {
+    use embedded_tls::*;
+    use tokio::net::TcpStream;
+
+    let addr = setup();
+
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let extensions = [CustomExtension {
+        extension_type: 0xff00,
+        data: b"device-class",
+    }];
+    let config = TlsConfig::builder()
+        .with_server_name("localhost")
+        .with_custom_extensions(&extensions)
+        .build()
+        .expect("invalid configuration");
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .await
+    .expect("error establishing TLS connection");
+
+    tls.write_all(b"ping").await.expect("error writing data");
+    tls.flush().await.expect("error flushing data");
+
+    let mut rx_buf = [0; 4096];
+    let sz = tls.read(&mut rx_buf).await.expect("error reading data");
+    assert_eq!(b"ping", &rx_buf[..sz]);
+
+    tls.close()
+        .await
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+}
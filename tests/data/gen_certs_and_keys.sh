@@ -42,3 +42,35 @@ openssl x509 -req -CA rsa-ca-cert.pem -CAkey rsa-ca-key.pem -in rsa-server-cert.
 # Create RSA private key, certificate signing request (CSR) and certificate for client
 openssl req -newkey rsa:2048 -keyout rsa-client-key.pem -nodes -out rsa-client-cert.csr -sha256
 openssl x509 -req -CA rsa-ca-cert.pem -CAkey rsa-ca-key.pem -in rsa-client-cert.csr -out rsa-client-cert.pem -days 10000 -CAcreateserial
+
+# Create Ed448 CA private key and certificate
+openssl genpkey -algorithm ed448 -out ed448-ca-key.pem
+openssl req -new -x509 -key ed448-ca-key.pem -days 10000 -out ed448-ca-cert.pem -subj "/CN=Ed448 CA"
+
+# Create Ed448 private key, certificate signing request (CSR) and certificate for server with hostname
+openssl genpkey -algorithm ed448 -out ed448-server-key.pem
+openssl req -new -key ed448-server-key.pem -out ed448-server.csr -subj "/CN=localhost" -addext "subjectAltName = DNS:localhost"
+openssl x509 -req -in ed448-server.csr -CA ed448-ca-cert.pem -CAkey ed448-ca-key.pem -CAcreateserial -out ed448-server-cert.pem -days 10000 -copy_extensions copy
+
+# Create Ed448 private key, certificate signing request (CSR) and certificate for client
+openssl genpkey -algorithm ed448 -out ed448-client-key.pem
+openssl req -new -key ed448-client-key.pem -out ed448-client.csr -subj "/CN=client"
+openssl x509 -req -in ed448-client.csr -CA ed448-ca-cert.pem -CAkey ed448-ca-key.pem -CAcreateserial -out ed448-client-cert.pem -days 10000
+
+# Create SM2 CA private key and certificate, signed with the default distinguishing identifier
+openssl genpkey -algorithm SM2 -out sm2-ca-key.pem
+openssl req -new -x509 -key sm2-ca-key.pem -sm3 -sigopt distid:1234567812345678 -days 10000 -out sm2-ca-cert.pem -subj "/CN=SM2 CA"
+
+# Create SM2 private key, certificate signing request (CSR) and certificate for server with hostname
+openssl genpkey -algorithm SM2 -out sm2-server-key.pem
+openssl req -new -key sm2-server-key.pem -sm3 -sigopt distid:1234567812345678 -out sm2-server.csr -subj "/CN=localhost" -addext "subjectAltName = DNS:localhost"
+openssl x509 -req -in sm2-server.csr -CA sm2-ca-cert.pem -CAkey sm2-ca-key.pem -sm3 -sigopt distid:1234567812345678 -vfyopt distid:1234567812345678 -CAcreateserial -out sm2-server-cert.pem -days 10000 -copy_extensions copy
+
+# Create brainpoolP512r1 CA private key and certificate
+openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:brainpoolP512r1 -pkeyopt ec_param_enc:named_curve -out bp512-ca-key.pem
+openssl req -new -x509 -key bp512-ca-key.pem -sha512 -days 10000 -out bp512-ca-cert.pem -subj "/CN=Brainpool CA"
+
+# Create brainpoolP512r1 private key, certificate signing request (CSR) and certificate for server with hostname
+openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:brainpoolP512r1 -pkeyopt ec_param_enc:named_curve -out bp512-server-key.pem
+openssl req -new -key bp512-server-key.pem -sha512 -out bp512-server.csr -subj "/CN=localhost" -addext "subjectAltName = DNS:localhost"
+openssl x509 -req -in bp512-server.csr -CA bp512-ca-cert.pem -CAkey bp512-ca-key.pem -sha512 -CAcreateserial -out bp512-server-cert.pem -days 10000 -copy_extensions copy
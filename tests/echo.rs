@@ -0,0 +1,88 @@
+//! A handshake between an embedded-tls client and server over TCP, after which the server
+//! echoes a message of the client.
+use embedded_io_adapters::tokio_1::FromTokio;
+use embedded_io_async::{Read as _, Write as _};
+use embedded_tls::*;
+use std::net::SocketAddr;
+use std::sync::Once;
+use tokio::net::{TcpListener, TcpStream};
+
+static LOG_INIT: Once = Once::new();
+
+pub fn init_log() {
+    LOG_INIT.call_once(|| {
+        env_logger::init();
+    });
+}
+
+pub async fn echo_server<Provider: CryptoProvider>(
+    listener: TcpListener,
+    context: TlsContext<'_, Provider>,
+) -> Result<(), TlsError>
+where
+    Provider::CipherSuite: 'static,
+{
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let mut tls = TlsServerConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+    tls.open(context).await?;
+
+    let mut buf = [0; 64];
+    let len = tls.read(&mut buf).await?;
+    tls.write_all(&buf[..len]).await?;
+    tls.flush().await?;
+    tls.shutdown().await.map_err(|(_, e)| e)?;
+    Ok(())
+}
+
+pub async fn echo_client<Provider: CryptoProvider>(
+    addr: SocketAddr,
+    context: TlsContext<'_, Provider>,
+) where
+    Provider::CipherSuite: 'static,
+{
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+    tls.open(context)
+        .await
+        .expect("error establishing TLS connection");
+
+    tls.write_all(b"ping").await.expect("error writing data");
+    tls.flush().await.expect("error flushing data");
+
+    let mut buf = [0; 4];
+    tls.read_exact(&mut buf).await.expect("error reading data");
+    assert_eq!(b"ping", &buf);
+
+    tls.shutdown().await.map_err(|(_, e)| e).unwrap();
+}
+
+/// Run [`echo_server`] and [`echo_client`] against each other with the given contexts.
+pub async fn echo<ServerProvider: CryptoProvider, ClientProvider: CryptoProvider>(
+    server: TlsContext<'_, ServerProvider>,
+    client: TlsContext<'_, ClientProvider>,
+) where
+    ServerProvider::CipherSuite: 'static,
+    ClientProvider::CipherSuite: 'static,
+{
+    init_log();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (server, ()) = tokio::join!(echo_server(listener, server), echo_client(addr, client));
+    server.expect("server error");
+}
@@ -0,0 +1,108 @@
+#![cfg(all(feature = "ed448", feature = "x448"))]
+use ed448_goldilocks_plus::SigningKey;
+use embedded_tls::pki::CertVerifier;
+use embedded_tls::*;
+use rand::rngs::OsRng;
+use std::time::SystemTime;
+
+mod echo;
+
+/// A pure Ed448 signature of RFC 8032.
+struct Ed448Signature([u8; 114]);
+
+impl AsRef<[u8]> for Ed448Signature {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+struct Ed448Signer(SigningKey);
+
+impl signature::Signer<Ed448Signature> for Ed448Signer {
+    fn try_sign(&self, msg: &[u8]) -> Result<Ed448Signature, signature::Error> {
+        Ok(Ed448Signature(self.0.sign_raw(msg).to_bytes()))
+    }
+}
+
+struct Ed448Provider<'a> {
+    rng: OsRng,
+    verifier: CertVerifier<'a, Aes128GcmSha256, SystemTime, 4096>,
+    priv_key: &'a [u8],
+    cert: &'a [u8],
+}
+
+impl CryptoProvider for Ed448Provider<'_> {
+    type CipherSuite = Aes128GcmSha256;
+    type Signature = Ed448Signature;
+
+    fn rng(&mut self) -> impl CryptoRngCore {
+        &mut self.rng
+    }
+
+    fn verifier(&mut self) -> Result<&mut impl TlsVerifier<Aes128GcmSha256>, TlsError> {
+        Ok(&mut self.verifier)
+    }
+
+    fn signer(
+        &mut self,
+    ) -> Result<(impl signature::SignerMut<Self::Signature>, SignatureScheme), TlsError> {
+        // The raw private key ends the PKCS#8 structure
+        let secret_key = self.priv_key[self.priv_key.len() - 57..]
+            .try_into()
+            .map_err(|_| TlsError::InvalidPrivateKey)?;
+        Ok((
+            Ed448Signer(SigningKey::from_bytes(&secret_key)),
+            SignatureScheme::Ed448,
+        ))
+    }
+
+    fn client_cert(&mut self) -> Option<Certificate<impl AsRef<[u8]>>> {
+        Some(Certificate::X509(self.cert))
+    }
+
+    fn server_cert(&mut self) -> Option<Certificate<impl AsRef<[u8]>>> {
+        Some(Certificate::X509(self.cert))
+    }
+}
+
+#[tokio::test]
+async fn test_ed448_mutual_auth_x448() {
+    let ca = pem_parser::pem_to_der(include_str!("data/ed448-ca-cert.pem"));
+    let server_cert = pem_parser::pem_to_der(include_str!("data/ed448-server-cert.pem"));
+    let server_key = pem_parser::pem_to_der(include_str!("data/ed448-server-key.pem"));
+    let client_cert = pem_parser::pem_to_der(include_str!("data/ed448-client-cert.pem"));
+    let client_key = pem_parser::pem_to_der(include_str!("data/ed448-client-key.pem"));
+
+    let server_config = TlsConfig::builder()
+        .with_named_groups(&[NamedGroup::X448])
+        .enable_client_auth()
+        .build()
+        .unwrap();
+    let client_config = TlsConfig::builder()
+        .with_server_name("localhost")
+        .with_signature_schemes(&[SignatureScheme::Ed448])
+        .with_named_groups(&[NamedGroup::X448, NamedGroup::Secp256r1])
+        .build()
+        .unwrap();
+    echo::echo(
+        TlsContext::new(
+            &server_config,
+            Ed448Provider {
+                rng: OsRng,
+                verifier: CertVerifier::new(Certificate::X509(&ca)).for_client_certificates(),
+                priv_key: &server_key,
+                cert: &server_cert,
+            },
+        ),
+        TlsContext::new(
+            &client_config,
+            Ed448Provider {
+                rng: OsRng,
+                verifier: CertVerifier::new(Certificate::X509(&ca)),
+                priv_key: &client_key,
+                cert: &client_cert,
+            },
+        ),
+    )
+    .await;
+}
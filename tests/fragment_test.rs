@@ -0,0 +1,239 @@
+use embedded_io_adapters::tokio_1::FromTokio;
+use embedded_tls::*;
+use rand::rngs::OsRng;
+use std::net::SocketAddr;
+use std::sync::Once;
+use tokio::net::TcpStream;
+
+mod tlsserver;
+
+static LOG_INIT: Once = Once::new();
+
+fn init_log() {
+    LOG_INIT.call_once(|| {
+        env_logger::init();
+    });
+}
+
+/// Start a TLS 1.3 server that splits its records into fragments of `max_fragment_size` bytes,
+/// so that handshake messages span several records.
+fn setup(max_fragment_size: usize) -> SocketAddr {
+    setup_with_cert(max_fragment_size, "server-cert.pem", "server-key.pem")
+}
+
+fn setup_with_cert(max_fragment_size: usize, cert: &str, key: &str) -> SocketAddr {
+    init_log();
+
+    let test_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let certs = tlsserver::load_certs(&test_dir.join("data").join(cert));
+    let privkey = tlsserver::load_private_key(&test_dir.join("data").join(key));
+    let mut server_config = rustls::ServerConfig::builder()
+        .with_cipher_suites(rustls::ALL_CIPHER_SUITES)
+        .with_kx_groups(&rustls::ALL_KX_GROUPS)
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(certs, privkey)
+        .unwrap();
+    server_config.max_fragment_size = Some(max_fragment_size);
+
+    let listener =
+        mio::net::TcpListener::bind("127.0.0.1:0".parse().unwrap()).expect("cannot listen on port");
+    let addr = listener
+        .local_addr()
+        .expect("error retrieving socket address");
+    std::thread::spawn(move || tlsserver::run_with_config(listener, server_config));
+    addr
+}
+
+#[tokio::test]
+async fn test_fragmented_handshake() {
+    let addr = setup(64);
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16640];
+    let mut write_record_buffer = [0; 16640];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .await
+    .expect("error establishing TLS connection");
+
+    tls.write(b"ping").await.expect("error writing data");
+    tls.flush().await.expect("error flushing data");
+
+    let mut rx = [0; 4];
+    let len = tls.read(&mut rx).await.expect("error reading data");
+    assert_eq!(b"ping", &rx[..len]);
+
+    tls.close()
+        .await
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+}
+
+#[test]
+fn test_fragmented_handshake_blocking() {
+    use embedded_io_adapters::std::FromStd;
+    use embedded_tls::blocking::TlsConnection;
+
+    let addr = setup(64);
+    let stream = std::net::TcpStream::connect(addr).expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16640];
+    let mut write_record_buffer = [0; 16640];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls: TlsConnection<_, Aes128GcmSha256> = TlsConnection::new(
+        FromStd::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .expect("error establishing TLS connection");
+
+    tls.write(b"ping").expect("error writing data");
+    tls.flush().expect("error flushing data");
+
+    let mut rx = [0; 4];
+    let len = tls.read(&mut rx).expect("error reading data");
+    assert_eq!(b"ping", &rx[..len]);
+
+    tls.close()
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+}
+
+#[tokio::test]
+async fn test_streamed_certificate() {
+    let addr = setup_with_cert(64, "chain-cert.pem", "im-server-key.pem");
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    // The server Certificate message does not fit, but its entries do
+    let mut read_record_buffer = [0; 768];
+    let mut write_record_buffer = [0; 16640];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .await
+    .expect("error establishing TLS connection");
+
+    tls.write(b"ping").await.expect("error writing data");
+    tls.flush().await.expect("error flushing data");
+
+    let mut rx = [0; 4];
+    let len = tls.read(&mut rx).await.expect("error reading data");
+    assert_eq!(b"ping", &rx[..len]);
+}
+
+#[cfg(feature = "rustpki")]
+#[tokio::test]
+async fn test_streamed_certificate_chain_verified() {
+    use embedded_tls::pki::CertVerifier;
+    use std::time::SystemTime;
+
+    struct RustPkiProvider<'a> {
+        rng: OsRng,
+        verifier: CertVerifier<'a, Aes128GcmSha256, SystemTime, 1024>,
+    }
+
+    impl CryptoProvider for RustPkiProvider<'_> {
+        type CipherSuite = Aes128GcmSha256;
+        type Signature = p256::ecdsa::DerSignature;
+
+        fn rng(&mut self) -> impl embedded_tls::CryptoRngCore {
+            &mut self.rng
+        }
+
+        fn verifier(&mut self) -> Result<&mut impl TlsVerifier<Aes128GcmSha256>, TlsError> {
+            Ok(&mut self.verifier)
+        }
+    }
+
+    let addr = setup_with_cert(64, "chain-cert.pem", "im-server-key.pem");
+    let der = pem_parser::pem_to_der(include_str!("data/ca-cert.pem"));
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    // Smaller than the certificate chain
+    let mut read_record_buffer = [0; 768];
+    let mut write_record_buffer = [0; 16640];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    tls.open(TlsContext::new(
+        &config,
+        RustPkiProvider {
+            rng: OsRng,
+            verifier: CertVerifier::new(Certificate::X509(&der[..])),
+        },
+    ))
+    .await
+    .expect("error establishing TLS connection");
+
+    tls.write(b"ping").await.expect("error writing data");
+    tls.flush().await.expect("error flushing data");
+
+    let mut rx = [0; 4];
+    let len = tls.read(&mut rx).await.expect("error reading data");
+    assert_eq!(b"ping", &rx[..len]);
+}
+
+#[tokio::test]
+async fn test_fragmented_message_too_large() {
+    let addr = setup(64);
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    // Records fit, but the server certificate does not
+    let mut read_record_buffer = [0; 256];
+    let mut write_record_buffer = [0; 16640];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    let result = tls
+        .open(TlsContext::new(
+            &config,
+            UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+        ))
+        .await;
+    assert!(matches!(result, Err(TlsError::InsufficientSpace)));
+}
@@ -0,0 +1,33 @@
+#![cfg(feature = "integrity-only")]
+use embedded_tls::*;
+use rand::rngs::OsRng;
+
+mod echo;
+
+async fn echo<CipherSuite: TlsCipherSuite + 'static>() {
+    let cert = pem_parser::pem_to_der(include_str!("data/server-cert.pem"));
+    let key = pem_parser::pem_to_der(include_str!("data/server-key.pem"));
+
+    let server_config = TlsConfig::new();
+    let client_config = TlsConfig::new().with_server_name("localhost");
+    echo::echo(
+        TlsContext::new(
+            &server_config,
+            UnsecureProvider::new::<CipherSuite>(OsRng)
+                .with_cert(Certificate::X509(&cert))
+                .with_priv_key(&key),
+        ),
+        TlsContext::new(&client_config, UnsecureProvider::new::<CipherSuite>(OsRng)),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_sha256_sha256() {
+    echo::<Sha256Sha256>().await;
+}
+
+#[tokio::test]
+async fn test_sha384_sha384() {
+    echo::<Sha384Sha384>().await;
+}
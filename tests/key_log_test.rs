@@ -0,0 +1,54 @@
+#![cfg(feature = "key-log")]
+use embedded_tls::*;
+use rand::rngs::OsRng;
+
+mod echo;
+
+#[tokio::test]
+async fn test_key_log() {
+    let cert = pem_parser::pem_to_der(include_str!("data/server-cert.pem"));
+    let key = pem_parser::pem_to_der(include_str!("data/server-key.pem"));
+
+    let mut server_key_log = NssKeyLog::new(Vec::new());
+    let mut client_key_log = NssKeyLog::new(Vec::new());
+    let server_config = TlsConfig::new();
+    let client_config = TlsConfig::new().with_server_name("localhost");
+    echo::echo(
+        TlsContext::new(
+            &server_config,
+            UnsecureProvider::new::<Aes128GcmSha256>(OsRng)
+                .with_cert(Certificate::X509(&cert))
+                .with_priv_key(&key),
+        )
+        .with_key_log(&mut server_key_log),
+        TlsContext::new(
+            &client_config,
+            UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+        )
+        .with_key_log(&mut client_key_log),
+    )
+    .await;
+
+    let server_key_log = String::from_utf8(server_key_log.into_inner()).unwrap();
+    let client_key_log = String::from_utf8(client_key_log.into_inner()).unwrap();
+    let labels: Vec<_> = client_key_log
+        .lines()
+        .map(|line| line.split(' ').next().unwrap())
+        .collect();
+    assert_eq!(
+        labels,
+        [
+            "CLIENT_HANDSHAKE_TRAFFIC_SECRET",
+            "SERVER_HANDSHAKE_TRAFFIC_SECRET",
+            "CLIENT_TRAFFIC_SECRET_0",
+            "SERVER_TRAFFIC_SECRET_0",
+            "EXPORTER_SECRET",
+        ]
+    );
+    // Both sides derive the same secrets for the same client random
+    let mut server_lines: Vec<_> = server_key_log.lines().collect();
+    let mut client_lines: Vec<_> = client_key_log.lines().collect();
+    server_lines.sort_unstable();
+    client_lines.sort_unstable();
+    assert_eq!(server_lines, client_lines);
+}
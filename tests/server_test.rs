@@ -0,0 +1,462 @@
+use embedded_io_adapters::{std::FromStd, tokio_1::FromTokio};
+use embedded_io_async::{Read as _, Write as _};
+use embedded_tls::*;
+use rand::rngs::OsRng;
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::sync::{Arc, Once};
+use tokio::net::{TcpListener, TcpStream};
+
+static LOG_INIT: Once = Once::new();
+
+fn init_log() {
+    LOG_INIT.call_once(|| {
+        env_logger::init();
+    });
+}
+
+async fn listen() -> (TcpListener, SocketAddr) {
+    init_log();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    (listener, addr)
+}
+
+async fn echo_server(listener: TcpListener, config: TlsConfig<'_>) -> Result<(), TlsError> {
+    let cert = pem_parser::pem_to_der(include_str!("data/server-cert.pem"));
+    let key = pem_parser::pem_to_der(include_str!("data/server-key.pem"));
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let mut tls = TlsServerConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng)
+            .with_cert(Certificate::X509(&cert))
+            .with_priv_key(&key),
+    ))
+    .await?;
+
+    let mut buf = [0; 64];
+    let len = tls.read(&mut buf).await?;
+    tls.write_all(&buf[..len]).await?;
+    tls.flush().await?;
+    tls.shutdown().await.map_err(|(_, e)| e)?;
+    Ok(())
+}
+
+async fn echo_client(addr: SocketAddr, config: TlsConfig<'_>) {
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .await
+    .expect("error establishing TLS connection");
+
+    tls.write_all(b"ping").await.expect("error writing data");
+    tls.flush().await.expect("error flushing data");
+
+    let mut buf = [0; 4];
+    tls.read_exact(&mut buf).await.expect("error reading data");
+    assert_eq!(b"ping", &buf);
+
+    tls.shutdown().await.map_err(|(_, e)| e).unwrap();
+}
+
+#[tokio::test]
+async fn test_embedded_tls_client() {
+    let (listener, addr) = listen().await;
+
+    let (server, ()) = tokio::join!(
+        echo_server(listener, TlsConfig::new()),
+        echo_client(addr, TlsConfig::new().with_server_name("localhost")),
+    );
+    server.expect("server error");
+}
+
+#[tokio::test]
+async fn test_embedded_tls_client_psk() {
+    let (listener, addr) = listen().await;
+
+    let (server, ()) = tokio::join!(
+        echo_server(
+            listener,
+            TlsConfig::new().with_psk(&[0xaa, 0xbb, 0xcc, 0xdd], &[b"luke", b"vader"]),
+        ),
+        echo_client(
+            addr,
+            TlsConfig::new().with_psk(&[0xaa, 0xbb, 0xcc, 0xdd], &[b"vader"]),
+        ),
+    );
+    server.expect("server error");
+}
+
+#[tokio::test]
+async fn test_embedded_tls_client_alpn() {
+    let (listener, addr) = listen().await;
+
+    let (server, ()) = tokio::join!(
+        echo_server(listener, TlsConfig::new().with_alpn(&[b"h2", b"http/1.1"])),
+        echo_client(addr, TlsConfig::new().with_alpn(&[b"http/1.1", b"h2"])),
+    );
+    server.expect("server error");
+}
+
+#[tokio::test]
+async fn test_psk_binder_mismatch() {
+    let (listener, addr) = listen().await;
+
+    let client = async {
+        let stream = TcpStream::connect(addr)
+            .await
+            .expect("error connecting to server");
+
+        let mut read_record_buffer = [0; 16384];
+        let mut write_record_buffer = [0; 16384];
+        let config = TlsConfig::new().with_psk(&[0x11, 0x22, 0x33, 0x44], &[b"vader"]);
+        let mut tls = TlsConnection::new(
+            FromTokio::new(stream),
+            &mut read_record_buffer,
+            &mut write_record_buffer,
+        );
+
+        tls.open(TlsContext::new(
+            &config,
+            UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+        ))
+        .await
+    };
+
+    let (server, client) = tokio::join!(
+        echo_server(
+            listener,
+            TlsConfig::new().with_psk(&[0xaa, 0xbb, 0xcc, 0xdd], &[b"vader"]),
+        ),
+        client,
+    );
+    assert!(matches!(
+        server,
+        Err(TlsError::AbortHandshake(
+            alert::AlertLevel::Fatal,
+            alert::AlertDescription::DecryptError
+        ))
+    ));
+    assert!(matches!(
+        client,
+        Err(TlsError::HandshakeAborted(
+            alert::AlertLevel::Fatal,
+            alert::AlertDescription::DecryptError
+        ))
+    ));
+}
+
+const EXPORTER_LABEL: &[u8] = b"EXPORTER-Channel-Binding";
+
+#[tokio::test]
+async fn test_export_keying_material() {
+    let (listener, addr) = listen().await;
+
+    let server = async {
+        let cert = pem_parser::pem_to_der(include_str!("data/server-cert.pem"));
+        let key = pem_parser::pem_to_der(include_str!("data/server-key.pem"));
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut read_record_buffer = [0; 16384];
+        let mut write_record_buffer = [0; 16384];
+        let mut tls = TlsServerConnection::new(
+            FromTokio::new(stream),
+            &mut read_record_buffer,
+            &mut write_record_buffer,
+        );
+
+        let config = TlsConfig::new();
+        tls.open(TlsContext::new(
+            &config,
+            UnsecureProvider::new::<Aes128GcmSha256>(OsRng)
+                .with_cert(Certificate::X509(&cert))
+                .with_priv_key(&key),
+        ))
+        .await?;
+
+        let mut material = [[0; 32]; 2];
+        tls.export_keying_material(EXPORTER_LABEL, b"", &mut material[0])?;
+        tls.export_keying_material(EXPORTER_LABEL, b"context", &mut material[1])?;
+        tls.shutdown().await.map_err(|(_, e)| e)?;
+        Ok::<_, TlsError>(material)
+    };
+
+    let client = async {
+        let stream = TcpStream::connect(addr)
+            .await
+            .expect("error connecting to server");
+
+        let mut read_record_buffer = [0; 16384];
+        let mut write_record_buffer = [0; 16384];
+        let mut tls = TlsConnection::new(
+            FromTokio::new(stream),
+            &mut read_record_buffer,
+            &mut write_record_buffer,
+        );
+
+        let mut material = [[0; 32]; 2];
+        assert!(matches!(
+            tls.export_keying_material(EXPORTER_LABEL, b"", &mut material[0]),
+            Err(TlsError::MissingHandshake)
+        ));
+
+        let config = TlsConfig::new().with_server_name("localhost");
+        tls.open(TlsContext::new(
+            &config,
+            UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+        ))
+        .await
+        .expect("error establishing TLS connection");
+
+        tls.export_keying_material(EXPORTER_LABEL, b"", &mut material[0])
+            .unwrap();
+        tls.export_keying_material(EXPORTER_LABEL, b"context", &mut material[1])
+            .unwrap();
+        let mut buf = [0; 1];
+        assert_eq!(tls.read(&mut buf).await.unwrap(), 0);
+        tls.shutdown().await.map_err(|(_, e)| e).unwrap();
+        material
+    };
+
+    let (server, client) = tokio::join!(server, client);
+    let server = server.expect("server error");
+    assert_eq!(server, client);
+    assert_ne!(client[0], client[1]);
+}
+
+fn rustls_client_config(
+    client_auth: Option<(Vec<rustls::Certificate>, rustls::PrivateKey)>,
+) -> Arc<rustls::ClientConfig> {
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut include_bytes!("data/im-cert.pem").as_slice()).unwrap() {
+        roots.add(&rustls::Certificate(cert)).unwrap();
+    }
+
+    let builder = rustls::ClientConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .with_root_certificates(roots);
+
+    Arc::new(match client_auth {
+        Some((certs, key)) => builder.with_client_auth_cert(certs, key).unwrap(),
+        None => builder.with_no_client_auth(),
+    })
+}
+
+fn rustls_echo(addr: SocketAddr, config: Arc<rustls::ClientConfig>) -> std::io::Result<()> {
+    let mut conn = rustls::ClientConnection::new(config, "localhost".try_into().unwrap()).unwrap();
+    let mut sock = std::net::TcpStream::connect(addr).unwrap();
+    let mut tls = rustls::Stream::new(&mut conn, &mut sock);
+
+    tls.write_all(b"ping")?;
+    let mut buf = [0; 4];
+    tls.read_exact(&mut buf)?;
+    assert_eq!(b"ping", &buf);
+    Ok(())
+}
+
+fn blocking_echo_server<Provider>(
+    config: TlsConfig<'static>,
+    provider: Provider,
+) -> (SocketAddr, std::thread::JoinHandle<Result<(), TlsError>>)
+where
+    Provider: CryptoProvider<CipherSuite = Aes128GcmSha256> + Send + 'static,
+{
+    init_log();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let h = std::thread::spawn(move || {
+        use embedded_io::Write as _;
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut read_record_buffer = [0; 16384];
+        let mut write_record_buffer = [0; 16384];
+        let mut tls = blocking::TlsServerConnection::new(
+            FromStd::new(stream),
+            &mut read_record_buffer,
+            &mut write_record_buffer,
+        );
+
+        tls.open(TlsContext::new(&config, provider))?;
+
+        let mut buf = [0; 64];
+        let len = tls.read(&mut buf)?;
+        tls.write_all(&buf[..len])?;
+        tls.flush()?;
+        tls.close().map_err(|(_, e)| e)?;
+        Ok(())
+    });
+
+    (addr, h)
+}
+
+fn im_server_provider() -> UnsecureProvider<'static, Aes128GcmSha256, OsRng> {
+    let cert = pem_parser::pem_to_der(include_str!("data/im-server-cert.pem"));
+    let key = pem_parser::pem_to_der(include_str!("data/im-server-key.pem"));
+    UnsecureProvider::new::<Aes128GcmSha256>(OsRng)
+        .with_cert(Certificate::X509(cert.leak()))
+        .with_priv_key(key.leak())
+}
+
+#[test]
+fn test_rustls_client() {
+    let (addr, h) = blocking_echo_server(TlsConfig::new(), im_server_provider());
+
+    rustls_echo(addr, rustls_client_config(None)).expect("error talking to server");
+    h.join().unwrap().expect("server error");
+}
+
+#[test]
+fn test_rustls_client_export_keying_material() {
+    init_log();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let h = std::thread::spawn(move || {
+        use embedded_io::Read as _;
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut read_record_buffer = [0; 16384];
+        let mut write_record_buffer = [0; 16384];
+        let mut tls = blocking::TlsServerConnection::new(
+            FromStd::new(stream),
+            &mut read_record_buffer,
+            &mut write_record_buffer,
+        );
+
+        let config = TlsConfig::new();
+        tls.open(TlsContext::new(&config, im_server_provider()))?;
+
+        let mut material = [[0; 32]; 2];
+        tls.export_keying_material(EXPORTER_LABEL, b"", &mut material[0])?;
+        tls.export_keying_material(b"EXPERIMENTAL-test", b"context", &mut material[1])?;
+
+        let mut buf = [0; 4];
+        tls.read_exact(&mut buf).map_err(|e| match e {
+            embedded_io::ReadExactError::Other(e) => e,
+            embedded_io::ReadExactError::UnexpectedEof => TlsError::ConnectionClosed,
+        })?;
+        tls.close().map_err(|(_, e)| e)?;
+        Ok::<_, TlsError>(material)
+    });
+
+    let mut conn =
+        rustls::ClientConnection::new(rustls_client_config(None), "localhost".try_into().unwrap())
+            .unwrap();
+    let mut sock = std::net::TcpStream::connect(addr).unwrap();
+    let mut tls = rustls::Stream::new(&mut conn, &mut sock);
+    tls.write_all(b"ping").unwrap();
+
+    let channel_binding = conn
+        .export_keying_material([0; 32], EXPORTER_LABEL, None)
+        .unwrap();
+    let test = conn
+        .export_keying_material([0; 32], b"EXPERIMENTAL-test", Some(b"context"))
+        .unwrap();
+
+    let server = h.join().unwrap().expect("server error");
+    assert_eq!(server, [channel_binding, test]);
+}
+
+#[cfg(feature = "webpki")]
+mod client_auth {
+    use super::*;
+    use embedded_tls::webpki::CertVerifier;
+    use std::time::SystemTime;
+
+    struct ServerProvider {
+        inner: UnsecureProvider<'static, Aes128GcmSha256, OsRng>,
+        verifier: CertVerifier<'static, Aes128GcmSha256, SystemTime, 4096>,
+    }
+
+    impl CryptoProvider for ServerProvider {
+        type CipherSuite = Aes128GcmSha256;
+        type Signature = p256::ecdsa::DerSignature;
+
+        fn rng(&mut self) -> impl CryptoRngCore {
+            self.inner.rng()
+        }
+
+        fn verifier(&mut self) -> Result<&mut impl TlsVerifier<Aes128GcmSha256>, TlsError> {
+            Ok(&mut self.verifier)
+        }
+
+        fn signer(
+            &mut self,
+        ) -> Result<(impl signature::SignerMut<Self::Signature>, SignatureScheme), TlsError>
+        {
+            self.inner.signer()
+        }
+
+        fn server_cert(&mut self) -> Option<Certificate<impl AsRef<[u8]>>> {
+            self.inner.server_cert()
+        }
+    }
+
+    fn server_provider() -> ServerProvider {
+        let ca = pem_parser::pem_to_der(include_str!("data/ca-cert.pem"));
+        ServerProvider {
+            inner: im_server_provider(),
+            verifier: CertVerifier::new(Certificate::X509(ca.leak())).for_client_certificates(),
+        }
+    }
+
+    #[test]
+    fn test_rustls_client_certificate() {
+        let (addr, h) =
+            blocking_echo_server(TlsConfig::new().enable_client_auth(), server_provider());
+
+        let certs =
+            rustls_pemfile::certs(&mut include_bytes!("data/client-cert.pem").as_slice()).unwrap();
+        let key =
+            rustls_pemfile::ec_private_keys(&mut include_bytes!("data/client-key.pem").as_slice())
+                .unwrap();
+        let config = rustls_client_config(Some((
+            certs.into_iter().map(rustls::Certificate).collect(),
+            rustls::PrivateKey(key[0].clone()),
+        )));
+
+        rustls_echo(addr, config).expect("error talking to server");
+        h.join().unwrap().expect("server error");
+    }
+
+    #[test]
+    fn test_rustls_client_without_certificate() {
+        let (addr, h) =
+            blocking_echo_server(TlsConfig::new().enable_client_auth(), server_provider());
+
+        assert!(rustls_echo(addr, rustls_client_config(None)).is_err());
+        assert!(matches!(
+            h.join().unwrap(),
+            Err(TlsError::AbortHandshake(
+                alert::AlertLevel::Fatal,
+                alert::AlertDescription::CertificateRequired
+            ))
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+#![cfg(feature = "shangmi")]
+use embedded_tls::*;
+use rand::rngs::OsRng;
+
+mod echo;
+
+#[tokio::test]
+async fn test_sm4_gcm_sm3_curve_sm2() {
+    let cert = pem_parser::pem_to_der(include_str!("data/server-cert.pem"));
+    let key = pem_parser::pem_to_der(include_str!("data/server-key.pem"));
+
+    let server_config = TlsConfig::builder()
+        .with_named_groups(&[NamedGroup::CurveSm2])
+        .build()
+        .unwrap();
+    let client_config = TlsConfig::builder()
+        .with_server_name("localhost")
+        .with_named_groups(&[NamedGroup::CurveSm2, NamedGroup::Secp256r1])
+        .build()
+        .unwrap();
+    echo::echo(
+        TlsContext::new(
+            &server_config,
+            UnsecureProvider::new::<Sm4GcmSm3>(OsRng)
+                .with_cert(Certificate::X509(&cert))
+                .with_priv_key(&key),
+        ),
+        TlsContext::new(&client_config, UnsecureProvider::new::<Sm4GcmSm3>(OsRng)),
+    )
+    .await;
+}
+
+#[cfg(feature = "rustpki")]
+mod sm2_certificate {
+    use super::echo;
+    use embedded_tls::pki::CertVerifier;
+    use embedded_tls::*;
+    use rand::rngs::OsRng;
+    use sm2::dsa::SigningKey;
+    use sm2::pkcs8::DecodePrivateKey;
+    use std::time::SystemTime;
+
+    /// A DER encoded SM2 signature, as sent in `CertificateVerify`.
+    struct Sm2Signature(ecdsa::der::Signature<sm2::Sm2>);
+
+    impl AsRef<[u8]> for Sm2Signature {
+        fn as_ref(&self) -> &[u8] {
+            self.0.as_bytes()
+        }
+    }
+
+    struct Sm2Signer(SigningKey);
+
+    impl signature::Signer<Sm2Signature> for Sm2Signer {
+        fn try_sign(&self, msg: &[u8]) -> Result<Sm2Signature, signature::Error> {
+            let signature: sm2::dsa::Signature = self.0.try_sign(msg)?;
+            let signature = ecdsa::Signature::<sm2::Sm2>::from_scalars(
+                signature.r_bytes(),
+                signature.s_bytes(),
+            )?;
+            Ok(Sm2Signature(signature.to_der()))
+        }
+    }
+
+    struct Sm2Provider<'a> {
+        rng: OsRng,
+        verifier: CertVerifier<'a, Sm4GcmSm3, SystemTime, 4096>,
+        priv_key: Option<&'a [u8]>,
+        cert: Option<&'a [u8]>,
+    }
+
+    impl CryptoProvider for Sm2Provider<'_> {
+        type CipherSuite = Sm4GcmSm3;
+        type Signature = Sm2Signature;
+
+        fn rng(&mut self) -> impl CryptoRngCore {
+            &mut self.rng
+        }
+
+        fn verifier(&mut self) -> Result<&mut impl TlsVerifier<Sm4GcmSm3>, TlsError> {
+            Ok(&mut self.verifier)
+        }
+
+        fn signer(
+            &mut self,
+        ) -> Result<(impl signature::SignerMut<Self::Signature>, SignatureScheme), TlsError>
+        {
+            let key_der = self.priv_key.ok_or(TlsError::InvalidPrivateKey)?;
+            let secret_key =
+                sm2::SecretKey::from_pkcs8_der(key_der).map_err(|_| TlsError::InvalidPrivateKey)?;
+            // RFC 8998, Section 3.2.1: the distinguishing identifier of TLS 1.3
+            let signing_key =
+                SigningKey::new("TLSv1.3", &secret_key).map_err(|_| TlsError::InvalidPrivateKey)?;
+            Ok((Sm2Signer(signing_key), SignatureScheme::Sm2sigSm3))
+        }
+
+        fn server_cert(&mut self) -> Option<Certificate<impl AsRef<[u8]>>> {
+            self.cert.map(Certificate::X509)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sm2_certificate() {
+        let ca = pem_parser::pem_to_der(include_str!("data/sm2-ca-cert.pem"));
+        let cert = pem_parser::pem_to_der(include_str!("data/sm2-server-cert.pem"));
+        let key = pem_parser::pem_to_der(include_str!("data/sm2-server-key.pem"));
+
+        let server_config = TlsConfig::builder()
+            .with_named_groups(&[NamedGroup::CurveSm2])
+            .build()
+            .unwrap();
+        let client_config = TlsConfig::builder()
+            .with_server_name("localhost")
+            .with_signature_schemes(&[SignatureScheme::Sm2sigSm3])
+            .with_named_groups(&[NamedGroup::CurveSm2])
+            .build()
+            .unwrap();
+        echo::echo(
+            TlsContext::new(
+                &server_config,
+                Sm2Provider {
+                    rng: OsRng,
+                    verifier: CertVerifier::new(Certificate::X509(&ca)).for_client_certificates(),
+                    priv_key: Some(&key),
+                    cert: Some(&cert),
+                },
+            ),
+            TlsContext::new(
+                &client_config,
+                Sm2Provider {
+                    rng: OsRng,
+                    verifier: CertVerifier::new(Certificate::X509(&ca)),
+                    priv_key: None,
+                    cert: None,
+                },
+            ),
+        )
+        .await;
+    }
+}
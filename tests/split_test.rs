@@ -4,6 +4,7 @@ use embedded_io_adapters::std::FromStd;
 use rand_core::OsRng;
 use std::net::{SocketAddr, TcpStream};
 use std::sync::Once;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 mod tlsserver;
 
@@ -63,6 +64,22 @@ impl embedded_io::Write for Clonable<TcpStream> {
     }
 }
 
+#[test]
+fn test_blocking_split_half_duplex() {
+    use embedded_tls::blocking::*;
+    use std::sync::Arc;
+    let addr = setup();
+    let stream = TcpStream::connect(addr).expect("error connecting to server");
+
+    let mut record_buffer = [0; 32768];
+    let mut tls = TlsConnection::<_, Aes128GcmSha256>::new_half_duplex(
+        Clonable(Arc::new(stream)),
+        &mut record_buffer,
+    );
+
+    assert!(matches!(tls.try_split(), Err(TlsError::BufferInUse)));
+}
+
 #[test]
 fn test_blocking_borrowed() {
     use embedded_tls::blocking::*;
@@ -105,3 +122,168 @@ fn test_blocking_borrowed() {
         .map_err(|(_, e)| e)
         .expect("error closing session");
 }
+
+#[test]
+fn test_blocking_split_half_close() {
+    use embedded_tls::blocking::*;
+    use std::net::TcpStream;
+    use std::sync::Arc;
+    let addr = setup();
+    let stream = TcpStream::connect(addr).expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls = TlsConnection::new(
+        Clonable(Arc::new(stream)),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .expect("error establishing TLS connection");
+
+    let (mut reader, mut writer) = tls.split();
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            let mut buffer = [0; 4];
+            reader.read_exact(&mut buffer).expect("Failed to read data");
+            assert_eq!(b"ping", &buffer);
+
+            // The server answers our close_notify with its own
+            let sz = reader.read(&mut buffer).expect("Failed to read EOF");
+            assert_eq!(0, sz);
+        });
+        scope.spawn(|| {
+            writer.write(b"ping").expect("Failed to write data");
+            writer.shutdown_write().expect("Failed to shut down");
+            assert!(matches!(
+                writer.write(b"ping"),
+                Err(TlsError::ConnectionClosed)
+            ));
+        });
+    });
+
+    tls.close()
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+}
+
+/// A socket flipping a bit of the received data at a given offset.
+#[derive(Clone)]
+struct Corrupting {
+    stream: std::sync::Arc<TcpStream>,
+    received: std::sync::Arc<AtomicUsize>,
+    corrupt_at: std::sync::Arc<AtomicUsize>,
+}
+
+impl Corrupting {
+    /// Corrupt the byte at `offset` in the data received from now on.
+    fn corrupt(&self, offset: usize) {
+        self.corrupt_at.store(
+            self.received.load(Ordering::SeqCst) + offset,
+            Ordering::SeqCst,
+        );
+    }
+}
+
+impl embedded_io::ErrorType for Corrupting {
+    type Error = std::io::Error;
+}
+
+impl embedded_io::Read for Corrupting {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let len = FromStd::new(self.stream.as_ref()).read(buf)?;
+        let start = self.received.fetch_add(len, Ordering::SeqCst);
+        let offset = self.corrupt_at.load(Ordering::SeqCst).wrapping_sub(start);
+        if offset < len {
+            buf[offset] ^= 1;
+        }
+        Ok(len)
+    }
+}
+
+impl embedded_io::Write for Corrupting {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        FromStd::new(self.stream.as_ref()).write(buf)
+    }
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        FromStd::new(self.stream.as_ref()).flush()
+    }
+}
+
+#[test]
+fn test_blocking_split_reader_error_sends_alert() {
+    use embedded_tls::blocking::*;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let cert = pem_parser::pem_to_der(include_str!("data/server-cert.pem"));
+        let key = pem_parser::pem_to_der(include_str!("data/server-key.pem"));
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut read_record_buffer = [0; 16384];
+        let mut write_record_buffer = [0; 16384];
+        let mut tls = TlsServerConnection::new(
+            FromStd::new(stream),
+            &mut read_record_buffer,
+            &mut write_record_buffer,
+        );
+        let config = TlsConfig::new();
+        tls.open(TlsContext::new(
+            &config,
+            UnsecureProvider::new::<Aes128GcmSha256>(OsRng)
+                .with_cert(Certificate::X509(&cert))
+                .with_priv_key(&key),
+        ))?;
+
+        tls.write_all(b"ping")?;
+        tls.flush()?;
+        let mut buf = [0; 4];
+        tls.read(&mut buf)
+    });
+
+    let socket = Corrupting {
+        stream: std::sync::Arc::new(TcpStream::connect(addr).unwrap()),
+        received: std::sync::Arc::default(),
+        corrupt_at: std::sync::Arc::new(AtomicUsize::new(usize::MAX)),
+    };
+    let mut read_record_buffer = [0; 16384];
+    let mut write_record_buffer = [0; 16384];
+    let config = TlsConfig::new().with_server_name("localhost");
+    let mut tls = TlsConnection::new(
+        socket.clone(),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .expect("error establishing TLS connection");
+
+    // Past the record header, in the encrypted data
+    socket.corrupt(8);
+    let (mut reader, mut writer) = tls.split();
+    let mut buf = [0; 4];
+    assert!(matches!(reader.read(&mut buf), Err(TlsError::BadRecordMac)));
+
+    // The writer sends the alert for the error of the reader
+    assert!(matches!(
+        writer.write(b"ping"),
+        Err(TlsError::MissingHandshake)
+    ));
+    assert!(matches!(
+        server.join().unwrap(),
+        Err(TlsError::HandshakeAborted(
+            embedded_tls::alert::AlertLevel::Fatal,
+            embedded_tls::alert::AlertDescription::BadRecordMac
+        ))
+    ));
+}
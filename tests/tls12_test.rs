@@ -0,0 +1,408 @@
+#![cfg(feature = "tls12")]
+use embedded_io_adapters::{std::FromStd, tokio_1::FromTokio};
+use embedded_tls::blocking::TlsConnection as BlockingTlsConnection;
+use embedded_tls::*;
+use openssl::ssl;
+use rand::rngs::OsRng;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Once;
+use tokio::net::TcpStream;
+
+mod tlsserver;
+
+static INIT: Once = Once::new();
+
+fn init_log() {
+    INIT.call_once(|| {
+        env_logger::init();
+    });
+}
+
+/// `SSL_OP_NO_EXTENDED_MASTER_SECRET` of OpenSSL 3.0, which the openssl crate does not name.
+const NO_EXTENDED_MASTER_SECRET: ssl::SslOptions = ssl::SslOptions::from_bits_retain(1 << 0);
+
+/// Start an OpenSSL server that only speaks TLS 1.2 with the `name` key and certificate of the
+/// test data, and echoes the first message it receives.
+fn setup_openssl(
+    name: &str,
+    options: ssl::SslOptions,
+) -> (SocketAddr, std::thread::JoinHandle<()>) {
+    init_log();
+
+    let mut builder =
+        ssl::SslAcceptor::mozilla_intermediate_v5(ssl::SslMethod::tls_server()).unwrap();
+    builder
+        .set_private_key_file(format!("tests/data/{name}-key.pem"), ssl::SslFiletype::PEM)
+        .unwrap();
+    builder
+        .set_certificate_chain_file(format!("tests/data/{name}-cert.pem"))
+        .unwrap();
+    builder
+        .set_max_proto_version(Some(ssl::SslVersion::TLS1_2))
+        .unwrap();
+    builder.set_options(options);
+    let acceptor = builder.build();
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("cannot listen on port");
+    let addr = listener
+        .local_addr()
+        .expect("error retrieving socket address");
+
+    let h = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        if let Ok(mut conn) = acceptor.accept(stream) {
+            let mut buf = [0; 64];
+            let len = conn.read(&mut buf[..]).unwrap();
+            conn.write_all(&buf[..len]).unwrap();
+            let _ = conn.shutdown();
+        }
+    });
+    (addr, h)
+}
+
+#[tokio::test]
+async fn test_tls12_open() {
+    let (addr, h) = setup_openssl("server", ssl::SslOptions::empty());
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16640];
+    let mut write_record_buffer = [0; 16640];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .await
+    .expect("error establishing TLS 1.2 connection");
+
+    // Keying material exporters are only supported with TLS 1.3
+    assert!(matches!(
+        tls.export_keying_material(b"EXPORTER-Channel-Binding", b"", &mut [0; 32]),
+        Err(TlsError::Unimplemented)
+    ));
+
+    tls.write(b"ping").await.unwrap();
+    tls.flush().await.unwrap();
+
+    let mut rx = [0; 4];
+    let len = tls.read(&mut rx).await.unwrap();
+    assert_eq!(b"ping", &rx[..len]);
+
+    tls.close()
+        .await
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+    h.join().unwrap();
+}
+
+#[test]
+fn test_tls12_blocking_aes256() {
+    let (addr, h) = setup_openssl("server", ssl::SslOptions::empty());
+    let stream = std::net::TcpStream::connect(addr).expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16640];
+    let mut write_record_buffer = [0; 16640];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls = BlockingTlsConnection::new(
+        FromStd::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes256GcmSha384>(OsRng),
+    ))
+    .expect("error establishing TLS 1.2 connection");
+
+    tls.write(b"ping").unwrap();
+    tls.flush().unwrap();
+
+    let mut rx = [0; 4];
+    let len = tls.read(&mut rx).unwrap();
+    assert_eq!(b"ping", &rx[..len]);
+
+    tls.close()
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+    h.join().unwrap();
+}
+
+#[tokio::test]
+async fn test_tls12_requires_extended_master_secret() {
+    let (addr, h) = setup_openssl("server", NO_EXTENDED_MASTER_SECRET);
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16640];
+    let mut write_record_buffer = [0; 16640];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    let result = tls
+        .open(TlsContext::new(
+            &config,
+            UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+        ))
+        .await;
+    assert!(matches!(
+        result,
+        Err(TlsError::AbortHandshake(
+            alert::AlertLevel::Fatal,
+            alert::AlertDescription::HandshakeFailure
+        ))
+    ));
+    h.join().unwrap();
+}
+
+#[tokio::test]
+async fn test_tls12_rustls() {
+    init_log();
+
+    let test_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let certs = tlsserver::load_certs(&test_dir.join("data").join("server-cert.pem"));
+    let privkey = tlsserver::load_private_key(&test_dir.join("data").join("server-key.pem"));
+    let server_config = rustls::ServerConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(&[&rustls::version::TLS12])
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(certs, privkey)
+        .unwrap();
+
+    let listener =
+        mio::net::TcpListener::bind("127.0.0.1:0".parse().unwrap()).expect("cannot listen on port");
+    let addr = listener
+        .local_addr()
+        .expect("error retrieving socket address");
+    std::thread::spawn(move || tlsserver::run_with_config(listener, server_config));
+
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16640];
+    let mut write_record_buffer = [0; 16640];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .await
+    .expect("error establishing TLS 1.2 connection");
+
+    tls.write(b"ping").await.unwrap();
+    tls.flush().await.unwrap();
+
+    let mut rx = [0; 4];
+    let len = tls.read(&mut rx).await.unwrap();
+    assert_eq!(b"ping", &rx[..len]);
+
+    tls.close()
+        .await
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+}
+
+#[tokio::test]
+async fn test_tls12_rustls_fragmented() {
+    init_log();
+
+    let test_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let certs = tlsserver::load_certs(&test_dir.join("data").join("server-cert.pem"));
+    let privkey = tlsserver::load_private_key(&test_dir.join("data").join("server-key.pem"));
+    let mut server_config = rustls::ServerConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(&[&rustls::version::TLS12])
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(certs, privkey)
+        .unwrap();
+    // Split the server flight into records smaller than the Certificate message
+    server_config.max_fragment_size = Some(64);
+
+    let listener =
+        mio::net::TcpListener::bind("127.0.0.1:0".parse().unwrap()).expect("cannot listen on port");
+    let addr = listener
+        .local_addr()
+        .expect("error retrieving socket address");
+    std::thread::spawn(move || tlsserver::run_with_config(listener, server_config));
+
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16640];
+    let mut write_record_buffer = [0; 16640];
+    let config = TlsConfig::new().with_server_name("localhost");
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    tls.open(TlsContext::new(
+        &config,
+        UnsecureProvider::new::<Aes128GcmSha256>(OsRng),
+    ))
+    .await
+    .expect("error establishing TLS 1.2 connection");
+
+    tls.write(b"ping").await.unwrap();
+    tls.flush().await.unwrap();
+
+    let mut rx = [0; 4];
+    let len = tls.read(&mut rx).await.unwrap();
+    assert_eq!(b"ping", &rx[..len]);
+
+    tls.close()
+        .await
+        .map_err(|(_, e)| e)
+        .expect("error closing session");
+}
+
+#[cfg(feature = "webpki")]
+#[tokio::test]
+async fn test_tls12_server_certificate_validation() {
+    use embedded_tls::webpki::CertVerifier;
+    use std::time::SystemTime;
+
+    struct WebPkiProvider<'a> {
+        verifier: CertVerifier<'a, Aes128GcmSha256, SystemTime, 4096>,
+    }
+
+    impl CryptoProvider for WebPkiProvider<'_> {
+        type CipherSuite = Aes128GcmSha256;
+        type Signature = &'static [u8];
+
+        fn rng(&mut self) -> impl CryptoRngCore {
+            OsRng
+        }
+
+        fn verifier(&mut self) -> Result<&mut impl TlsVerifier<Aes128GcmSha256>, TlsError> {
+            Ok(&mut self.verifier)
+        }
+    }
+
+    let (addr, h) = setup_openssl("server", ssl::SslOptions::empty());
+    let der = pem_parser::pem_to_der(include_str!("data/ca-cert.pem"));
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16640];
+    let mut write_record_buffer = [0; 16640];
+    let config = TlsConfig::new();
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    tls.open(TlsContext::new(
+        &config,
+        WebPkiProvider {
+            verifier: CertVerifier::new(Certificate::X509(&der[..])),
+        },
+    ))
+    .await
+    .expect("error establishing TLS 1.2 connection");
+
+    tls.write(b"ping").await.unwrap();
+    tls.flush().await.unwrap();
+
+    let mut rx = [0; 4];
+    let len = tls.read(&mut rx).await.unwrap();
+    assert_eq!(b"ping", &rx[..len]);
+    h.join().unwrap();
+}
+
+#[cfg(feature = "rsa")]
+#[tokio::test]
+async fn test_tls12_ecdhe_rsa() {
+    use embedded_tls::pki::CertVerifier;
+    use std::time::SystemTime;
+
+    struct RustPkiProvider<'a> {
+        verifier: CertVerifier<'a, Aes128GcmSha256, SystemTime, 4096>,
+    }
+
+    impl CryptoProvider for RustPkiProvider<'_> {
+        type CipherSuite = Aes128GcmSha256;
+        type Signature = &'static [u8];
+
+        fn rng(&mut self) -> impl CryptoRngCore {
+            OsRng
+        }
+
+        fn verifier(&mut self) -> Result<&mut impl TlsVerifier<Aes128GcmSha256>, TlsError> {
+            Ok(&mut self.verifier)
+        }
+    }
+
+    let (addr, h) = setup_openssl("rsa-server", ssl::SslOptions::empty());
+    let der = pem_parser::pem_to_der(include_str!("data/rsa-ca-cert.pem"));
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("error connecting to server");
+
+    let mut read_record_buffer = [0; 16640];
+    let mut write_record_buffer = [0; 16640];
+    // Only offer PKCS#1 v1.5, so the server signs its ECDHE parameters with it
+    let config = TlsConfig::builder()
+        .with_server_name("localhost")
+        .with_signature_schemes(&[SignatureScheme::RsaPkcs1Sha256])
+        .build()
+        .unwrap();
+
+    let mut tls = TlsConnection::new(
+        FromTokio::new(stream),
+        &mut read_record_buffer,
+        &mut write_record_buffer,
+    );
+
+    tls.open(TlsContext::new(
+        &config,
+        RustPkiProvider {
+            verifier: CertVerifier::new(Certificate::X509(&der[..])),
+        },
+    ))
+    .await
+    .expect("error establishing TLS 1.2 connection");
+
+    tls.write(b"ping").await.unwrap();
+    tls.flush().await.unwrap();
+
+    let mut rx = [0; 4];
+    let len = tls.read(&mut rx).await.unwrap();
+    assert_eq!(b"ping", &rx[..len]);
+    h.join().unwrap();
+}
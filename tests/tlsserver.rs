@@ -94,6 +94,7 @@ struct Connection {
     token: mio::Token,
     closing: bool,
     closed: bool,
+    sent_close_notify: bool,
     mode: ServerMode,
     tls_session: rustls::ServerConnection,
     back: Option<TcpStream>,
@@ -127,6 +128,7 @@ impl Connection {
             token,
             closing: false,
             closed: false,
+            sent_close_notify: false,
             mode,
             tls_session,
             back,
@@ -217,6 +219,13 @@ impl Connection {
             log::debug!("plaintext read {:?}", buf.len());
             self.incoming_plaintext(&buf);
         }
+
+        // The client sent close_notify: answer with our own once the echo is written.
+        if rc.is_ok() && !self.sent_close_notify {
+            log::debug!("close_notify received");
+            self.tls_session.send_close_notify();
+            self.sent_close_notify = true;
+        }
     }
 
     fn try_back_read(&mut self) {